@@ -2,9 +2,9 @@
 #![deny(unused_must_use)]
 extern crate proc_macro;
 
-use proc_macro2::{Span, TokenStream};
-use quote::quote;
-use std::collections::HashMap;
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+use syn::ext::IdentExt;
 use syn::parse::{Parse, ParseStream, Result as ParseResult};
 use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
@@ -13,10 +13,15 @@ use syn::{braced, parse_macro_input, FnArg, Ident, ItemFn, Pat, PatIdent, PatTyp
 
 type Error = syn::parse::Error;
 
+#[derive(Clone)]
 struct TemplateArg {
     ident: syn::Ident,
     is_pattern: bool,
-    ignore_fn: Option<syn::Path>,
+    /// `<arg> = comments("<prefix>")`: argument is collected from the comments of the test file
+    /// starting with the prefix (given as `value`).
+    is_comments: bool,
+    /// Either a path to a function or an inline closure
+    ignore_fn: Option<syn::Expr>,
     value: syn::LitStr,
 }
 
@@ -32,15 +37,46 @@ impl Parse for TemplateArg {
             let _eq = input.parse::<syn::token::Eq>()?;
             false
         };
-        let value = input.parse::<syn::LitStr>()?;
+        let is_comments = !is_pattern && input.peek(syn::Ident) && input.peek2(syn::token::Paren);
+        let value = if is_comments {
+            let kind = input.parse::<Ident>()?;
+            if kind != "comments" {
+                return Err(Error::new(
+                    kind.span(),
+                    format!(
+                        "unknown mapping kind `{}`; expected `comments(\"<prefix>\")`",
+                        kind
+                    ),
+                ));
+            }
+            let content;
+            let _paren = syn::parenthesized!(content in input);
+            let prefix = content.parse::<syn::LitStr>()?;
+            if prefix.value().is_empty() {
+                return Err(Error::new(
+                    prefix.span(),
+                    "comment prefix must not be empty",
+                ));
+            }
+            prefix
+        } else {
+            input.parse::<syn::LitStr>()?
+        };
         if is_pattern && input.peek(syn::token::If) {
             let _if = input.parse::<syn::token::If>()?;
-            let _not = input.parse::<syn::token::Bang>()?;
-            ignore_fn = Some(input.parse::<syn::Path>()?);
+            let _not = input.parse::<syn::token::Not>()?;
+            ignore_fn = Some(
+                if input.peek(syn::token::Or) || input.peek(syn::token::Move) {
+                    syn::Expr::Closure(input.parse::<syn::ExprClosure>()?)
+                } else {
+                    syn::Expr::Path(input.parse::<syn::ExprPath>()?)
+                },
+            );
         }
         Ok(Self {
             ident,
             is_pattern,
+            is_comments,
             ignore_fn,
             value,
         })
@@ -53,38 +89,1014 @@ impl Parse for TemplateArg {
 /// ```ignore
 /// #[files("<root>", {
 ///   <arg_name> in "<regexp>",
-///   <arg_name> in "<template>",
-/// }]
+///   <arg_name> = "<template>",
+///   <arg_name> = comments("<prefix>"),
+/// }, <options>]
 /// ```
+///
+/// See [`AttrOptions`] for the supported options.
 struct FilesTestArgs {
     root: String,
-    args: HashMap<Ident, TemplateArg>,
+    /// Variant of `MissingRoot` (from the option or from the configuration).
+    missing_root: Ident,
+    options: AttrOptions,
+    args: Vec<TemplateArg>,
+    /// Span of the `{ ... }` block with mappings, used to point diagnostics at the attribute.
+    mappings_span: Span,
+}
+
+impl FilesTestArgs {
+    /// Pattern mapping (parser guarantees there is exactly one).
+    fn pattern(&self) -> &TemplateArg {
+        self.args
+            .iter()
+            .find(|arg| arg.is_pattern)
+            .expect("pattern mapping is required")
+    }
+
+    fn mapping(&self, ident: &Ident) -> Option<&TemplateArg> {
+        self.args
+            .iter()
+            .find(|arg| arg.ident.unraw() == ident.unraw())
+    }
 }
 
 /// See `syn` crate documentation / sources for more examples.
 impl Parse for FilesTestArgs {
     fn parse(input: ParseStream) -> ParseResult<Self> {
-        let root = input.parse::<syn::LitStr>()?;
-        let _comma = input.parse::<syn::token::Comma>()?;
-        let content;
-        let _brace_token = braced!(content in input);
-
-        let args: Punctuated<TemplateArg, Comma> = content.parse_terminated(TemplateArg::parse)?;
-        let args = args
-            .into_pairs()
-            .map(|p| {
-                let value = p.into_value();
-                (value.ident.clone(), value)
-            })
-            .collect();
+        let args = input.parse::<PartialFilesArgs>()?;
+        FilesTestArgs::from_partial(args, None)
+    }
+}
+
+impl FilesTestArgs {
+    /// Build arguments from the (possibly incomplete) attribute arguments, taking missing parts
+    /// from the `defaults` (arguments of the module-level attribute) and validate the result.
+    fn from_partial(
+        args: PartialFilesArgs,
+        defaults: Option<&PartialFilesArgs>,
+    ) -> ParseResult<Self> {
+        let root = match args.root.or_else(|| defaults.and_then(|d| d.root.clone())) {
+            Some(root) => root,
+            None => {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "root directory is not specified (expected `#[files(\"<root>\", { .. })]`)",
+                ))
+            }
+        };
+
+        // Mappings given on the function override mappings with the same name given on the
+        // module. Pattern given on the function replaces the pattern given on the module.
+        let (args_mappings, args_span) = match args.mappings {
+            Some((mappings, span)) => (mappings, Some(span)),
+            None => (Vec::new(), None),
+        };
+        let overrides_pattern = args_mappings.iter().any(|arg| arg.is_pattern);
+        let mut mappings = Vec::new();
+        if let Some((ref default_mappings, _)) = defaults.and_then(|d| d.mappings.as_ref()) {
+            let inherited = default_mappings.iter().filter(|arg| {
+                let overridden = (overrides_pattern && arg.is_pattern)
+                    || args_mappings
+                        .iter()
+                        .any(|other| other.ident.unraw() == arg.ident.unraw());
+                !overridden
+            });
+            mappings.extend(inherited.cloned());
+        }
+        mappings.extend(args_mappings);
+        let mappings_span = args_span
+            .or_else(|| defaults.and_then(|d| d.mappings.as_ref().map(|(_, span)| *span)))
+            .unwrap_or_else(Span::call_site);
+        let options = match defaults {
+            Some(defaults) => args.options.merge(&defaults.options),
+            None => args.options,
+        };
+        options.reject_data_options("#[files(..)]")?;
+        let args = mappings;
+
+        // Duplicate mappings would silently shadow each other
+        for (idx, arg) in args.iter().enumerate() {
+            if let Some(first) = args[..idx]
+                .iter()
+                .find(|prev| prev.ident.unraw() == arg.ident.unraw())
+            {
+                let mut err = Error::new(
+                    arg.ident.span(),
+                    format!("mapping for `{}` is defined more than once", arg.ident),
+                );
+                err.combine(Error::new(first.ident.span(), "first defined here"));
+                return Err(err);
+            }
+        }
+
+        let mut patterns = args.iter().filter(|arg| arg.is_pattern);
+        let pattern = match (patterns.next(), patterns.next()) {
+            (Some(pattern), None) => pattern,
+            (Some(_), Some(second)) => {
+                return Err(Error::new(
+                    second.ident.span(),
+                    "two patterns are not allowed!",
+                ))
+            }
+            (None, _) => {
+                return Err(Error::new(
+                    mappings_span,
+                    "must have exactly one pattern mapping defined via `pattern in r#\"<regular expression>\"`",
+                ))
+            }
+        };
+
+        let regex = regex::Regex::new(&pattern.value.value()).map_err(|err| {
+            Error::new(
+                pattern.value.span(),
+                format!("invalid regular expression: {}", err),
+            )
+        })?;
+        for template in args
+            .iter()
+            .filter(|arg| !arg.is_pattern && !arg.is_comments)
+        {
+            validate_template(&regex, &template.value)?;
+        }
 
+        if let Some(ref template) = options.name {
+            for segment in parse_name_template(template)? {
+                if let NameSegment::Placeholder(placeholder) = segment {
+                    let known = match placeholder.as_str() {
+                        "fn" | "path" | "file" | "stem" | "namespace" => true,
+                        param if options.matrix_param(param).is_some() => true,
+                        group => match group.parse::<usize>() {
+                            Ok(idx) => idx < regex.captures_len(),
+                            Err(_) => regex.capture_names().any(|name| name == Some(group)),
+                        },
+                    };
+                    if !known {
+                        return Err(Error::new(
+                            template.span(),
+                            format!(
+                                "unknown placeholder `{{{}}}`; expected `{{fn}}`, `{{path}}`, `{{file}}`, `{{stem}}`, `{{namespace}}`, a capture group of the pattern or a matrix parameter",
+                                placeholder
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+        if let Some(ref matrix) = options.matrix {
+            for param in &matrix.params {
+                if let Some(arg) = args.iter().find(|arg| arg.ident == param.ident) {
+                    let mut err = Error::new(
+                        param.ident.span(),
+                        format!("`{}` is both a matrix parameter and a mapping", param.ident),
+                    );
+                    err.combine(Error::new(arg.ident.span(), "mapping is defined here"));
+                    return Err(err);
+                }
+            }
+        }
+        let config = Config::load(root.span())?;
+        let missing_root = match options.missing_root {
+            Some(ref policy) => missing_root_variant(&policy.to_string(), policy.span())?,
+            None => {
+                let policy = config
+                    .files
+                    .missing_root
+                    .as_ref()
+                    .map_or("fail", String::as_str);
+                missing_root_variant(policy, root.span())?
+            }
+        };
         Ok(Self {
-            root: root.value(),
+            root: config.files_root(&root.value()),
+            missing_root,
+            options,
             args,
+            mappings_span,
+        })
+    }
+}
+
+/// Arguments of `#[files(..)]` attribute where any part could be omitted. Used for the module-level
+/// attribute (which defines defaults for all test functions in the module) and for attributes on
+/// the test functions inside such module (which override these defaults).
+///
+/// ```ignore
+/// #[files("<root>", { <mappings> }, <options>)]
+/// mod tests {
+///   #[files({ <mappings> }, <options>)]
+///   fn test(..) {}
+/// }
+/// ```
+#[derive(Default)]
+struct PartialFilesArgs {
+    root: Option<syn::LitStr>,
+    mappings: Option<(Vec<TemplateArg>, Span)>,
+    options: AttrOptions,
+}
+
+impl Parse for PartialFilesArgs {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        let root = if input.peek(syn::LitStr) {
+            Some(input.parse::<syn::LitStr>()?)
+        } else {
+            None
+        };
+        let mut mappings = None;
+        let has_mappings = if root.is_some() {
+            input.peek(syn::token::Comma) && input.peek2(syn::token::Brace)
+        } else {
+            input.peek(syn::token::Brace)
+        };
+        if has_mappings {
+            if root.is_some() {
+                let _comma = input.parse::<syn::token::Comma>()?;
+            }
+            let content;
+            let brace_token = braced!(content in input);
+            let args: Punctuated<TemplateArg, Comma> =
+                content.parse_terminated(TemplateArg::parse, Comma)?;
+            mappings = Some((args.into_iter().collect(), brace_token.span.join()));
+        }
+        let leading_comma = root.is_some() || mappings.is_some();
+        let options = AttrOptions::parse_list(input, leading_comma)?;
+        Ok(Self {
+            root,
+            mappings,
+            options,
+        })
+    }
+}
+
+/// Options shared by `#[files(..)]` and `#[data(..)]` attributes. Options are given after the
+/// main arguments of the attribute as a comma-separated list of `<name> = <value>` pairs:
+///
+/// ```ignore
+/// #[files("<root>", { .. }, cfg = unix)]
+/// #[data("<path>", cfg = all(unix, feature = "fs-tests"))]
+/// ```
+#[derive(Default, Clone)]
+struct AttrOptions {
+    /// Path to the `datatest` crate, if it is re-exported under a different name.
+    krate: Option<syn::Path>,
+    /// `cfg = <predicate>`: only compile and register the test if the predicate holds (same syntax
+    /// as in `#[cfg(..)]` attribute).
+    cfg: Option<syn::Meta>,
+    /// `name = "<template>"`: template for generated test names (see [`NameSegment`]).
+    name: Option<syn::LitStr>,
+    /// `unmapped = default` or `unmapped = <path>`: construct test function arguments which are
+    /// not provided by the test (via `Default::default()` or by calling the function at `<path>`).
+    unmapped: Option<syn::Path>,
+    /// `matrix(<param> = [<value>, ..], ..)`: run each test for every combination of values.
+    matrix: Option<Matrix>,
+    /// `ide = true`: generate hidden `#[test]` entry point for every test file found at compile
+    /// time, so IDEs could run individual cases.
+    ide: Option<syn::LitBool>,
+    /// `missing_root = fail | skip | warn`: what to do if the root directory does not exist.
+    missing_root: Option<Ident>,
+    /// `duplicates = keep | fail | index`: what to do with test cases of `#[data(..)]` test which
+    /// have the same name or content (`keep` by default).
+    duplicates: Option<Ident>,
+    /// `order_by = <path>`: function returning the sort key of the test file path (`&Path`) or
+    /// the test case (`&T`), defining the order of the cases.
+    order_by: Option<syn::Path>,
+    /// `timeout = "<duration>"`: maximum duration of each test case (for example, `"5s"`).
+    timeout: Option<syn::LitStr>,
+    /// `memory_limit = "<size>"`: maximum memory each test case could use (for example, `"512MB"`),
+    /// cases are run in a separate process with limited address space.
+    memory_limit: Option<syn::LitStr>,
+    /// `priority = <integer>`: cases with higher priority are started first when tests run in
+    /// parallel (default is `0`, negative values are allowed).
+    priority: Option<Priority>,
+    /// `depends_on = "<test>"` or `depends_on = ["<test>", ..]`: run the test after all cases of
+    /// the given tests (functions in the same module) finished, failing it if any of them failed.
+    depends_on: Option<DependsOn>,
+    /// `compile_fail = true`: instead of calling the test function, compile the test file matched
+    /// by the pattern and compare diagnostics with the `.stderr` file next to it.
+    compile_fail: Option<syn::LitBool>,
+    /// `differential(reference = <path>, candidate = <path>)`: instead of calling the test
+    /// function, call both functions with the same arguments and compare their outputs.
+    differential: Option<Differential>,
+    /// `command = [<program>, <arg>, ..]`: instead of calling the test function, run the command
+    /// (with `{<arg>}` placeholders replaced by the paths of the mapped arguments) and compare its
+    /// output with the golden files next to the test file.
+    command: Option<syn::ExprArray>,
+    /// `reduce = lines | bytes | <path>`: reduce the failing input while the failure reproduces.
+    reduce: Option<syn::Path>,
+    /// `content_matches = <path>` or `content_matches = |head: &[u8]| ..`: function deciding by the
+    /// beginning of the matched file (magic number, shebang, etc) if it becomes a test case.
+    content_matches: Option<syn::Expr>,
+    /// `dedup = true`: skip test files with the same content as one of the previous files (for
+    /// example, the same input saved both in the fuzzing corpus and in the crash artifacts).
+    dedup: Option<syn::LitBool>,
+    /// `sandbox = copy | chdir`: run each case in a temporary copy of the directory of the test
+    /// file (and change the working directory into it, with `chdir`).
+    sandbox: Option<Ident>,
+    /// `out_dir = true`: path of the data file is relative to `OUT_DIR` (directory the build script
+    /// generates files into) instead of the crate root directory.
+    out_dir: Option<syn::LitBool>,
+    /// `relative_to = crate | source`: if the path of the data file is relative to the crate root
+    /// directory (default) or to the directory of the source file containing the test (like
+    /// `include_str!`).
+    relative_to: Option<Ident>,
+    /// `named = true`: test cases are read as `Named<T>`, named after the name given in the data
+    /// file, while the test function receives `T`.
+    named: Option<syn::LitBool>,
+    /// `schema = "<path>"`: JSON Schema the cases of the data file are validated against by the
+    /// validation pre-pass, along with the test case type.
+    schema: Option<syn::LitStr>,
+}
+
+/// Scheduling priority of the test cases, see [`AttrOptions::priority`].
+#[derive(Clone)]
+struct Priority {
+    span: Span,
+    value: i32,
+}
+
+impl Parse for Priority {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        let minus = input.parse::<Option<syn::token::Minus>>()?;
+        let lit = input.parse::<syn::LitInt>()?;
+        let value = lit.base10_parse::<i32>()?;
+        Ok(Priority {
+            span: lit.span(),
+            value: if minus.is_some() { -value } else { value },
+        })
+    }
+}
+
+/// Prerequisites of the test, see [`AttrOptions::depends_on`].
+#[derive(Clone)]
+struct DependsOn {
+    span: Span,
+    tests: Vec<syn::LitStr>,
+}
+
+impl Parse for DependsOn {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        if input.peek(syn::token::Bracket) {
+            let content;
+            let bracket = syn::bracketed!(content in input);
+            let tests: Punctuated<syn::LitStr, Comma> =
+                content.parse_terminated(<syn::LitStr as Parse>::parse, Comma)?;
+            if tests.is_empty() {
+                return Err(Error::new(
+                    bracket.span.join(),
+                    "no prerequisite tests are given",
+                ));
+            }
+            Ok(DependsOn {
+                span: bracket.span.join(),
+                tests: tests.into_iter().collect(),
+            })
+        } else {
+            let test = input.parse::<syn::LitStr>()?;
+            Ok(DependsOn {
+                span: test.span(),
+                tests: vec![test],
+            })
+        }
+    }
+}
+
+/// Functions compared by the differential test, see [`AttrOptions::differential`].
+#[derive(Clone)]
+struct Differential {
+    span: Span,
+    reference: syn::Path,
+    candidate: syn::Path,
+}
+
+impl Differential {
+    fn parse(span: Span, input: ParseStream) -> ParseResult<Self> {
+        let mut reference = None;
+        let mut candidate = None;
+        let items: Punctuated<(Ident, syn::Path), Comma> = input.parse_terminated(
+            |input| {
+                let name = input.parse::<Ident>()?;
+                let _eq = input.parse::<syn::token::Eq>()?;
+                Ok((name, input.parse::<syn::Path>()?))
+            },
+            Comma,
+        )?;
+        for (name, path) in items {
+            let slot = match name.to_string().as_str() {
+                "reference" => &mut reference,
+                "candidate" => &mut candidate,
+                _ => {
+                    return Err(Error::new(
+                        name.span(),
+                        format!(
+                            "unknown parameter `{}`; expected `reference` or `candidate`",
+                            name
+                        ),
+                    ))
+                }
+            };
+            if slot.is_some() {
+                return Err(duplicate_option(&name));
+            }
+            *slot = Some(path);
+        }
+        match (reference, candidate) {
+            (Some(reference), Some(candidate)) => Ok(Differential {
+                span,
+                reference,
+                candidate,
+            }),
+            _ => Err(Error::new(
+                span,
+                "both `reference` and `candidate` functions must be given",
+            )),
+        }
+    }
+}
+
+/// Value parameters given via `matrix(..)` option of `#[files(..)]` attribute. Each test is run
+/// for every combination of parameter values (in order, last parameter changes the fastest):
+///
+/// ```ignore
+/// #[files("<root>", { .. }, matrix(mode = [Mode::Strict, Mode::Lenient], level = [1, 2]))]
+/// ```
+#[derive(Clone)]
+struct Matrix {
+    span: Span,
+    params: Vec<MatrixParam>,
+}
+
+#[derive(Clone)]
+struct MatrixParam {
+    ident: Ident,
+    values: Vec<syn::Expr>,
+}
+
+impl Parse for MatrixParam {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        let ident = input.parse::<Ident>()?;
+        let _eq = input.parse::<syn::token::Eq>()?;
+        let array = input.parse::<syn::ExprArray>()?;
+        if array.elems.is_empty() {
+            return Err(Error::new(
+                array.bracket_token.span.join(),
+                format!("no values are given for the matrix parameter `{}`", ident),
+            ));
+        }
+        Ok(MatrixParam {
+            ident,
+            values: array.elems.into_iter().collect(),
+        })
+    }
+}
+
+impl MatrixParam {
+    /// Human-readable labels of the values, used in the test names (string and character literals
+    /// are used as-is, other expressions are rendered as written in the source).
+    fn labels(&self) -> Vec<String> {
+        self.values
+            .iter()
+            .map(|value| match value {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(lit),
+                    ..
+                }) => lit.value(),
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Char(lit),
+                    ..
+                }) => lit.value().to_string(),
+                value => quote!(#value).to_string().replace(' ', ""),
+            })
+            .collect()
+    }
+}
+
+impl AttrOptions {
+    /// Parse options following the main arguments of the attribute (`leading_comma` indicates that
+    /// options are preceded by other arguments, so the list starts with a comma).
+    fn parse_list(input: ParseStream, leading_comma: bool) -> ParseResult<Self> {
+        let mut options = AttrOptions::default();
+        let mut first = true;
+        while !input.is_empty() {
+            if leading_comma || !first {
+                let _comma = input.parse::<syn::token::Comma>()?;
+                if input.is_empty() {
+                    break;
+                }
+            }
+            first = false;
+            let name = input.call(Ident::parse_any)?;
+            if name == "matrix" && input.peek(syn::token::Paren) {
+                if options.matrix.is_some() {
+                    return Err(duplicate_option(&name));
+                }
+                let content;
+                let _paren = syn::parenthesized!(content in input);
+                let params: Punctuated<MatrixParam, Comma> =
+                    content.parse_terminated(MatrixParam::parse, Comma)?;
+                let params = params.into_iter().collect::<Vec<_>>();
+                for (idx, param) in params.iter().enumerate() {
+                    if params[..idx].iter().any(|prev| prev.ident == param.ident) {
+                        return Err(Error::new(
+                            param.ident.span(),
+                            format!(
+                                "matrix parameter `{}` is defined more than once",
+                                param.ident
+                            ),
+                        ));
+                    }
+                }
+                options.matrix = Some(Matrix {
+                    span: name.span(),
+                    params,
+                });
+                continue;
+            }
+            if name == "differential" && input.peek(syn::token::Paren) {
+                if options.differential.is_some() {
+                    return Err(duplicate_option(&name));
+                }
+                let content;
+                let _paren = syn::parenthesized!(content in input);
+                options.differential = Some(Differential::parse(name.span(), &content)?);
+                continue;
+            }
+            let _eq = input.parse::<syn::token::Eq>()?;
+            match name.to_string().as_str() {
+                "cfg" if options.cfg.is_none() => options.cfg = Some(input.parse()?),
+                "cfg" => return Err(duplicate_option(&name)),
+                "name" if options.name.is_none() => {
+                    let template = input.parse::<syn::LitStr>()?;
+                    parse_name_template(&template)?;
+                    options.name = Some(template);
+                }
+                "name" => return Err(duplicate_option(&name)),
+                "crate" if options.krate.is_none() => {
+                    options.krate = Some(input.parse::<syn::LitStr>()?.parse()?);
+                }
+                "crate" => return Err(duplicate_option(&name)),
+                "unmapped" if options.unmapped.is_none() => {
+                    options.unmapped = Some(input.parse()?);
+                }
+                "unmapped" => return Err(duplicate_option(&name)),
+                "ide" if options.ide.is_none() => options.ide = Some(input.parse()?),
+                "ide" => return Err(duplicate_option(&name)),
+                "missing_root" if options.missing_root.is_none() => {
+                    let policy = input.parse::<Ident>()?;
+                    missing_root_variant(&policy.to_string(), policy.span())?;
+                    options.missing_root = Some(policy);
+                }
+                "missing_root" => return Err(duplicate_option(&name)),
+                "duplicates" if options.duplicates.is_none() => {
+                    let policy = input.parse::<Ident>()?;
+                    duplicates_variant(&policy.to_string(), policy.span())?;
+                    options.duplicates = Some(policy);
+                }
+                "duplicates" => return Err(duplicate_option(&name)),
+                "order_by" if options.order_by.is_none() => {
+                    options.order_by = Some(input.parse()?);
+                }
+                "order_by" => return Err(duplicate_option(&name)),
+                "timeout" if options.timeout.is_none() => {
+                    options.timeout = Some(input.parse::<syn::LitStr>()?);
+                }
+                "timeout" => return Err(duplicate_option(&name)),
+                "memory_limit" if options.memory_limit.is_none() => {
+                    let limit = input.parse::<syn::LitStr>()?;
+                    parse_size(&limit)?;
+                    options.memory_limit = Some(limit);
+                }
+                "memory_limit" => return Err(duplicate_option(&name)),
+                "priority" if options.priority.is_none() => {
+                    options.priority = Some(input.parse()?);
+                }
+                "priority" => return Err(duplicate_option(&name)),
+                "depends_on" if options.depends_on.is_none() => {
+                    options.depends_on = Some(input.parse()?);
+                }
+                "depends_on" => return Err(duplicate_option(&name)),
+                "compile_fail" if options.compile_fail.is_none() => {
+                    options.compile_fail = Some(input.parse()?);
+                }
+                "compile_fail" => return Err(duplicate_option(&name)),
+                "command" if options.command.is_none() => {
+                    let command = input.parse::<syn::ExprArray>()?;
+                    if command.elems.is_empty() {
+                        return Err(Error::new(
+                            command.bracket_token.span.join(),
+                            "command must include the program to run",
+                        ));
+                    }
+                    options.command = Some(command);
+                }
+                "command" => return Err(duplicate_option(&name)),
+                "reduce" if options.reduce.is_none() => options.reduce = Some(input.parse()?),
+                "reduce" => return Err(duplicate_option(&name)),
+                "content_matches" if options.content_matches.is_none() => {
+                    options.content_matches = Some(
+                        if input.peek(syn::token::Or) || input.peek(syn::token::Move) {
+                            syn::Expr::Closure(input.parse::<syn::ExprClosure>()?)
+                        } else {
+                            syn::Expr::Path(input.parse::<syn::ExprPath>()?)
+                        },
+                    );
+                }
+                "content_matches" => return Err(duplicate_option(&name)),
+                "dedup" if options.dedup.is_none() => options.dedup = Some(input.parse()?),
+                "dedup" => return Err(duplicate_option(&name)),
+                "out_dir" if options.out_dir.is_none() => options.out_dir = Some(input.parse()?),
+                "out_dir" => return Err(duplicate_option(&name)),
+                "named" if options.named.is_none() => options.named = Some(input.parse()?),
+                "named" => return Err(duplicate_option(&name)),
+                "schema" if options.schema.is_none() => options.schema = Some(input.parse()?),
+                "schema" => return Err(duplicate_option(&name)),
+                "relative_to" if options.relative_to.is_none() => {
+                    // `crate` is a keyword
+                    let base = input.call(Ident::parse_any)?;
+                    relative_to_source(&base)?;
+                    options.relative_to = Some(base);
+                }
+                "relative_to" => return Err(duplicate_option(&name)),
+                "sandbox" if options.sandbox.is_none() => {
+                    let mode = input.parse::<Ident>()?;
+                    sandbox_variant(&mode.to_string(), mode.span())?;
+                    options.sandbox = Some(mode);
+                }
+                "sandbox" => return Err(duplicate_option(&name)),
+                _ => {
+                    return Err(Error::new(
+                        name.span(),
+                        format!("unknown option `{}`", name),
+                    ))
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    /// Fill in options not given explicitly from the `defaults` (options of the module-level
+    /// attribute).
+    fn merge(self, defaults: &AttrOptions) -> AttrOptions {
+        AttrOptions {
+            krate: self.krate.or_else(|| defaults.krate.clone()),
+            cfg: self.cfg.or_else(|| defaults.cfg.clone()),
+            name: self.name.or_else(|| defaults.name.clone()),
+            unmapped: self.unmapped.or_else(|| defaults.unmapped.clone()),
+            matrix: self.matrix.or_else(|| defaults.matrix.clone()),
+            ide: self.ide.or_else(|| defaults.ide.clone()),
+            missing_root: self.missing_root.or_else(|| defaults.missing_root.clone()),
+            duplicates: self.duplicates.or_else(|| defaults.duplicates.clone()),
+            order_by: self.order_by.or_else(|| defaults.order_by.clone()),
+            timeout: self.timeout.or_else(|| defaults.timeout.clone()),
+            memory_limit: self.memory_limit.or_else(|| defaults.memory_limit.clone()),
+            priority: self.priority.or_else(|| defaults.priority.clone()),
+            depends_on: self.depends_on.or_else(|| defaults.depends_on.clone()),
+            compile_fail: self.compile_fail.or_else(|| defaults.compile_fail.clone()),
+            differential: self.differential.or_else(|| defaults.differential.clone()),
+            command: self.command.or_else(|| defaults.command.clone()),
+            reduce: self.reduce.or_else(|| defaults.reduce.clone()),
+            content_matches: self
+                .content_matches
+                .or_else(|| defaults.content_matches.clone()),
+            dedup: self.dedup.or_else(|| defaults.dedup.clone()),
+            sandbox: self.sandbox.or_else(|| defaults.sandbox.clone()),
+            out_dir: self.out_dir.or_else(|| defaults.out_dir.clone()),
+            relative_to: self.relative_to.or_else(|| defaults.relative_to.clone()),
+            named: self.named.or_else(|| defaults.named.clone()),
+            schema: self.schema.or_else(|| defaults.schema.clone()),
+        }
+    }
+
+    /// Find matrix parameter with the given name.
+    fn matrix_param(&self, name: &str) -> Option<&MatrixParam> {
+        self.matrix.as_ref().and_then(|matrix| {
+            matrix
+                .params
+                .iter()
+                .find(|param| param.ident.unraw() == name)
+        })
+    }
+
+    /// Fail if options specific to `#[files(..)]` are given for the attribute which does not
+    /// support them.
+    fn reject_files_options(&self, attr: &str) -> ParseResult<()> {
+        let unsupported = |option: &str, span: Span| {
+            Err(Error::new(
+                span,
+                format!("`{}` option is not supported by `{}`", option, attr),
+            ))
+        };
+        if let Some(ref matrix) = self.matrix {
+            return unsupported("matrix", matrix.span);
+        }
+        if let Some(ref ide) = self.ide {
+            return unsupported("ide", ide.span);
+        }
+        if let Some(ref missing_root) = self.missing_root {
+            return unsupported("missing_root", missing_root.span());
+        }
+        if let Some(ref compile_fail) = self.compile_fail {
+            return unsupported("compile_fail", compile_fail.span);
+        }
+        if let Some(ref differential) = self.differential {
+            return unsupported("differential", differential.span);
+        }
+        if let Some(ref command) = self.command {
+            return unsupported("command", command.bracket_token.span.join());
+        }
+        if let Some(ref reduce) = self.reduce {
+            return unsupported("reduce", reduce.span());
+        }
+        if let Some(ref content_matches) = self.content_matches {
+            return unsupported("content_matches", content_matches.span());
+        }
+        if let Some(ref dedup) = self.dedup {
+            return unsupported("dedup", dedup.span);
+        }
+        if let Some(ref sandbox) = self.sandbox {
+            return unsupported("sandbox", sandbox.span());
+        }
+        Ok(())
+    }
+
+    /// Fail if options specific to `#[data(..)]` are given for the attribute which does not
+    /// support them.
+    fn reject_data_options(&self, attr: &str) -> ParseResult<()> {
+        if let Some(ref duplicates) = self.duplicates {
+            return Err(Error::new(
+                duplicates.span(),
+                format!("`duplicates` option is not supported by `{}`", attr),
+            ));
+        }
+        if let Some(ref out_dir) = self.out_dir {
+            return Err(Error::new(
+                out_dir.span,
+                format!("`out_dir` option is not supported by `{}`", attr),
+            ));
+        }
+        if let Some(ref relative_to) = self.relative_to {
+            return Err(Error::new(
+                relative_to.span(),
+                format!("`relative_to` option is not supported by `{}`", attr),
+            ));
+        }
+        if let Some(ref named) = self.named {
+            return Err(Error::new(
+                named.span,
+                format!("`named` option is not supported by `{}`", attr),
+            ));
+        }
+        if let Some(ref schema) = self.schema {
+            return Err(Error::new(
+                schema.span(),
+                format!("`schema` option is not supported by `{}`", attr),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Expression of type `Option<Duration>` for the `timeout` option. Duration is parsed by
+    /// `datatest` itself in a constant, so invalid durations fail the compilation.
+    fn timeout_tokens(&self) -> TokenStream {
+        let krate = self.crate_path();
+        match self.timeout {
+            Some(ref timeout) => quote_spanned! {timeout.span()=>
+                Some({
+                    const TIMEOUT: ::std::time::Duration =
+                        #krate::__internal::duration(#timeout);
+                    TIMEOUT
+                })
+            },
+            None => quote!(None),
+        }
+    }
+
+    /// Expression of type `Option<u64>` for the `memory_limit` option (in bytes).
+    fn memory_limit_tokens(&self) -> TokenStream {
+        match self.memory_limit {
+            Some(ref limit) => match parse_size(limit) {
+                Ok(bytes) => quote!(Some(#bytes)),
+                Err(err) => err.to_compile_error(),
+            },
+            None => quote!(None),
+        }
+    }
+
+    /// Scheduling priority of the test cases (`0` if not given).
+    fn priority(&self) -> i32 {
+        self.priority.as_ref().map_or(0, |priority| priority.value)
+    }
+
+    /// Full names of the prerequisite tests (`&'static [&'static str]` expression).
+    fn depends_on_tokens(&self) -> TokenStream {
+        let tests = self
+            .depends_on
+            .iter()
+            .flat_map(|depends_on| &depends_on.tests);
+        quote!(&[#(concat!(module_path!(), "::", #tests)),*])
+    }
+
+    /// If per-case entry points for IDEs should be generated.
+    fn ide(&self) -> bool {
+        self.ide.as_ref().is_some_and(|ide| ide.value)
+    }
+
+    /// If test files with the same content should be skipped.
+    fn dedup(&self) -> bool {
+        self.dedup.as_ref().is_some_and(|flag| flag.value)
+    }
+
+    /// If the path of the data file is relative to `OUT_DIR`.
+    fn out_dir(&self) -> bool {
+        self.out_dir.as_ref().is_some_and(|flag| flag.value)
+    }
+
+    /// If test cases are read as `Named<T>`.
+    fn named(&self) -> bool {
+        self.named.as_ref().is_some_and(|flag| flag.value)
+    }
+
+    /// If the path of the data file is relative to the source file containing the test.
+    fn source_relative(&self) -> bool {
+        self.relative_to
+            .as_ref()
+            .is_some_and(|base| relative_to_source(base).unwrap_or(false))
+    }
+
+    /// If test files should be compiled instead of calling the test function.
+    fn compile_fail(&self) -> bool {
+        self.compile_fail.as_ref().is_some_and(|flag| flag.value)
+    }
+
+    /// Path to the `datatest` crate to use in the generated code.
+    fn crate_path(&self) -> TokenStream {
+        crate_path(&self.krate)
+    }
+
+    /// Expression constructing an argument not provided by the test, if enabled by the `unmapped`
+    /// option.
+    fn unmapped_arg(&self) -> Option<TokenStream> {
+        self.unmapped.as_ref().map(|path| {
+            if path.is_ident("default") {
+                quote!(::std::default::Default::default())
+            } else {
+                quote!(#path())
+            }
         })
     }
+
+    /// `#[cfg(..)]` attribute to put on each generated item (empty if there is no condition).
+    fn cfg_attr(&self) -> TokenStream {
+        match self.cfg {
+            Some(ref cfg) => quote!(#[cfg(#cfg)]),
+            None => TokenStream::new(),
+        }
+    }
+}
+
+/// Segment of the test name template. Templates are strings like `"{fn}_{stem}"`, where each
+/// `{<placeholder>}` is replaced with the corresponding value (`{{` and `}}` are escaped braces).
+///
+/// For `#[files(..)]` tests, supported placeholders are `{fn}` (test function name), `{path}` (path
+/// of the matched file relative to the root directory), `{file}` (file name), `{stem}` (file name
+/// without extension) and capture groups of the pattern (`{1}`, `{name}`).
+///
+/// For `#[data(..)]` tests, supported placeholders are `{fn}`, `{index}` (index of the test case),
+/// `{case}` (test case name, see `datatest::DataTestCaseDesc`, or location if case has no name),
+/// `{location}` (location of the test case) and any other placeholder is treated as a field of the
+/// test case (`{id}` is `case.id`), which must implement [`ToString`].
+enum NameSegment {
+    Text(String),
+    Placeholder(String),
+}
+
+fn parse_name_template(template: &syn::LitStr) -> ParseResult<Vec<NameSegment>> {
+    let value = template.value();
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(Error::new(
+                            template.span(),
+                            "unterminated placeholder in name template (use `{{` for literal `{`)",
+                        )),
+                    }
+                }
+                let is_number = placeholder.parse::<u32>().is_ok();
+                let is_ident = placeholder
+                    .starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && placeholder
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !is_number && !is_ident {
+                    return Err(Error::new(
+                        template.span(),
+                        format!("invalid placeholder `{{{}}}` in name template", placeholder),
+                    ));
+                }
+                if !text.is_empty() {
+                    segments.push(NameSegment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(NameSegment::Placeholder(placeholder));
+            }
+            '}' => {
+                return Err(Error::new(
+                    template.span(),
+                    "unmatched `}` in name template (use `}}` for literal `}`)",
+                ))
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        segments.push(NameSegment::Text(text));
+    }
+    Ok(segments)
+}
+
+fn duplicate_option(name: &Ident) -> Error {
+    Error::new(
+        name.span(),
+        format!("option `{}` is specified more than once", name),
+    )
+}
+
+/// Verify that template only references capture groups defined by the pattern. Uses the same
+/// syntax as [`regex::Regex::replace`]: `$1`, `${1}`, `$name`, `${name}` and `$$` (escaped `$`).
+fn validate_template(regex: &regex::Regex, template: &syn::LitStr) -> ParseResult<()> {
+    let value = template.value();
+    let mut rest = value.as_str();
+    while let Some(pos) = rest.find('$') {
+        rest = &rest[pos + 1..];
+        let group = if rest.starts_with('$') {
+            rest = &rest[1..];
+            continue;
+        } else if rest.starts_with('{') {
+            match rest.find('}') {
+                Some(end) => {
+                    let group = &rest[1..end];
+                    rest = &rest[end + 1..];
+                    group
+                }
+                // Not a reference, regex treats it literally
+                None => continue,
+            }
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let group = &rest[..end];
+            rest = &rest[end..];
+            group
+        };
+
+        if group.is_empty() {
+            continue;
+        }
+        let exists = match group.parse::<usize>() {
+            Ok(idx) => idx < regex.captures_len(),
+            Err(_) => regex.capture_names().any(|name| name == Some(group)),
+        };
+        if !exists {
+            let digits = group
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect::<String>();
+            let hint = if !digits.is_empty() && digits.len() < group.len() {
+                format!(
+                    " (use `${{{}}}` to separate the group from the following text)",
+                    digits
+                )
+            } else {
+                String::new()
+            };
+            return Err(Error::new(
+                template.span(),
+                format!(
+                    "template references capture group `{}`, which is not defined by the pattern{}",
+                    group, hint
+                ),
+            ));
+        }
+    }
+    Ok(())
 }
 
+#[derive(Clone, Copy)]
 enum Registration {
     /// Register test cases via "global" constructors (https://crates.io/crates/ctor)
     Ctor,
@@ -151,11 +1163,11 @@ pub fn files_test_case_internal(
 /// There are four fields specific for these type of tests we need to fill in:
 ///
 /// 1. `root`, which is the root directory to scan for the tests (relative to the root of the crate
-/// with tests)
+///    with tests)
 /// 2. `params`, slice of strings, each string is either a template or pattern assigned to the
-/// function argument
+///    function argument
 /// 3. `pattern`, an index of the "pattern" argument (since exactly one is required, it is just an
-/// index in the `params` array).
+///    index in the `params` array).
 /// 4. `testfn`, test function trampoline.
 ///
 /// Few words about trampoline function. Each test function could have a unique signature, depending
@@ -181,22 +1193,120 @@ fn files_internal(
     func: proc_macro::TokenStream,
     channel: Registration,
 ) -> proc_macro::TokenStream {
-    let mut func_item: ItemFn = parse_macro_input!(func as ItemFn);
-    let args: FilesTestArgs = parse_macro_input!(args as FilesTestArgs);
+    let item = parse_macro_input!(func as syn::Item);
+    match item {
+        syn::Item::Mod(item_mod) => {
+            let defaults = parse_macro_input!(args as PartialFilesArgs);
+            test_module(item_mod, "files", |func_item, overrides| {
+                let overrides = overrides
+                    .map(|attr| parse_attr_args::<PartialFilesArgs>(&attr))
+                    .transpose()?
+                    .unwrap_or_default();
+                let args = FilesTestArgs::from_partial(overrides, Some(&defaults))?;
+                files_stacked(args, func_item, channel, |attr| {
+                    let overrides = parse_attr_args::<PartialFilesArgs>(attr)?;
+                    FilesTestArgs::from_partial(overrides, Some(&defaults))
+                })
+            })
+            .into()
+        }
+        syn::Item::Fn(func_item) => {
+            let args = parse_macro_input!(args as FilesTestArgs);
+            files_stacked(args, func_item, channel, |attr| attr.parse_args())
+                .unwrap_or_else(|err| err.to_compile_error())
+                .into()
+        }
+        item => Error::new_spanned(
+            item,
+            "`#[files(..)]` can only be applied to functions and inline modules",
+        )
+        .to_compile_error()
+        .into(),
+    }
+}
+
+/// Expand `#[files(..)]` test function, which could have more `#[files(..)]` attributes stacked on
+/// it (for the same test applied to different roots or patterns). Each attribute generates its own
+/// test descriptor (parsing the arguments of the stacked ones via `parse`), and the test cases of
+/// all of them are listed under the test function.
+fn files_stacked<F>(
+    args: FilesTestArgs,
+    mut func_item: ItemFn,
+    channel: Registration,
+    parse: F,
+) -> ParseResult<TokenStream>
+where
+    F: Fn(&syn::Attribute) -> ParseResult<FilesTestArgs>,
+{
+    let mut stacked = Vec::new();
+    func_item.attrs.retain(|attr| {
+        let is_files = attr
+            .path()
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "files");
+        if is_files {
+            stacked.push(attr.clone());
+        }
+        !is_files
+    });
+    let mut output = TokenStream::new();
+    for (idx, attr) in stacked.iter().enumerate() {
+        let args = parse(attr)?;
+        output.extend(files_test(args, func_item.clone(), channel, idx + 1));
+    }
+    output.extend(files_test(args, func_item, channel, 0));
+    Ok(output)
+}
+
+/// Generate test descriptor and trampoline for the `#[files(..)]` test function. Descriptors of the
+/// stacked attributes (`stacked` is the position of the attribute, starting from 1) only refer to
+/// the test function, which is emitted by the first attribute (`stacked` is 0).
+fn files_test(
+    args: FilesTestArgs,
+    mut func_item: ItemFn,
+    channel: Registration,
+    stacked: usize,
+) -> TokenStream {
     let info = handle_common_attrs(&mut func_item, false);
+    if let Err(err) = check_signature(&func_item.sig) {
+        return err.to_compile_error();
+    }
+    let krate = args.options.crate_path();
     let func_ident = &func_item.sig.ident;
-    let func_name_str = func_ident.to_string();
-    let desc_ident = Ident::new(&format!("__TEST_{}", func_ident), func_ident.span());
+    let func_name_str = func_ident.unraw().to_string();
+    let suffix = if stacked == 0 {
+        String::new()
+    } else {
+        format!("_{}", stacked)
+    };
+    let desc_ident = Ident::new(
+        &format!("__TEST_{}{}", func_ident.unraw(), suffix),
+        func_ident.span(),
+    );
     let trampoline_func_ident = Ident::new(
-        &format!("__TEST_TRAMPOLINE_{}", func_ident),
+        &format!("__TEST_TRAMPOLINE_{}{}", func_ident.unraw(), suffix),
         func_ident.span(),
     );
     let ignore = info.ignore;
-    let root = args.root;
+    let ignore_message = option_tokens(&info.ignore_message);
+    let only = info.only;
+    let xfail = info.xfail;
+    let resources = match info.resources {
+        Ok(ref resources) => resources,
+        Err(ref err) => return err.to_compile_error(),
+    };
+    let root = &args.root;
+    let missing_root = &args.missing_root;
+    let name_template = option_tokens(&args.options.name);
     let mut pattern_idx = None;
     let mut params: Vec<String> = Vec::new();
+    let mut reads: Vec<TokenStream> = Vec::new();
     let mut invoke_args: Vec<TokenStream> = Vec::new();
     let mut ignore_fn = None;
+    let mut comment_args = Vec::new();
+    // Names of the mapped arguments and indices of their paths, for the `command` option
+    let mut placeholders = Vec::new();
 
     // Match function arguments with our parsed list of mappings
     // We do the following in this loop:
@@ -204,45 +1314,76 @@ fn files_internal(
     // 2. For each argument we collect piece of code to create argument from the `&[PathBuf]` slice
     // given to us by the test runner.
     // 3. Capture the index of the argument corresponding to the "pattern" mapping
-    for (mut idx, arg) in func_item.sig.inputs.iter().enumerate() {
+    for (pos, arg) in func_item.sig.inputs.iter().enumerate() {
         match match_arg(arg) {
             Some((pat_ident, ty)) => {
-                if info.bench {
-                    if idx == 0 {
-                        // FIXME: verify is Bencher!
-                        invoke_args.push(quote!(#pat_ident));
-                        continue;
-                    } else {
-                        idx -= 1;
-                    }
+                if info.bench && pos == 0 {
+                    // FIXME: verify is Bencher!
+                    invoke_args.push(bencher_arg(ty, &krate));
+                    continue;
                 }
 
-                if let Some(arg) = args.args.get(&pat_ident.ident) {
+                if let Some(arg) = args.mapping(&pat_ident.ident).filter(|arg| arg.is_comments) {
+                    // Pattern path is not known yet, see below
+                    comment_args.push((invoke_args.len(), arg.value.clone()));
+                    invoke_args.push(quote!());
+                } else if let Some(arg) = args.mapping(&pat_ident.ident) {
+                    // Index of the path in the `&[PathBuf]` slice given by the test runner
+                    let idx = params.len();
                     if arg.is_pattern {
-                        if pattern_idx.is_some() {
-                            return Error::new(arg.ident.span(), "two patterns are not allowed!")
-                                .to_compile_error()
-                                .into();
-                        }
                         pattern_idx = Some(idx);
                         ignore_fn = arg.ignore_fn.clone();
                     }
 
+                    // Lifetimes of the function could not be named in the trampoline
+                    let ty = elide_lifetimes(quote!(#ty)).unwrap_or_else(|| quote!(#ty));
+                    placeholders.push((pat_ident.ident.unraw().to_string(), idx));
                     params.push(arg.value.value());
+                    reads.push(quote!(<#ty as #krate::__internal::DeriveArg>::READS_FILE));
                     invoke_args.push(quote! {
-                        ::datatest::__internal::TakeArg::take(&mut <#ty as ::datatest::__internal::DeriveArg>::derive(&paths_arg[#idx]))
+                        #krate::__internal::TakeArg::take(&mut <#ty as #krate::__internal::DeriveArg>::derive(&paths_arg[#idx]))
                     })
+                } else if args
+                    .options
+                    .matrix_param(&pat_ident.ident.unraw().to_string())
+                    .is_some()
+                {
+                    let local = matrix_local(&pat_ident.ident);
+                    invoke_args.push(quote!(#local));
+                } else if let Some(provided) = provided_arg(ty, &krate) {
+                    invoke_args.push(provided);
+                } else if let Some(unmapped) = args.options.unmapped_arg() {
+                    invoke_args.push(unmapped);
                 } else {
-                    return Error::new(pat_ident.span(), "mapping is not defined for the argument")
-                        .to_compile_error()
-                        .into();
+                    let candidates = args.args.iter().map(|arg| &arg.ident);
+                    let mut err = Error::new(
+                        pat_ident.span(),
+                        format!(
+                            "mapping is not defined for the argument `{}`",
+                            pat_ident.ident
+                        ),
+                    );
+                    match closest_ident(&pat_ident.ident, candidates) {
+                        Some(similar) => err.combine(Error::new(
+                            similar.span(),
+                            format!("a mapping with a similar name exists: `{}`", similar),
+                        )),
+                        None => err.combine(Error::new(
+                            args.mappings_span,
+                            format!("add a mapping for `{}` here", pat_ident.ident),
+                        )),
+                    }
+                    return err.to_compile_error();
                 }
             }
+            None if !(info.bench && pos == 0) && args.options.unmapped.is_some() => {
+                invoke_args.extend(args.options.unmapped_arg());
+            }
             None => {
                 return Error::new(
                     arg.span(),
-                    "unexpected argument; only simple argument types are allowed (`&str`, `String`, `&[u8]`, `Vec<u8>`, `&Path`, etc)",
-                ).to_compile_error().into();
+                    "argument must be a plain identifier (like `input: &str`), as arguments are mapped by their names",
+                ).to_compile_error();
             }
         }
     }
@@ -253,59 +1394,551 @@ fn files_internal(
         quote!(None)
     };
 
+    let timeout = args.options.timeout_tokens();
+    let memory_limit = args.options.memory_limit_tokens();
+    let priority = args.options.priority();
+    let depends_on = args.options.depends_on_tokens();
+    let reduce = match args.options.reduce {
+        Some(ref reducer) if reducer.is_ident("lines") => {
+            quote!(Some(#krate::__internal::Reducer::Lines))
+        }
+        Some(ref reducer) if reducer.is_ident("bytes") => {
+            quote!(Some(#krate::__internal::Reducer::Bytes))
+        }
+        Some(ref reducer) => quote!(Some(#krate::__internal::Reducer::Custom(#reducer))),
+        None => quote!(None),
+    };
+    let dedup = args.options.dedup();
+    let content_matches = match args.options.content_matches {
+        Some(ref predicate) => quote!(Some(#predicate)),
+        None => quote!(None),
+    };
+    let sandbox = match args.options.sandbox {
+        Some(ref mode) => match sandbox_variant(&mode.to_string(), mode.span()) {
+            Ok(variant) => quote!(Some(#krate::__internal::Sandbox::#variant)),
+            Err(err) => return err.to_compile_error(),
+        },
+        None => quote!(None),
+    };
+    let order = match args.options.order_by {
+        Some(ref key) => quote! {
+            Some(|files: &mut [::std::path::PathBuf]| files.sort_by_cached_key(|path| #key(path)))
+        },
+        None => quote!(None),
+    };
+
+    // Pattern is defined, but is not consumed by any argument -- likely a typo.
     if pattern_idx.is_none() {
-        return Error::new(
-            Span::call_site(),
-            "must have exactly one pattern mapping defined via `pattern in r#\"<regular expression>\"`",
-        )
-            .to_compile_error()
-            .into();
+        let pattern = args.pattern();
+        let func_args = func_item
+            .sig
+            .inputs
+            .iter()
+            .skip(if info.bench { 1 } else { 0 })
+            .filter_map(match_arg)
+            .map(|(pat_ident, _)| &pat_ident.ident);
+        let mut err = Error::new(
+            pattern.ident.span(),
+            format!(
+                "pattern mapping `{}` does not match any argument",
+                pattern.ident
+            ),
+        );
+        if let Some(similar) = closest_ident(&pattern.ident, func_args) {
+            err.combine(Error::new(
+                similar.span(),
+                format!("an argument with a similar name exists: `{}`", similar),
+            ));
+        }
+        return err.to_compile_error();
+    }
+
+    // Expectations are collected from the test file matched by the pattern
+    for (pos, prefix) in comment_args {
+        invoke_args[pos] = quote! {
+            #krate::__internal::TakeArg::take(&mut #krate::Expectations::load(&paths_arg[#pattern_idx], #prefix))
+        };
+    }
+
+    // Matrix parameters are selected by the index of the combination of values given by the runner
+    let matrix_params = args
+        .options
+        .matrix
+        .as_ref()
+        .map_or(&[][..], |matrix| &matrix.params[..]);
+    for param in matrix_params {
+        let used = func_item
+            .sig
+            .inputs
+            .iter()
+            .filter_map(match_arg)
+            .any(|(pat_ident, _)| pat_ident.ident.unraw() == param.ident.unraw());
+        if !used {
+            return Error::new(
+                param.ident.span(),
+                format!(
+                    "matrix parameter `{}` does not match any argument",
+                    param.ident
+                ),
+            )
+            .to_compile_error();
+        }
     }
+    let mut stride = 1usize;
+    let mut matrix_lets = Vec::new();
+    for param in matrix_params.iter().rev() {
+        let local = matrix_local(&param.ident);
+        let len = param.values.len();
+        let indices = 0..len;
+        let values = &param.values;
+        matrix_lets.push(quote! {
+            let #local = match (variant / #stride) % #len {
+                #(#indices => #values,)*
+                _ => unreachable!(),
+            };
+        });
+        stride *= len;
+    }
+    matrix_lets.reverse();
+    let variant_param = if matrix_params.is_empty() {
+        quote!(_variant)
+    } else {
+        quote!(variant)
+    };
+    let matrix_desc = matrix_params.iter().map(|param| {
+        let name = param.ident.to_string();
+        let labels = param.labels();
+        quote!((#name, &[#(#labels),*]))
+    });
 
-    let (kind, bencher_param) = if info.bench {
+    let (kind, testfn, bencher_param) = if info.bench {
         (
             quote!(BenchFn),
-            quote!(bencher: &mut ::datatest::__internal::Bencher,),
+            quote!(#trampoline_func_ident),
+            quote!(bencher: &mut #krate::__internal::Bencher,),
         )
     } else {
-        (quote!(TestFn), quote!())
+        (quote!(TestFn), quote!(&#trampoline_func_ident), quote!())
+    };
+
+    // In compile-fail, differential and command modes, test function only declares the test (and
+    // is never called)
+    let special_modes = [
+        args.options
+            .compile_fail
+            .as_ref()
+            .filter(|flag| flag.value)
+            .map(|flag| ("compile_fail", flag.span)),
+        args.options
+            .differential
+            .as_ref()
+            .map(|differential| ("differential", differential.span)),
+        args.options
+            .command
+            .as_ref()
+            .map(|command| ("command", command.bracket_token.span.join())),
+    ];
+    let mut special_modes = special_modes.iter().flatten();
+    let special_mode = special_modes.next();
+    if let (Some((first, span)), Some((second, _))) = (special_mode, special_modes.next()) {
+        return Error::new(
+            *span,
+            format!(
+                "`{}` and `{}` options cannot be used together",
+                first, second
+            ),
+        )
+        .to_compile_error();
+    }
+    if let Some(&(option, span)) = special_mode {
+        if info.bench || !matrix_params.is_empty() {
+            return Error::new(
+                span,
+                format!(
+                    "`{}` option is not supported for benchmarks and parameter matrix",
+                    option
+                ),
+            )
+            .to_compile_error();
+        }
+        func_item
+            .attrs
+            .push(syn::parse_quote!(#[allow(unused_variables)]));
+    }
+    let invoke = if args.options.compile_fail() {
+        quote! {
+            let _ = #func_ident;
+            #krate::compile_fail(&paths_arg[#pattern_idx]);
+        }
+    } else if let Some(ref differential) = args.options.differential {
+        let reference = &differential.reference;
+        let candidate = &differential.candidate;
+        quote! {
+            let _ = #func_ident;
+            let reference = #reference(#(#invoke_args),*);
+            let candidate = #candidate(#(#invoke_args),*);
+            #krate::__internal::assert_same_output(&paths_arg[#pattern_idx], &reference, &candidate);
+        }
+    } else if let Some(ref command) = args.options.command {
+        let program = &command.elems[0];
+        let command_args = command.elems.iter().skip(1);
+        let placeholders = placeholders
+            .iter()
+            .map(|(name, idx)| quote!((#name, paths_arg[#idx].as_path())));
+        quote! {
+            let _ = #func_ident;
+            let placeholders: &[(&str, &::std::path::Path)] = &[#(#placeholders),*];
+            let mut command = ::std::process::Command::new(#krate::__internal::command_arg(#program, placeholders));
+            #(command.arg(#krate::__internal::command_arg(#command_args, placeholders));)*
+            #krate::golden_command(&paths_arg[#pattern_idx], command);
+        }
+    } else {
+        quote! {
+            #(#matrix_lets)*
+            let result = #func_ident(#(#invoke_args),*);
+            #krate::__internal::assert_test_result(result);
+        }
     };
 
-    let registration = test_registration(channel, &desc_ident);
+    let cfg = args.options.cfg_attr();
+
+    // Entry points are only used as a target for the IDE to run, actual test is executed by the
+    // test runner (see `datatest::runner`).
+    let mut entry_points = Vec::new();
+    let mut entry_fns = Vec::new();
+    if args.options.ide() {
+        let regex = regex::Regex::new(&args.pattern().value.value()).unwrap();
+        for relative in files_at_compile_time(&args.root, &regex) {
+            let mut entry_name = format!(
+                "{}{}__{}",
+                func_ident.unraw(),
+                suffix,
+                sanitize_ident(&relative)
+            );
+            while entry_points.iter().any(|(name, _)| *name == entry_name) {
+                entry_name += "_";
+            }
+            let entry_ident = Ident::new(&entry_name, func_ident.span());
+            entry_fns.push(quote! {
+                #cfg
+                #[test]
+                #[doc(hidden)]
+                #[allow(non_snake_case)]
+                fn #entry_ident() {}
+            });
+            entry_points.push((entry_name, relative));
+        }
+    }
+    let entry_points = entry_points
+        .iter()
+        .map(|(name, relative)| quote!((#name, #relative)));
+
+    let config_dependency = Config::dependency();
+    let registration = test_registration(channel, &desc_ident, &cfg, &krate);
+    let func_item = if stacked == 0 {
+        quote! {
+            #cfg
+            #func_item
+        }
+    } else {
+        TokenStream::new()
+    };
     let output = quote! {
         #registration
+        #cfg
         #[automatically_derived]
         #[allow(non_upper_case_globals)]
-        static #desc_ident: ::datatest::__internal::FilesTestDesc = ::datatest::__internal::FilesTestDesc {
-            name: concat!(module_path!(), "::", #func_name_str),
-            ignore: #ignore,
-            root: #root,
-            params: &[#(#params),*],
-            pattern: #pattern_idx,
-            ignorefn: #ignore_func_ref,
-            testfn: ::datatest::__internal::FilesTestFn::#kind(#trampoline_func_ident),
-            source_file: file!(),
+        static #desc_ident: #krate::__internal::FilesTestDesc = #krate::__internal::FilesTestDesc::new(
+            concat!(module_path!(), "::", #func_name_str),
+            #root,
+            #krate::__internal::FilesTestFn::#kind(#testfn),
+            file!(),
+        )
+        .with_ignore(#ignore)
+        .with_ignore_message(#ignore_message)
+        .with_only(#only)
+        .with_missing_root(#krate::__internal::MissingRoot::#missing_root)
+        .with_params(&[#(#params),*])
+        .with_reads(&[#(#reads),*])
+        .with_pattern(#pattern_idx)
+        .with_ignorefn(#ignore_func_ref)
+        .with_content_matches(#content_matches)
+        .with_name_template(#name_template)
+        .with_order(#order)
+        .with_timeout(#timeout)
+        .with_memory_limit(#memory_limit)
+        .with_priority(#priority)
+        .with_depends_on(#depends_on)
+        .with_resources(&[#(#resources),*])
+        .with_reduce(#reduce)
+        .with_dedup(#dedup)
+        .with_sandbox(#sandbox)
+        .with_xfail(#xfail)
+        .with_matrix(&[#(#matrix_desc),*])
+        .with_entry_points(&[#(#entry_points),*]);
+
+        #cfg
+        #[automatically_derived]
+        #[allow(non_snake_case)]
+        fn #trampoline_func_ident(#bencher_param paths_arg: &[::std::path::PathBuf], #variant_param: usize) {
+            #invoke
+        }
+
+        #(#entry_fns)*
+        #config_dependency
+
+        #func_item
+    };
+    output
+}
+
+/// List test files matching the pattern (paths relative to the root directory, using `/` as a
+/// separator), the same way test runner would do at runtime. Empty if not invoked via cargo or the
+/// root directory does not exist. Note that new files are only picked up when the test is
+/// recompiled.
+fn files_at_compile_time(root: &str, regex: &regex::Regex) -> Vec<String> {
+    fn scan(dir: &std::path::Path, prefix: &str, found: &mut Vec<String>) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
         };
+        for entry in entries.filter_map(Result::ok) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Skip hidden files
+            if name.starts_with('.') {
+                continue;
+            }
+            let relative = format!("{}{}", prefix, name);
+            let path = entry.path();
+            if path.is_dir() {
+                scan(&path, &format!("{}/", relative), found);
+            } else if path.is_file() {
+                found.push(relative);
+            }
+        }
+    }
+
+    let manifest_dir = match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => return Vec::new(),
+    };
+    let mut found = Vec::new();
+    scan(&manifest_dir.join(root), "", &mut found);
+    found.retain(|relative| regex.is_match(&format!("{}/{}", root, relative)));
+    found.sort();
+    found
+}
+
+/// Turn arbitrary string into a valid identifier part by replacing everything except ASCII letters
+/// and digits with `_`.
+fn sanitize_ident(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Local variable holding the value of the matrix parameter in the trampoline function.
+fn matrix_local(ident: &Ident) -> Ident {
+    Ident::new(&format!("__matrix_{}", ident.unraw()), ident.span())
+}
+
+/// Reject signatures the generated code cannot call: the test runner calls test functions
+/// synchronously, with arguments of concrete types.
+fn check_signature(sig: &syn::Signature) -> ParseResult<()> {
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new(
+            asyncness.span(),
+            "`async` test functions are not supported; block on the future inside the test instead",
+        ));
+    }
+    if let Some(variadic) = &sig.variadic {
+        return Err(Error::new(
+            variadic.span(),
+            "variadic test functions are not supported",
+        ));
+    }
+    for param in &sig.generics.params {
+        match param {
+            syn::GenericParam::Lifetime(_) => {}
+            syn::GenericParam::Type(_) | syn::GenericParam::Const(_) => return Err(Error::new(
+                param.span(),
+                "generic test functions are not supported; only lifetime parameters are allowed",
+            )),
+        }
+    }
+    for arg in &sig.inputs {
+        match arg {
+            FnArg::Receiver(receiver) => {
+                return Err(Error::new(
+                    receiver.span(),
+                    "test function cannot take `self`",
+                ))
+            }
+            FnArg::Typed(PatType { ty, .. }) => {
+                if let Some(span) = find_impl_trait(quote!(#ty)) {
+                    return Err(Error::new(
+                        span,
+                        "`impl Trait` arguments are not supported; use a concrete type (like `&str` or `&Path`)",
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Span of the `impl` keyword in the given type, if it has an `impl Trait` anywhere.
+fn find_impl_trait(tokens: TokenStream) -> Option<Span> {
+    tokens.into_iter().find_map(|token| match token {
+        TokenTree::Ident(ident) if ident == "impl" => Some(ident.span()),
+        TokenTree::Group(group) => find_impl_trait(group.stream()),
+        _ => None,
+    })
+}
+
+fn match_arg(arg: &FnArg) -> Option<(&PatIdent, &Type)> {
+    if let FnArg::Typed(PatType { pat, ty, .. }) = arg {
+        if let Pat::Ident(pat_ident) = pat.as_ref() {
+            return Some((pat_ident, ty));
+        }
+    }
+    None
+}
+
+/// Expression constructing the argument which does not need a mapping, if the argument has one of
+/// the types provided by `datatest`, named by its full path (see [`is_datatest_type`]):
+/// `datatest::SeededRng` (or `&mut datatest::SeededRng`) or `datatest::Shared<T>` (or
+/// `&datatest::Shared<T>`).
+fn provided_arg(ty: &Type, krate: &TokenStream) -> Option<TokenStream> {
+    let (reference, inner) = match ty {
+        Type::Reference(type_ref) => (Some(type_ref), type_ref.elem.as_ref()),
+        _ => (None, ty),
+    };
+    let mutable = reference.map(|type_ref| type_ref.mutability.is_some());
+    let ref_token = match mutable {
+        Some(true) => quote!(&mut),
+        Some(false) => quote!(&),
+        None => TokenStream::new(),
+    };
+    if is_datatest_type(inner, "SeededRng") && mutable != Some(false) {
+        Some(quote!(#ref_token #krate::SeededRng::for_current_case()))
+    } else if is_datatest_type(inner, "Shared") && mutable != Some(true) {
+        Some(quote!(#ref_token <#inner>::get()))
+    } else {
+        None
+    }
+}
+
+/// Benchmark function argument created from the harness of the test runner (`bencher`): benchmarks
+/// take either `test::Bencher` as is or `datatest::Bencher` wrapping it.
+fn bencher_arg(ty: &Type, krate: &TokenStream) -> TokenStream {
+    match ty {
+        Type::Reference(type_ref) if is_datatest_type(&type_ref.elem, "Bencher") => {
+            quote!(&mut #krate::__internal::bencher(bencher))
+        }
+        _ => quote!(bencher),
+    }
+}
 
-        #[automatically_derived]
-        #[allow(non_snake_case)]
-        fn #trampoline_func_ident(#bencher_param paths_arg: &[::std::path::PathBuf]) {
-            let result = #func_ident(#(#invoke_args),*);
-            ::datatest::__internal::assert_test_result(result);
+/// If the type is the given type of `datatest` named by its full path (`datatest::<name>` or
+/// `::datatest::<name>`), as imported names cannot be resolved by the procedural macro.
+fn is_datatest_type(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => {
+            let segments = &type_path.path.segments;
+            segments.len() == 2 && segments[0].ident == "datatest" && segments[1].ident == name
         }
+        _ => false,
+    }
+}
 
-        #func_item
-    };
-    output.into()
+/// Type with all its lifetimes replaced by `'_` (so it could be used in function bodies where the
+/// lifetime is inferred), or `None` if the type has no lifetimes (owned test case type).
+fn elide_lifetimes(tokens: TokenStream) -> Option<TokenStream> {
+    let mut elided = false;
+    let mut output = Vec::new();
+    let mut tokens = tokens.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '\'' => {
+                let name = tokens.next();
+                elided = true;
+                output.push(TokenTree::Punct(punct));
+                if let Some(name) = name {
+                    output.push(TokenTree::Ident(Ident::new("_", name.span())));
+                }
+            }
+            TokenTree::Group(group) => {
+                let stream = match elide_lifetimes(group.stream()) {
+                    Some(stream) => {
+                        elided = true;
+                        stream
+                    }
+                    None => group.stream(),
+                };
+                let mut replaced = proc_macro2::Group::new(group.delimiter(), stream);
+                replaced.set_span(group.span());
+                output.push(TokenTree::Group(replaced));
+            }
+            token => output.push(token),
+        }
+    }
+    if elided {
+        Some(output.into_iter().collect())
+    } else {
+        None
+    }
 }
 
-fn match_arg(arg: &FnArg) -> Option<(&PatIdent, &Type)> {
-    if let FnArg::Typed(PatType { pat, ty, .. }) = arg {
-        if let Pat::Ident(pat_ident) = pat.as_ref() {
-            return Some((pat_ident, ty));
+/// Find the identifier most similar to the given one (used to suggest fixes for typos). Only
+/// candidates which are "close enough" (in terms of edit distance) are considered.
+fn closest_ident<'a>(
+    ident: &Ident,
+    candidates: impl Iterator<Item = &'a Ident>,
+) -> Option<&'a Ident> {
+    let name = ident.to_string();
+    let max_distance = std::cmp::max(name.chars().count() / 3, 1);
+    candidates
+        .map(|candidate| (edit_distance(&name, &candidate.to_string()), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edit distance between two strings (Levenshtein distance where swapping two adjacent characters
+/// counts as a single edit).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut value = (dist[i - 1][j] + 1)
+                .min(dist[i][j - 1] + 1)
+                .min(dist[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                value = value.min(dist[i - 2][j - 2] + 1);
+            }
+            dist[i][j] = value;
         }
     }
-    None
+    dist[a.len()][b.len()]
+}
+
+/// Generate `Some(<value>)` or `None` expression for the optional value.
+fn option_tokens<T: quote::ToTokens>(value: &Option<T>) -> TokenStream {
+    match value {
+        Some(value) => quote!(Some(#value)),
+        None => quote!(None),
+    }
 }
 
 enum ShouldPanic {
@@ -316,8 +1949,78 @@ enum ShouldPanic {
 
 struct FuncInfo {
     ignore: bool,
+    /// Reason given via `#[ignore = "<reason>"]`
+    ignore_message: Option<String>,
     bench: bool,
     should_panic: ShouldPanic,
+    /// Test is focused via `#[only]`
+    only: bool,
+    /// Test cases are expected to fail, given via `#[xfail]` (or `#[xfail = "<reason>"]`)
+    xfail: bool,
+    /// Names of the resources test cases use exclusively, given via `#[resource("<name>", ..)]`
+    resources: ParseResult<Vec<syn::LitStr>>,
+}
+
+/// Expand module-level `#[files(..)]` / `#[data(..)]` attribute: every function in the module which
+/// is marked with `#[test]`, `#[bench]` or `#[<attr>(..)]` becomes a test generated by `expand`. Own
+/// `#[<attr>(..)]` attribute of the function (if any) is passed to `expand` to override the arguments
+/// given on the module.
+fn test_module<F>(mut item_mod: syn::ItemMod, attr: &str, mut expand: F) -> TokenStream
+where
+    F: FnMut(ItemFn, Option<syn::Attribute>) -> ParseResult<TokenStream>,
+{
+    let content = match item_mod.content {
+        Some((_, ref mut items)) => items,
+        None => {
+            return Error::new(
+                item_mod.ident.span(),
+                format!(
+                    "`#[{}(..)]` can only be applied to inline modules (`mod {} {{ .. }}`)",
+                    attr, item_mod.ident
+                ),
+            )
+            .to_compile_error()
+        }
+    };
+    let mut errors = TokenStream::new();
+    for item in content.iter_mut() {
+        let func_item = match item {
+            syn::Item::Fn(func_item) => func_item,
+            _ => continue,
+        };
+        let pos = func_item.attrs.iter().position(|a| {
+            a.path()
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == attr)
+        });
+        let is_test = func_item
+            .attrs
+            .iter()
+            .any(|a| a.path().is_ident("test") || a.path().is_ident("bench"));
+        if pos.is_none() && !is_test {
+            continue;
+        }
+        let mut func_item = func_item.clone();
+        let overrides = pos.map(|pos| func_item.attrs.remove(pos));
+        match expand(func_item, overrides) {
+            Ok(tokens) => *item = syn::Item::Verbatim(tokens),
+            Err(err) => errors.extend(err.to_compile_error()),
+        }
+    }
+    quote! {
+        #item_mod
+        #errors
+    }
+}
+
+/// Parse arguments of the attribute; attribute without arguments gives default value.
+fn parse_attr_args<T: Parse + Default>(attr: &syn::Attribute) -> ParseResult<T> {
+    if let syn::Meta::Path(_) = attr.meta {
+        Ok(T::default())
+    } else {
+        attr.parse_args()
+    }
 }
 
 /// Only allows certain attributes (`#[should_panic]`, for example) when used against a "regular"
@@ -329,7 +2032,7 @@ fn handle_common_attrs(func: &mut ItemFn, regular_test: bool) -> FuncInfo {
     let test_pos = func
         .attrs
         .iter()
-        .position(|attr| attr.path.is_ident("test"));
+        .position(|attr| attr.path().is_ident("test"));
     if let Some(pos) = test_pos {
         func.attrs.remove(pos);
     }
@@ -338,7 +2041,7 @@ fn handle_common_attrs(func: &mut ItemFn, regular_test: bool) -> FuncInfo {
     let bench_pos = func
         .attrs
         .iter()
-        .position(|attr| attr.path.is_ident("bench"));
+        .position(|attr| attr.path().is_ident("bench"));
     if let Some(pos) = bench_pos {
         func.attrs.remove(pos);
     }
@@ -347,18 +2050,69 @@ fn handle_common_attrs(func: &mut ItemFn, regular_test: bool) -> FuncInfo {
     let ignore_pos = func
         .attrs
         .iter()
-        .position(|attr| attr.path.is_ident("ignore"));
+        .position(|attr| attr.path().is_ident("ignore"));
+    let mut ignore_message = None;
     if let Some(pos) = ignore_pos {
+        if let syn::Meta::NameValue(ref nv) = func.attrs[pos].meta {
+            if let syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(ref value),
+                ..
+            }) = nv.value
+            {
+                ignore_message = Some(value.value());
+            }
+        }
         func.attrs.remove(pos);
     }
 
+    // Focus on this test via `#[only]`
+    let only_pos = func
+        .attrs
+        .iter()
+        .position(|attr| attr.path().is_ident("only"));
+    if let Some(pos) = only_pos {
+        func.attrs.remove(pos);
+    }
+
+    // Expected failures are only supported by `datatest` tests
+    let xfail_pos = func
+        .attrs
+        .iter()
+        .position(|attr| attr.path().is_ident("xfail"))
+        .filter(|_| !regular_test);
+    if let Some(pos) = xfail_pos {
+        func.attrs.remove(pos);
+    }
+
+    // Resources used exclusively, only supported by `datatest` tests
+    let mut resources = Ok(Vec::new());
+    while let Some(pos) = func
+        .attrs
+        .iter()
+        .position(|attr| attr.path().is_ident("resource"))
+        .filter(|_| !regular_test)
+    {
+        let attr = func.attrs.remove(pos);
+        let names = attr
+            .parse_args_with(Punctuated::<syn::LitStr, Comma>::parse_terminated)
+            .and_then(|names| match names.is_empty() {
+                true => Err(Error::new_spanned(&attr, "no resource names are given")),
+                false => Ok(names),
+            });
+        match (&mut resources, names) {
+            (Ok(resources), Ok(names)) => resources.extend(names),
+            (Ok(_), Err(err)) => resources = Err(err),
+            (Err(_), _) => {}
+        }
+    }
+
     let mut should_panic = ShouldPanic::No;
     if regular_test {
         // Regular tests support (on stable channel): allow `#[should_panic]`
         let should_panic_pos = func
             .attrs
             .iter()
-            .position(|attr| attr.path.is_ident("should_panic"));
+            .position(|attr| attr.path().is_ident("should_panic"));
         if let Some(pos) = should_panic_pos {
             let attr = &func.attrs[pos];
             should_panic = parse_should_panic(attr);
@@ -368,49 +2122,146 @@ fn handle_common_attrs(func: &mut ItemFn, regular_test: bool) -> FuncInfo {
 
     FuncInfo {
         ignore: ignore_pos.is_some(),
+        ignore_message,
         bench: bench_pos.is_some(),
         should_panic,
+        only: only_pos.is_some(),
+        xfail: xfail_pos.is_some(),
+        resources,
     }
 }
 
 fn parse_should_panic(attr: &syn::Attribute) -> ShouldPanic {
-    if let Ok(meta) = attr.parse_meta() {
-        if let syn::Meta::List(list) = meta {
-            for item in list.nested {
-                match item {
-                    syn::NestedMeta::Meta(syn::Meta::NameValue(ref nv))
-                        if nv.path.is_ident("expected") =>
-                    {
-                        if let syn::Lit::Str(ref value) = nv.lit {
-                            return ShouldPanic::YesWithMessage(value.value());
-                        }
-                    }
-                    _ => {}
-                }
+    let mut should_panic = ShouldPanic::Yes;
+    if let syn::Meta::List(_) = attr.meta {
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("expected") {
+                let value = meta.value()?.parse::<syn::LitStr>()?;
+                should_panic = ShouldPanic::YesWithMessage(value.value());
             }
-        }
+            Ok(())
+        });
     }
-    ShouldPanic::Yes
+    should_panic
+}
+
+/// Parse `#[data(...)]` attribute arguments. The first argument is the source of test cases,
+/// followed by options (see [`AttrOptions`]).
+struct DataTestArgs {
+    source: DataSource,
+    options: AttrOptions,
 }
 
-/// Parse `#[data(...)]` attribute arguments. It's either a function returning
-/// `Vec<datatest::DataTestCaseDesc<T>>` (where `T` is a test case type) or string literal, which
-/// is interpreted as `datatest::yaml("<path>")`
+/// Source of the test cases. It's either an expression evaluating to
+/// `Vec<datatest::DataTestCaseDesc<T>>` (where `T` is a test case type) or to a `Result` of it, or
+/// string literal, which is interpreted as `datatest::data_file("<path>")`, or `concat!(..)` /
+/// `env!(..)` macro call giving the path (like `concat!(env!("OUT_DIR"), "/cases.yaml")`), which
+/// is interpreted the same way.
 #[allow(clippy::large_enum_variant)]
-enum DataTestArgs {
+#[derive(Clone)]
+enum DataSource {
     Literal(syn::LitStr),
+    PathMacro(syn::ExprMacro),
     Expression(syn::Expr),
 }
 
 /// See `syn` crate documentation / sources for more examples.
 impl Parse for DataTestArgs {
     fn parse(input: ParseStream) -> ParseResult<Self> {
-        let lookahead = input.lookahead1();
-        if lookahead.peek(syn::LitStr) {
-            input.parse::<syn::LitStr>().map(DataTestArgs::Literal)
-        } else {
-            input.parse::<syn::Expr>().map(DataTestArgs::Expression)
+        let args = input.parse::<PartialDataArgs>()?;
+        DataTestArgs::from_partial(args, None)
+    }
+}
+
+impl DataTestArgs {
+    /// Build arguments from the (possibly incomplete) attribute arguments, taking missing parts
+    /// from the `defaults` (arguments of the module-level attribute).
+    fn from_partial(
+        args: PartialDataArgs,
+        defaults: Option<&PartialDataArgs>,
+    ) -> ParseResult<Self> {
+        let source = match args.source {
+            Some(source) => source,
+            None => match defaults.and_then(|d| d.source.clone()) {
+                Some(source) => source,
+                None => {
+                    return Err(Error::new(
+                        Span::call_site(),
+                        "test cases source is not specified (expected `#[data(<source>)]`)",
+                    ))
+                }
+            },
+        };
+        let options = match defaults {
+            Some(defaults) => args.options.merge(&defaults.options),
+            None => args.options,
+        };
+        options.reject_files_options("#[data(..)]")?;
+        let source = match source {
+            // Paths relative to `OUT_DIR` or to the source file are not affected by the configured
+            // prefix
+            DataSource::Literal(path) if options.out_dir() || options.source_relative() => {
+                DataSource::Literal(path)
+            }
+            DataSource::Literal(path) => {
+                let config = Config::load(path.span())?;
+                DataSource::Literal(syn::LitStr::new(
+                    &config.data_path(&path.value()),
+                    path.span(),
+                ))
+            }
+            source => source,
+        };
+        if let (Some(out_dir), Some(relative_to)) = (&options.out_dir, &options.relative_to) {
+            let mut err = Error::new(
+                out_dir.span,
+                "`out_dir` and `relative_to` options cannot be used together",
+            );
+            err.combine(Error::new(
+                relative_to.span(),
+                "`relative_to` is given here",
+            ));
+            return Err(err);
         }
+        let literal = matches!(source, DataSource::Literal(_));
+        if let (Some(relative_to), false) = (&options.relative_to, literal) {
+            return Err(Error::new(
+                relative_to.span(),
+                "`relative_to` option requires the data file path to be a string literal",
+            ));
+        }
+        if let (Some(out_dir), false) = (&options.out_dir, literal) {
+            return Err(Error::new(
+                out_dir.span,
+                "`out_dir` option requires the data file path to be a string literal",
+            ));
+        }
+        Ok(Self { source, options })
+    }
+}
+
+/// Arguments of `#[data(..)]` attribute where the source could be omitted. See `PartialFilesArgs`.
+#[derive(Default)]
+struct PartialDataArgs {
+    source: Option<DataSource>,
+    options: AttrOptions,
+}
+
+impl Parse for PartialDataArgs {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        // `<option> = <value>` starts the list of options
+        let source = if input.is_empty() || (input.peek(Ident) && input.peek2(syn::token::Eq)) {
+            None
+        } else if input.peek(syn::LitStr) {
+            Some(input.parse::<syn::LitStr>().map(DataSource::Literal)?)
+        } else {
+            Some(match input.parse::<syn::Expr>()? {
+                syn::Expr::Macro(call) if is_path_macro(&call) => DataSource::PathMacro(call),
+                expr => DataSource::Expression(expr),
+            })
+        };
+        let options = AttrOptions::parse_list(input, source.is_some())?;
+        Ok(Self { source, options })
     }
 }
 
@@ -461,103 +2312,699 @@ fn data_internal(
     func: proc_macro::TokenStream,
     channel: Registration,
 ) -> proc_macro::TokenStream {
-    let mut func_item = parse_macro_input!(func as ItemFn);
-    let cases: DataTestArgs = parse_macro_input!(args as DataTestArgs);
+    let item = parse_macro_input!(func as syn::Item);
+    match item {
+        syn::Item::Mod(item_mod) => {
+            let defaults = parse_macro_input!(args as PartialDataArgs);
+            test_module(item_mod, "data", |func_item, overrides| {
+                let overrides = overrides
+                    .map(|attr| parse_attr_args::<PartialDataArgs>(&attr))
+                    .transpose()?
+                    .unwrap_or_default();
+                let args = DataTestArgs::from_partial(overrides, Some(&defaults))?;
+                Ok(data_test(args, func_item, channel))
+            })
+            .into()
+        }
+        syn::Item::Fn(func_item) => {
+            let args = parse_macro_input!(args as DataTestArgs);
+            data_test(args, func_item, channel).into()
+        }
+        item => Error::new_spanned(
+            item,
+            "`#[data(..)]` can only be applied to functions and inline modules",
+        )
+        .to_compile_error()
+        .into(),
+    }
+}
+
+/// Generate test descriptor and trampoline for the `#[data(..)]` test function.
+fn data_test(args: DataTestArgs, mut func_item: ItemFn, channel: Registration) -> TokenStream {
     let info = handle_common_attrs(&mut func_item, false);
-    let cases = match cases {
-        DataTestArgs::Literal(path) => quote!(datatest::yaml(#path)),
-        DataTestArgs::Expression(expr) => quote!(#expr),
+    if let Err(err) = check_signature(&func_item.sig) {
+        return err.to_compile_error();
+    }
+    let krate = args.options.crate_path();
+    // Path of the data file the cases are read from (custom sources have none)
+    let data_path = match args.source {
+        DataSource::Literal(ref path) if args.options.out_dir() => {
+            let out_dir = match std::env::var_os("OUT_DIR") {
+                Some(dir) => std::path::PathBuf::from(dir),
+                None => {
+                    return Error::new(
+                        path.span(),
+                        "`OUT_DIR` is not set; `out_dir` option requires the crate to have a build script",
+                    )
+                    .to_compile_error()
+                }
+            };
+            if let Err(err) =
+                check_data_file_exists(&path.value(), out_dir.join(path.value()), path.span())
+            {
+                return err.to_compile_error();
+            }
+            Some(quote!(concat!(env!("OUT_DIR"), "/", #path)))
+        }
+        DataSource::Literal(ref path) if args.options.source_relative() => {
+            // Location of the source file is not known to the procedural macro, so the path is only
+            // resolved at runtime
+            Some(quote!(&#krate::__internal::source_relative_path(
+                env!("CARGO_MANIFEST_DIR"),
+                file!(),
+                #path,
+            )))
+        }
+        DataSource::Literal(ref path) => {
+            // Not invoked via cargo, nothing to check against
+            let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR");
+            if let Some(dir) = manifest_dir {
+                let resolved = std::path::PathBuf::from(dir).join(path.value());
+                if let Err(err) = check_data_file_exists(&path.value(), resolved, path.span()) {
+                    return err.to_compile_error();
+                }
+            }
+            Some(quote!(#path))
+        }
+        DataSource::PathMacro(ref call) => {
+            // Path could only be checked if we know all the parts of it
+            if let Some(resolved) = eval_path_macro(&syn::Expr::Macro(call.clone())) {
+                let display = quote!(#call).to_string().replace("! (", "!(");
+                if let Err(err) = check_data_file_exists(&display, resolved.into(), call.span()) {
+                    return err.to_compile_error();
+                }
+            }
+            Some(quote!(#call))
+        }
+        DataSource::Expression(_) => None,
+    };
+    let cases = match (&data_path, &args.source) {
+        (Some(path), _) => quote!(#krate::data_file(#path)),
+        (None, DataSource::Expression(expr)) => quote!(#expr),
+        (None, _) => unreachable!("data files always have the path"),
     };
+    if let (None, Some(schema)) = (&data_path, &args.options.schema) {
+        return Error::new(
+            schema.span(),
+            "`schema` option requires the path of the data file as the source",
+        )
+        .to_compile_error();
+    }
     let func_ident = &func_item.sig.ident;
 
-    let func_name_str = func_ident.to_string();
-    let desc_ident = Ident::new(&format!("__TEST_{}", func_ident), func_ident.span());
+    let func_name_str = func_ident.unraw().to_string();
+    let desc_ident = Ident::new(&format!("__TEST_{}", func_ident.unraw()), func_ident.span());
     let describe_func_ident = Ident::new(
-        &format!("__TEST_DESCRIBE_{}", func_ident),
+        &format!("__TEST_DESCRIBE_{}", func_ident.unraw()),
         func_ident.span(),
     );
     let trampoline_func_ident = Ident::new(
-        &format!("__TEST_TRAMPOLINE_{}", func_ident),
+        &format!("__TEST_TRAMPOLINE_{}", func_ident.unraw()),
+        func_ident.span(),
+    );
+    let validate_func_ident = Ident::new(
+        &format!("__TEST_VALIDATE_{}", func_ident.unraw()),
         func_ident.span(),
     );
 
     let ignore = info.ignore;
-    // FIXME: check file exists!
-    let mut args = func_item.sig.inputs.iter();
+    let ignore_message = option_tokens(&info.ignore_message);
+    let only = info.only;
+    let xfail = info.xfail;
+    let resources = match info.resources {
+        Ok(ref resources) => resources,
+        Err(ref err) => return err.to_compile_error(),
+    };
+    let mut inputs = func_item.sig.inputs.iter();
 
+    let mut bencher = TokenStream::new();
     if info.bench {
         // Skip Bencher argument
         // FIXME: verify it is &mut Bencher
-        args.next();
+        if let Some((_, ty)) = inputs.next().and_then(match_arg) {
+            bencher = bencher_arg(ty, &krate);
+        }
     }
 
-    let arg = args.next();
+    let arg = inputs.next();
+    // Any other arguments could only be constructed via `unmapped` option
+    let mut extra_args = Vec::new();
+    for extra in inputs {
+        let provided = match_arg(extra).and_then(|(_, ty)| provided_arg(ty, &krate));
+        match provided.or(args.options.unmapped_arg()) {
+            Some(unmapped) => extra_args.push(unmapped),
+            None => {
+                return Error::new(
+                    extra.span(),
+                    "unexpected argument; test function takes only the test case (use `unmapped = default` option to construct other arguments via `Default`)",
+                )
+                .to_compile_error()
+            }
+        }
+    }
     let ty = match arg {
         Some(FnArg::Typed(PatType { ty, .. })) => Some(ty.as_ref()),
         _ => None,
     };
-    let (ref_token, ty) = match ty {
-        Some(syn::Type::Reference(type_ref)) => (quote!(&), Some(type_ref.elem.as_ref())),
-        _ => (TokenStream::new(), ty),
+    // Trampoline owns the case, so test functions could borrow it mutably as well
+    let (ref_token, mut_token, ty) = match ty {
+        Some(syn::Type::Reference(type_ref)) if type_ref.mutability.is_some() => {
+            (quote!(&mut), quote!(mut), Some(type_ref.elem.as_ref()))
+        }
+        Some(syn::Type::Reference(type_ref)) => {
+            (quote!(&), TokenStream::new(), Some(type_ref.elem.as_ref()))
+        }
+        _ => (TokenStream::new(), TokenStream::new(), ty),
     };
 
+    // Test case types borrowing from the source (`Line<'a>`) are deserialized from the
+    // `BorrowedCase` right before the case runs, as the source must outlive them
+    let borrowed = ty.and_then(|ty| elide_lifetimes(quote!(#ty)));
+    if args.options.named() && borrowed.is_some() {
+        let span = args
+            .options
+            .named
+            .as_ref()
+            .map_or_else(Span::call_site, |flag| flag.span);
+        return Error::new(
+            span,
+            "`named` option is not supported for test case types borrowing from the source",
+        )
+        .to_compile_error();
+    }
+    // With `named = true`, source gives `Named<T>` cases, but the test function receives `T`
+    let (case_ty, case_arg) = match ty {
+        Some(ty) if args.options.named() => (Some(quote!(#krate::Named<#ty>)), quote!(arg.value)),
+        _ => (ty.map(|ty| quote!(#ty)), quote!(arg)),
+    };
     let (case_ctor, bencher_param, bencher_arg) = if info.bench {
         (
-            quote!(::datatest::__internal::DataTestFn::BenchFn(Box::new(::datatest::__internal::DataBenchFn(#trampoline_func_ident, case)))),
-            quote!(bencher: &mut ::datatest::__internal::Bencher,),
-            quote!(bencher,),
+            quote!(#krate::__internal::DataTestFn::BenchFn(Box::new(#krate::__internal::DataBenchFn(#trampoline_func_ident, case)))),
+            quote!(bencher: &mut #krate::__internal::Bencher,),
+            quote!(#bencher,),
         )
     } else {
         (
-            quote!(::datatest::__internal::DataTestFn::TestFn(Box::new(move || #trampoline_func_ident(case)))),
+            quote!(#krate::__internal::DataTestFn::TestFn(Box::new(move || #trampoline_func_ident(case)))),
             quote!(),
             quote!(),
         )
     };
 
-    let registration = test_registration(channel, &desc_ident);
+    // With name template, case names are fully rendered by the describe function
+    let name_template = option_tokens(&args.options.name);
+    let case_name = match args.options.name {
+        Some(ref template) => {
+            let segments = match parse_name_template(template) {
+                Ok(segments) => segments,
+                Err(err) => return err.to_compile_error(),
+            };
+            let parts = segments.into_iter().map(|segment| match segment {
+                NameSegment::Text(text) => quote!(#text),
+                NameSegment::Placeholder(placeholder) => match placeholder.as_str() {
+                    "fn" => quote!(#func_name_str),
+                    "index" => quote!(&index.to_string()),
+                    "case" => quote!(input.name.as_ref().unwrap_or(&input.location)),
+                    "location" => quote!(&input.location),
+                    field => {
+                        let member = match field.parse::<u32>() {
+                            Ok(idx) => syn::Member::Unnamed(syn::Index {
+                                index: idx,
+                                span: template.span(),
+                            }),
+                            Err(_) => syn::Member::Named(Ident::new(field, template.span())),
+                        };
+                        quote!(&::std::string::ToString::to_string(&case.#member))
+                    }
+                },
+            });
+            match borrowed {
+                // Cases which cannot be deserialized are named after their location
+                Some(ref elided) => quote! {
+                    match input.case.try_deserialize::<#elided>() {
+                        Ok(ref case) => {
+                            let mut name = String::new();
+                            #(name.push_str(#parts);)*
+                            Some(name)
+                        }
+                        Err(_) => None,
+                    }
+                },
+                None => {
+                    // Type of the case is not inferred yet at this point, so we need to annotate it
+                    let case_ty = case_ty.as_ref().map(|ty| quote!(: &#ty));
+                    quote! {
+                        {
+                            #[allow(unused_variables)]
+                            let case #case_ty = &input.case;
+                            let mut name = String::new();
+                            #(name.push_str(#parts);)*
+                            Some(name)
+                        }
+                    }
+                }
+            }
+        }
+        None => match borrowed {
+            Some(ref elided) => quote! {
+                {
+                    let _ = index;
+                    match input.name {
+                        Some(name) => Some(name),
+                        None => input
+                            .case
+                            .try_deserialize::<#elided>()
+                            .ok()
+                            .and_then(|case| #krate::__internal::TestNameWithDefault::name(&case)),
+                    }
+                }
+            },
+            None => quote! {
+                {
+                    let _ = index;
+                    input.name
+                }
+            },
+        },
+    };
+
+    // Borrowed cases are deserialized from the `BorrowedCase` right before the case runs, so
+    // invalid cases fail on their own
+    let (trampoline_param, trampoline_arg) = match borrowed {
+        Some(ref elided) => (
+            quote!(input: #krate::BorrowedCase),
+            quote!(let #mut_token arg: #elided = input.deserialize();),
+        ),
+        None => (quote!(#mut_token arg: #case_ty), quote!()),
+    };
+
+    // Cases are ordered by name, unless custom order is given. Key of each case is computed once;
+    // borrowed cases which cannot be deserialized go last, and fail once run.
+    let sort_cases = match (&args.options.order_by, &borrowed) {
+        (Some(key), Some(elided)) => quote! {
+            cases.sort_by_cached_key(|input| {
+                let case = input.case.try_deserialize::<#elided>();
+                (case.is_err(), case.ok().map(|case| #key(&case)))
+            });
+        },
+        (Some(key), None) => quote! {
+            cases.sort_by_cached_key(|input| #key(&input.case));
+        },
+        (None, _) => quote!(),
+    };
+    // Sort is stable, so cases without names keep the order of the source
+    let sort_names = match args.options.order_by {
+        Some(_) => quote!(),
+        None => quote! {
+            result.sort_by(|a, b| a.name.cmp(&b.name));
+        },
+    };
+
+    let duplicates = match args.options.duplicates {
+        Some(ref policy) => match duplicates_variant(&policy.to_string(), policy.span()) {
+            Ok(variant) => variant,
+            Err(err) => return err.to_compile_error(),
+        },
+        None => Ident::new("Keep", Span::call_site()),
+    };
+
+    let timeout = args.options.timeout_tokens();
+    let memory_limit = args.options.memory_limit_tokens();
+    let priority = args.options.priority();
+    let depends_on = args.options.depends_on_tokens();
+    let cfg = args.options.cfg_attr();
+    let config_dependency = Config::dependency();
+    let registration = test_registration(channel, &desc_ident, &cfg, &krate);
+
+    // Validation pre-pass checks the cases of the data file against the test case type (cases of
+    // tests without the case argument are only checked against the schema)
+    let (validate_func, validatefn) = match data_path {
+        Some(ref path) if borrowed.is_none() => {
+            let case_ty = case_ty.unwrap_or_else(|| quote!(#krate::__internal::IgnoredAny));
+            let schema = option_tokens(&args.options.schema);
+            let func = quote! {
+                #cfg
+                #[automatically_derived]
+                #[allow(non_snake_case)]
+                fn #validate_func_ident() -> Vec<String> {
+                    #krate::validate_data_file::<#case_ty>(#path, #schema)
+                }
+            };
+            (func, quote!(Some(#validate_func_ident)))
+        }
+        _ => (quote!(), quote!(None)),
+    };
+
     let output = quote! {
         #registration
+        #cfg
         #[automatically_derived]
         #[allow(non_upper_case_globals)]
-        static #desc_ident: ::datatest::__internal::DataTestDesc = ::datatest::__internal::DataTestDesc {
-            name: concat!(module_path!(), "::", #func_name_str),
-            ignore: #ignore,
-            describefn: #describe_func_ident,
-            source_file: file!(),
-        };
+        static #desc_ident: #krate::__internal::DataTestDesc = #krate::__internal::DataTestDesc::new(
+            concat!(module_path!(), "::", #func_name_str),
+            &#describe_func_ident,
+            file!(),
+        )
+        .with_ignore(#ignore)
+        .with_ignore_message(#ignore_message)
+        .with_only(#only)
+        .with_name_template(#name_template)
+        .with_timeout(#timeout)
+        .with_memory_limit(#memory_limit)
+        .with_priority(#priority)
+        .with_depends_on(#depends_on)
+        .with_resources(&[#(#resources),*])
+        .with_xfail(#xfail)
+        .with_validatefn(#validatefn);
 
+        #cfg
         #[automatically_derived]
         #[allow(non_snake_case)]
-        fn #trampoline_func_ident(#bencher_param arg: #ty) {
-            let result = #func_ident(#bencher_arg #ref_token arg);
-            ::datatest::__internal::assert_test_result(result);
+        fn #trampoline_func_ident(#bencher_param #trampoline_param) {
+            #trampoline_arg
+            let result = #func_ident(#bencher_arg #ref_token #case_arg #(, #extra_args)*);
+            #krate::__internal::assert_test_result(result);
         }
 
+        #cfg
         #[automatically_derived]
         #[allow(non_snake_case)]
-        fn #describe_func_ident() -> Vec<::datatest::DataTestCaseDesc<::datatest::__internal::DataTestFn>> {
-            let result = #cases
+        fn #describe_func_ident() -> ::std::result::Result<
+            Vec<#krate::DataTestCaseDesc<#krate::__internal::DataTestFn>>,
+            String,
+        > {
+            #[allow(unused_mut)]
+            let mut cases = #krate::__internal::IntoCases::into_cases(#cases)?;
+            #sort_cases
+            let mut contents = Vec::new();
+            let mut result = cases
                 .into_iter()
-                .map(|input| {
-                    let case = input.case;
-                    ::datatest::DataTestCaseDesc {
-                        case: #case_ctor,
-                        name: input.name,
-                        location: input.location,
-                    }
+                .enumerate()
+                .map(|(index, input)| {
+                    let name = #case_name;
+                    contents.push(#krate::__internal::TestContentWithDefault::content(&input.case));
+                    let mut desc = input.map(|case| #case_ctor);
+                    desc.name = name;
+                    desc
                 })
                 .collect::<Vec<_>>();
-            assert!(!result.is_empty(), "no test cases were found!");
-            result
+            if result.is_empty() {
+                return Err(String::from("no test cases were found"));
+            }
+            #krate::__internal::check_duplicate_cases(
+                &mut result,
+                &contents,
+                #krate::__internal::DuplicateCases::#duplicates,
+            )?;
+            #sort_names
+            Ok(result)
         }
+        #validate_func
+        #config_dependency
 
+        #cfg
         #func_item
     };
-    output.into()
+    output
+}
+
+/// Project configuration (`datatest.toml`), defining defaults for the attributes. Only the part
+/// consulted at compile time is parsed here; runner reads its own part at runtime.
+///
+/// ```toml
+/// [files]
+/// # Prefix for relative root directories of `#[files(..)]` tests
+/// root = "tests/fixtures"
+/// # Default for `missing_root` option
+/// missing_root = "skip"
+///
+/// [data]
+/// # Prefix for relative data file paths of `#[data(..)]` tests
+/// root = "tests/cases"
+/// ```
+#[derive(Default, serde::Deserialize)]
+struct Config {
+    #[serde(default)]
+    files: FilesConfig,
+    #[serde(default)]
+    data: DataConfig,
+}
+
+#[derive(Default, serde::Deserialize)]
+struct FilesConfig {
+    root: Option<String>,
+    /// Default for `missing_root` option
+    missing_root: Option<String>,
+}
+
+#[derive(Default, serde::Deserialize)]
+struct DataConfig {
+    root: Option<String>,
+}
+
+impl Config {
+    const FILE_NAME: &'static str = "datatest.toml";
+
+    /// Find the configuration file: either in the crate root directory or in any of its parent
+    /// directories up to the root of the workspace the crate belongs to (so it could be shared by
+    /// all crates in the workspace). Search stops at the crate root directory if the crate is not a
+    /// member of a workspace, so files outside of the project are never picked up.
+    fn find() -> Option<std::path::PathBuf> {
+        let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")?;
+        let dirs = std::path::Path::new(&manifest_dir)
+            .ancestors()
+            .collect::<Vec<_>>();
+        let root = dirs
+            .iter()
+            .position(|dir| Config::is_workspace_root(dir))
+            .unwrap_or(0);
+        dirs[..=root]
+            .iter()
+            .map(|dir| dir.join(Config::FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// If the directory has `Cargo.toml` with the `[workspace]` section.
+    fn is_workspace_root(dir: &std::path::Path) -> bool {
+        std::fs::read_to_string(dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|manifest| toml::from_str::<toml::Value>(&manifest).ok())
+            .is_some_and(|manifest| manifest.get("workspace").is_some())
+    }
+
+    /// Load the configuration file, if any. Errors are reported at the given span.
+    fn load(span: Span) -> ParseResult<Config> {
+        let path = match Config::find() {
+            Some(path) => path,
+            None => return Ok(Config::default()),
+        };
+        std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|content| toml::from_str(&content).map_err(|err| err.to_string()))
+            .map_err(|err| {
+                Error::new(
+                    span,
+                    format!("cannot read configuration '{}': {}", path.display(), err),
+                )
+            })
+    }
+
+    /// Make generated code depend on the configuration file, so tests are recompiled when it
+    /// changes.
+    fn dependency() -> TokenStream {
+        match Config::find() {
+            Some(path) => {
+                let path = path.to_string_lossy();
+                quote!(
+                    const _: &[u8] = include_bytes!(#path);
+                )
+            }
+            None => TokenStream::new(),
+        }
+    }
+
+    fn files_root(&self, root: &str) -> String {
+        prefixed(self.files.root.as_ref(), root)
+    }
+
+    fn data_path(&self, path: &str) -> String {
+        prefixed(self.data.root.as_ref(), path)
+    }
+}
+
+/// Variant of `datatest::__internal::Sandbox` for the `sandbox` option.
+fn sandbox_variant(mode: &str, span: Span) -> ParseResult<Ident> {
+    let variant = match mode {
+        "copy" => "Copy",
+        "chdir" => "Chdir",
+        _ => {
+            return Err(Error::new(
+                span,
+                format!(
+                    "unknown sandbox mode `{}`; expected `copy` or `chdir`",
+                    mode
+                ),
+            ))
+        }
+    };
+    Ok(Ident::new(variant, span))
+}
+
+/// Variant of `datatest::__internal::MissingRoot` for the `missing_root` policy.
+fn missing_root_variant(policy: &str, span: Span) -> ParseResult<Ident> {
+    let variant = match policy {
+        "fail" => "Fail",
+        "skip" => "Skip",
+        "warn" => "Warn",
+        _ => {
+            return Err(Error::new(
+                span,
+                format!(
+                    "unknown missing root policy `{}`; expected `fail`, `skip` or `warn`",
+                    policy
+                ),
+            ))
+        }
+    };
+    Ok(Ident::new(variant, span))
+}
+
+/// Parse size like `"512MB"` (units are `KB`, `MB` and `GB`, powers of 1024) into bytes.
+fn parse_size(lit: &syn::LitStr) -> ParseResult<u64> {
+    let value = lit.value();
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let scale = match unit.trim() {
+        "KB" => 1024.0,
+        "MB" => 1024.0 * 1024.0,
+        "GB" => 1024.0 * 1024.0 * 1024.0,
+        _ => {
+            return Err(Error::new(
+                lit.span(),
+                format!(
+                    "invalid size `{}`; expected a number followed by `KB`, `MB` or `GB`",
+                    value
+                ),
+            ))
+        }
+    };
+    match number.parse::<f64>() {
+        Ok(number) => Ok((number * scale) as u64),
+        Err(_) => Err(Error::new(lit.span(), format!("invalid size `{}`", value))),
+    }
+}
+
+/// Variant of `datatest::__internal::DuplicateCases` for the `duplicates` policy.
+fn duplicates_variant(policy: &str, span: Span) -> ParseResult<Ident> {
+    let variant = match policy {
+        "keep" => "Keep",
+        "fail" => "Fail",
+        "index" => "Index",
+        _ => {
+            return Err(Error::new(
+                span,
+                format!(
+                    "unknown duplicates policy `{}`; expected `keep`, `fail` or `index`",
+                    policy
+                ),
+            ))
+        }
+    };
+    Ok(Ident::new(variant, span))
+}
+
+/// If the value of `relative_to` option is `source` (rather than `crate`).
+fn relative_to_source(base: &Ident) -> ParseResult<bool> {
+    match base.to_string().as_str() {
+        "crate" => Ok(false),
+        "source" => Ok(true),
+        other => Err(Error::new(
+            base.span(),
+            format!(
+                "unknown `relative_to` base `{}`; expected `crate` or `source`",
+                other
+            ),
+        )),
+    }
+}
+
+/// Join relative path with the prefix from the configuration (absolute paths are used as-is).
+fn prefixed(prefix: Option<&String>, path: &str) -> String {
+    match prefix {
+        Some(prefix) if std::path::Path::new(path).is_relative() => std::path::Path::new(prefix)
+            .join(path)
+            .to_string_lossy()
+            .into_owned(),
+        _ => path.to_string(),
+    }
+}
+
+/// Verify that the data file given as a literal exists, so misspelled paths are reported at compile
+/// time rather than as a runtime failure. Paths are relative to the crate root, which is also the
+/// working directory when tests are executed.
+fn check_data_file_exists(
+    path: &str,
+    resolved: std::path::PathBuf,
+    span: Span,
+) -> Result<(), Error> {
+    if !resolved.is_file() {
+        return Err(Error::new(
+            span,
+            format!(
+                "data file '{}' does not exist (resolved to '{}')",
+                path,
+                resolved.display()
+            ),
+        ));
+    }
+    Ok(())
 }
 
-fn test_registration(channel: Registration, desc_ident: &syn::Ident) -> TokenStream {
+/// If the macro call gives the path of the data file (`concat!(..)` or `env!(..)`).
+fn is_path_macro(call: &syn::ExprMacro) -> bool {
+    call.mac
+        .path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "concat" || segment.ident == "env")
+}
+
+/// Evaluate the path given by string literals, `concat!(..)` and `env!(..)` macro calls the same
+/// way compiler does (`None` if it has other parts or environment variable is not set).
+fn eval_path_macro(expr: &syn::Expr) -> Option<String> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit),
+            ..
+        }) => Some(lit.value()),
+        syn::Expr::Macro(call) if is_path_macro(call) => {
+            let parts = call
+                .mac
+                .parse_body_with(Punctuated::<syn::Expr, Comma>::parse_terminated)
+                .ok()?;
+            let name = &call.mac.path.segments.last()?.ident;
+            if name == "env" {
+                let var = eval_path_macro(parts.first()?)?;
+                std::env::var(var).ok()
+            } else {
+                parts.iter().map(eval_path_macro).collect()
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Generate registration code for the test descriptor. `cfg` is the (possibly empty) `#[cfg(..)]`
+/// attribute to put on the generated items, `krate` is the path to the `datatest` crate.
+fn test_registration(
+    channel: Registration,
+    desc_ident: &syn::Ident,
+    cfg: &TokenStream,
+    krate: &TokenStream,
+) -> TokenStream {
     match channel {
         // On nightly, we rely on `custom_test_frameworks` feature
         Registration::Nightly => quote!(#[test_case]),
@@ -567,17 +3014,18 @@ fn test_registration(channel: Registration, desc_ident: &syn::Ident) -> TokenStr
                 syn::Ident::new(&format!("{}__REGISTRATION", desc_ident), desc_ident.span());
             let check_fn = syn::Ident::new(&format!("{}__CHECK", desc_ident), desc_ident.span());
             let tokens = quote! {
+                #cfg
                 #[automatically_derived]
                 #[allow(non_snake_case)]
-                #[datatest::__internal::ctor]
+                #[#krate::__internal::ctor]
                 fn #registration_fn() {
-                    use ::datatest::__internal::RegistrationNode;
+                    use #krate::__internal::RegistrationNode;
                     static mut REGISTRATION: RegistrationNode = RegistrationNode {
                         descriptor: &#desc_ident,
                         next: None,
                     };
                     // This runs only once during initialization, so should be safe
-                    ::datatest::__internal::register(unsafe { &mut REGISTRATION });
+                    #krate::__internal::register(unsafe { &mut REGISTRATION });
                 }
 
                 // Make sure we our registry was actually scanned!
@@ -585,12 +3033,13 @@ fn test_registration(channel: Registration, desc_ident: &syn::Ident) -> TokenStr
                 // test runner (either by replacing the whole harness or by overriding test runner).
                 // So, for every test we have registered, we make sure this test actually gets
                 // executed.
+                #cfg
                 #[automatically_derived]
                 #[allow(non_snake_case)]
                 mod #check_fn {
-                    #[datatest::__internal::dtor]
+                    #[#krate::__internal::dtor]
                     fn check_fn() {
-                        ::datatest::__internal::check_test_runner();
+                        #krate::__internal::check_test_runner();
                     }
                 }
             };
@@ -603,34 +3052,44 @@ fn test_registration(channel: Registration, desc_ident: &syn::Ident) -> TokenStr
 /// used when the whole test harness is replaced.
 #[proc_macro_attribute]
 pub fn test_ctor_registration(
-    _args: proc_macro::TokenStream,
+    args: proc_macro::TokenStream,
     func: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
     let mut func_item = parse_macro_input!(func as ItemFn);
+    let args = parse_macro_input!(args as TestArgs);
+    let krate = crate_path(&args.krate);
     let info = handle_common_attrs(&mut func_item, true);
+    if let Err(err) = check_signature(&func_item.sig) {
+        return err.to_compile_error().into();
+    }
     let func_ident = &func_item.sig.ident;
-    let func_name_str = func_ident.to_string();
-    let desc_ident = Ident::new(&format!("__TEST_{}", func_ident), func_ident.span());
+    let func_name_str = func_ident.unraw().to_string();
+    let desc_ident = Ident::new(&format!("__TEST_{}", func_ident.unraw()), func_ident.span());
 
     let ignore = info.ignore;
+    let ignore_message = option_tokens(&info.ignore_message);
+    let only = info.only;
     let should_panic = match info.should_panic {
-        ShouldPanic::No => quote!(::datatest::__internal::RegularShouldPanic::No),
-        ShouldPanic::Yes => quote!(::datatest::__internal::RegularShouldPanic::Yes),
+        ShouldPanic::No => quote!(#krate::__internal::RegularShouldPanic::No),
+        ShouldPanic::Yes => quote!(#krate::__internal::RegularShouldPanic::Yes),
         ShouldPanic::YesWithMessage(v) => {
-            quote!(::datatest::__internal::RegularShouldPanic::YesWithMessage(#v))
+            quote!(#krate::__internal::RegularShouldPanic::YesWithMessage(#v))
         }
     };
-    let registration = test_registration(Registration::Ctor, &desc_ident);
+    let registration =
+        test_registration(Registration::Ctor, &desc_ident, &TokenStream::new(), &krate);
     let output = quote! {
         #registration
         #[automatically_derived]
         #[allow(non_upper_case_globals)]
-        static #desc_ident: ::datatest::__internal::RegularTestDesc = ::datatest::__internal::RegularTestDesc {
+        static #desc_ident: #krate::__internal::RegularTestDesc = #krate::__internal::RegularTestDesc {
             name: concat!(module_path!(), "::", #func_name_str),
             ignore: #ignore,
+            ignore_message: #ignore_message,
+            only: #only,
             testfn: || {
                 let result = #func_ident();
-                ::datatest::__internal::assert_test_result(result);
+                #krate::__internal::assert_test_result(result);
             },
             should_panic: #should_panic,
             source_file: file!(),
@@ -648,11 +3107,93 @@ fn guarded_test_attribute(
     implementation: Ident,
 ) -> proc_macro::TokenStream {
     let args: TokenStream = args.into();
+    let krate = crate_path(&find_crate_option(&args));
     let header = quote! {
         #[cfg(test)]
-        #[::datatest::__internal::#implementation(#args)]
+        #[#krate::__internal::#implementation(#args)]
     };
     let mut out: proc_macro::TokenStream = header.into();
     out.extend(item);
     out
 }
+
+/// Arguments of the `#[datatest::test]` attribute.
+struct TestArgs {
+    krate: Option<syn::Path>,
+}
+
+impl Parse for TestArgs {
+    fn parse(input: ParseStream) -> ParseResult<Self> {
+        let options = AttrOptions::parse_list(input, false)?;
+        if let Some(ref name) = options.name {
+            return Err(Error::new(
+                name.span(),
+                "`name` option is not supported for regular tests",
+            ));
+        }
+        if let Some(ref cfg) = options.cfg {
+            return Err(Error::new_spanned(
+                cfg,
+                "`cfg` option is not supported for regular tests, use `#[cfg(..)]` attribute",
+            ));
+        }
+        options.reject_files_options("#[test]")?;
+        options.reject_data_options("#[test]")?;
+        if let Some(ref order_by) = options.order_by {
+            return Err(Error::new_spanned(
+                order_by,
+                "`order_by` option is not supported for regular tests",
+            ));
+        }
+        if let Some(ref timeout) = options.timeout {
+            return Err(Error::new(
+                timeout.span(),
+                "`timeout` option is not supported for regular tests",
+            ));
+        }
+        if let Some(ref memory_limit) = options.memory_limit {
+            return Err(Error::new(
+                memory_limit.span(),
+                "`memory_limit` option is not supported for regular tests",
+            ));
+        }
+        if let Some(ref priority) = options.priority {
+            return Err(Error::new(
+                priority.span,
+                "`priority` option is not supported for regular tests",
+            ));
+        }
+        if let Some(ref depends_on) = options.depends_on {
+            return Err(Error::new(
+                depends_on.span,
+                "`depends_on` option is not supported for regular tests",
+            ));
+        }
+        Ok(TestArgs {
+            krate: options.krate,
+        })
+    }
+}
+
+/// Path to the `datatest` crate: either given by the `crate = "<path>"` option or `::datatest`.
+fn crate_path(krate: &Option<syn::Path>) -> TokenStream {
+    match krate {
+        Some(path) => quote!(#path),
+        None => quote!(::datatest),
+    }
+}
+
+/// Find the `crate = "<path>"` option in the raw attribute arguments. We need it before the actual
+/// parsing happens, to know the path to the internal attribute implementing the test.
+fn find_crate_option(args: &TokenStream) -> Option<syn::Path> {
+    let tokens = args.clone().into_iter().collect::<Vec<_>>();
+    tokens.windows(3).find_map(|window| match window {
+        [TokenTree::Ident(ident), TokenTree::Punct(eq), TokenTree::Literal(lit)]
+            if ident == "crate" && eq.as_char() == '=' =>
+        {
+            let lit: syn::LitStr = syn::parse2(TokenTree::Literal(lit.clone()).into()).ok()?;
+            lit.parse().ok()
+        }
+        _ => None,
+    })
+}
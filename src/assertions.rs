@@ -0,0 +1,230 @@
+//! Assertion helpers for data-driven tests
+use crate::compile_fail::{is_blessing, BLESS_ENV_VAR};
+use crate::diff::text_diff;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt::{Debug, Display};
+use std::path::Path;
+
+/// Check the result of the operation under test against the expected error of the test case. This
+/// allows positive and negative cases to share the same test function, with the test case
+/// declaring the expected error in an optional field (by convention, `error`, or `expect_err`):
+///
+/// * if `expected` is `None`, the result must be `Ok` and the value is returned (to be checked by
+///   the test);
+/// * otherwise, the result must be an error which message contains the expected one (and `None`
+///   is returned).
+///
+/// ```rust
+/// #[derive(serde::Deserialize)]
+/// struct ParseCase {
+///   input: String,
+///   #[serde(default)]
+///   expected: Option<i32>,
+///   #[serde(default, alias = "expect_err")]
+///   error: Option<String>,
+/// }
+///
+/// fn parse_test(case: &ParseCase) {
+///   if let Some(value) = datatest::assert_err_matches(case.input.parse::<i32>(), &case.error) {
+///     assert_eq!(Some(value), case.expected);
+///   }
+/// }
+/// ```
+#[track_caller]
+pub fn assert_err_matches<T, E, S>(result: Result<T, E>, expected: &Option<S>) -> Option<T>
+where
+    T: Debug,
+    E: Display,
+    S: AsRef<str>,
+{
+    match (result, expected) {
+        (Ok(value), None) => Some(value),
+        (Ok(value), Some(expected)) => panic!(
+            "expected error containing `{}`, got Ok({:?})",
+            expected.as_ref(),
+            value
+        ),
+        (Err(err), None) => panic!("expected Ok, got error `{}`", err),
+        (Err(err), Some(expected)) => {
+            let message = err.to_string();
+            if !message.contains(expected.as_ref()) {
+                panic!(
+                    "expected error containing `{}`, got error `{}`",
+                    expected.as_ref(),
+                    message
+                );
+            }
+            None
+        }
+    }
+}
+
+/// Compare outputs of the reference and the candidate implementations for the test file `fixture`
+/// (used by `#[datatest::files]` tests with `differential(..)` option). Failure message points to
+/// the first line where pretty-printed outputs diverge.
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_same_output<T: PartialEq + Debug>(fixture: &Path, reference: &T, candidate: &T) {
+    if reference == candidate {
+        return;
+    }
+    let reference = format!("{:#?}", reference);
+    let candidate = format!("{:#?}", candidate);
+    let (line, first_reference, first_candidate) = first_difference(&reference, &candidate);
+    panic!(
+        "reference and candidate diverged on '{}'\n\
+         first difference on line {} of the output:\n  reference: {}\n  candidate: {}\n\
+         reference output:\n{}\ncandidate output:\n{}",
+        fixture.display(),
+        line,
+        first_reference.trim(),
+        first_candidate.trim(),
+        reference,
+        candidate
+    );
+}
+
+/// First line where the texts differ: line number (starting from 1) and the line of each text
+/// (`<end>` if the text has fewer lines).
+fn first_difference<'a>(left: &'a str, right: &'a str) -> (usize, &'a str, &'a str) {
+    let mut left_lines = left.lines();
+    let mut right_lines = right.lines();
+    let mut line = 1;
+    loop {
+        match (left_lines.next(), right_lines.next()) {
+            (Some(a), Some(b)) if a == b => line += 1,
+            (a, b) => return (line, a.unwrap_or("<end>"), b.unwrap_or("<end>")),
+        }
+    }
+}
+
+/// Compare the text with the contents of the file, see [`assert_matches_file!`](crate::assert_matches_file).
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_matches_file(actual: &impl AsRef<str>, path: &impl AsRef<Path>) {
+    let (actual, path) = (actual.as_ref(), path.as_ref());
+    if is_blessing(path) {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .unwrap_or_else(|err| panic!("cannot create '{}': {}", parent.display(), err));
+        }
+        std::fs::write(path, actual)
+            .unwrap_or_else(|err| panic!("cannot write '{}': {}", path.display(), err));
+        return;
+    }
+    let expected = match std::fs::read_to_string(path) {
+        Ok(expected) => expected.replace("\r\n", "\n"),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => panic!(
+            "file '{}' does not exist (run with {}=1 to create it)\nactual:\n{}",
+            path.display(),
+            BLESS_ENV_VAR,
+            actual
+        ),
+        Err(err) => panic!("cannot read '{}': {}", path.display(), err),
+    };
+    let actual = actual.replace("\r\n", "\n");
+    if expected == actual {
+        return;
+    }
+    panic!(
+        "value does not match '{}' (run with {}=1 to update the file)\n{}",
+        path.display(),
+        BLESS_ENV_VAR,
+        text_diff(&expected, &actual)
+    );
+}
+
+/// Maximum number of differences listed by [`assert_json_eq`].
+const MAX_JSON_DIFFERENCES: usize = 20;
+
+/// Compare values structurally as JSON, see [`assert_json_eq!`](crate::assert_json_eq).
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_json_eq<A: Serialize + ?Sized, E: Serialize + ?Sized>(actual: &A, expected: &E) {
+    let to_json = |value: Result<Value, serde_json::Error>| {
+        value.unwrap_or_else(|err| panic!("cannot convert value to JSON: {}", err))
+    };
+    let actual = to_json(serde_json::to_value(actual));
+    let expected = to_json(serde_json::to_value(expected));
+    let mut differences = Vec::new();
+    json_differences(
+        String::from("expected"),
+        &expected,
+        &actual,
+        &mut differences,
+    );
+    if differences.is_empty() {
+        return;
+    }
+    let count = differences.len();
+    differences.truncate(MAX_JSON_DIFFERENCES);
+    let mut message = format!("JSON values differ in {} places:\n", count);
+    for difference in &differences {
+        message.push_str("  ");
+        message.push_str(difference);
+        message.push('\n');
+    }
+    if count > MAX_JSON_DIFFERENCES {
+        message.push_str(&format!(
+            "  ... and {} more\n",
+            count - MAX_JSON_DIFFERENCES
+        ));
+    }
+    panic!("{}", message.trim_end());
+}
+
+/// Collect differences between the expected and the actual JSON values, prefixed with the path of
+/// the differing value (like `expected[3].name`).
+fn json_differences(path: String, expected: &Value, actual: &Value, out: &mut Vec<String>) {
+    match (expected, actual) {
+        (Value::Object(expected), Value::Object(actual)) => {
+            for (key, expected) in expected {
+                let path = json_key_path(&path, key);
+                match actual.get(key) {
+                    Some(actual) => json_differences(path, expected, actual, out),
+                    None => out.push(format!("{}: missing (expected {})", path, expected)),
+                }
+            }
+            for (key, actual) in actual {
+                if !expected.contains_key(key) {
+                    let path = json_key_path(&path, key);
+                    out.push(format!("{}: unexpected (got {})", path, actual));
+                }
+            }
+        }
+        (Value::Array(expected), Value::Array(actual)) => {
+            for (idx, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                json_differences(format!("{}[{}]", path, idx), expected, actual, out);
+            }
+            if expected.len() != actual.len() {
+                out.push(format!(
+                    "{}: expected {} elements, got {}",
+                    path,
+                    expected.len(),
+                    actual.len()
+                ));
+            }
+        }
+        // Same number written differently (`1` and `1.0`)
+        (Value::Number(a), Value::Number(b)) if a.as_f64() == b.as_f64() => {}
+        (expected, actual) if expected != actual => {
+            out.push(format!("{}: expected {}, got {}", path, expected, actual));
+        }
+        _ => {}
+    }
+}
+
+/// Path of the object field: `path.key` or `path["key"]` (if key is not an identifier).
+fn json_key_path(path: &str, key: &str) -> String {
+    let identifier = key.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if identifier {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{}]", path, Value::from(key))
+    }
+}
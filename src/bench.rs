@@ -0,0 +1,43 @@
+//! Benchmarks of `#[datatest::files(..)]` and `#[datatest::data(..)]` tests (marked with
+//! `#[bench]`). `Bencher` of the standard test crate could only be named on the nightly channel,
+//! so benchmark functions could take [`Bencher`] owned by this crate instead.
+
+/// Argument of the benchmark functions (`fn bench(bencher: &mut datatest::Bencher, case: &Case)`),
+/// compatible with the `Bencher` of the standard test crate. Iterations are timed by the test
+/// runner: with `--bench` (like under `cargo bench`), the iteration is repeated and measured with
+/// [`std::time::Instant`], otherwise it runs once, as a test.
+pub struct Bencher<'a> {
+    harness: &'a mut crate::rustc_test::Bencher,
+    /// Number of bytes processed by each iteration; if set, throughput is reported as well.
+    pub bytes: u64,
+}
+
+impl Bencher<'_> {
+    /// Run the iteration (repeatedly, if benchmarks are measured).
+    pub fn iter<T, F: FnMut() -> T>(&mut self, inner: F) {
+        self.harness.iter(inner)
+    }
+
+    /// Same as [`Bencher::iter`], but the iteration could fail: iterations stop at the first error,
+    /// which is returned, so benchmark functions returning `Result` could fail with it via `?`.
+    pub fn try_iter<T, E, F: FnMut() -> Result<T, E>>(&mut self, mut inner: F) -> Result<(), E> {
+        let mut error = None;
+        self.harness.iter(|| match error {
+            Some(_) => None,
+            None => inner().map_err(|err| error = Some(err)).ok(),
+        });
+        error.map_or(Ok(()), Err)
+    }
+}
+
+impl Drop for Bencher<'_> {
+    fn drop(&mut self) {
+        self.harness.bytes = self.bytes;
+    }
+}
+
+/// Wrap the harness of the test runner for the benchmark function taking `&mut datatest::Bencher`
+/// (the wrapper lives until the end of the call, then passes the throughput on to the harness).
+pub fn bencher(harness: &mut crate::rustc_test::Bencher) -> Bencher<'_> {
+    Bencher { harness, bytes: 0 }
+}
@@ -0,0 +1,66 @@
+//! Time budget of the whole run (`--max-duration 10m`): test cases are selected before the run by
+//! their expected durations (see [`Timings`](crate::timings::Timings)), and the cases which would
+//! start after the budget is spent are not run, but reported as ignored (skipped for time).
+use std::collections::BTreeSet;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+/// Exit status of the run which passed, but did not run some of the test cases as the time budget
+/// was spent (failed runs exit with the status of the failure, as usual).
+pub(crate) const SKIPPED_FOR_TIME_EXIT_CODE: i32 = 3;
+
+/// Reason of ignoring the test cases which do not fit into the budget.
+pub(crate) const SKIPPED_FOR_TIME: &str = "skipped for time";
+
+/// Budget of the run and the test cases not run as they do not fit into it.
+static SKIPPED: Mutex<Option<(Duration, BTreeSet<String>)>> = Mutex::new(None);
+
+/// Select the test cases which start before the budget is spent, given their expected durations
+/// in seconds (in the order the cases are started) and the number of test threads: each case
+/// starts on the thread which frees up first.
+pub(crate) fn select(budget: Duration, expected: &[f64], threads: usize) -> Vec<bool> {
+    let budget = budget.as_secs_f64();
+    let mut threads = vec![0.0_f64; threads.max(1)];
+    expected
+        .iter()
+        .map(|expected| {
+            let thread = threads
+                .iter_mut()
+                .min_by(|a, b| a.total_cmp(b))
+                .expect("at least one thread");
+            if *thread >= budget {
+                return false;
+            }
+            *thread += expected;
+            true
+        })
+        .collect()
+}
+
+/// Record the test cases not run as they do not fit into the budget.
+pub(crate) fn skip(budget: Duration, names: impl IntoIterator<Item = String>) {
+    *SKIPPED.lock().unwrap_or_else(PoisonError::into_inner) =
+        Some((budget, names.into_iter().collect()));
+}
+
+/// Print test cases skipped for time (if any) and return their number.
+pub(crate) fn print_summary() -> usize {
+    let skipped = SKIPPED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    let (budget, skipped) = match skipped {
+        Some((budget, skipped)) if !skipped.is_empty() => (budget, skipped),
+        _ => return 0,
+    };
+    println!(
+        "skipped for time (budget of {:.1}s is not enough, {} test cases not run):",
+        budget.as_secs_f64(),
+        skipped.len()
+    );
+    for name in &skipped {
+        println!("    {}", name);
+    }
+    println!();
+    skipped.len()
+}
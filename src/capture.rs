@@ -0,0 +1,168 @@
+//! Outcomes of the test cases together with the output captured while they run, so they could be
+//! summarized and attached to the structured reports.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Outcome of the test case which was run.
+struct Outcome {
+    failure: Option<Failure>,
+    duration: Duration,
+}
+
+/// Details of the failed test case.
+#[derive(Clone)]
+pub(crate) struct Failure {
+    /// Panic message.
+    pub(crate) message: String,
+    /// Output captured while the test case ran (if the output was captured at all).
+    pub(crate) output: Option<String>,
+    /// Files written for the test case to help with the investigation (like reduced inputs).
+    pub(crate) artifacts: Vec<PathBuf>,
+    pub(crate) duration: Duration,
+}
+
+/// Buffer the output is captured into.
+type Buffer = Arc<Mutex<Vec<u8>>>;
+
+/// Outcomes of the test cases run so far, by the test name.
+static OUTCOMES: Mutex<Option<HashMap<String, Outcome>>> = Mutex::new(None);
+
+thread_local! {
+    /// Artifacts of the test case running on the current thread.
+    static CURRENT_ARTIFACTS: RefCell<Option<Artifacts>> = const { RefCell::new(None) };
+}
+
+/// Artifacts written for the test case, see [`add_artifact`].
+#[derive(Clone, Default)]
+pub(crate) struct Artifacts(Arc<Mutex<Vec<PathBuf>>>);
+
+impl Artifacts {
+    /// Artifacts of the test case running on the current thread.
+    pub(crate) fn current() -> Option<Artifacts> {
+        CURRENT_ARTIFACTS.with(|current| current.borrow().clone())
+    }
+
+    /// Collect the artifacts added on the current thread into these ones until the returned guard
+    /// is dropped (for the threads running the test case on behalf of the test runner thread).
+    pub(crate) fn enter(self) -> ArtifactsScope {
+        let previous = CURRENT_ARTIFACTS.with(|current| current.replace(Some(self)));
+        ArtifactsScope(previous)
+    }
+}
+
+pub(crate) struct ArtifactsScope(Option<Artifacts>);
+
+impl Drop for ArtifactsScope {
+    fn drop(&mut self) {
+        CURRENT_ARTIFACTS.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+/// Attach the file written for the investigation of the failure to the test case running on the
+/// current thread.
+pub(crate) fn add_artifact(path: &Path) {
+    if let Some(artifacts) = Artifacts::current() {
+        artifacts
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(path.to_path_buf());
+    }
+}
+
+/// Run the test case and record its outcome. Output of the test case (everything printed via
+/// `print!`, `eprint!` and friends, including the panic message) is captured in the meantime.
+/// Captured output is passed on to the standard test runner, so it is still shown along with the
+/// failure; if the case fails, it is also kept for the reports (see [`failure`]).
+///
+/// Output is only captured if the standard test runner captures it (so not with `--nocapture`).
+pub(crate) fn record_case(name: &str, testfn: impl FnOnce()) {
+    let started = Instant::now();
+    let buffer: Buffer = Arc::new(Mutex::new(Vec::new()));
+    let previous = std::io::set_output_capture(Some(buffer.clone()));
+    if previous.is_none() {
+        // Output is not captured by the test runner, keep it that way
+        std::io::set_output_capture(None);
+    }
+    let artifacts = Artifacts::default();
+
+    let result = {
+        let _artifacts = artifacts.clone().enter();
+        catch_unwind(AssertUnwindSafe(testfn))
+    };
+
+    let mut output = None;
+    if let Some(previous) = previous {
+        std::io::set_output_capture(Some(previous.clone()));
+        let captured = std::mem::take(&mut *buffer.lock().unwrap_or_else(PoisonError::into_inner));
+        if result.is_err() {
+            output = Some(String::from_utf8_lossy(&captured).into_owned());
+        }
+        previous
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(&captured);
+    }
+    let duration = started.elapsed();
+    let failure = result.as_ref().err().map(|payload| Failure {
+        message: crate::runner::panic_message(payload.as_ref()),
+        output,
+        artifacts: std::mem::take(&mut *artifacts.0.lock().unwrap_or_else(PoisonError::into_inner)),
+        duration,
+    });
+    // Case might have been run before (in watch mode), the latest outcome wins
+    OUTCOMES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_or_insert_with(HashMap::new)
+        .insert(name.to_string(), Outcome { failure, duration });
+
+    if let Err(payload) = result {
+        resume_unwind(payload);
+    }
+}
+
+/// If the test case with the given name passed (`None` if it was not run).
+pub(crate) fn passed(name: &str) -> Option<bool> {
+    OUTCOMES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .and_then(|outcomes| outcomes.get(name))
+        .map(|outcome| outcome.failure.is_none())
+}
+
+/// Duration of the test case with the given name (`None` if it was not run).
+pub(crate) fn duration(name: &str) -> Option<Duration> {
+    OUTCOMES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .and_then(|outcomes| outcomes.get(name))
+        .map(|outcome| outcome.duration)
+}
+
+/// Details of the failure of the test case with the given name, if it failed.
+pub(crate) fn failure(name: &str) -> Option<Failure> {
+    OUTCOMES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .and_then(|outcomes| outcomes.get(name))
+        .and_then(|outcome| outcome.failure.clone())
+}
+
+/// Durations of all test cases run so far, by the test name.
+pub(crate) fn durations() -> Vec<(String, Duration)> {
+    OUTCOMES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .flatten()
+        .map(|(name, outcome)| (name.clone(), outcome.duration))
+        .collect()
+}
@@ -0,0 +1,85 @@
+//! Context of the test case running on the current thread, so helper functions shared by the tests
+//! could attribute their diagnostics and artifacts to the case.
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+thread_local! {
+    static CURRENT_CASE: RefCell<Option<CaseInfo>> = const { RefCell::new(None) };
+}
+
+/// Test case running on the current thread, see [`current_case`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseInfo {
+    name: String,
+    fixtures: Vec<PathBuf>,
+    location: String,
+}
+
+impl CaseInfo {
+    /// Full name of the test case, as printed by the test runner (for example,
+    /// `tests::sample_test::case-01.input.txt`).
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Files of the test case: paths mapped to the arguments of `#[datatest::files(..)]` test (in
+    /// the order of the arguments, pointing into the sandbox if the test runs in one) or the data
+    /// file of `#[datatest::data(..)]` test.
+    pub fn fixtures(&self) -> &[PathBuf] {
+        &self.fixtures
+    }
+
+    /// Location of the test case: the test file of the files-driven test or `<path>:<line>` of the
+    /// case in the data file, the same as printed when the case fails.
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// Directory for the artifacts of this test case (for example, outputs to inspect once the
+    /// case fails): `target/datatest-artifacts/<case name>` (or under the directory given by
+    /// `DATATEST_ARTIFACTS` environment variable). The directory is not created.
+    pub fn artifact_dir(&self) -> PathBuf {
+        crate::reduce::artifacts_dir().join(crate::runner::artifact_name(&self.name))
+    }
+}
+
+/// Context of the test case running on the current thread: its name, files and location. Returns
+/// `None` if called outside of the `datatest` test case (for example, from a thread spawned by the
+/// test).
+///
+/// ```ignore
+/// fn check_output(output: &str) {
+///     let case = datatest::current_case().expect("called outside of the test case");
+///     let dir = case.artifact_dir();
+///     std::fs::create_dir_all(&dir).unwrap();
+///     std::fs::write(dir.join("output.txt"), output).unwrap();
+///     assert!(output.is_empty(), "unexpected output of {}", case.location());
+/// }
+/// ```
+pub fn current_case() -> Option<CaseInfo> {
+    CURRENT_CASE
+        .try_with(|current| current.try_borrow().ok()?.clone())
+        .ok()
+        .flatten()
+}
+
+/// Marks the test case as running on the current thread until dropped.
+pub(crate) struct CaseScope;
+
+impl CaseScope {
+    pub(crate) fn enter(name: String, fixtures: Vec<PathBuf>, location: String) -> CaseScope {
+        let case = CaseInfo {
+            name,
+            fixtures,
+            location,
+        };
+        CURRENT_CASE.with(|current| *current.borrow_mut() = Some(case));
+        CaseScope
+    }
+}
+
+impl Drop for CaseScope {
+    fn drop(&mut self) {
+        CURRENT_CASE.with(|current| *current.borrow_mut() = None);
+    }
+}
@@ -0,0 +1,570 @@
+//! HTTP record/replay cassettes: local proxy which records interactions with the upstream server
+//! or serves them back from the cassette file.
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+/// Environment variable which, if set, makes cassettes record the interactions with the upstream
+/// server instead of replaying them.
+const RECORD_ENV_VAR: &str = "DATATEST_RECORD";
+
+/// Single recorded request and the response to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    path: String,
+    #[serde(default, skip_serializing_if = "Body::is_empty")]
+    body: Body,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Body::is_empty")]
+    body: Body,
+}
+
+/// Body of the request or the response, kept as is (bodies are not necessarily text) and stored
+/// in the cassette file encoded as base64.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Body(Vec<u8>);
+
+impl Body {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Serialize for Body {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&encode_base64(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Body {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        decode_base64(&encoded)
+            .map(Body)
+            .ok_or_else(|| serde::de::Error::custom("body is not valid base64"))
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as base64 (standard alphabet, with padding).
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (idx, byte)| {
+            group | (*byte as u32) << (16 - 8 * idx)
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                let sextet = (group >> (18 - 6 * idx)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decode base64 (standard alphabet, padding is optional, whitespace is ignored); `None` if the
+/// input is not valid base64.
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let sextets = encoded
+        .bytes()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .take_while(|byte| *byte != b'=')
+        .map(|byte| BASE64_ALPHABET.iter().position(|c| *c == byte))
+        .collect::<Option<Vec<_>>>()?;
+    if sextets.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(sextets.len() * 3 / 4);
+    for chunk in sextets.chunks(4) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (idx, sextet)| {
+            group | (*sextet as u32) << (18 - 6 * idx)
+        });
+        for idx in 0..chunk.len() - 1 {
+            bytes.push((group >> (16 - 8 * idx)) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// Whether the cassette talks to the upstream server or serves recorded interactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Requests are forwarded to the upstream server; interactions are written to the cassette
+    /// file once the cassette is dropped.
+    Record,
+    /// Requests are answered with the recorded responses; upstream server is not contacted.
+    Replay,
+}
+
+/// Plain HTTP server requests are forwarded to in record mode.
+struct Upstream {
+    host: String,
+    addr: String,
+}
+
+struct State {
+    interactions: Vec<Interaction>,
+    /// Interactions already served in replay mode.
+    used: Vec<bool>,
+    errors: Vec<String>,
+    closed: bool,
+}
+
+/// Recording proxy handle: local HTTP server the code under test should talk to instead of the
+/// real server (see [`Cassette::url`]).
+///
+/// In record mode (`DATATEST_RECORD` environment variable is set), requests are forwarded to the
+/// upstream server and interactions are written to the cassette file when the handle is dropped.
+/// Otherwise, requests are answered from the cassette file without contacting the upstream server;
+/// a request which was not recorded fails the test, and so do the recorded interactions which were
+/// not requested by the time the handle is dropped. Bodies are stored in the cassette file encoded
+/// as base64, as they are not necessarily text.
+///
+/// Only plain HTTP/1.1 is supported (client should talk to the proxy without TLS, and the upstream
+/// server in record mode must be an `http://` one).
+///
+/// ```ignore
+/// #[datatest::files("tests/api", { input in r"\.json$" })]
+/// fn api_test(input: &str) {
+///   let cassette = datatest::Cassette::start("http://api.example.com");
+///   let client = MyClient::new(&cassette.url());
+///   assert_eq!(client.fetch(input), ...);
+/// }
+/// ```
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    server: Option<JoinHandle<()>>,
+}
+
+impl Cassette {
+    /// Start the proxy for the currently running test case. Cassette is stored in `cassettes`
+    /// directory next to the fixture of the case (test file of the files-driven test or the data
+    /// file of the data-driven test), keyed by the case name. Panics if called outside of the
+    /// `datatest` test case.
+    pub fn start(upstream: &str) -> Cassette {
+        let case = crate::case::current_case()
+            .expect("`Cassette::start` must be called from the `datatest` test case");
+        let path = cassette_path(case.name(), case.location());
+        if std::env::var_os(RECORD_ENV_VAR).is_some() {
+            Cassette::record(path, upstream)
+        } else {
+            Cassette::replay(path)
+        }
+    }
+
+    /// Start the proxy forwarding requests to the `upstream` server (`http://host[:port]`) and
+    /// recording interactions to the cassette file at `path`.
+    pub fn record(path: impl Into<PathBuf>, upstream: &str) -> Cassette {
+        let upstream = parse_upstream(upstream);
+        Cassette::serve(
+            path.into(),
+            CassetteMode::Record,
+            Vec::new(),
+            Some(upstream),
+        )
+    }
+
+    /// Start the proxy serving interactions recorded in the cassette file at `path`.
+    pub fn replay(path: impl Into<PathBuf>) -> Cassette {
+        let path = path.into();
+        let input = std::fs::read_to_string(&path).unwrap_or_else(|err| {
+            panic!(
+                "cannot read cassette '{}' (run with {}=1 to record it): {}",
+                path.display(),
+                RECORD_ENV_VAR,
+                err
+            )
+        });
+        let interactions = serde_yaml::from_str(&input)
+            .unwrap_or_else(|err| panic!("invalid cassette '{}': {}", path.display(), err));
+        Cassette::serve(path, CassetteMode::Replay, interactions, None)
+    }
+
+    fn serve(
+        path: PathBuf,
+        mode: CassetteMode,
+        interactions: Vec<Interaction>,
+        upstream: Option<Upstream>,
+    ) -> Cassette {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("cannot start cassette proxy");
+        let addr = listener.local_addr().expect("cannot start cassette proxy");
+        let state = Arc::new(Mutex::new(State {
+            used: vec![false; interactions.len()],
+            interactions,
+            errors: Vec::new(),
+            closed: false,
+        }));
+        let server_state = state.clone();
+        let server = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if lock(&server_state).closed {
+                    break;
+                }
+                if let Ok(mut stream) = stream {
+                    if let Err(err) = handle(&mut stream, &server_state, upstream.as_ref()) {
+                        lock(&server_state).errors.push(err);
+                    }
+                }
+            }
+        });
+        Cassette {
+            path,
+            mode,
+            addr,
+            state,
+            server: Some(server),
+        }
+    }
+
+    /// Base URL of the proxy (`http://127.0.0.1:<port>`), to be used instead of the URL of the
+    /// upstream server.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Path of the cassette file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+}
+
+impl Drop for Cassette {
+    fn drop(&mut self) {
+        lock(&self.state).closed = true;
+        // Wake up the server blocked on accepting the connection
+        let _ = TcpStream::connect(self.addr);
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+
+        let mut state = lock(&self.state);
+        if self.mode == CassetteMode::Replay {
+            let unused = state
+                .interactions
+                .iter()
+                .zip(&state.used)
+                .filter(|(_, used)| !**used)
+                .map(|(interaction, _)| {
+                    format!(
+                        "{} {}: recorded interaction was not requested (run with {}=1 to \
+                         re-record)",
+                        interaction.request.method, interaction.request.path, RECORD_ENV_VAR
+                    )
+                })
+                .collect::<Vec<_>>();
+            state.errors.extend(unused);
+        }
+        if self.mode == CassetteMode::Record && state.errors.is_empty() {
+            let output =
+                serde_yaml::to_string(&state.interactions).expect("cannot serialize cassette");
+            let result = self
+                .path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|_| std::fs::write(&self.path, output));
+            if let Err(err) = result {
+                state.errors.push(format!("cannot write cassette: {}", err));
+            }
+        }
+        if !state.errors.is_empty() && !std::thread::panicking() {
+            panic!(
+                "cassette '{}' failed:\n{}",
+                self.path.display(),
+                state.errors.join("\n")
+            );
+        }
+    }
+}
+
+fn lock(state: &Mutex<State>) -> std::sync::MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Path of the cassette of the test case: `cassettes/<case name>.yaml` next to the fixture (which
+/// could be a `<path>:<line>` location of the case in the data file).
+fn cassette_path(name: &str, fixture: &str) -> PathBuf {
+    let file = crate::runner::location_file(fixture);
+    let dir = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+    dir.join("cassettes")
+        .join(format!("{}.yaml", crate::runner::artifact_name(name)))
+}
+
+fn parse_upstream(upstream: &str) -> Upstream {
+    let host = upstream
+        .strip_prefix("http://")
+        .unwrap_or_else(|| panic!("only plain HTTP upstream is supported, got '{}'", upstream))
+        .trim_end_matches('/');
+    let addr = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:80", host)
+    };
+    Upstream {
+        host: host.to_string(),
+        addr,
+    }
+}
+
+/// Serve single request (connections are not kept alive).
+fn handle(
+    stream: &mut TcpStream,
+    state: &Mutex<State>,
+    upstream: Option<&Upstream>,
+) -> Result<(), String> {
+    let request = match read_message(&mut BufReader::new(&*stream), false) {
+        Ok(Some(request)) => request,
+        // Connection used to wake up the server
+        Ok(None) => return Ok(()),
+        Err(err) => return Err(format!("cannot read request: {}", err)),
+    };
+    let mut parts = request.start_line.split_whitespace();
+    let recorded = RecordedRequest {
+        method: parts.next().unwrap_or_default().to_string(),
+        path: parts.next().unwrap_or_default().to_string(),
+        body: Body(request.body.clone()),
+    };
+
+    let (response, error) = match upstream {
+        Some(upstream) => match forward(upstream, &request) {
+            Ok(response) => {
+                let interaction = Interaction {
+                    request: recorded,
+                    response: response.clone(),
+                };
+                lock(state).interactions.push(interaction);
+                (response, None)
+            }
+            Err(err) => (
+                error_response(&err.to_string()),
+                Some(format!(
+                    "{} {}: cannot forward request: {}",
+                    recorded.method, recorded.path, err
+                )),
+            ),
+        },
+        None => {
+            let mut state = lock(state);
+            let found = (0..state.interactions.len()).find(|&idx| {
+                let request = &state.interactions[idx].request;
+                !state.used[idx]
+                    && request.method == recorded.method
+                    && request.path == recorded.path
+                    && request.body == recorded.body
+            });
+            match found {
+                Some(idx) => {
+                    state.used[idx] = true;
+                    (state.interactions[idx].response.clone(), None)
+                }
+                None => {
+                    let err = format!(
+                        "{} {}: no matching interaction recorded (run with {}=1 to re-record)",
+                        recorded.method, recorded.path, RECORD_ENV_VAR
+                    );
+                    (error_response(&err.to_string()), Some(err))
+                }
+            }
+        }
+    };
+    write_response(stream, &response).map_err(|err| format!("cannot write response: {}", err))?;
+    match error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Send the request to the upstream server and read the response.
+fn forward(upstream: &Upstream, request: &Message) -> std::io::Result<RecordedResponse> {
+    let mut stream = TcpStream::connect(&upstream.addr)?;
+    let mut head = format!("{}\r\n", request.start_line);
+    for (name, value) in &request.headers {
+        // Responses are recorded uncompressed, so they could be served to any client
+        let skip = ["host", "connection", "accept-encoding", "content-length"];
+        if !skip.iter().any(|skip| name.eq_ignore_ascii_case(skip)) {
+            head += &format!("{}: {}\r\n", name, value);
+        }
+    }
+    head += &format!(
+        "Host: {}\r\nConnection: close\r\nContent-Length: {}\r\n\r\n",
+        upstream.host,
+        request.body.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&request.body)?;
+
+    let response = read_message(&mut BufReader::new(stream), true)?
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "empty response"))?;
+    let status = response
+        .start_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid status"))?;
+    let headers = response
+        .headers
+        .into_iter()
+        .filter(|(name, _)| {
+            let skip = ["connection", "content-length", "transfer-encoding"];
+            !skip.iter().any(|skip| name.eq_ignore_ascii_case(skip))
+        })
+        .collect();
+    Ok(RecordedResponse {
+        status,
+        headers,
+        body: Body(response.body),
+    })
+}
+
+fn error_response(message: &str) -> RecordedResponse {
+    RecordedResponse {
+        status: 500,
+        headers: vec![("Content-Type".to_string(), "text/plain".to_string())],
+        body: Body(message.as_bytes().to_vec()),
+    }
+}
+
+fn write_response(stream: &mut TcpStream, response: &RecordedResponse) -> std::io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\n",
+        response.status,
+        reason(response.status)
+    );
+    for (name, value) in &response.headers {
+        head += &format!("{}: {}\r\n", name, value);
+    }
+    head += &format!(
+        "Connection: close\r\nContent-Length: {}\r\n\r\n",
+        response.body.0.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&response.body.0)?;
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+/// HTTP request or response.
+struct Message {
+    start_line: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Message {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Read HTTP message; body of the response without length is read until the connection is closed.
+/// Returns `None` if the connection is closed without sending anything.
+fn read_message(reader: &mut impl BufRead, response: bool) -> std::io::Result<Option<Message>> {
+    let mut start_line = String::new();
+    if reader.read_line(&mut start_line)? == 0 {
+        return Ok(None);
+    }
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut message = Message {
+        start_line: start_line.trim_end().to_string(),
+        headers,
+        body: Vec::new(),
+    };
+
+    let chunked = message
+        .header("transfer-encoding")
+        .map_or(false, |value| value.eq_ignore_ascii_case("chunked"));
+    let length = message
+        .header("content-length")
+        .and_then(|length| length.parse::<usize>().ok());
+    if chunked {
+        message.body = read_chunked(reader)?;
+    } else if let Some(length) = length {
+        message.body = vec![0; length];
+        reader.read_exact(&mut message.body)?;
+    } else if response {
+        reader.read_to_end(&mut message.body)?;
+    }
+    Ok(Some(message))
+}
+
+fn read_chunked(reader: &mut impl BufRead) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let size = line.trim().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        if size == 0 {
+            // Skip trailers
+            loop {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                    return Ok(body);
+                }
+            }
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+}
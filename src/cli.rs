@@ -0,0 +1,180 @@
+//! Custom command line flags of the test binaries (`harness = false`), parsed by the runner along
+//! with the flags of the standard test runner.
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+/// Custom command line flags of the test binary, parsed by the `datatest` runner (which owns the
+/// command line of the test binary) and exposed to the tests via [`cli_flag`] and [`cli_option`].
+///
+/// ```ignore
+/// datatest::harness!(
+///     datatest::CliFlags::new()
+///         .flag("update-goldens", "Rewrite golden files instead of comparing with them")
+///         .option("corpus-dir", "PATH", "Directory of the additional corpus")
+/// );
+/// ```
+///
+/// Then, tests could be run with `cargo test -- --update-goldens --corpus-dir ../corpus` (values
+/// could also be given as `--corpus-dir=../corpus`). Custom flags are listed by `--help`.
+#[derive(Debug, Clone, Default)]
+pub struct CliFlags {
+    flags: Vec<CliFlag>,
+}
+
+#[derive(Debug, Clone)]
+struct CliFlag {
+    /// Name of the flag, without leading `--`.
+    name: String,
+    /// Name of the value shown by `--help`, if the flag takes a value.
+    value_name: Option<String>,
+    help: String,
+}
+
+impl CliFlags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Boolean flag `--<name>` (name is given without leading `--`), see [`cli_flag`].
+    pub fn flag(mut self, name: &str, help: &str) -> Self {
+        self.push(name, None, help);
+        self
+    }
+
+    /// Flag taking a value, `--<name> <VALUE>` or `--<name>=<VALUE>`, see [`cli_option`].
+    pub fn option(mut self, name: &str, value_name: &str, help: &str) -> Self {
+        self.push(name, Some(value_name), help);
+        self
+    }
+
+    fn push(&mut self, name: &str, value_name: Option<&str>, help: &str) {
+        let name = name.trim_start_matches('-');
+        assert!(
+            !self.flags.iter().any(|flag| flag.name == name),
+            "flag `--{}` is registered twice",
+            name
+        );
+        self.flags.push(CliFlag {
+            name: name.to_string(),
+            value_name: value_name.map(str::to_string),
+            help: help.to_string(),
+        });
+    }
+
+    /// Run the tests (same as `datatest::harness!()` does), accepting these flags in addition to
+    /// the flags of the standard test runner.
+    pub fn run(self) {
+        *REGISTERED.lock().unwrap_or_else(PoisonError::into_inner) = self.flags;
+        crate::runner::runner(&[]);
+    }
+}
+
+/// Flags registered via [`CliFlags::run`].
+static REGISTERED: Mutex<Vec<CliFlag>> = Mutex::new(Vec::new());
+
+/// Custom flags given on the command line, by name; flags without values map to `None`.
+static PARSED: OnceLock<HashMap<String, Option<String>>> = OnceLock::new();
+
+/// If the custom boolean flag (see [`CliFlags::flag`]) is given on the command line of the test
+/// binary, like `--update-goldens`.
+pub fn cli_flag(name: &str) -> bool {
+    parsed().contains_key(name.trim_start_matches('-'))
+}
+
+/// Value of the custom flag (see [`CliFlags::option`]) given on the command line of the test
+/// binary, like `--corpus-dir PATH`. If the flag is given multiple times, the last value is used.
+pub fn cli_option(name: &str) -> Option<&'static str> {
+    parsed()
+        .get(name.trim_start_matches('-'))
+        .and_then(|value| value.as_deref())
+}
+
+fn parsed() -> &'static HashMap<String, Option<String>> {
+    PARSED.get_or_init(HashMap::new)
+}
+
+/// Custom flags given on the command line, as arguments passed on to the child processes running
+/// the test cases (see [`crate::isolation`]).
+pub(crate) fn forwarded_args() -> Vec<String> {
+    parsed()
+        .iter()
+        .map(|(name, value)| match value {
+            Some(value) => format!("--{}={}", name, value),
+            None => format!("--{}", name),
+        })
+        .collect()
+}
+
+/// Remove the registered custom flags (and their values) from the arguments of the test binary,
+/// so the rest is parsed by the standard test runner.
+pub(crate) fn take_custom_flags(args: &mut Vec<String>) -> Result<(), String> {
+    let registered = REGISTERED.lock().unwrap_or_else(PoisonError::into_inner);
+    if registered.is_empty() {
+        return Ok(());
+    }
+    let mut parsed = HashMap::new();
+    let mut remaining = Vec::with_capacity(args.len());
+    let mut iter = std::mem::take(args).into_iter();
+    while let Some(arg) = iter.next() {
+        // Arguments after `--` are never flags
+        if arg == "--" {
+            remaining.push(arg);
+            remaining.extend(iter.by_ref());
+            break;
+        }
+        let (name, inline_value) = match arg.strip_prefix("--") {
+            Some(flag) => match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            },
+            None => ("", None),
+        };
+        let flag = match registered.iter().find(|flag| flag.name == name) {
+            Some(flag) => flag,
+            None => {
+                remaining.push(arg);
+                continue;
+            }
+        };
+        let value = match (&flag.value_name, inline_value) {
+            (None, None) => None,
+            (None, Some(_)) => return Err(format!("flag `--{}` does not take a value", name)),
+            (Some(_), Some(value)) => Some(value),
+            (Some(value_name), None) => match iter.next() {
+                Some(value) => Some(value),
+                None => {
+                    return Err(format!(
+                        "flag `--{}` requires a value (`--{} {}`)",
+                        name, name, value_name
+                    ))
+                }
+            },
+        };
+        parsed.insert(flag.name.clone(), value);
+    }
+    *args = remaining;
+    // Watch mode runs the tests again in the same process, but flags could only change on restart
+    let _ = PARSED.set(parsed);
+    Ok(())
+}
+
+/// Print the custom flags, shown by `--help` along with the flags of the standard test runner.
+pub(crate) fn print_help() {
+    let registered = REGISTERED.lock().unwrap_or_else(PoisonError::into_inner);
+    if registered.is_empty() {
+        return;
+    }
+    println!("Custom flags:");
+    let usages = registered
+        .iter()
+        .map(|flag| match flag.value_name {
+            Some(ref value_name) => format!("--{} {}", flag.name, value_name),
+            None => format!("--{}", flag.name),
+        })
+        .collect::<Vec<_>>();
+    let width = usages.iter().map(String::len).max().unwrap_or(0);
+    for (usage, flag) in usages.iter().zip(registered.iter()) {
+        println!("    {:width$}  {}", usage, flag.help, width = width);
+    }
+    println!();
+}
@@ -0,0 +1,350 @@
+//! Support for compile-fail tests: test files which must fail to compile with the expected
+//! diagnostics.
+use crate::config::BlessPolicy;
+use crate::diff::text_diff;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+/// Environment variable which, if set, makes compile-fail tests overwrite `.stderr` files with the
+/// actual diagnostics instead of comparing them.
+pub(crate) const BLESS_ENV_VAR: &str = "DATATEST_BLESS";
+
+/// If the expected file at `path` should be written with the actual output instead of being
+/// compared with it: if `DATATEST_BLESS` is set or if the file does not exist yet, depending on the
+/// `bless` policy of the configuration.
+pub(crate) fn is_blessing(path: &Path) -> bool {
+    match crate::config::bless_policy() {
+        BlessPolicy::Allow => std::env::var_os(BLESS_ENV_VAR).is_some(),
+        BlessPolicy::Missing => std::env::var_os(BLESS_ENV_VAR).is_some() || !path.exists(),
+        BlessPolicy::Never => false,
+    }
+}
+
+/// Compile the Rust source file at `path` and check that compilation fails with the diagnostics
+/// given in the `.stderr` file next to it (`tests/ui/foo.rs` is checked against
+/// `tests/ui/foo.stderr`). Used by `#[datatest::files]` tests with `compile_fail = true` option, but
+/// could be called directly, too.
+///
+/// The file is compiled standalone as a library, with the edition of the crate under test, by the
+/// `rustc` of the toolchain the tests were built with (or the compiler given by `RUSTC` environment
+/// variable). The crate under test and its direct dependencies (as built for the test binary) are
+/// available to it. Wording and rendering of the diagnostics change between the versions of the
+/// compiler, so only the message and the location of each diagnostic are compared (lines starting
+/// with `error` or `warning`, and their `-->` lines): directory of the file is replaced by `$DIR`,
+/// paths of the standard library sources by `$RUST`, and the summary lines (like `error: aborting
+/// due to 1 previous error`) are removed.
+///
+/// If `DATATEST_BLESS` environment variable is set, `.stderr` file is written with the actual
+/// diagnostics instead (see also the `bless` option of the configuration).
+pub fn compile_fail(path: &Path) {
+    let out_dir = out_dir(path);
+    std::fs::create_dir_all(&out_dir).unwrap_or_else(|err| {
+        panic!(
+            "cannot create output directory '{}': {}",
+            out_dir.display(),
+            err
+        )
+    });
+    let rustc = rustc();
+    let mut command = Command::new(&rustc);
+    command
+        .arg(format!("--edition={}", package().edition))
+        .arg("--crate-type=lib")
+        .arg("--emit=metadata")
+        .arg("--color=never")
+        .arg("--out-dir")
+        .arg(&out_dir);
+    if let Some((deps, externs)) = dependencies() {
+        command.arg("-L").arg(deps);
+        command.args(
+            externs
+                .iter()
+                .flat_map(|arg| [OsString::from("--extern"), arg.clone()]),
+        );
+    }
+    let output = command
+        .arg(path)
+        .output()
+        .unwrap_or_else(|err| panic!("cannot run {:?}: {}", rustc, err));
+    let _ = std::fs::remove_dir_all(&out_dir);
+    if output.status.success() {
+        panic!("expected '{}' to fail to compile", path.display());
+    }
+
+    let actual = normalize_diagnostics(&String::from_utf8_lossy(&output.stderr), path);
+    let expected_path = path.with_extension("stderr");
+    if is_blessing(&expected_path) {
+        std::fs::write(&expected_path, &actual)
+            .unwrap_or_else(|err| panic!("cannot write '{}': {}", expected_path.display(), err));
+        return;
+    }
+    let expected = match std::fs::read_to_string(&expected_path) {
+        Ok(expected) => expected.replace("\r\n", "\n"),
+        Err(err) => panic!(
+            "cannot read expected diagnostics from '{}' (run with {}=1 to create it): {}\n\
+             actual diagnostics:\n{}",
+            expected_path.display(),
+            BLESS_ENV_VAR,
+            err,
+            actual
+        ),
+    };
+    if expected != actual {
+        panic!(
+            "diagnostics of '{}' do not match '{}' (run with {}=1 to update it)\n{}",
+            path.display(),
+            expected_path.display(),
+            BLESS_ENV_VAR,
+            text_diff(&expected, &actual)
+        );
+    }
+}
+
+/// Directory for the compilation artifacts, unique for each test file so tests could run in
+/// parallel (the readable part of the name could be the same for different paths, so it is followed
+/// by the hash of the path).
+fn out_dir(path: &Path) -> PathBuf {
+    let name = path
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    std::env::temp_dir()
+        .join("datatest-compile-fail")
+        .join(format!("{}-{:016x}", name, hasher.finish()))
+}
+
+/// Compiler to build the files with: the one given by `RUSTC` environment variable, or `rustc` of
+/// the toolchain running the tests (next to its `cargo`), falling back to `rustc` found in `PATH`.
+fn rustc() -> OsString {
+    if let Some(rustc) = std::env::var_os("RUSTC") {
+        return rustc;
+    }
+    std::env::var_os("CARGO")
+        .or_else(|| option_env!("CARGO").map(OsString::from))
+        .and_then(|cargo| {
+            let bin = Path::new(&cargo).parent()?;
+            let rustc = bin.join(format!("rustc{}", std::env::consts::EXE_SUFFIX));
+            Some(rustc).filter(|rustc| rustc.is_file())
+        })
+        .map_or_else(|| OsString::from("rustc"), PathBuf::into_os_string)
+}
+
+/// Crate under test, as described by its `Cargo.toml` (found via `CARGO_MANIFEST_DIR`).
+struct Package {
+    /// Edition of the crate (inherited from the workspace, if asked to).
+    edition: String,
+    /// Crates the compiled files could use: the crate itself and its direct dependencies (regular
+    /// and development ones), as pairs of the name in the code and the name of the library file.
+    crates: Vec<(String, String)>,
+}
+
+fn package() -> &'static Package {
+    static PACKAGE: OnceLock<Package> = OnceLock::new();
+    PACKAGE.get_or_init(|| {
+        let dir = std::env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        let manifest =
+            read_manifest(&dir).unwrap_or_else(|| toml::Value::Table(Default::default()));
+        let package = manifest.get("package");
+        let edition = match package.and_then(|package| package.get("edition")) {
+            Some(toml::Value::String(edition)) => Some(edition.clone()),
+            // `edition.workspace = true`
+            Some(_) => dir
+                .ancestors()
+                .skip(1)
+                .filter_map(read_manifest)
+                .find_map(|manifest| {
+                    let edition = manifest.get("workspace")?.get("package")?.get("edition")?;
+                    edition.as_str().map(String::from)
+                }),
+            None => None,
+        };
+
+        let crate_name = |name: &str| name.replace('-', "_");
+        let mut crates = Vec::new();
+        let lib = manifest
+            .get("lib")
+            .and_then(|lib| lib.get("name"))
+            .and_then(toml::Value::as_str)
+            .or_else(|| package?.get("name")?.as_str())
+            .map(crate_name);
+        if let Some(lib) = lib {
+            crates.push((lib.clone(), lib));
+        }
+        let targets = manifest
+            .get("target")
+            .and_then(toml::Value::as_table)
+            .into_iter()
+            .flat_map(|targets| targets.values());
+        for table in std::iter::once(&manifest).chain(targets) {
+            for section in ["dependencies", "dev-dependencies"] {
+                let deps = match table.get(section).and_then(toml::Value::as_table) {
+                    Some(deps) => deps,
+                    None => continue,
+                };
+                for (name, dep) in deps {
+                    // Renamed dependencies are used under the name of the key
+                    let package = dep
+                        .get("package")
+                        .and_then(toml::Value::as_str)
+                        .unwrap_or(name);
+                    crates.push((crate_name(name), crate_name(package)));
+                }
+            }
+        }
+        Package {
+            edition: edition.unwrap_or_else(|| String::from("2015")),
+            crates,
+        }
+    })
+}
+
+/// Parsed `Cargo.toml` in the directory, if any.
+fn read_manifest(dir: &Path) -> Option<toml::Value> {
+    let manifest = std::fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    toml::from_str(&manifest).ok()
+}
+
+/// Library search path (`dependency=<dir>`) and `--extern` arguments giving the compiled files
+/// access to the crates of the [`Package`], as built for the test binary: libraries next to it in
+/// the `deps` directory of the target directory (newest build of each crate, if there are several).
+fn dependencies() -> Option<&'static (OsString, Vec<OsString>)> {
+    static DEPENDENCIES: OnceLock<Option<(OsString, Vec<OsString>)>> = OnceLock::new();
+    DEPENDENCIES
+        .get_or_init(|| {
+            let exe = std::env::current_exe().ok()?;
+            let deps = exe.parent()?;
+            let mut crates: BTreeMap<String, (SystemTime, PathBuf)> = BTreeMap::new();
+            for entry in std::fs::read_dir(deps).ok()?.flatten() {
+                let path = entry.path();
+                let file_name = entry.file_name();
+                let file_name = file_name.to_string_lossy();
+                let is_library = path.extension().map_or(false, |ext| {
+                    ext == "rlib" || ext == std::env::consts::DLL_EXTENSION
+                });
+                // Libraries are named `lib<crate>-<hash>.<extension>`
+                let name = file_name
+                    .strip_prefix("lib")
+                    .and_then(|name| name.rsplit_once('-'))
+                    .map(|(name, _)| name.to_string());
+                let name = match name {
+                    Some(name) if is_library => name,
+                    _ => continue,
+                };
+                let modified = entry
+                    .metadata()
+                    .and_then(|metadata| metadata.modified())
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
+                if crates
+                    .get(&name)
+                    .map_or(true, |(newest, _)| modified > *newest)
+                {
+                    crates.insert(name, (modified, path));
+                }
+            }
+            let mut search_path = OsString::from("dependency=");
+            search_path.push(deps);
+            let externs = package()
+                .crates
+                .iter()
+                .filter_map(|(name, library)| {
+                    let (_, path) = crates.get(library)?;
+                    let mut arg = OsString::from(format!("{}=", name));
+                    arg.push(path);
+                    Some(arg)
+                })
+                .collect();
+            Some((search_path, externs))
+        })
+        .as_ref()
+}
+
+/// Lines of the compiler output which depend on the version of the compiler rather than on the
+/// compiled file.
+const UNSTABLE_LINES: [&str; 4] = [
+    "error: aborting due to",
+    "For more information about this error",
+    "For more information about an error",
+    "Some errors have detailed explanations",
+];
+
+/// Make output independent of the location of the file and of the platform.
+pub(crate) fn normalize(stderr: &str, path: &Path) -> String {
+    let mut normalized = stderr.replace("\r\n", "\n");
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        let dir = dir.to_string_lossy();
+        normalized = normalized.replace(&format!("{}/", dir), "$DIR/");
+        normalized = normalized.replace(&format!("{}\\", dir), "$DIR/");
+    }
+    let mut lines = normalized
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n");
+    lines.push('\n');
+    lines
+}
+
+/// Make diagnostics independent of the location of the file, of the compiler and of the platform:
+/// only the message and the primary location of each diagnostic are kept, separated by the empty
+/// lines.
+fn normalize_diagnostics(stderr: &str, path: &Path) -> String {
+    let normalized = normalize(stderr, path);
+    let mut diagnostics = Vec::new();
+    for line in normalized.lines() {
+        if UNSTABLE_LINES.iter().any(|prefix| line.starts_with(prefix)) {
+            continue;
+        }
+        if line.starts_with("error") || line.starts_with("warning") {
+            diagnostics.push(vec![line.to_string()]);
+        } else if line.trim_start().starts_with("--> ") {
+            if let Some(diagnostic) = diagnostics.last_mut().filter(|lines| lines.len() == 1) {
+                diagnostic.push(normalize_rust_src(line));
+            }
+        }
+    }
+    let mut lines = diagnostics
+        .iter()
+        .map(|lines| lines.join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    lines.push('\n');
+    lines
+}
+
+/// Replace the path of the standard library sources (either within the toolchain or the path the
+/// standard library was built at, `/rustc/<commit>/library/`) with `$RUST`. Line numbers within the
+/// standard library change between the versions of the compiler, so they are removed.
+fn normalize_rust_src(line: &str) -> String {
+    const TOOLCHAIN_SRC: &str = "/lib/rustlib/src/rust/library/";
+    let start = match line.find(" --> ").or_else(|| line.find(" ::: ")) {
+        Some(start) => start + " --> ".len(),
+        None => return line.to_string(),
+    };
+    let (prefix, path) = line.split_at(start);
+    let library = match path.find(TOOLCHAIN_SRC) {
+        Some(idx) => Some(idx + TOOLCHAIN_SRC.len()),
+        None if path.starts_with("/rustc/") => {
+            path.find("/library/").map(|idx| idx + "/library/".len())
+        }
+        None => None,
+    };
+    match library {
+        Some(idx) => {
+            let file = &path[idx..];
+            let file = file.split(':').next().unwrap_or(file);
+            format!("{}$RUST/{}", prefix, file)
+        }
+        None => line.to_string(),
+    }
+}
@@ -0,0 +1,250 @@
+//! Support for the project configuration file (`datatest.toml`)
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const FILE_NAME: &str = "datatest.toml";
+
+/// Part of the project configuration consulted by the test runner (`[runner]` section). Defaults
+/// for the attributes are read by the proc macro at compile time.
+///
+/// ```toml
+/// [runner]
+/// # Either "fail" (default) or "allow": if run with tests marked with `#[only]` should fail
+/// focus = "allow"
+/// # Either "allow" (default) or "fail": if run should fail when some files under root directories
+/// # of `#[datatest::files]` tests are not used by any test
+/// orphans = "fail"
+/// # Path to write the fixture coverage report to (JSON)
+/// report = "target/datatest-coverage.json"
+/// # Path to write the JUnit XML report of the run to
+/// junit = "target/datatest-junit.xml"
+/// # StatsD server to send the durations and outcomes of the test cases to
+/// metrics = "statsd://127.0.0.1:8125"
+/// # Either "allow" (default) or "fail": if test cases marked as expected failures (`xfail`) which
+/// # pass should fail the run
+/// xpass = "fail"
+/// # Checked-in list of flaky cases whose failures do not fail the run
+/// quarantine = "tests/quarantine.toml"
+/// # Path to keep the durations of the test cases at, used to balance shards (`DATATEST_SHARD`)
+/// timings = "tests/timings.json"
+/// # Either "never" (default), "on-failure" or "always": if sandboxes of the test cases should be
+/// # kept after the case finishes
+/// keep = "on-failure"
+/// # Checked-in SHA-256 hashes of the fixtures (regenerated with `DATATEST_UPDATE_MANIFEST=1`)
+/// manifest = "tests/fixtures.sha256"
+/// # Either "fail" (default) or "warn": if fixtures which differ from the manifest should fail the
+/// # run
+/// manifest_drift = "warn"
+///
+/// # Default timeout of the test cases of `#[datatest::files]` and `#[datatest::data]` tests which
+/// # do not have their own one
+/// timeout = "30s"
+/// # Either "allow" (default), "missing" or "never": if expected files are written with
+/// # `DATATEST_BLESS`, if missing expected files are written as well, or if `DATATEST_BLESS` is
+/// # ignored (for CI)
+/// bless = "missing"
+/// # Either "pretty", "terse" or "json": output format of the run, unless given via `--format`
+/// reporter = "terse"
+/// # Check every case of the data files against the test case type (and the schema given by the
+/// # `schema` option) before running the tests, reporting all invalid cases at once
+/// validate_data = true
+///
+/// # Tags selecting the tests by name (substrings of the names), run via `DATATEST_TAG=<tag>`
+/// [runner.tags]
+/// slow = ["fuzz_corpus::", "integration::"]
+///
+/// # Named configurations: every case of `#[datatest::files]` and `#[datatest::data]` tests is run
+/// # under each of them
+/// [runner.configurations.sqlite]
+/// env = { DATABASE_URL = "sqlite::memory:" }
+/// flags = ["in-memory"]
+/// ```
+#[derive(Default, Deserialize)]
+pub struct RunnerConfig {
+    #[serde(default)]
+    pub focus: FocusPolicy,
+    #[serde(default)]
+    pub orphans: OrphansPolicy,
+    #[serde(default)]
+    pub report: Option<String>,
+    #[serde(default)]
+    pub junit: Option<String>,
+    #[serde(default)]
+    pub metrics: Option<String>,
+    #[serde(default)]
+    pub xpass: XpassPolicy,
+    #[serde(default)]
+    pub quarantine: Option<String>,
+    #[serde(default)]
+    pub timings: Option<String>,
+    #[serde(default)]
+    pub keep: KeepPolicy,
+    #[serde(default)]
+    pub manifest: Option<String>,
+    #[serde(default)]
+    pub manifest_drift: DriftPolicy,
+    #[serde(default)]
+    pub timeout: Option<String>,
+    #[serde(default)]
+    pub bless: BlessPolicy,
+    #[serde(default)]
+    pub reporter: Option<Reporter>,
+    #[serde(default)]
+    pub validate_data: bool,
+    #[serde(default)]
+    pub tags: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub configurations: BTreeMap<String, ConfigurationSpec>,
+}
+
+/// Named configuration of the test suite (`[runner.configurations.<name>]` section).
+#[derive(Clone, Default, Deserialize)]
+pub struct ConfigurationSpec {
+    /// Environment variables set while the cases run under this configuration.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Toggles readable by the code under test via [`Configuration::is_enabled`].
+    ///
+    /// [`Configuration::is_enabled`]: crate::Configuration::is_enabled
+    #[serde(default)]
+    pub flags: Vec<String>,
+}
+
+/// What to do when some of the tests are marked with `#[only]`.
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FocusPolicy {
+    /// Run only focused tests and fail the run, so focus markers are not committed by accident.
+    #[default]
+    Fail,
+    /// Run only focused tests.
+    Allow,
+}
+
+/// What to do with files under root directories of `#[datatest::files]` tests which are not used by
+/// any test.
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrphansPolicy {
+    /// Ignore such files.
+    #[default]
+    Allow,
+    /// Fail the run, listing all such files.
+    Fail,
+}
+
+/// What to do with test cases marked as expected failures (`xfail`) which pass.
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum XpassPolicy {
+    /// Only list such cases after the run.
+    #[default]
+    Allow,
+    /// Fail such cases.
+    Fail,
+}
+
+/// What to do with the sandbox of the test case (see `sandbox` option) once the case finishes.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeepPolicy {
+    /// Remove the sandbox.
+    #[default]
+    Never,
+    /// Keep the sandbox of the failed case, so the state it left behind could be inspected.
+    OnFailure,
+    /// Keep all sandboxes.
+    Always,
+}
+
+/// What to do when fixtures differ from the manifest (see `manifest` option).
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriftPolicy {
+    /// Fail the run, listing the changed, added and removed fixtures.
+    #[default]
+    Fail,
+    /// Only print the changed, added and removed fixtures.
+    Warn,
+}
+
+/// When expected files (golden files, diagnostics of compile-fail tests and files compared via
+/// `assert_matches_file!`) are written with the actual output instead of being compared with it.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlessPolicy {
+    /// Only with `DATATEST_BLESS` environment variable set.
+    #[default]
+    Allow,
+    /// With `DATATEST_BLESS` set, and also the expected files which do not exist yet.
+    Missing,
+    /// Never, `DATATEST_BLESS` is ignored (so CI could not bless the files by accident).
+    Never,
+}
+
+/// Output format of the run (`reporter` option).
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reporter {
+    /// Line per test.
+    Pretty,
+    /// Character per test.
+    Terse,
+    /// JSON object per event.
+    Json,
+}
+
+#[derive(Default, Deserialize)]
+struct Config {
+    #[serde(default)]
+    runner: RunnerConfig,
+}
+
+/// Find the configuration file: either in the crate root directory or in any of its parent
+/// directories up to the root of the workspace the crate belongs to (so it could be shared by all
+/// crates in the workspace). Search stops at the crate root directory if the crate is not a member
+/// of a workspace, so files outside of the project are never picked up.
+fn find() -> Option<PathBuf> {
+    let start = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok())?;
+    let dirs = start.ancestors().collect::<Vec<_>>();
+    let root = dirs
+        .iter()
+        .position(|dir| is_workspace_root(dir))
+        .unwrap_or(0);
+    dirs[..=root]
+        .iter()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// If the directory has `Cargo.toml` with the `[workspace]` section.
+fn is_workspace_root(dir: &Path) -> bool {
+    std::fs::read_to_string(dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|manifest| toml::from_str::<toml::Value>(&manifest).ok())
+        .map_or(false, |manifest| manifest.get("workspace").is_some())
+}
+
+/// Bless policy of the configuration, loaded once (expected files are also compared outside of the
+/// test runner, see [`BlessPolicy`]).
+pub(crate) fn bless_policy() -> BlessPolicy {
+    static POLICY: OnceLock<BlessPolicy> = OnceLock::new();
+    *POLICY.get_or_init(|| load().bless)
+}
+
+/// Load runner configuration from `datatest.toml`, if any.
+pub fn load() -> RunnerConfig {
+    let path = match find() {
+        Some(path) => path,
+        None => return RunnerConfig::default(),
+    };
+    let content = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("cannot read configuration '{}': {}", path.display(), e));
+    let config: Config = toml::from_str(&content)
+        .unwrap_or_else(|e| panic!("invalid configuration '{}': {}", path.display(), e));
+    config.runner
+}
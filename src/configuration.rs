@@ -0,0 +1,117 @@
+//! Running test cases under the named configurations of the test suite.
+use crate::config::ConfigurationSpec;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// Configuration the current test case runs under (see `[runner.configurations]` section of
+/// `datatest.toml`).
+///
+/// ```ignore
+/// #[datatest::files("tests/queries", { input in r"\.sql$" })]
+/// fn query(input: &str) {
+///   let config = datatest::Configuration::current();
+///   if config.map_or(false, |config| config.is_enabled("in-memory")) {
+///     ..
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Configuration {
+    name: String,
+    flags: Vec<String>,
+}
+
+impl Configuration {
+    /// Configuration of the test cases currently running, if any. Cases running under different
+    /// configurations never run concurrently, so this is also the configuration of the current
+    /// test case.
+    pub fn current() -> Option<Configuration> {
+        lock(&GATE)
+            .active
+            .as_ref()
+            .map(|active| active.config.clone())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn flags(&self) -> &[String] {
+        &self.flags
+    }
+
+    /// If the flag is set in the configuration.
+    pub fn is_enabled(&self, flag: &str) -> bool {
+        self.flags.iter().any(|enabled| enabled == flag)
+    }
+}
+
+/// Configuration which is currently applied.
+struct Active {
+    config: Configuration,
+    /// Number of test cases running under this configuration.
+    running: usize,
+}
+
+struct Gate {
+    active: Option<Active>,
+}
+
+/// Configuration is shared by the whole process (see [`Configuration::current`]), so only cases of
+/// one configuration could run at a time.
+static GATE: Mutex<Gate> = Mutex::new(Gate { active: None });
+static GATE_CHANGED: Condvar = Condvar::new();
+
+fn lock(gate: &Mutex<Gate>) -> MutexGuard<'_, Gate> {
+    gate.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Marks the test case as running under the configuration; configuration is applied by the first
+/// case and reverted once the last case finishes. Environment variables of the configuration are
+/// not set here, cases run in a child process with the variables set instead (see
+/// [`crate::isolation`]).
+pub(crate) struct ConfigurationScope;
+
+impl ConfigurationScope {
+    /// Wait until no cases of other configurations are running and apply the configuration.
+    pub(crate) fn enter(name: &str, spec: &ConfigurationSpec) -> ConfigurationScope {
+        let mut gate = lock(&GATE);
+        loop {
+            match gate.active {
+                Some(ref mut active) if active.config.name == name => {
+                    active.running += 1;
+                    break;
+                }
+                Some(_) => {
+                    gate = GATE_CHANGED
+                        .wait(gate)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                None => {
+                    gate.active = Some(Active {
+                        config: Configuration {
+                            name: name.to_string(),
+                            flags: spec.flags.clone(),
+                        },
+                        running: 1,
+                    });
+                    break;
+                }
+            }
+        }
+        ConfigurationScope
+    }
+}
+
+impl Drop for ConfigurationScope {
+    fn drop(&mut self) {
+        let mut gate = lock(&GATE);
+        let finished = gate.active.as_mut().map_or(false, |active| {
+            active.running -= 1;
+            active.running == 0
+        });
+        if finished {
+            gate.active = None;
+            GATE_CHANGED.notify_all();
+        }
+    }
+}
@@ -1,75 +1,1203 @@
 //! Support module for `#[datatest::data(..)]`
+use crate::format::Format;
 use rustc_test::{Bencher, TDynBenchFn};
-use serde::de::DeserializeOwned;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::ops::Range;
 use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "yaml")]
 use yaml_rust::parser::Event;
-use yaml_rust::scanner::Marker;
+#[cfg(feature = "yaml")]
+use yaml_rust::scanner::{Marker, ScanError};
 
-/// Descriptor used internally for `#[datatest::data(..)]` tests.
-#[doc(hidden)]
+/// Descriptor of `#[datatest::data(..)]` tests, either generated by the procedural macro or built
+/// at runtime via [`DataTestDesc::builder`] (new options could be added, so it cannot be
+/// constructed directly).
+#[non_exhaustive]
 pub struct DataTestDesc {
     pub name: &'static str,
     pub ignore: bool,
-    pub describefn: fn() -> Vec<DataTestCaseDesc<DataTestFn>>,
+    pub ignore_message: Option<&'static str>,
+    /// Test is focused via `#[only]`, so only focused tests are run.
+    pub only: bool,
+    /// Enumerates test cases; error is reported as a failure of this test only.
+    pub describefn: &'static DescribeFn,
+    /// If set, case names are already rendered from the template by `describefn`.
+    pub name_template: Option<&'static str>,
+    /// Maximum duration of each test case, given by the `timeout` option.
+    pub timeout: Option<Duration>,
+    /// Maximum memory of each test case in bytes, given by the `memory_limit` option.
+    pub memory_limit: Option<u64>,
+    /// Scheduling priority of the test cases, given by the `priority` option.
+    pub priority: i32,
+    /// Full names of the tests which must pass before this one, given by the `depends_on` option.
+    pub depends_on: &'static [&'static str],
+    /// Names of the resources the test cases use exclusively, given via `#[resource(..)]`.
+    pub resources: &'static [&'static str],
+    /// All test cases are expected to fail, given by `#[xfail]`.
+    pub xfail: bool,
+    /// Validates the cases of the data file for the validation pre-pass (see
+    /// [`validate_data_file`]). Not set for custom sources.
+    pub validatefn: Option<fn() -> Vec<String>>,
     pub source_file: &'static str,
 }
 
-/// Used internally for `#[datatest::data(..)]` tests.
+/// Enumerates the test cases of `#[datatest::data(..)]` test.
+pub type DescribeFn = dyn Fn() -> Result<Vec<DataTestCaseDesc<DataTestFn>>, String> + Send + Sync;
+
+/// Used by the procedural macro, which cannot use struct expressions of the descriptor.
 #[doc(hidden)]
+impl DataTestDesc {
+    /// Descriptor with the default options (changed via `with_*` methods).
+    pub const fn new(
+        name: &'static str,
+        describefn: &'static DescribeFn,
+        source_file: &'static str,
+    ) -> Self {
+        DataTestDesc {
+            name,
+            ignore: false,
+            ignore_message: None,
+            only: false,
+            describefn,
+            name_template: None,
+            timeout: None,
+            memory_limit: None,
+            priority: 0,
+            depends_on: &[],
+            resources: &[],
+            xfail: false,
+            validatefn: None,
+            source_file,
+        }
+    }
+
+    pub const fn with_ignore(mut self, ignore: bool) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    pub const fn with_ignore_message(mut self, ignore_message: Option<&'static str>) -> Self {
+        self.ignore_message = ignore_message;
+        self
+    }
+
+    pub const fn with_only(mut self, only: bool) -> Self {
+        self.only = only;
+        self
+    }
+
+    pub const fn with_name_template(mut self, name_template: Option<&'static str>) -> Self {
+        self.name_template = name_template;
+        self
+    }
+
+    pub const fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub const fn with_memory_limit(mut self, memory_limit: Option<u64>) -> Self {
+        self.memory_limit = memory_limit;
+        self
+    }
+
+    pub const fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub const fn with_depends_on(mut self, depends_on: &'static [&'static str]) -> Self {
+        self.depends_on = depends_on;
+        self
+    }
+
+    pub const fn with_resources(mut self, resources: &'static [&'static str]) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    pub const fn with_xfail(mut self, xfail: bool) -> Self {
+        self.xfail = xfail;
+        self
+    }
+
+    pub const fn with_validatefn(mut self, validatefn: Option<fn() -> Vec<String>>) -> Self {
+        self.validatefn = validatefn;
+        self
+    }
+}
+
+impl DataTestDesc {
+    /// Start building a `#[datatest::data(..)]`-like test at runtime, without the procedural macro
+    /// (see [`register_suite`](crate::register_suite)). `name` is the full name of the test,
+    /// including the crate name (like `concat!(module_path!(), "::my_test")`). `describefn`
+    /// (which could capture the data of the test, like a boxed closure) enumerates the test cases
+    /// when the test runner starts; an error is reported as a failure of this test.
+    #[track_caller]
+    pub fn builder(
+        name: impl Into<String>,
+        describefn: impl Fn() -> Result<Vec<DataTestCaseDesc<DataTestFn>>, String>
+            + Send
+            + Sync
+            + 'static,
+    ) -> DataTestBuilder {
+        DataTestBuilder(DataTestDesc::new(
+            crate::files::leak(name.into()),
+            Box::leak(Box::new(describefn)),
+            std::panic::Location::caller().file(),
+        ))
+    }
+}
+
+/// Builder of [`DataTestDesc`] for tests constructed at runtime, see [`DataTestDesc::builder`].
+pub struct DataTestBuilder(DataTestDesc);
+
+impl DataTestBuilder {
+    pub fn with_ignore(mut self, ignore: bool) -> Self {
+        self.0.ignore = ignore;
+        self
+    }
+
+    /// Ignore the test, giving the reason (like `#[ignore = "<reason>"]`).
+    pub fn with_ignore_message(mut self, message: impl Into<String>) -> Self {
+        self.0.ignore = true;
+        self.0.ignore_message = Some(crate::files::leak(message.into()));
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.0.timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum memory of each test case in bytes, same as the `memory_limit` option.
+    pub fn with_memory_limit(mut self, limit: u64) -> Self {
+        self.0.memory_limit = Some(limit);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.0.priority = priority;
+        self
+    }
+
+    /// Full names of the tests which must pass before this one, same as the `depends_on` option.
+    pub fn with_depends_on<S: Into<String>>(mut self, tests: impl IntoIterator<Item = S>) -> Self {
+        self.0.depends_on = crate::files::leak_all(tests);
+        self
+    }
+
+    /// Names of the resources the test cases use exclusively, same as `#[resource(..)]`.
+    pub fn with_resources<S: Into<String>>(
+        mut self,
+        resources: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.0.resources = crate::files::leak_all(resources);
+        self
+    }
+
+    pub fn with_xfail(mut self, xfail: bool) -> Self {
+        self.0.xfail = xfail;
+        self
+    }
+
+    pub fn build(self) -> DataTestDesc {
+        self.0
+    }
+}
+
+/// Test function of the data test case: regular test or benchmark.
 pub enum DataTestFn {
     TestFn(Box<dyn FnOnce() + Send + 'static>),
     BenchFn(Box<dyn TDynBenchFn + 'static>),
 }
 
-/// Descriptor of the data test case where the type of the test case data is `T`.
+/// Descriptor of the data test case where the type of the test case data is `T`. New options of
+/// the test cases could be added, so it is created via [`DataTestCaseDesc::new`].
+#[non_exhaustive]
 pub struct DataTestCaseDesc<T> {
     pub case: T,
     pub name: Option<String>,
+    /// Location of the test case definition, printed if the test case fails. Locations in the
+    /// `<path>:<line>` format are shortened to `line <line>` in test names.
     pub location: String,
+    /// Maximum duration of the test case, overriding the timeout of the test. For data files, it
+    /// is given by the `timeout` option of the case (for example, `datatest: {timeout: 5s}`).
+    pub timeout: Option<Duration>,
+    /// Environment variables set for the duration of the test case. For data files, they are given
+    /// by the `env` option of the case (for example, `datatest: {env: {FEATURE_X: "on"}}`).
+    pub env: Vec<(String, String)>,
+    /// Test case is expected to fail (see `#[xfail]`). For data files, it is given by the `xfail`
+    /// option of the case: either `true` or the reason (`datatest: {xfail: "broken by #123"}`).
+    pub xfail: bool,
+}
+
+impl<T> DataTestCaseDesc<T> {
+    /// Test case without any options.
+    pub fn new(case: T, name: Option<String>, location: impl Into<String>) -> Self {
+        DataTestCaseDesc {
+            case,
+            name,
+            location: location.into(),
+            timeout: None,
+            env: Vec::new(),
+            xfail: false,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_env(mut self, env: Vec<(String, String)>) -> Self {
+        self.env = env;
+        self
+    }
+
+    pub fn with_xfail(mut self, xfail: bool) -> Self {
+        self.xfail = xfail;
+        self
+    }
+
+    /// Same test case (with the same options) with the data converted by the function.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> DataTestCaseDesc<U> {
+        DataTestCaseDesc {
+            case: f(self.case),
+            name: self.name,
+            location: self.location,
+            timeout: self.timeout,
+            env: self.env,
+            xfail: self.xfail,
+        }
+    }
 }
 
+#[cfg(feature = "yaml")]
 pub fn yaml<T: DeserializeOwned + TestNameWithDefault + Send + 'static>(
     path: &str,
 ) -> Vec<DataTestCaseDesc<T>> {
+    try_yaml(path).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// Path of the data file given relative to the source file containing the test (`relative_to =
+/// source` option). `source_file` is given by `file!()`, which is relative either to the crate root
+/// directory or to one of its parents (workspace root directory), so the first of these directories
+/// containing the source file is used. Resulting path is relative to the working directory, if
+/// possible, to keep test case locations short.
+#[doc(hidden)]
+pub fn source_relative_path(manifest_dir: &str, source_file: &str, path: &str) -> String {
+    let source_file = Path::new(source_file);
+    let source = Path::new(manifest_dir)
+        .ancestors()
+        .map(|base| base.join(source_file))
+        .find(|source| crate::listing::is_file(source))
+        .unwrap_or_else(|| Path::new(manifest_dir).join(source_file));
+    let resolved = match source.parent() {
+        Some(dir) => dir.join(path),
+        None => Path::new(path).to_path_buf(),
+    };
+    let relative = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| resolved.strip_prefix(cwd).ok().map(Path::to_path_buf));
+    relative.unwrap_or(resolved).to_string_lossy().into_owned()
+}
+
+/// Same as [`yaml`], but returns an error instead of panicking if the file cannot be read or
+/// parsed.
+#[cfg(feature = "yaml")]
+pub fn try_yaml<T: DeserializeOwned + TestNameWithDefault + Send + 'static>(
+    path: &str,
+) -> Result<Vec<DataTestCaseDesc<T>>, String> {
     let input = std::fs::read_to_string(Path::new(path))
-        .unwrap_or_else(|_| panic!("cannot read file '{}'", path));
+        .map_err(|err| format!("cannot read file '{}': {}", path, err))?;
 
-    let index = index_cases(&input);
-    let cases: Vec<T> = serde_yaml::from_str(&input).unwrap();
-    assert_eq!(index.len(), cases.len(), "index does not match test cases");
+    let index =
+        index_cases(&input).map_err(|err| format!("invalid YAML in '{}': {}", path, err))?;
+    let cases: Vec<T> =
+        serde_yaml::from_str(&input).map_err(|err| describe_error(path, &index, err))?;
+    if index.len() != cases.len() {
+        return Err(format!("index does not match test cases in '{}'", path));
+    }
+    let options = case_options(path, &index, &input)?;
 
-    index
+    Ok(index
         .into_iter()
         .zip(cases)
-        .map(|(marker, case)| DataTestCaseDesc {
+        .zip(options)
+        .map(|((marker, case), options)| DataTestCaseDesc {
             name: TestNameWithDefault::name(&case),
             case,
-            location: format!("line {}", marker.line()),
+            location: format!("{}:{}", path, marker.line()),
+            timeout: options.timeout,
+            env: options.env,
+            xfail: options.xfail,
+        })
+        .collect())
+}
+
+/// Read test cases from the data file at `path` in the format given by its extension: TOML files
+/// (`.toml`) list the cases as `[[case]]` tables, while JSON, YAML and files with other extensions
+/// are read by [`try_yaml`] (YAML parser also reads JSON). Without the `yaml` feature, JSON files
+/// are read by `serde_json` instead, and YAML files could not be read. String literal sources of
+/// `#[datatest::data(..)]` use this function, and it could be given to [`expand_files`] to cover a
+/// directory mixing the formats.
+pub fn data_file<T: DeserializeOwned + TestNameWithDefault + Send + 'static>(
+    path: &str,
+) -> Result<Vec<DataTestCaseDesc<T>>, String> {
+    match Format::of(Path::new(path)) {
+        Some(Format::Toml) => try_toml(path),
+        #[cfg(feature = "yaml")]
+        Some(Format::Json) | Some(Format::Yaml) | None => try_yaml(path),
+        #[cfg(not(feature = "yaml"))]
+        Some(Format::Json) => try_json(path),
+        #[cfg(not(feature = "yaml"))]
+        _ => Err(format!(
+            "cannot read test cases from '{}': only JSON and TOML files could be read without the \
+             `yaml` feature of `datatest`",
+            path
+        )),
+    }
+}
+
+/// Read test cases from the JSON file (an array of the test cases) when YAML parser is not
+/// available.
+#[cfg(not(feature = "yaml"))]
+fn try_json<T: DeserializeOwned + TestNameWithDefault>(
+    path: &str,
+) -> Result<Vec<DataTestCaseDesc<T>>, String> {
+    let input = std::fs::read_to_string(Path::new(path))
+        .map_err(|err| format!("cannot read file '{}': {}", path, err))?;
+    let values: Vec<serde_json::Value> = serde_json::from_str(&input)
+        .map_err(|err| format!("cannot parse test cases from '{}': {}", path, err))?;
+    let lines = json_element_lines(&input);
+    if lines.len() != values.len() {
+        return Err(format!("index does not match test cases in '{}'", path));
+    }
+    values
+        .into_iter()
+        .zip(lines)
+        .map(|(value, line)| {
+            let location = format!("{}:{}", path, line);
+            let options = CaseOptions::parse(value.get(OPTIONS_FIELD), &location)?;
+            let case: T = serde_json::from_value(value)
+                .map_err(|err| format!("cannot parse test case at {}: {}", location, err))?;
+            Ok(DataTestCaseDesc {
+                name: TestNameWithDefault::name(&case),
+                case,
+                location,
+                timeout: options.timeout,
+                env: options.env,
+                xfail: options.xfail,
+            })
         })
         .collect()
 }
 
-/// Trait abstracting two scenarios: test case implementing [`ToString`] and test case not
-/// implementing [`ToString`].
+/// Lines (starting from 1) where the elements of the top-level array of the valid JSON document
+/// start.
+#[cfg(not(feature = "yaml"))]
+fn json_element_lines(input: &str) -> Vec<usize> {
+    let mut lines = Vec::new();
+    let (mut line, mut depth) = (1, 0);
+    let (mut in_string, mut escaped) = (false, false);
+    // Next value at the depth of the elements starts an element
+    let mut expect_element = false;
+    for c in input.chars() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        if c == '\n' {
+            line += 1;
+        }
+        if c.is_whitespace() {
+            continue;
+        }
+        if expect_element && c != ']' {
+            lines.push(line);
+        }
+        expect_element = false;
+        match c {
+            '"' => in_string = true,
+            '[' | '{' => {
+                depth += 1;
+                expect_element = depth == 1;
+            }
+            ']' | '}' => depth -= 1,
+            ',' => expect_element = depth == 1,
+            _ => {}
+        }
+    }
+    lines
+}
+
+/// Test cases of the TOML file, given as an array of `[[case]]` tables.
+#[derive(Deserialize)]
+#[serde(bound = "T: DeserializeOwned")]
+struct TomlCases<T> {
+    #[serde(default = "Vec::new")]
+    case: Vec<T>,
+}
+
+fn try_toml<T: DeserializeOwned + TestNameWithDefault>(
+    path: &str,
+) -> Result<Vec<DataTestCaseDesc<T>>, String> {
+    let input = std::fs::read_to_string(Path::new(path))
+        .map_err(|err| format!("cannot read file '{}': {}", path, err))?;
+    let cases: TomlCases<T> = Format::Toml
+        .deserialize(&input)
+        .map_err(|err| format!("cannot parse test cases from '{}': {}", path, err))?;
+    // Options of the test cases, given under the reserved `datatest` table of each case
+    let tables: TomlCases<toml::Value> = Format::Toml
+        .deserialize(&input)
+        .map_err(|err| format!("cannot parse test cases from '{}': {}", path, err))?;
+
+    let locations = toml_locations(path, &input, cases.case.len());
+    cases
+        .case
+        .into_iter()
+        .zip(tables.case)
+        .zip(locations)
+        .map(|((case, table), location)| {
+            let reserved = table
+                .get(OPTIONS_FIELD)
+                .map(serde_json::to_value)
+                .transpose()
+                .map_err(|err| {
+                    format!("invalid options of the test case at {}: {}", location, err)
+                })?;
+            let options = CaseOptions::parse(reserved.as_ref(), &location)?;
+            Ok(DataTestCaseDesc {
+                name: TestNameWithDefault::name(&case),
+                case,
+                location,
+                timeout: options.timeout,
+                env: options.env,
+                xfail: options.xfail,
+            })
+        })
+        .collect()
+}
+
+/// Locations of the `count` test cases of the TOML file: lines of the `[[case]]` headers, if every
+/// case has one (inline arrays have no headers), or the path of the file otherwise.
+fn toml_locations(path: &str, input: &str, count: usize) -> Vec<String> {
+    let headers = input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| line.split_whitespace().collect::<String>() == "[[case]]")
+        .map(|(idx, _)| format!("{}:{}", path, idx + 1))
+        .collect::<Vec<_>>();
+    if headers.len() == count {
+        headers
+    } else {
+        vec![path.to_string(); count]
+    }
+}
+
+/// Validate every test case of the data file at `path` (in any format read by [`data_file`])
+/// against the test case type `T` and, if given, against the JSON Schema at `schema` (requires the
+/// `json_schema` feature), so all invalid cases are reported at once rather than one case at a
+/// time across repeated runs. Returns the problems found, each one giving the location of the
+/// case. The validation pre-pass of the test runner calls it for the data files of
+/// `#[datatest::data(..)]` tests.
+pub fn validate_data_file<T: DeserializeOwned>(path: &str, schema: Option<&str>) -> Vec<String> {
+    let cases = match raw_cases(path) {
+        Ok(cases) => cases,
+        Err(err) => return vec![err],
+    };
+    let schema = match schema.map(crate::schema::Schema::load).transpose() {
+        Ok(schema) => schema,
+        Err(err) => return vec![err],
+    };
+    let mut problems = Vec::new();
+    for (location, mut case) in cases {
+        if let Err(err) = serde_json::from_value::<T>(case.clone()) {
+            problems.push(format!("invalid test case at {}: {}", location, err));
+        }
+        if let Some(ref schema) = schema {
+            // Schema describes the test case type, which is not aware of the options of the case
+            if let Some(fields) = case.as_object_mut() {
+                fields.remove(OPTIONS_FIELD);
+            }
+            problems.extend(schema.validate(&case).into_iter().map(|err| {
+                format!(
+                    "test case at {} does not match the schema: {}",
+                    location, err
+                )
+            }));
+        }
+    }
+    problems
+}
+
+/// Test cases of the data file at `path` as JSON values, along with their locations.
+fn raw_cases(path: &str) -> Result<Vec<(String, serde_json::Value)>, String> {
+    let input = std::fs::read_to_string(Path::new(path))
+        .map_err(|err| format!("cannot read file '{}': {}", path, err))?;
+    match Format::of(Path::new(path)) {
+        Some(Format::Toml) => {
+            let tables: TomlCases<serde_json::Value> = Format::Toml
+                .deserialize(&input)
+                .map_err(|err| format!("cannot parse test cases from '{}': {}", path, err))?;
+            let locations = toml_locations(path, &input, tables.case.len());
+            Ok(locations.into_iter().zip(tables.case).collect())
+        }
+        #[cfg(feature = "yaml")]
+        Some(Format::Json) | Some(Format::Yaml) | None => {
+            let index = index_cases(&input)
+                .map_err(|err| format!("invalid YAML in '{}': {}", path, err))?;
+            let values: Vec<serde_yaml::Value> =
+                serde_yaml::from_str(&input).map_err(|err| describe_error(path, &index, err))?;
+            if index.len() != values.len() {
+                return Err(format!("index does not match test cases in '{}'", path));
+            }
+            index
+                .into_iter()
+                .zip(values)
+                .map(|(marker, value)| {
+                    let location = format!("{}:{}", path, marker.line());
+                    let case = serde_json::to_value(value)
+                        .map_err(|err| format!("invalid test case at {}: {}", location, err))?;
+                    Ok((location, case))
+                })
+                .collect()
+        }
+        #[cfg(not(feature = "yaml"))]
+        Some(Format::Json) => {
+            let values: Vec<serde_json::Value> = serde_json::from_str(&input)
+                .map_err(|err| format!("cannot parse test cases from '{}': {}", path, err))?;
+            let lines = json_element_lines(&input);
+            if lines.len() != values.len() {
+                return Err(format!("index does not match test cases in '{}'", path));
+            }
+            let locations = lines.into_iter().map(|line| format!("{}:{}", path, line));
+            Ok(locations.zip(values).collect())
+        }
+        #[cfg(not(feature = "yaml"))]
+        _ => Err(format!(
+            "cannot read test cases from '{}': only JSON and TOML files could be read without the \
+             `yaml` feature of `datatest`",
+            path
+        )),
+    }
+}
+
+/// Segment of the file split into multiple test cases by [`segments`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    /// Name given on the delimiter line, after the delimiter (`==== <name>`), if any.
+    pub name: Option<String>,
+    /// Contents of the segment, not including the delimiter line.
+    pub text: String,
+    /// Line of the file (starting from 1) where the contents of the segment start, so locations
+    /// within the segment could be translated to the locations within the file.
+    pub line: usize,
+}
+
+/// Split the file at `path` into test cases on the lines starting with the `delimiter`, so a file
+/// packing many test cases yields a separate test for each of them. Text following the delimiter
+/// on the same line is used as the name of the case. Text before the first delimiter (for example,
+/// description of the file) is ignored.
+///
+/// ```ignore
+/// #[datatest::data(datatest::segments("tests/corpus.txt", "===="))]
+/// fn corpus_test(segment: datatest::Segment) {}
+/// ```
+pub fn segments(path: &str, delimiter: &str) -> Result<Vec<DataTestCaseDesc<Segment>>, String> {
+    let input = std::fs::read_to_string(Path::new(path))
+        .map_err(|err| format!("cannot read file '{}': {}", path, err))?;
+
+    let mut cases = Vec::new();
+    let mut current: Option<Segment> = None;
+    for (idx, line) in input.lines().enumerate() {
+        if let Some(header) = line.strip_prefix(delimiter) {
+            cases.extend(current.take());
+            let name = header.trim();
+            current = Some(Segment {
+                name: Some(name.to_string()).filter(|name| !name.is_empty()),
+                text: String::new(),
+                line: idx + 2,
+            });
+        } else if let Some(ref mut segment) = current {
+            segment.text += line;
+            segment.text.push('\n');
+        }
+    }
+    cases.extend(current);
+
+    Ok(cases
+        .into_iter()
+        .map(|segment| DataTestCaseDesc {
+            name: segment.name.clone(),
+            // Location of the delimiter line
+            location: format!("{}:{}", path, segment.line - 1),
+            case: segment,
+            timeout: None,
+            env: Vec::new(),
+            xfail: false,
+        })
+        .collect())
+}
+
+/// Expand each file under the `root` directory matching the `pattern` (the same way as
+/// `#[datatest::files(..)]` matches test files) into multiple test cases via the `parse` function,
+/// for corpora made of many data files each containing a list of test cases. Files are parsed in
+/// the order of their paths, any of the data sources taking a path could be used as a parser
+/// ([`try_yaml`], which also reads JSON, [`segments`], etc). Cases are named after the file and the
+/// name of the case (`<file>::<name>`), or its index in the file if the case has no name
+/// (`<file>::case_<index>`).
+///
+/// ```ignore
+/// #[datatest::data(datatest::expand_files("tests/corpus", r"\.yaml$", datatest::try_yaml))]
+/// fn corpus_test(case: Case) {}
+/// ```
+pub fn expand_files<T, C, F>(
+    root: &str,
+    pattern: &str,
+    parse: F,
+) -> Result<Vec<DataTestCaseDesc<T>>, String>
+where
+    C: IntoCases<T>,
+    F: Fn(&str) -> C,
+{
+    let regex = regex::Regex::new(pattern)
+        .map_err(|err| format!("invalid regular expression '{}': {}", pattern, err))?;
+    let mut files = crate::listing::list_directory(Path::new(root))
+        .map_err(|err| format!("cannot scan '{}': {}", root, err))?;
+    files.sort();
+
+    let mut cases = Vec::new();
+    for file in files {
+        let path = file.to_string_lossy();
+        if !regex.is_match(&path) {
+            continue;
+        }
+        let relative = file.strip_prefix(root).unwrap_or(&file).to_string_lossy();
+        for (index, mut case) in parse(&path).into_cases()?.into_iter().enumerate() {
+            let name = case
+                .name
+                .take()
+                .unwrap_or_else(|| format!("case_{}", index));
+            case.name = Some(format!("{}::{}", relative, name));
+            cases.push(case);
+        }
+    }
+    Ok(cases)
+}
+
+/// Test case read by [`json_lines`], which keeps the whole source file alive, so test case types
+/// could borrow from it (`&'a str`, `&'a [u8]`, etc) instead of copying the data into owned values.
+#[derive(Clone)]
+pub struct BorrowedCase {
+    source: Arc<str>,
+    range: Range<usize>,
+    location: String,
+}
+
+impl BorrowedCase {
+    /// Text of the test case (single line of the file).
+    pub fn text(&self) -> &str {
+        &self.source[self.range.clone()]
+    }
+
+    /// Deserialize the test case borrowing from the source file. Panics if the test case cannot be
+    /// deserialized into `T`.
+    pub fn deserialize<'a, T: Deserialize<'a>>(&'a self) -> T {
+        self.try_deserialize()
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as [`BorrowedCase::deserialize`], but returns an error instead of panicking.
+    pub fn try_deserialize<'a, T: Deserialize<'a>>(&'a self) -> Result<T, String> {
+        serde_json::from_str(self.text())
+            .map_err(|err| format!("cannot parse test case at {}: {}", self.location, err))
+    }
+}
+
+impl Debug for BorrowedCase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.text(), f)
+    }
+}
+
+/// Read test cases from the file at `path` in the JSON Lines format (one JSON value per line, blank
+/// lines are skipped). Test cases borrow from the file read once (see [`BorrowedCase`]), so test
+/// case types could have borrowed fields:
+///
+/// ```ignore
+/// #[derive(serde::Deserialize)]
+/// struct Line<'a> {
+///   #[serde(borrow)]
+///   text: &'a str,
+/// }
+///
+/// #[datatest::data(datatest::json_lines("tests/corpus.jsonl"))]
+/// fn corpus_test(line: Line<'_>) {}
+/// ```
+pub fn json_lines(path: &str) -> Result<Vec<DataTestCaseDesc<BorrowedCase>>, String> {
+    let input = std::fs::read_to_string(Path::new(path))
+        .map_err(|err| format!("cannot read file '{}': {}", path, err))?;
+    let source: Arc<str> = Arc::from(input);
+
+    let mut cases = Vec::new();
+    let mut offset = 0;
+    for (idx, line) in source.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        let line = line.trim_end();
+        if line.trim_start().is_empty() {
+            continue;
+        }
+        let location = format!("{}:{}", path, idx + 1);
+        serde_json::from_str::<IgnoredAny>(line)
+            .map_err(|err| format!("cannot parse test case at {}: {}", location, err))?;
+        cases.push(DataTestCaseDesc {
+            name: None,
+            location: location.clone(),
+            case: BorrowedCase {
+                source: source.clone(),
+                range: start..start + line.len(),
+                location,
+            },
+            timeout: None,
+            env: Vec::new(),
+            xfail: false,
+        });
+    }
+    Ok(cases)
+}
+
+/// Options of the test case given under the reserved `datatest` field of the test cases in data
+/// files (`timeout`, `env` and `xfail`), so they do not collide with the fields of the test case
+/// types. Test case types are not aware of this field, so it is read from the parsed document
+/// directly, converted to JSON value so every format shares the same rules.
+#[derive(Default)]
+struct CaseOptions {
+    timeout: Option<Duration>,
+    env: Vec<(String, String)>,
+    xfail: bool,
+}
+
+/// Reserved field of the test cases in data files holding the options of the test case.
+const OPTIONS_FIELD: &str = "datatest";
+
+impl CaseOptions {
+    /// Parse the value of the reserved field of the test case at `location` (`<path>:<line>`).
+    fn parse(reserved: Option<&serde_json::Value>, location: &str) -> Result<CaseOptions, String> {
+        let mut options = CaseOptions::default();
+        let reserved = match reserved {
+            Some(reserved) => reserved,
+            None => return Ok(options),
+        };
+        let mapping = reserved.as_object().ok_or_else(|| {
+            format!(
+                "invalid `{}` of the test case at {}: expected a mapping of the options",
+                OPTIONS_FIELD, location
+            )
+        })?;
+        for key in mapping.keys() {
+            match key.as_str() {
+                "timeout" | "env" | "xfail" => {}
+                _ => {
+                    return Err(format!(
+                        "unknown option {:?} of the test case at {}, expected `timeout`, `env` or \
+                         `xfail`",
+                        key, location
+                    ))
+                }
+            }
+        }
+        if let Some(timeout) = mapping.get("timeout") {
+            let timeout = parse_timeout(timeout).map_err(|err| {
+                format!("invalid timeout of the test case at {}: {}", location, err)
+            })?;
+            options.timeout = Some(timeout);
+        }
+        if let Some(env) = mapping.get("env") {
+            options.env = parse_env(env).map_err(|err| {
+                format!(
+                    "invalid environment of the test case at {}: {}",
+                    location, err
+                )
+            })?;
+        }
+        if let Some(xfail) = mapping.get("xfail") {
+            options.xfail = match xfail {
+                serde_json::Value::Bool(xfail) => *xfail,
+                // Reason of the expected failure
+                serde_json::Value::String(_) => true,
+                _ => {
+                    return Err(format!(
+                        "invalid `xfail` of the test case at {}: expected `true` or the reason of \
+                         the expected failure",
+                        location
+                    ))
+                }
+            };
+        }
+        Ok(options)
+    }
+}
+
+/// Read options of each test case.
+#[cfg(feature = "yaml")]
+fn case_options(path: &str, index: &[Marker], input: &str) -> Result<Vec<CaseOptions>, String> {
+    let values: Vec<serde_yaml::Value> = serde_yaml::from_str(input)
+        .map_err(|err| format!("cannot parse test cases from '{}': {}", path, err))?;
+    values
+        .iter()
+        .zip(index)
+        .map(|(value, marker)| {
+            let location = format!("{}:{}", path, marker.line());
+            let reserved = value
+                .get(OPTIONS_FIELD)
+                .map(serde_json::to_value)
+                .transpose()
+                .map_err(|err| {
+                    format!("invalid options of the test case at {}: {}", location, err)
+                })?;
+            CaseOptions::parse(reserved.as_ref(), &location)
+        })
+        .collect()
+}
+
+/// Parse timeout given either as a duration string or as a number of seconds.
+fn parse_timeout(value: &serde_json::Value) -> Result<Duration, String> {
+    match value {
+        serde_json::Value::String(timeout) => crate::duration::parse(timeout),
+        serde_json::Value::Number(secs) => secs
+            .as_f64()
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .ok_or_else(|| format!("invalid number of seconds `{}`", secs)),
+        _ => Err(String::from("expected duration like `5s` or `500ms`")),
+    }
+}
+
+/// Parse mapping of environment variable names to their values (strings, numbers or booleans).
+fn parse_env(value: &serde_json::Value) -> Result<Vec<(String, String)>, String> {
+    let mapping = value
+        .as_object()
+        .ok_or_else(|| String::from("expected mapping of variable names to values"))?;
+    mapping
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::String(value) => value.clone(),
+                serde_json::Value::Number(value) => value.to_string(),
+                serde_json::Value::Bool(value) => value.to_string(),
+                _ => return Err(format!("value of variable '{}' must be a scalar", key)),
+            };
+            Ok((key.clone(), value))
+        })
+        .collect()
+}
+
+/// Render deserialization error with the file path, the location and the index of the test case
+/// being parsed.
+#[cfg(feature = "yaml")]
+fn describe_error(path: &str, index: &[Marker], err: serde_yaml::Error) -> String {
+    match err.location() {
+        Some(location) => {
+            // Error belongs to the last case which starts before the error location
+            let case = index
+                .iter()
+                .rposition(|marker| marker.index() <= location.index());
+            let case = case.map_or_else(String::new, |case| format!(", case #{}", case));
+            format!(
+                "cannot parse test cases from '{}' (line {}, column {}{}): {}",
+                path,
+                location.line(),
+                location.column(),
+                case,
+                err
+            )
+        }
+        None => format!("cannot parse test cases from '{}': {}", path, err),
+    }
+}
+
+/// Conversion of the value returned by the source of the `#[datatest::data(..)]` test into the
+/// list of test cases. Sources can either return test cases directly or return a `Result`, in
+/// which case the error fails the test instead of aborting the whole test run.
+#[doc(hidden)]
+pub trait IntoCases<T> {
+    fn into_cases(self) -> Result<Vec<DataTestCaseDesc<T>>, String>;
+}
+
+impl<T> IntoCases<T> for Vec<DataTestCaseDesc<T>> {
+    fn into_cases(self) -> Result<Vec<DataTestCaseDesc<T>>, String> {
+        Ok(self)
+    }
+}
+
+impl<T, E: Display> IntoCases<T> for Result<Vec<DataTestCaseDesc<T>>, E> {
+    fn into_cases(self) -> Result<Vec<DataTestCaseDesc<T>>, String> {
+        self.map_err(|err| err.to_string())
+    }
+}
+
+/// Name of the `#[datatest::data(..)]` test case, appended to the name of the test. By default,
+/// test cases implementing [`Display`] are named by it (and other cases by their location), so the
+/// trait only needs to be implemented by the types which `Display` is unsuitable for names (for
+/// example, too long or spanning multiple lines):
+///
+/// ```
+/// struct Query {
+///     id: u32,
+///     sql: String,
+/// }
+///
+/// impl std::fmt::Display for Query {
+///     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+///         write!(f, "{}", self.sql)
+///     }
+/// }
+///
+/// impl datatest::TestName for Query {
+///     fn test_name(&self) -> String {
+///         format!("query-{}", self.id)
+///     }
+/// }
+/// ```
+pub trait TestName {
+    fn test_name(&self) -> String;
+}
+
+/// Test case paired with its name, so the test case type does not need to implement [`TestName`]
+/// or [`Display`] to get a readable name. It could be deserialized from any of the following shapes
+/// (YAML shown, other formats work the same way):
+///
+/// ```yaml
+/// # Name along with the value
+/// - name: empty
+///   value: { input: "", output: 0 }
+/// # Name along with the fields of the value
+/// - name: single
+///   input: "a"
+///   output: 1
+/// # Value keyed by its name
+/// - pair: { input: "ab", output: 2 }
+/// # Pair of the name and the value
+/// - [triple, { input: "abc", output: 3 }]
+/// ```
+///
+/// When the name is given along with the fields of the value, the `name` field is not passed on to
+/// the value.
+///
+/// With `named = true` option of `#[datatest::data(..)]`, test cases are read as `Named<T>`, while
+/// the test function still receives `T` (or `&T`, `&mut T`):
+///
+/// ```ignore
+/// #[datatest::data("tests/lengths.yaml", named = true)]
+/// fn length(case: &Case) {
+///   assert_eq!(case.input.len(), case.output);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Named<T> {
+    pub name: String,
+    pub value: T,
+}
+
+impl<T> TestName for Named<T> {
+    fn test_name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+impl<T> std::ops::Deref for Named<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> std::ops::DerefMut for Named<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+/// Shapes [`Named`] could be deserialized from, tried in order.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NamedRepr<T> {
+    /// Name along with the value
+    Value { name: String, value: T },
+    /// Name along with the fields of the value
+    Fields {
+        name: String,
+        #[serde(flatten)]
+        value: T,
+    },
+    /// Pair of the name and the value
+    Pair(String, T),
+    /// Value keyed by its name (mapping with a single entry)
+    Keyed(std::collections::BTreeMap<String, T>),
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Named<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        const EXPECTED: &str = "expected test case with `name` field, mapping of the name to the \
+                                test case or pair of the name and the test case";
+        match NamedRepr::deserialize(deserializer).map_err(|_| D::Error::custom(EXPECTED))? {
+            NamedRepr::Value { name, value }
+            | NamedRepr::Fields { name, value }
+            | NamedRepr::Pair(name, value) => Ok(Named { name, value }),
+            NamedRepr::Keyed(cases) if cases.len() == 1 => {
+                let (name, value) = cases.into_iter().next().unwrap();
+                Ok(Named { name, value })
+            }
+            NamedRepr::Keyed(_) => Err(D::Error::custom(EXPECTED)),
+        }
+    }
+}
+
+/// Name of the test case: given by [`TestName`], if the test case implements it, otherwise given
+/// by [`ToString`] (if implemented).
 #[doc(hidden)]
 pub trait TestNameWithDefault {
     fn name(&self) -> Option<String>;
 }
 
-// For those types which do not implement `ToString`/`Display`.
 impl<T> TestNameWithDefault for T {
     default fn name(&self) -> Option<String> {
+        DisplayName::display_name(self)
+    }
+}
+
+impl<T: TestName> TestNameWithDefault for T {
+    fn name(&self) -> Option<String> {
+        Some(self.test_name())
+    }
+}
+
+/// Trait abstracting two scenarios: test case implementing [`ToString`] and test case not
+/// implementing [`ToString`].
+trait DisplayName {
+    fn display_name(&self) -> Option<String>;
+}
+
+// For those types which do not implement `ToString`/`Display`.
+impl<T> DisplayName for T {
+    default fn display_name(&self) -> Option<String> {
         None
     }
 }
 
 // For those types which implement `ToString`/`Display`.
-impl<T: ToString> TestNameWithDefault for T {
-    fn name(&self) -> Option<String> {
+impl<T: ToString> DisplayName for T {
+    fn display_name(&self) -> Option<String> {
         Some(self.to_string())
     }
 }
 
+/// Trait abstracting two scenarios: test case implementing [`Debug`] (which is used to detect test
+/// cases with the same content) and test case not implementing [`Debug`].
+#[doc(hidden)]
+pub trait TestContentWithDefault {
+    fn content(&self) -> Option<String>;
+}
+
+// For those types which do not implement `Debug`.
+impl<T> TestContentWithDefault for T {
+    default fn content(&self) -> Option<String> {
+        None
+    }
+}
+
+// For those types which implement `Debug`.
+impl<T: Debug> TestContentWithDefault for T {
+    fn content(&self) -> Option<String> {
+        Some(format!("{:?}", self))
+    }
+}
+
+/// What to do with the test cases of one source which have the same name or content (usually,
+/// copy-pasted by mistake).
+#[doc(hidden)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateCases {
+    /// Run the duplicates as they are (default).
+    Keep,
+    /// Fail the test, pointing to the locations of both cases.
+    Fail,
+    /// Add index of the test case to the name of the duplicate.
+    Index,
+}
+
+/// Find test cases with the same name or content (`contents` are given in the same order as
+/// `cases`) and handle them according to the `policy`.
+#[doc(hidden)]
+pub fn check_duplicate_cases<T>(
+    cases: &mut [DataTestCaseDesc<T>],
+    contents: &[Option<String>],
+    policy: DuplicateCases,
+) -> Result<(), String> {
+    if policy == DuplicateCases::Keep {
+        return Ok(());
+    }
+    let mut names: HashMap<String, usize> = HashMap::new();
+    let mut seen_contents: HashMap<&str, usize> = HashMap::new();
+    let mut errors = Vec::new();
+    for index in 0..cases.len() {
+        let content = contents.get(index).and_then(Option::as_deref);
+        let same_name = cases[index]
+            .name
+            .as_ref()
+            .and_then(|name| names.get(name).copied());
+        let same_content = content.and_then(|content| seen_contents.get(content).copied());
+        let (first, reason) = match (same_name, same_content) {
+            (_, Some(first)) => (first, "same content"),
+            (Some(first), None) => (first, "same name"),
+            (None, None) => {
+                if let Some(ref name) = cases[index].name {
+                    names.insert(name.clone(), index);
+                }
+                if let Some(content) = content {
+                    seen_contents.insert(content, index);
+                }
+                continue;
+            }
+        };
+        match policy {
+            DuplicateCases::Keep => {}
+            DuplicateCases::Fail => errors.push(format!(
+                "'{}' and '{}' ({})",
+                cases[first].location, cases[index].location, reason
+            )),
+            DuplicateCases::Index => {
+                let name = cases[index].name.take().unwrap_or_default();
+                cases[index].name = Some(format!("{}#{}", name, index));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "found duplicate test cases (use `duplicates = index` option to keep them): {}",
+            errors.join(", ")
+        ))
+    }
+}
+
 #[doc(hidden)]
 pub struct DataBenchFn<T>(pub fn(&mut Bencher, T), pub T)
 where
@@ -85,12 +1213,13 @@ where
 }
 
 /// Build an index from the YAML source to the location of each test case (top level array elements).
-fn index_cases(source: &str) -> Vec<Marker> {
+#[cfg(feature = "yaml")]
+fn index_cases(source: &str) -> Result<Vec<Marker>, ScanError> {
     let mut parser = yaml_rust::parser::Parser::new(source.chars());
     let mut index = Vec::new();
     let mut depth = 0;
     loop {
-        let (event, marker) = parser.next().expect("invalid YAML");
+        let (event, marker) = parser.next()?;
         match event {
             Event::StreamEnd => {
                 break;
@@ -98,7 +1227,7 @@ fn index_cases(source: &str) -> Vec<Marker> {
             Event::Scalar(_, _, _, _) if depth == 1 => {
                 index.push(marker);
             }
-            Event::MappingStart(_idx) if depth == 1 => {
+            Event::MappingStart(_idx) | Event::SequenceStart(_idx) if depth == 1 => {
                 index.push(marker);
                 depth += 1;
             }
@@ -112,5 +1241,5 @@ fn index_cases(source: &str) -> Vec<Marker> {
         }
     }
 
-    index
+    Ok(index)
 }
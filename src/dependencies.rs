@@ -0,0 +1,34 @@
+//! Dependencies between tests (`depends_on` option): cases of the dependent test run after all
+//! cases of its prerequisites finished, and fail without running if any of them failed.
+use std::collections::BTreeMap;
+use std::sync::{Mutex, PoisonError};
+
+/// Cases not run because of the failed prerequisites, by the test name.
+static NOT_RUN: Mutex<BTreeMap<String, Vec<String>>> = Mutex::new(BTreeMap::new());
+
+/// Fail the test case instead of running it, as its prerequisites (`failed` test functions)
+/// failed.
+pub(crate) fn fail(name: &str, failed: Vec<String>) {
+    let message = format!(
+        "not run, as prerequisite tests failed: {}",
+        failed.join(", ")
+    );
+    NOT_RUN
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(name.to_string(), failed);
+    panic!("{}", message);
+}
+
+/// Print the cases not run because of the failed prerequisites.
+pub(crate) fn print_summary() {
+    let not_run = NOT_RUN.lock().unwrap_or_else(PoisonError::into_inner);
+    if not_run.is_empty() {
+        return;
+    }
+    println!("not run due to failed dependencies:");
+    for (name, failed) in not_run.iter() {
+        println!("    {} (depends on {})", name, failed.join(", "));
+    }
+    println!();
+}
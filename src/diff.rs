@@ -0,0 +1,159 @@
+//! Word-level diff of the texts for the failure messages. Lines are matched first, then the changed
+//! words within the changed lines are underlined, so one changed word in a long paragraph is easy
+//! to spot. Words are split at Unicode word boundaries (combining characters stay with their base
+//! characters) and underlines are aligned by display width (wide glyphs take two columns).
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Most pairs of lines (or words) compared to find the exact difference, as the comparison takes
+/// quadratic time. Beyond it, the whole changed middle of the texts is shown as removed and added.
+const MAX_COMPARISONS: usize = 4_000_000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+/// Render the difference between the expected and the actual text: each group of changed lines is
+/// preceded by `@@ line <N> @@` header (line number in the expected text), removed lines are
+/// prefixed with `- `, added lines with `+ `, and changed words are underlined with `^`.
+pub(crate) fn text_diff(expected: &str, actual: &str) -> String {
+    let expected_lines = expected.lines().collect::<Vec<_>>();
+    let actual_lines = actual.lines().collect::<Vec<_>>();
+    let changes = diff(&expected_lines, &actual_lines, MAX_COMPARISONS);
+
+    let mut output = String::new();
+    let (mut expected_idx, mut actual_idx) = (0, 0);
+    let mut pos = 0;
+    while pos < changes.len() {
+        if changes[pos] == Change::Same {
+            expected_idx += 1;
+            actual_idx += 1;
+            pos += 1;
+            continue;
+        }
+        let end = changes[pos..]
+            .iter()
+            .position(|change| *change == Change::Same)
+            .map_or(changes.len(), |len| pos + len);
+        let removed_count = changes[pos..end]
+            .iter()
+            .filter(|change| **change == Change::Removed)
+            .count();
+        let removed = &expected_lines[expected_idx..expected_idx + removed_count];
+        let added = &actual_lines[actual_idx..actual_idx + (end - pos - removed_count)];
+        output.push_str(&format!("@@ line {} @@\n", expected_idx + 1));
+        render_hunk(removed, added, &mut output);
+        expected_idx += removed.len();
+        actual_idx += added.len();
+        pos = end;
+    }
+    if output.is_empty() && expected != actual {
+        // Texts only differ in the trailing line break
+        output.push_str(if expected.ends_with('\n') {
+            "@@ end @@\n- <line break>\n"
+        } else {
+            "@@ end @@\n+ <line break>\n"
+        });
+    }
+    output
+}
+
+/// Render removed and added lines of one group: lines are paired in order and changed words of
+/// each pair are underlined; lines without a pair are shown as a whole.
+fn render_hunk(removed: &[&str], added: &[&str], output: &mut String) {
+    let pairs = removed.len().min(added.len());
+    for idx in 0..pairs {
+        let expected_words = removed[idx].split_word_bounds().collect::<Vec<_>>();
+        let actual_words = added[idx].split_word_bounds().collect::<Vec<_>>();
+        let changes = diff(&expected_words, &actual_words, MAX_COMPARISONS);
+        let expected_changed = changes.iter().filter(|c| **c != Change::Added);
+        let actual_changed = changes.iter().filter(|c| **c != Change::Removed);
+        render_line('-', &expected_words, expected_changed, output);
+        render_line('+', &actual_words, actual_changed, output);
+    }
+    for line in &removed[pairs..] {
+        output.push_str(&format!("- {}\n", line));
+    }
+    for line in &added[pairs..] {
+        output.push_str(&format!("+ {}\n", line));
+    }
+}
+
+/// Render the line with the underline of the changed words (`changes` gives the change of each
+/// word of the line).
+fn render_line<'a>(
+    marker: char,
+    words: &[&str],
+    changes: impl Iterator<Item = &'a Change>,
+    output: &mut String,
+) {
+    let mut underline = String::new();
+    for (word, change) in words.iter().zip(changes) {
+        let fill = if *change == Change::Same { ' ' } else { '^' };
+        // Changed zero-width characters (like combining marks) are still marked
+        let width = match word.width() {
+            0 if *change != Change::Same => 1,
+            width => width,
+        };
+        underline.extend(std::iter::repeat_n(fill, width));
+    }
+    output.push_str(&format!("{} {}\n", marker, words.concat()));
+    let underline = underline.trim_end();
+    if !underline.is_empty() {
+        output.push_str(&format!("  {}\n", underline));
+    }
+}
+
+/// Shortest edit script turning `left` into `right` (via the longest common subsequence). Common
+/// prefix and suffix are skipped; if the rest is too big to compare, it is replaced as a whole.
+fn diff<T: PartialEq>(left: &[T], right: &[T], max_comparisons: usize) -> Vec<Change> {
+    let prefix = left.iter().zip(right).take_while(|(a, b)| a == b).count();
+    let suffix = left[prefix..]
+        .iter()
+        .rev()
+        .zip(right[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let left_mid = &left[prefix..left.len() - suffix];
+    let right_mid = &right[prefix..right.len() - suffix];
+
+    let mut changes = vec![Change::Same; prefix];
+    if left_mid.len().saturating_mul(right_mid.len()) > max_comparisons {
+        changes.extend(std::iter::repeat_n(Change::Removed, left_mid.len()));
+        changes.extend(std::iter::repeat_n(Change::Added, right_mid.len()));
+    } else {
+        // Length of the longest common subsequence of the suffixes of the sequences
+        let (rows, cols) = (left_mid.len(), right_mid.len());
+        let mut lcs = vec![0usize; (rows + 1) * (cols + 1)];
+        for i in (0..rows).rev() {
+            for j in (0..cols).rev() {
+                lcs[i * (cols + 1) + j] = if left_mid[i] == right_mid[j] {
+                    lcs[(i + 1) * (cols + 1) + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * (cols + 1) + j].max(lcs[i * (cols + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < rows || j < cols {
+            if i < rows && j < cols && left_mid[i] == right_mid[j] {
+                changes.push(Change::Same);
+                i += 1;
+                j += 1;
+            } else if j == cols
+                || (i < rows && lcs[(i + 1) * (cols + 1) + j] >= lcs[i * (cols + 1) + j + 1])
+            {
+                changes.push(Change::Removed);
+                i += 1;
+            } else {
+                changes.push(Change::Added);
+                j += 1;
+            }
+        }
+    }
+    changes.extend(std::iter::repeat_n(Change::Same, suffix));
+    changes
+}
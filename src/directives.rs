@@ -0,0 +1,281 @@
+//! Support for directives embedded into test files (in the style of LLVM `lit`)
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Single directive of the test file: `<comment> <NAME>: <value>` line, for example,
+/// `// RUN: cat %s`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directive {
+    pub name: String,
+    pub value: String,
+    /// Line of the test file (starting from 1) where directive is defined.
+    pub line: usize,
+}
+
+/// Test file along with the directives embedded into it. Directives are lines starting with a
+/// comment (`//`, `#`, `;` or `--`) followed by the upper-case directive name and a colon:
+///
+/// ```text
+/// // RUN: mycc --check %s
+/// // EXPECT-ERROR: undefined variable `x`
+/// // SKIP: windows
+/// ```
+///
+/// Could be used as an argument of `#[datatest::files]` test function, in which case it is loaded
+/// from the matched file.
+#[derive(Debug, Default, Clone)]
+pub struct Directives {
+    path: PathBuf,
+    source: String,
+    directives: Vec<Directive>,
+}
+
+impl Directives {
+    /// Parse directives from the `source` of the test file at `path`.
+    pub fn parse(path: impl Into<PathBuf>, source: String) -> Directives {
+        let directives = parse_directives(&source);
+        Directives {
+            path: path.into(),
+            source,
+            directives,
+        }
+    }
+
+    /// Read the test file at `path` and parse its directives.
+    pub fn load(path: &Path) -> Directives {
+        Directives::parse(path, crate::read_to_string(path))
+    }
+
+    /// Check if the test file at `path` is skipped on the current platform via `SKIP` directive.
+    /// Could be used to ignore test files of `#[datatest::files]` tests:
+    ///
+    /// ```ignore
+    /// #[datatest::files("tests/lit", { input in r"\.c$" if !datatest::Directives::is_skipped })]
+    /// ```
+    pub fn is_skipped(path: &Path) -> bool {
+        Directives::load(path).skip_reason().is_some()
+    }
+
+    /// Path of the test file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Full contents of the test file, including the directives.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// All directives, in order of their definition.
+    pub fn iter(&self) -> std::slice::Iter<'_, Directive> {
+        self.directives.iter()
+    }
+
+    /// Value of the first directive with the given name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.directives
+            .iter()
+            .find(|directive| directive.name == name)
+            .map(|directive| directive.value.as_str())
+    }
+
+    /// Values of all directives with the given name.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.directives
+            .iter()
+            .filter(move |directive| directive.name == name)
+            .map(|directive| directive.value.as_str())
+    }
+
+    /// Value of the first `SKIP` directive which matches the current platform. `SKIP` directive
+    /// lists comma-separated platforms: operating system (`linux`, `macos`, `windows`, ..), family
+    /// (`unix` or `windows`) or `all`.
+    pub fn skip_reason(&self) -> Option<&str> {
+        self.get_all("SKIP").find(|value| {
+            value.split(',').map(str::trim).any(|platform| {
+                platform == "all"
+                    || platform == std::env::consts::OS
+                    || platform == std::env::consts::FAMILY
+            })
+        })
+    }
+
+    /// Built-in driver: execute `RUN` directives (with `%s` substituted by the path of the test
+    /// file) via the system shell, in order. If the file has `EXPECT-ERROR` directives, the last
+    /// command must fail and its error output must contain each of them; otherwise, all commands
+    /// must succeed. Returns the standard output of the last command.
+    pub fn run(&self) -> String {
+        let commands = self
+            .directives
+            .iter()
+            .filter(|directive| directive.name == "RUN")
+            .collect::<Vec<_>>();
+        if commands.is_empty() {
+            panic!("no RUN directives found in '{}'", self.path.display());
+        }
+        let expected_errors = self.get_all("EXPECT-ERROR").collect::<Vec<_>>();
+
+        let mut stdout = String::new();
+        for (idx, directive) in commands.iter().enumerate() {
+            let command = directive.value.replace("%s", &self.path.to_string_lossy());
+            let output = shell(&command).unwrap_or_else(|err| {
+                panic!(
+                    "cannot run command `{}` ({}:{}): {}",
+                    command,
+                    self.path.display(),
+                    directive.line,
+                    err
+                )
+            });
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let last = idx + 1 == commands.len();
+            if last && !expected_errors.is_empty() {
+                if output.status.success() {
+                    panic!(
+                        "command `{}` ({}:{}) succeeded, but an error was expected",
+                        command,
+                        self.path.display(),
+                        directive.line
+                    );
+                }
+                for expected in &expected_errors {
+                    if !stderr.contains(expected) {
+                        panic!(
+                            "error output of the command `{}` ({}:{}) does not contain `{}`:\n{}",
+                            command,
+                            self.path.display(),
+                            directive.line,
+                            expected,
+                            stderr
+                        );
+                    }
+                }
+            } else if !output.status.success() {
+                panic!(
+                    "command `{}` ({}:{}) failed with {}:\n{}",
+                    command,
+                    self.path.display(),
+                    directive.line,
+                    output.status,
+                    stderr
+                );
+            }
+            stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        }
+        stdout
+    }
+}
+
+/// Single expectation of the test file: text following the comment prefix, for example,
+/// `#~ expect: 42`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expectation {
+    /// Line of the test file (starting from 1) where expectation is given.
+    pub line: usize,
+    pub text: String,
+}
+
+/// Expectations collected from the comments of the test file, so the input and the expected
+/// output could live in the same file. Each line containing the prefix gives an expectation: the
+/// text following the prefix.
+///
+/// Could be used as an argument of `#[datatest::files]` test function via `comments(..)` mapping:
+///
+/// ```ignore
+/// #[datatest::files("tests/scripts", {
+///   input in r"\.py$",
+///   expected = comments("#~ expect:"),
+/// })]
+/// fn script_test(input: &str, expected: &datatest::Expectations) {}
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Expectations {
+    expectations: Vec<Expectation>,
+}
+
+impl Expectations {
+    /// Collect expectations given after the `prefix` in the `source`.
+    pub fn parse(source: &str, prefix: &str) -> Expectations {
+        let expectations = source
+            .lines()
+            .enumerate()
+            .filter_map(|(idx, line)| {
+                line.find(prefix).map(|pos| Expectation {
+                    line: idx + 1,
+                    text: line[pos + prefix.len()..].trim().to_string(),
+                })
+            })
+            .collect();
+        Expectations { expectations }
+    }
+
+    /// Read the test file at `path` and collect expectations given after the `prefix`.
+    pub fn load(path: &Path, prefix: &str) -> Expectations {
+        Expectations::parse(&crate::read_to_string(path), prefix)
+    }
+
+    /// All expectations, in order of their definition.
+    pub fn iter(&self) -> std::slice::Iter<'_, Expectation> {
+        self.expectations.iter()
+    }
+
+    /// Texts of all expectations, in order of their definition.
+    pub fn texts(&self) -> Vec<&str> {
+        self.expectations
+            .iter()
+            .map(|expectation| expectation.text.as_str())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.expectations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expectations.is_empty()
+    }
+}
+
+/// Find all directives in the `source`.
+pub(crate) fn parse_directives(source: &str) -> Vec<Directive> {
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            parse_directive(line).map(|(name, value)| Directive {
+                name: name.to_string(),
+                value: value.to_string(),
+                line: idx + 1,
+            })
+        })
+        .collect()
+}
+
+/// Split the line into directive name and value, if the line is a directive.
+fn parse_directive(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    let rest = ["//", "--", "#", ";"]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))?;
+    let rest = rest.trim_start_matches(&['/', '#', ';', '-'][..]);
+    let (name, value) = rest.trim_start().split_once(':')?;
+    let is_name = name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-');
+    if is_name {
+        Some((name, value.trim()))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> std::io::Result<Output> {
+    Command::new("sh").arg("-c").arg(command).output()
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> std::io::Result<Output> {
+    Command::new("cmd").arg("/C").arg(command).output()
+}
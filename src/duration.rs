@@ -0,0 +1,121 @@
+//! Durations given as a number followed by the unit: `ms`, `s`, `m` or `h` (for example, `500ms`
+//! or `1.5s`). The same parser is used for the `timeout` option of the attributes (evaluated at
+//! compile time, as the procedural macro cannot share code with this crate) and for the durations
+//! given at runtime, so it only uses integer arithmetic available in constants.
+use std::time::Duration;
+
+/// Why the duration could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DurationError {
+    /// Number is missing or malformed.
+    Number,
+    /// Unit is missing or unknown.
+    Unit,
+    /// Duration does not fit into 64 bits of nanoseconds.
+    Overflow,
+}
+
+impl DurationError {
+    pub const fn message(self) -> &'static str {
+        match self {
+            DurationError::Number => "invalid duration, expected a number followed by the unit",
+            DurationError::Unit => {
+                "invalid duration, expected a number followed by `ms`, `s`, `m` or `h`"
+            }
+            DurationError::Overflow => "invalid duration, the duration is too long",
+        }
+    }
+}
+
+/// Nanoseconds in one unit of the duration.
+const fn unit_nanos(unit: &[u8]) -> Option<u64> {
+    match unit {
+        b"ms" => Some(1_000_000),
+        b"s" => Some(1_000_000_000),
+        b"m" => Some(60_000_000_000),
+        b"h" => Some(3_600_000_000_000),
+        _ => None,
+    }
+}
+
+/// Parse the duration (surrounding whitespace is allowed).
+pub const fn parse_duration(value: &str) -> Result<Duration, DurationError> {
+    let bytes = value.as_bytes();
+    let mut start = 0;
+    let mut end = bytes.len();
+    while start < end && bytes[start].is_ascii_whitespace() {
+        start += 1;
+    }
+    while end > start && bytes[end - 1].is_ascii_whitespace() {
+        end -= 1;
+    }
+
+    // Integer part and the fraction (as the numerator over the power of ten)
+    let mut idx = start;
+    let mut whole: u64 = 0;
+    let mut digits = 0;
+    while idx < end && bytes[idx].is_ascii_digit() {
+        whole = match whole.checked_mul(10) {
+            Some(whole) => match whole.checked_add((bytes[idx] - b'0') as u64) {
+                Some(whole) => whole,
+                None => return Err(DurationError::Overflow),
+            },
+            None => return Err(DurationError::Overflow),
+        };
+        digits += 1;
+        idx += 1;
+    }
+    let mut fraction: u64 = 0;
+    let mut denominator: u64 = 1;
+    if idx < end && bytes[idx] == b'.' {
+        idx += 1;
+        while idx < end && bytes[idx].is_ascii_digit() {
+            // Digits beyond nanosecond precision of the hours are dropped
+            if denominator < 1_000_000_000_000_000 {
+                fraction = fraction * 10 + (bytes[idx] - b'0') as u64;
+                denominator *= 10;
+            }
+            digits += 1;
+            idx += 1;
+        }
+    }
+    if digits == 0 {
+        return Err(DurationError::Number);
+    }
+
+    // Unit, optionally separated by whitespace
+    while idx < end && bytes[idx].is_ascii_whitespace() {
+        idx += 1;
+    }
+    let (_, rest) = bytes.split_at(idx);
+    let (unit, _) = rest.split_at(end - idx);
+    let scale = match unit_nanos(unit) {
+        Some(scale) => scale,
+        None => return Err(DurationError::Unit),
+    };
+
+    let nanos = match whole.checked_mul(scale) {
+        Some(nanos) => nanos,
+        None => return Err(DurationError::Overflow),
+    };
+    // Fraction is below `10^17` and the scale below `2^42`, so the product fits into 128 bits
+    let fraction_nanos = (fraction as u128 * scale as u128 / denominator as u128) as u64;
+    match nanos.checked_add(fraction_nanos) {
+        Some(nanos) => Ok(Duration::from_nanos(nanos)),
+        None => Err(DurationError::Overflow),
+    }
+}
+
+/// Same as [`parse_duration`], but panics if the duration is invalid. Used by the procedural macro
+/// in constants, so invalid `timeout` options fail the compilation.
+pub const fn duration(value: &str) -> Duration {
+    match parse_duration(value) {
+        Ok(duration) => duration,
+        Err(err) => panic!("{}", err.message()),
+    }
+}
+
+/// Parse the duration given at runtime, with the error mentioning the value.
+pub(crate) fn parse(value: &str) -> Result<Duration, String> {
+    parse_duration(value).map_err(|err| format!("{} (`{}`)", err.message(), value.trim()))
+}
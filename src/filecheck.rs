@@ -0,0 +1,169 @@
+//! Matcher validating produced output against the checks embedded into the test file (in the style
+//! of LLVM `FileCheck`).
+//!
+//! Checks are directives (see [`Directives`](crate::Directives)) of the test file:
+//!
+//! * `CHECK: <pattern>` -- pattern must occur in the output, after the line matched by the
+//!   previous check;
+//! * `CHECK-NEXT: <pattern>` -- pattern must occur on the line right after the line matched by the
+//!   previous check;
+//! * `CHECK-NOT: <pattern>` -- pattern must not occur between the lines matched by the surrounding
+//!   checks (or the start or the end of the output).
+//!
+//! Patterns are matched as substrings, except for the parts enclosed in `{{..}}`, which are
+//! regular expressions (`CHECK: error at line {{[0-9]+}}`).
+use crate::directives::Directive;
+use regex::Regex;
+
+/// Verify the `output` against the checks embedded into the test file `checks` (usually, the
+/// contents of the test file). Panics with the first unmatched check and the output, marking the
+/// lines which were searched.
+///
+/// ```rust
+/// let checks = "// CHECK: fn main\n// CHECK-NEXT: ret {{[0-9]+}}\n// CHECK-NOT: panic\n";
+/// datatest::filecheck::verify("define\nfn main\nret 0\n", checks);
+/// ```
+#[track_caller]
+pub fn verify(output: &str, checks: &str) {
+    if let Err(err) = check(output, checks) {
+        panic!("{}", err);
+    }
+}
+
+/// Kind of the check directive.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Check,
+    Next,
+    Not,
+}
+
+impl Kind {
+    fn from_name(name: &str) -> Option<Kind> {
+        match name {
+            "CHECK" => Some(Kind::Check),
+            "CHECK-NEXT" => Some(Kind::Next),
+            "CHECK-NOT" => Some(Kind::Not),
+            _ => None,
+        }
+    }
+}
+
+struct Check {
+    kind: Kind,
+    directive: Directive,
+    pattern: Regex,
+}
+
+fn check(output: &str, checks: &str) -> Result<(), String> {
+    let checks = crate::directives::parse_directives(checks)
+        .into_iter()
+        .filter_map(|directive| Kind::from_name(&directive.name).map(|kind| (kind, directive)))
+        .map(|(kind, directive)| {
+            let pattern = compile_pattern(&directive.value).map_err(|err| {
+                format!(
+                    "invalid pattern of {} on line {}: {}",
+                    directive.name, directive.line, err
+                )
+            })?;
+            Ok(Check {
+                kind,
+                directive,
+                pattern,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    if checks.is_empty() {
+        return Err(String::from("no CHECK directives found"));
+    }
+
+    let lines = output.lines().collect::<Vec<_>>();
+    // Index of the line where the search for the next check starts
+    let mut cursor = 0;
+    let mut pending_not: Vec<&Check> = Vec::new();
+    for check in &checks {
+        let found = match check.kind {
+            Kind::Not => {
+                pending_not.push(check);
+                continue;
+            }
+            Kind::Check => (cursor..lines.len()).find(|&idx| check.pattern.is_match(lines[idx])),
+            Kind::Next => Some(cursor)
+                .filter(|&idx| idx > 0 && idx < lines.len() && check.pattern.is_match(lines[idx])),
+        };
+        let found = match found {
+            Some(found) => found,
+            None => return Err(failure(check, "did not match", &lines, cursor, None)),
+        };
+        check_not(&pending_not, &lines, cursor, found)?;
+        pending_not.clear();
+        cursor = found + 1;
+    }
+    check_not(&pending_not, &lines, cursor, lines.len())
+}
+
+/// Verify that none of the `CHECK-NOT` patterns occur in the lines `start..end`.
+fn check_not(checks: &[&Check], lines: &[&str], start: usize, end: usize) -> Result<(), String> {
+    for check in checks {
+        if let Some(found) = (start..end).find(|&idx| check.pattern.is_match(lines[idx])) {
+            return Err(failure(check, "matched", lines, start, Some(found)));
+        }
+    }
+    Ok(())
+}
+
+/// Convert pattern into regular expression: text is matched literally, `{{..}}` parts are regular
+/// expressions.
+fn compile_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut regex = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find("{{") {
+        regex += &regex::escape(&rest[..start]);
+        let inner = &rest[start + 2..];
+        match inner.find("}}") {
+            Some(end) => {
+                regex += &format!("(?:{})", &inner[..end]);
+                rest = &inner[end + 2..];
+            }
+            None => {
+                regex += &regex::escape(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    regex += &regex::escape(rest);
+    Regex::new(&regex)
+}
+
+/// Describe the failed check, followed by the output with line numbers. Lines searched for the
+/// check are marked with `>` and the offending line (if any) is marked with `!`.
+fn failure(
+    check: &Check,
+    reason: &str,
+    lines: &[&str],
+    cursor: usize,
+    offending: Option<usize>,
+) -> String {
+    let mut message = format!(
+        "{}: {} (check on line {}) {}\noutput:\n",
+        check.directive.name, check.directive.value, check.directive.line, reason
+    );
+    let searched = |idx: usize| match check.kind {
+        Kind::Next => idx == cursor,
+        _ => idx >= cursor,
+    };
+    for (idx, line) in lines.iter().enumerate() {
+        let marker = if Some(idx) == offending {
+            '!'
+        } else if searched(idx) {
+            '>'
+        } else {
+            ' '
+        };
+        message += &format!("{} {:>4} | {}\n", marker, idx + 1, line);
+    }
+    if lines.is_empty() {
+        message += "  (empty)\n";
+    }
+    message
+}
@@ -1,29 +1,387 @@
 //! Support module for `#[datatest::files(..)]`
+use crate::directives::{Directives, Expectations};
+use crate::reduce::Reducer;
+use crate::sandbox::Sandbox;
 use rustc_test::Bencher;
+use serde::de::DeserializeOwned;
 use std::borrow::Borrow;
+use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-/// Used internally for `#[datatest::files(..)]` tests to distinguish regular tests versus benchmark
-/// tests.
-#[doc(hidden)]
+/// Used for `#[datatest::files(..)]` tests to distinguish regular tests versus benchmark tests. Last
+/// argument is the index of the combination of matrix parameter values.
 pub enum FilesTestFn {
-    TestFn(fn(&[PathBuf])),
-    BenchFn(fn(&mut Bencher, &[PathBuf])),
+    TestFn(&'static (dyn Fn(&[PathBuf], usize) + Send + Sync)),
+    BenchFn(fn(&mut Bencher, &[PathBuf], usize)),
 }
 
-/// Descriptor used internally for `#[datatest::files(..)]` tests.
-#[doc(hidden)]
+/// What to do if the root directory of `#[datatest::files(..)]` test does not exist.
+#[derive(Clone, Copy)]
+pub enum MissingRoot {
+    /// Fail the test run.
+    Fail,
+    /// Silently skip the test.
+    Skip,
+    /// Skip the test, but print a warning.
+    Warn,
+}
+
+/// Descriptor of `#[datatest::files(..)]` tests, either generated by the procedural macro or built
+/// at runtime via [`FilesTestDesc::builder`] (new options could be added, so it cannot be
+/// constructed directly).
+#[non_exhaustive]
 pub struct FilesTestDesc {
     pub name: &'static str,
     pub ignore: bool,
+    pub ignore_message: Option<&'static str>,
+    pub only: bool,
     pub root: &'static str,
+    pub missing_root: MissingRoot,
     pub params: &'static [&'static str],
+    /// For each parameter, if the file is read to derive the argument (so it must exist).
+    pub reads: &'static [bool],
     pub pattern: usize,
     pub ignorefn: Option<fn(&Path) -> bool>,
+    /// Decides by the beginning of the matched file if it is a test case (`content_matches` option).
+    pub content_matches: Option<fn(&[u8]) -> bool>,
+    pub name_template: Option<&'static str>,
+    /// Custom order of the test files, given by the `order_by` option: sorts the files by their keys
+    /// (computed once for each file).
+    pub order: Option<fn(&mut [PathBuf])>,
+    /// Maximum duration of each test case, given by the `timeout` option.
+    pub timeout: Option<Duration>,
+    /// Maximum memory of each test case in bytes, given by the `memory_limit` option.
+    pub memory_limit: Option<u64>,
+    /// Scheduling priority of the test cases, given by the `priority` option.
+    pub priority: i32,
+    /// Full names of the tests which must pass before this one, given by the `depends_on` option.
+    pub depends_on: &'static [&'static str],
+    /// Names of the resources the test cases use exclusively, given via `#[resource(..)]`.
+    pub resources: &'static [&'static str],
+    /// Reducer of the failing input, given by the `reduce` option.
+    pub reduce: Option<Reducer>,
+    /// Skip test files with the same content as one of the previous files (`dedup` option).
+    pub dedup: bool,
+    /// Isolation of the test cases, given by the `sandbox` option.
+    pub sandbox: Option<Sandbox>,
+    /// All test cases are expected to fail, given by `#[xfail]`.
+    pub xfail: bool,
+    /// Matrix parameters: name of the parameter and labels of its values.
+    pub matrix: &'static [(&'static str, &'static [&'static str])],
+    /// Entry points generated for IDEs: name of the entry point function and path of the test file
+    /// (relative to the root directory).
+    pub entry_points: &'static [(&'static str, &'static str)],
     pub testfn: FilesTestFn,
     pub source_file: &'static str,
 }
 
+/// Used by the procedural macro, which cannot use struct expressions of the descriptor.
+#[doc(hidden)]
+impl FilesTestDesc {
+    /// Descriptor with the default options (changed via `with_*` methods).
+    pub const fn new(
+        name: &'static str,
+        root: &'static str,
+        testfn: FilesTestFn,
+        source_file: &'static str,
+    ) -> Self {
+        FilesTestDesc {
+            name,
+            ignore: false,
+            ignore_message: None,
+            only: false,
+            root,
+            missing_root: MissingRoot::Fail,
+            params: &[],
+            reads: &[],
+            pattern: 0,
+            ignorefn: None,
+            content_matches: None,
+            name_template: None,
+            order: None,
+            timeout: None,
+            memory_limit: None,
+            priority: 0,
+            depends_on: &[],
+            resources: &[],
+            reduce: None,
+            dedup: false,
+            sandbox: None,
+            xfail: false,
+            matrix: &[],
+            entry_points: &[],
+            testfn,
+            source_file,
+        }
+    }
+
+    pub const fn with_ignore(mut self, ignore: bool) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    pub const fn with_ignore_message(mut self, ignore_message: Option<&'static str>) -> Self {
+        self.ignore_message = ignore_message;
+        self
+    }
+
+    pub const fn with_only(mut self, only: bool) -> Self {
+        self.only = only;
+        self
+    }
+
+    pub const fn with_missing_root(mut self, missing_root: MissingRoot) -> Self {
+        self.missing_root = missing_root;
+        self
+    }
+
+    pub const fn with_params(mut self, params: &'static [&'static str]) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub const fn with_reads(mut self, reads: &'static [bool]) -> Self {
+        self.reads = reads;
+        self
+    }
+
+    pub const fn with_pattern(mut self, pattern: usize) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    pub const fn with_ignorefn(mut self, ignorefn: Option<fn(&Path) -> bool>) -> Self {
+        self.ignorefn = ignorefn;
+        self
+    }
+
+    pub const fn with_content_matches(
+        mut self,
+        content_matches: Option<fn(&[u8]) -> bool>,
+    ) -> Self {
+        self.content_matches = content_matches;
+        self
+    }
+
+    pub const fn with_name_template(mut self, name_template: Option<&'static str>) -> Self {
+        self.name_template = name_template;
+        self
+    }
+
+    pub const fn with_order(mut self, order: Option<fn(&mut [PathBuf])>) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub const fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub const fn with_memory_limit(mut self, memory_limit: Option<u64>) -> Self {
+        self.memory_limit = memory_limit;
+        self
+    }
+
+    pub const fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub const fn with_depends_on(mut self, depends_on: &'static [&'static str]) -> Self {
+        self.depends_on = depends_on;
+        self
+    }
+
+    pub const fn with_resources(mut self, resources: &'static [&'static str]) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    pub const fn with_reduce(mut self, reduce: Option<Reducer>) -> Self {
+        self.reduce = reduce;
+        self
+    }
+
+    pub const fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    pub const fn with_sandbox(mut self, sandbox: Option<Sandbox>) -> Self {
+        self.sandbox = sandbox;
+        self
+    }
+
+    pub const fn with_xfail(mut self, xfail: bool) -> Self {
+        self.xfail = xfail;
+        self
+    }
+
+    pub const fn with_matrix(
+        mut self,
+        matrix: &'static [(&'static str, &'static [&'static str])],
+    ) -> Self {
+        self.matrix = matrix;
+        self
+    }
+
+    pub const fn with_entry_points(
+        mut self,
+        entry_points: &'static [(&'static str, &'static str)],
+    ) -> Self {
+        self.entry_points = entry_points;
+        self
+    }
+}
+
+impl FilesTestDesc {
+    /// Start building a `#[datatest::files(..)]`-like test at runtime, without the procedural macro
+    /// (see [`register_suite`](crate::register_suite)). `name` is the full name of the test,
+    /// including the crate name (like `concat!(module_path!(), "::my_test")`). `pattern` is the
+    /// regular expression matching the test files under the `root` directory. `testfn` (which could
+    /// capture the data of the test, like a boxed closure) receives the paths of the matched file and
+    /// the files derived from it via [`with_param`] (in the same order), and the index of the
+    /// combination of matrix parameter values (always `0`).
+    ///
+    /// [`with_param`]: FilesTestBuilder::with_param
+    #[track_caller]
+    pub fn builder(
+        name: impl Into<String>,
+        root: impl Into<String>,
+        pattern: impl Into<String>,
+        testfn: impl Fn(&[PathBuf], usize) + Send + Sync + 'static,
+    ) -> FilesTestBuilder {
+        FilesTestBuilder {
+            desc: FilesTestDesc::new(
+                leak(name.into()),
+                leak(root.into()),
+                FilesTestFn::TestFn(Box::leak(Box::new(testfn))),
+                std::panic::Location::caller().file(),
+            ),
+            params: vec![pattern.into()],
+        }
+    }
+}
+
+/// Builder of [`FilesTestDesc`] for tests constructed at runtime, see [`FilesTestDesc::builder`].
+pub struct FilesTestBuilder {
+    desc: FilesTestDesc,
+    /// Pattern followed by the templates of the derived paths.
+    params: Vec<String>,
+}
+
+impl FilesTestBuilder {
+    /// Add a path derived from the matched file via template (like `r"${1}.output.txt"`), passed
+    /// to the test function after the previous ones.
+    pub fn with_param(mut self, template: impl Into<String>) -> Self {
+        self.params.push(template.into());
+        self
+    }
+
+    pub fn with_ignore(mut self, ignore: bool) -> Self {
+        self.desc.ignore = ignore;
+        self
+    }
+
+    /// Ignore the test, giving the reason (like `#[ignore = "<reason>"]`).
+    pub fn with_ignore_message(mut self, message: impl Into<String>) -> Self {
+        self.desc.ignore = true;
+        self.desc.ignore_message = Some(leak(message.into()));
+        self
+    }
+
+    /// Ignore matched files for which the function returns `true`.
+    pub fn with_ignorefn(mut self, ignorefn: fn(&Path) -> bool) -> Self {
+        self.desc.ignorefn = Some(ignorefn);
+        self
+    }
+
+    /// Only keep matched files for which the function returns `true` on the beginning of the file.
+    pub fn with_content_matches(mut self, predicate: fn(&[u8]) -> bool) -> Self {
+        self.desc.content_matches = Some(predicate);
+        self
+    }
+
+    /// Template of the test case names, same as the `name` option.
+    pub fn with_name_template(mut self, template: impl Into<String>) -> Self {
+        self.desc.name_template = Some(leak(template.into()));
+        self
+    }
+
+    pub fn with_missing_root(mut self, missing_root: MissingRoot) -> Self {
+        self.desc.missing_root = missing_root;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.desc.timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum memory of each test case in bytes, same as the `memory_limit` option.
+    pub fn with_memory_limit(mut self, limit: u64) -> Self {
+        self.desc.memory_limit = Some(limit);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.desc.priority = priority;
+        self
+    }
+
+    /// Full names of the tests which must pass before this one, same as the `depends_on` option.
+    pub fn with_depends_on<S: Into<String>>(mut self, tests: impl IntoIterator<Item = S>) -> Self {
+        self.desc.depends_on = leak_all(tests);
+        self
+    }
+
+    /// Names of the resources the test cases use exclusively, same as `#[resource(..)]`.
+    pub fn with_resources<S: Into<String>>(
+        mut self,
+        resources: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.desc.resources = leak_all(resources);
+        self
+    }
+
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.desc.dedup = dedup;
+        self
+    }
+
+    pub fn with_xfail(mut self, xfail: bool) -> Self {
+        self.desc.xfail = xfail;
+        self
+    }
+
+    pub fn build(self) -> FilesTestDesc {
+        // Derived paths are given to the test function as is, so they are not required to exist
+        let reads = vec![false; self.params.len()];
+        let params = self.params.into_iter().map(leak).collect::<Vec<_>>();
+        FilesTestDesc {
+            params: Box::leak(params.into_boxed_slice()),
+            reads: Box::leak(reads.into_boxed_slice()),
+            ..self.desc
+        }
+    }
+}
+
+/// Descriptors of the tests built at runtime live until the end of the test run, like the ones
+/// generated by the procedural macro.
+pub(crate) fn leak(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
+}
+
+/// Same as [`leak`], for the list of strings.
+pub(crate) fn leak_all<S: Into<String>>(
+    values: impl IntoIterator<Item = S>,
+) -> &'static [&'static str] {
+    let values = values.into_iter().map(|value| leak(value.into()));
+    Box::leak(values.collect::<Vec<_>>().into_boxed_slice())
+}
+
 /// Trait defining conversion into a function argument. We use it to convert discovered paths
 /// to test data (captured as `&Path`) into what is expected by the function.
 ///
@@ -57,7 +415,9 @@ pub struct FilesTestDesc {
 ///
 /// 1. `&Path` -> `&str`, `String` (reads file content into a string)
 /// 2. `&Path` -> `&[u8]`, `Vec<u8>` (reads file content into a byte buffer)
-/// 3. `&Path` -> `&Path` (gives path "as is")
+/// 3. `&Path` -> `&Path` (gives path "as is"), `&Utf8Path`, `Utf8PathBuf` (with `camino` feature)
+/// 4. `&Path` -> `&Directives`, `Directives` (reads file content and parses directives)
+/// 5. `&Path` -> `Deserialized<T>` (reads file content and deserializes it by the file extension)
 ///
 /// Conversion is two step: first, we need to derive some value. Second, we need to either borrow
 /// from that value (if we need `&str`, for example) or take from that value (if we need `String`,
@@ -67,6 +427,8 @@ pub trait DeriveArg<'a>: 'a + Sized {
     /// Type to hold temporary value when going from `&Path` into target type.
     /// Necessary for conversions from `&Path` to `&str`,
     type Derived: TakeArg<'a, Self>;
+    /// If conversion reads the file, so the file must exist.
+    const READS_FILE: bool = true;
     fn derive(path: &'a Path) -> Self::Derived;
 }
 
@@ -106,12 +468,97 @@ impl<'a> DeriveArg<'a> for Vec<u8> {
 
 impl<'a> DeriveArg<'a> for &'a Path {
     type Derived = &'a Path;
+    const READS_FILE: bool = false;
 
     fn derive(path: &'a Path) -> &'a Path {
         path
     }
 }
 
+// UTF-8 paths
+
+#[cfg(feature = "camino")]
+fn utf8_path(path: &Path) -> &camino::Utf8Path {
+    camino::Utf8Path::from_path(path)
+        .unwrap_or_else(|| panic!("path '{}' is not valid UTF-8", path.display()))
+}
+
+#[cfg(feature = "camino")]
+impl<'a> DeriveArg<'a> for &'a camino::Utf8Path {
+    type Derived = &'a camino::Utf8Path;
+    const READS_FILE: bool = false;
+
+    fn derive(path: &'a Path) -> &'a camino::Utf8Path {
+        utf8_path(path)
+    }
+}
+
+#[cfg(feature = "camino")]
+impl<'a> DeriveArg<'a> for camino::Utf8PathBuf {
+    type Derived = camino::Utf8PathBuf;
+    const READS_FILE: bool = false;
+
+    fn derive(path: &'a Path) -> camino::Utf8PathBuf {
+        utf8_path(path).to_path_buf()
+    }
+}
+
+// Directives
+
+impl<'a> DeriveArg<'a> for Directives {
+    type Derived = Directives;
+    fn derive(path: &'a Path) -> Directives {
+        Directives::load(path)
+    }
+}
+
+// Deserialized data files
+
+/// Contents of the data file deserialized into `T`, in the format given by the extension of the
+/// file (`.json`, `.yaml` / `.yml` or `.toml`), so a single test could cover a directory of
+/// fixtures mixing the formats. Test case fails if the file cannot be deserialized.
+///
+/// ```ignore
+/// #[derive(serde::Deserialize)]
+/// struct Config {
+///     name: String,
+/// }
+///
+/// #[datatest::files("tests/configs", { config in r"\.(json|yaml|toml)$" })]
+/// fn config_test(config: datatest::Deserialized<Config>) {
+///     assert!(!config.name.is_empty());
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deserialized<T>(pub T);
+
+impl<T> Deserialized<T> {
+    /// Take the deserialized value out of the wrapper.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Deserialized<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Deserialized<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<'a, T: DeserializeOwned + 'a> DeriveArg<'a> for Deserialized<T> {
+    type Derived = Option<T>;
+    fn derive(path: &'a Path) -> Option<T> {
+        Some(crate::format::deserialize_file(path).unwrap_or_else(|err| panic!("{}", err)))
+    }
+}
+
 #[doc(hidden)]
 pub trait TakeArg<'a, T: 'a> {
     fn take(&'a mut self) -> T;
@@ -138,6 +585,31 @@ impl<'a> TakeArg<'a, String> for String {
     }
 }
 
+impl<'a> TakeArg<'a, Directives> for Directives {
+    fn take(&mut self) -> Directives {
+        std::mem::take(self)
+    }
+}
+
+impl<'a> TakeArg<'a, Expectations> for Expectations {
+    fn take(&mut self) -> Expectations {
+        std::mem::take(self)
+    }
+}
+
+impl<'a, T: 'a> TakeArg<'a, Deserialized<T>> for Option<T> {
+    fn take(&mut self) -> Deserialized<T> {
+        Deserialized(Option::take(self).expect("argument is taken more than once"))
+    }
+}
+
+#[cfg(feature = "camino")]
+impl<'a> TakeArg<'a, camino::Utf8PathBuf> for camino::Utf8PathBuf {
+    fn take(&mut self) -> camino::Utf8PathBuf {
+        std::mem::take(self)
+    }
+}
+
 impl<'a> TakeArg<'a, Vec<u8>> for Vec<u8> {
     fn take(&mut self) -> Vec<u8> {
         std::mem::replace(self, Vec::new())
@@ -0,0 +1,56 @@
+//! Flake detection: with `--repeat N` (or `DATATEST_REPEAT=N`), every test case runs `N` times in a
+//! row, and cases which both pass and fail across the repetitions are reported as flaky.
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::{Mutex, PoisonError};
+
+/// Flaky cases run so far, by the test name: number of passed and failed repetitions.
+static FLAKY: Mutex<BTreeMap<String, (usize, usize)>> = Mutex::new(BTreeMap::new());
+
+/// Run each repetition of the test case. Case passes if every repetition passes and fails with the
+/// first failure if every repetition fails; if outcomes differ, case is recorded as flaky and
+/// fails.
+pub(crate) fn run_repeated(name: &str, repetitions: Vec<Box<dyn FnOnce() + Send>>) {
+    let total = repetitions.len();
+    let mut failures = Vec::new();
+    for testfn in repetitions {
+        if let Err(payload) = catch_unwind(AssertUnwindSafe(testfn)) {
+            failures.push(payload);
+        }
+    }
+    let failed = failures.len();
+    let passed = total - failed;
+    let mut failures = failures.into_iter();
+    let first = match failures.next() {
+        Some(first) => first,
+        None => return,
+    };
+    if passed == 0 {
+        resume_unwind(first);
+    }
+    // Case might have been run before (in watch mode), the latest outcome wins
+    FLAKY
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(name.to_string(), (passed, failed));
+    panic!(
+        "test case is flaky: {} out of {} repetitions passed, {} failed; first failure: {}",
+        passed,
+        total,
+        failed,
+        crate::runner::panic_message(first.as_ref())
+    );
+}
+
+/// Print flaky cases of the run along with their pass and fail counts.
+pub(crate) fn print_summary() {
+    let flaky = FLAKY.lock().unwrap_or_else(PoisonError::into_inner);
+    if flaky.is_empty() {
+        return;
+    }
+    println!("flaky test cases:");
+    for (name, (passed, failed)) in flaky.iter() {
+        println!("    {}: {} passed, {} failed", name, passed, failed);
+    }
+    println!();
+}
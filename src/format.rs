@@ -0,0 +1,54 @@
+//! Serde formats of the data files, picked by the file extension, so a directory of fixtures could
+//! mix JSON, YAML and TOML files.
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// Format of the file with the given path, by its extension (`.json`, `.yaml` / `.yml` or
+    /// `.toml`).
+    pub(crate) fn of(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    /// Deserialize the contents of the file in this format.
+    pub(crate) fn deserialize<T: DeserializeOwned>(self, input: &str) -> Result<T, String> {
+        match self {
+            Format::Json => serde_json::from_str(input).map_err(|err| err.to_string()),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_yaml::from_str(input).map_err(|err| err.to_string()),
+            #[cfg(not(feature = "yaml"))]
+            Format::Yaml => Err(String::from(
+                "YAML files could not be read without the `yaml` feature of `datatest`",
+            )),
+            Format::Toml => toml::from_str(input).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// Read the file at the given path and deserialize it in the format given by its extension.
+pub(crate) fn deserialize_file<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let format = Format::of(path).ok_or_else(|| {
+        format!(
+            "cannot parse '{}': unknown format (expected `.json`, `.yaml` or `.toml` file)",
+            path.display()
+        )
+    })?;
+    let input = std::fs::read_to_string(path)
+        .map_err(|err| format!("cannot read file '{}': {}", path.display(), err))?;
+    format
+        .deserialize(&input)
+        .map_err(|err| format!("cannot parse '{}': {}", path.display(), err))
+}
@@ -0,0 +1,20 @@
+//! Helpers for running fuzzing corpora and crash artifacts as test cases
+use arbitrary::{Arbitrary, Unstructured};
+
+/// Decode structured input from the raw bytes of the corpus or crash artifact file, the same way
+/// `cargo fuzz` does for fuzz targets taking [`Arbitrary`] input. Panics if input cannot be
+/// decoded.
+///
+/// ```rust
+/// fn parse_regression(input: &[u8]) {
+///   let (strict, data): (bool, Vec<u8>) = datatest::decode_arbitrary(input);
+///   // Fuzz target body, for example: `let _ = my_parser::parse(&data, strict);`
+/// }
+/// ```
+pub fn decode_arbitrary<T>(data: &[u8]) -> T
+where
+    T: for<'a> Arbitrary<'a>,
+{
+    T::arbitrary_take_rest(Unstructured::new(data))
+        .unwrap_or_else(|err| panic!("cannot decode fuzzing input: {}", err))
+}
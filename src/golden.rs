@@ -0,0 +1,107 @@
+//! Support for golden testing of external commands: output and exit code of the command are
+//! compared with the files next to the test file.
+use crate::compile_fail::{is_blessing, normalize, BLESS_ENV_VAR};
+use crate::diff::text_diff;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Run the `command` for the test file at `fixture` and compare its standard output, error output
+/// and exit code with the golden files next to the test file: `.stdout`, `.stderr` and `.status`
+/// (`tests/cli/foo.args` is checked against `tests/cli/foo.stdout` and so on). Missing golden file
+/// means the output is expected to be empty (or the exit code to be `0`). If the `.stdin` file
+/// exists next to the test file, it is given to the command as its standard input. Used by
+/// `#[datatest::files]` tests with `command = [..]` option, but could be called directly, too.
+///
+/// Directory of the test file is replaced by `$DIR` in the output, so it does not depend on the
+/// location of the project.
+///
+/// If `DATATEST_BLESS` environment variable is set, golden files are written with the actual
+/// output instead (files which would be empty are removed).
+pub fn golden_command(fixture: &Path, mut command: Command) {
+    let stdin_path = fixture.with_extension("stdin");
+    let stdin = if stdin_path.is_file() {
+        Stdio::from(
+            std::fs::File::open(&stdin_path)
+                .unwrap_or_else(|err| panic!("cannot open '{}': {}", stdin_path.display(), err)),
+        )
+    } else {
+        Stdio::null()
+    };
+    let output = command
+        .stdin(stdin)
+        .output()
+        .unwrap_or_else(|err| panic!("cannot run {:?}: {}", command, err));
+
+    let status = match output.status.code() {
+        Some(0) => String::new(),
+        Some(code) => format!("{}\n", code),
+        None => format!("{}\n", output.status),
+    };
+    let actual = [
+        (
+            "stdout",
+            normalize(&String::from_utf8_lossy(&output.stdout), fixture),
+        ),
+        (
+            "stderr",
+            normalize(&String::from_utf8_lossy(&output.stderr), fixture),
+        ),
+        ("status", status),
+    ];
+
+    let mut failures = Vec::new();
+    for (extension, actual) in &actual {
+        // Empty output is normalized into a single line break
+        let actual = if actual.trim().is_empty() { "" } else { actual };
+        let golden_path = fixture.with_extension(extension);
+        if is_blessing(&golden_path) {
+            bless_file(&golden_path, actual);
+            continue;
+        }
+        let expected = match std::fs::read_to_string(&golden_path) {
+            Ok(expected) => expected.replace("\r\n", "\n"),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => panic!("cannot read '{}': {}", golden_path.display(), err),
+        };
+        if expected != actual {
+            failures.push(format!(
+                "{} does not match '{}'\n{}",
+                extension,
+                golden_path.display(),
+                text_diff(&expected, actual)
+            ));
+        }
+    }
+    if !failures.is_empty() {
+        panic!(
+            "command {:?} failed for '{}' (run with {}=1 to update golden files)\n{}",
+            command,
+            fixture.display(),
+            BLESS_ENV_VAR,
+            failures.join("\n")
+        );
+    }
+}
+
+fn bless_file(path: &Path, actual: &str) {
+    let result = if actual.is_empty() {
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    } else {
+        std::fs::write(path, actual)
+    };
+    result.unwrap_or_else(|err| panic!("cannot write '{}': {}", path.display(), err));
+}
+
+/// Argument of the command given by `command = [..]` option: `{<arg>}` placeholders are replaced
+/// with the paths of the mapped arguments of the test function.
+#[doc(hidden)]
+pub fn command_arg(value: impl ToString, placeholders: &[(&str, &Path)]) -> String {
+    let mut value = value.to_string();
+    for (name, path) in placeholders {
+        value = value.replace(&format!("{{{}}}", name), &path.to_string_lossy());
+    }
+    value
+}
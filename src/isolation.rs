@@ -0,0 +1,174 @@
+//! Test cases running in a child process (the test binary running just that case). Allocation
+//! failure aborts the whole process, so cases with the memory limit (`memory_limit` option) run in
+//! a child process with limited address space, and the case fails if the child runs out of memory.
+//! Environment is shared by the whole process, and setting it while other threads read it is a data
+//! race, so cases with environment variables (the `env` option of the case, or the environment of
+//! the configuration) run in a child process with the variables set, too.
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Environment variable set for the child process running a single test case, giving the file the
+/// child writes the failure of the case into.
+const ISOLATED_ENV_VAR: &str = "DATATEST_ISOLATED";
+
+/// Failure of the test case run in the child process, passed back to the parent.
+#[derive(Serialize, Deserialize)]
+struct IsolatedFailure {
+    message: String,
+    output: Option<String>,
+}
+
+/// If this process is the child running a single test case.
+pub(crate) fn is_isolated() -> bool {
+    std::env::var_os(ISOLATED_ENV_VAR).is_some()
+}
+
+/// In the child process, pass the failure of the test case (the only one run) to the parent.
+pub(crate) fn report_failure(names: impl Iterator<Item = String>) {
+    let path = match std::env::var_os(ISOLATED_ENV_VAR) {
+        Some(path) => PathBuf::from(path),
+        None => return,
+    };
+    let failure = names
+        .filter_map(|name| crate::capture::failure(&name))
+        .next();
+    if let Some(failure) = failure {
+        let failure = IsolatedFailure {
+            message: failure.message,
+            output: failure.output,
+        };
+        let contents = serde_json::to_vec(&failure).expect("failure is serializable");
+        std::fs::write(&path, contents).unwrap_or_else(|err| {
+            panic!(
+                "cannot write test case failure '{}': {}",
+                path.display(),
+                err
+            )
+        });
+    }
+}
+
+/// If the test case with the given environment variables should run in a child process: some of the
+/// variables differ from the environment of this process (which is never the case in the child).
+pub(crate) fn needs_env(env: &[(String, String)]) -> bool {
+    env.iter()
+        .any(|(key, value)| std::env::var_os(key).map_or(true, |current| current != **value))
+}
+
+/// Run the test case with the given name in the child process with the environment variables set,
+/// which could use at most `limit` bytes of memory (if any). Custom command line flags are passed
+/// to the child. Panics if the case fails or exceeds the limit.
+pub(crate) fn run_isolated(name: &str, limit: Option<u64>, env: &[(String, String)]) {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+    let exe = std::env::current_exe()
+        .unwrap_or_else(|err| panic!("cannot find the test binary to run the case: {}", err));
+    let result = std::env::temp_dir().join(format!(
+        "datatest-isolated-{}-{}.json",
+        std::process::id(),
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut command = Command::new(exe);
+    command
+        .arg(name)
+        .args(["--exact", "--include-ignored", "--test-threads=1"])
+        .args(crate::cli::forwarded_args())
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .env(ISOLATED_ENV_VAR, &result)
+        // Every case is in the shard of the parent process
+        .env_remove("DATATEST_SHARD")
+        // Parent process repeats the case, so the child runs it once
+        .env_remove("DATATEST_REPEAT")
+        // Each allocator arena reserves plenty of address space, which counts towards the limit
+        .env("MALLOC_ARENA_MAX", "1");
+    if let Some(limit) = limit {
+        limit_address_space(&mut command, limit);
+    }
+    let output = command
+        .output()
+        .unwrap_or_else(|err| panic!("cannot run the test case in a separate process: {}", err));
+    let failure = std::fs::read(&result).ok();
+    let _ = std::fs::remove_file(&result);
+
+    if output.status.success() {
+        return;
+    }
+    // Failed allocation aborts the process, while the failed case makes the test binary exit
+    if let Some(limit) = limit.filter(|_| is_abort(output.status)) {
+        panic!(
+            "test case exceeded the memory limit of {}",
+            format_size(limit)
+        );
+    }
+    let failure =
+        failure.and_then(|failure| serde_json::from_slice::<IsolatedFailure>(&failure).ok());
+    match failure {
+        Some(failure) => {
+            // Output of the child is captured as the output of the case in this process
+            if let Some(output) = failure.output {
+                print!("{}", output);
+            }
+            panic!(
+                "test case failed in the separate process:\n{}",
+                failure.message
+            );
+        }
+        None => panic!(
+            "test case process failed ({}):\n{}{}",
+            output.status,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ),
+    }
+}
+
+/// Limit the address space of the child process.
+#[cfg(unix)]
+fn limit_address_space(command: &mut Command, limit: u64) {
+    use std::os::unix::process::CommandExt;
+
+    let limit = libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    };
+    // SAFETY: `setrlimit` is async-signal-safe, and the closure does not allocate
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Memory limits are only enforced on Unix.
+#[cfg(not(unix))]
+fn limit_address_space(_command: &mut Command, _limit: u64) {}
+
+/// If the process was killed by `SIGABRT`, which is how failed allocations end the process.
+#[cfg(unix)]
+fn is_abort(status: ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.signal() == Some(libc::SIGABRT)
+}
+
+#[cfg(not(unix))]
+fn is_abort(_status: ExitStatus) -> bool {
+    false
+}
+
+/// Human-readable size, like `512MB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [(&str, u64); 3] = [("GB", 1 << 30), ("MB", 1 << 20), ("KB", 1 << 10)];
+    for (unit, scale) in UNITS {
+        let (count, rest) = (bytes / scale, bytes % scale);
+        if count > 0 && rest == 0 {
+            return format!("{}{}", count, unit);
+        }
+    }
+    format!("{} bytes", bytes)
+}
@@ -1,6 +1,7 @@
 #![feature(test)]
 #![feature(specialization)]
 #![feature(termination_trait_lib)]
+#![feature(internal_output_capture)]
 //! Crate for supporting data-driven tests.
 //!
 //! Data-driven tests are tests where individual cases are defined via data rather than in code.
@@ -21,6 +22,8 @@
 //! * `&str`, `String`: capture file contents as string and pass it to the test function
 //! * `&[u8]`, `Vec<u8>`: capture file contents and pass it to the test function
 //! * `&Path`: pass file path as-is
+//! * `&Utf8Path`, `Utf8PathBuf` (with `camino` feature enabled): pass file path as UTF-8 path of
+//!   the [`camino`](https://docs.rs/camino) crate (test fails if the path is not valid UTF-8)
 //!
 //! ### Note
 //!
@@ -66,6 +69,259 @@
 //! }
 //! ```
 //!
+//! For one-off conditions, an inline closure could be used instead of the function name:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt" if !|path: &std::path::Path| path.ends_with("wip.input.txt"),
+//!   output = r"${1}.output.txt",
+//! })]
+//! fn sample_test(input: &str, output: &str) {
+//!   assert_eq!(format!("Hello, {}!", input), output);
+//! }
+//! ```
+//!
+//! ### Matching by content
+//!
+//! When file names are not enough to tell test files apart, `content_matches` option gives a
+//! function of signature `fn(&[u8]) -> bool` (or a closure) which decides by the beginning of the
+//! matched file (up to 4 KiB, enough for magic numbers, shebangs or headers) if it is a test case.
+//! Rejected files are not test cases at all (and are reported by the orphans check):
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/scripts", {
+//!   input in r".*",
+//! }, content_matches = |head: &[u8]| head.starts_with(b"#!/bin/sh"))]
+//! fn shell_scripts(input: &str) {
+//!   // ...
+//! }
+//! ```
+//!
+//! ### Directives
+//!
+//! Test files could describe how they are tested via embedded directives, in the style of LLVM
+//! `lit` (`// RUN: mycc %s`, `// EXPECT-ERROR: undefined variable`, `// SKIP: windows`). Test
+//! function taking [`Directives`] argument receives the file along with its parsed directives;
+//! [`Directives::run`] is a built-in driver executing `RUN` commands and checking `EXPECT-ERROR`
+//! expectations, and [`Directives::is_skipped`] could be used to ignore skipped files:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/lit", {
+//!   input in r"\.txt$" if !datatest::Directives::is_skipped,
+//! })]
+//! fn lit_test(input: datatest::Directives) {
+//!   input.run();
+//! }
+//! ```
+//!
+//! Output produced by the test could be verified against `CHECK:`, `CHECK-NEXT:` and `CHECK-NOT:`
+//! directives of the file via [`filecheck::verify`].
+//!
+//! ### Expectations in comments
+//!
+//! Input and the expected output could live in the same file, with expectations given in the
+//! comments. With `<arg> = comments("<prefix>")` mapping, the test function receives
+//! [`Expectations`] collected from the lines of the matched file containing the prefix:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//!   expected = comments("#~ expect:"),
+//! })]
+//! fn sample_test(input: &str, expected: &datatest::Expectations) {
+//!   for expectation in expected.iter() {
+//!     assert!(input.contains(&expectation.text), "line {}", expectation.line);
+//!   }
+//! }
+//! ```
+//!
+//! ### Compile-fail tests
+//!
+//! With `compile_fail = true` option, the test function is not called. Instead, each Rust file
+//! matched by the pattern is compiled (standalone, as a library, which could use the crate under
+//! test and its dependencies) and must fail to compile with the diagnostics given in the `.stderr`
+//! file next to it (only messages and locations of the diagnostics are compared, so the files do not
+//! depend on the version of the compiler). Run tests with `DATATEST_BLESS=1` to create or update
+//! `.stderr` files from the actual diagnostics. See [`compile_fail`] for details.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/ui", { input in r"\.rs$" }, compile_fail = true)]
+//! fn ui(input: &std::path::Path) {}
+//! ```
+//!
+//! ### Command golden tests
+//!
+//! With `command = [<program>, <arg>, ..]` option, the test function is not called. Instead, the
+//! command is run once for each matched file and its standard output, error output and exit code
+//! are compared with `.stdout`, `.stderr` and `.status` files next to it (missing file means empty
+//! output or zero exit code). Program and arguments are any expressions convertible to strings,
+//! `{<arg>}` placeholders are replaced by the paths mapped to the arguments of the test function.
+//! Binary of the crate itself could be given via `env!("CARGO_BIN_EXE_<name>")`. Run tests with
+//! `DATATEST_BLESS=1` to update golden files. See [`golden_command`] for details.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/cli", { input in r"\.sh$" }, command = ["sh", "{input}", "missing.txt"])]
+//! fn cli(input: &std::path::Path) {}
+//! ```
+//!
+//! ### Differential tests
+//!
+//! When migrating to a new implementation, both implementations could be run on each test file
+//! and their outputs compared with `differential(reference = <path>, candidate = <path>)` option.
+//! Both functions take the same arguments as the test function (which is not called) and must
+//! return the same type implementing [`PartialEq`] and [`std::fmt::Debug`]. Failure message names
+//! the test file and the first line where the outputs diverge.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! fn old_parser(input: &str) -> Vec<String> {
+//!   input.split(',').map(String::from).collect()
+//! }
+//!
+//! fn new_parser(input: &str) -> Vec<String> {
+//!   input.split_terminator(',').map(String::from).collect()
+//! }
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//! }, differential(reference = old_parser, candidate = new_parser))]
+//! fn parser(input: &str) {}
+//! ```
+//!
+//! ### Reducing failing inputs
+//!
+//! With `reduce = lines` (or `reduce = bytes`) option, if the test fails, the test file matched by
+//! the pattern is reduced by removing chunks of lines (bytes) while the test still fails. Reduced
+//! input is written to `target/datatest-artifacts` (or the directory given by `DATATEST_ARTIFACTS`
+//! environment variable), named after the test. Custom reducer could be given as a path to the
+//! function of signature `fn(&[u8], &mut dyn FnMut(&[u8]) -> bool) -> Vec<u8>`, which takes the
+//! input and the function checking if the failure reproduces on the candidate input.
+//!
+//! ### Fuzzing corpora
+//!
+//! Fuzzing corpora and crash artifacts (for example, of `cargo fuzz`) could be run as regular test
+//! cases, so fuzzing regressions are checked on every test run. With `dedup = true` option, test
+//! files with the same content as one of the previous files are skipped (the same input is often
+//! saved both in the corpus and in the artifacts). Failure output names the file which reproduced
+//! the failure. With `arbitrary` feature enabled, [`decode_arbitrary`] decodes structured inputs
+//! the same way fuzz targets do.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/fuzz", { input in r"/(corpus|artifacts)/" }, dedup = true)]
+//! fn parse_regressions(input: &[u8]) {
+//!   // Fuzz target body, for example: `let _ = my_parser::parse(input);`
+//! }
+//! ```
+//!
+//! ### Missing root directory
+//!
+//! By default, the test run fails if the root directory does not exist. If the directory is
+//! optional, `missing_root = skip` (or `missing_root = warn`, to print a warning) option could be
+//! used to skip the test instead.
+//!
+//! ### Stacked attributes
+//!
+//! The same test could be applied to several fixture layouts by stacking `#[datatest::files(..)]`
+//! attributes (with different roots, patterns or options) on one function. Test cases of all the
+//! attributes are generated under the name of the function; if two of them end up with the same
+//! name (same relative path under different roots), use `name = ".."` option to tell them apart.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/annotated", { input in r"\.txt$" })]
+//! #[datatest::files("tests/lit", { input in r"\.txt$" })]
+//! fn not_empty(input: &str) {
+//!   assert!(!input.is_empty());
+//! }
+//! ```
+//!
+//! ### Sandboxes
+//!
+//! With `sandbox = copy` option, each test case runs in a temporary directory mirroring the
+//! directory of its test file, with copies of the files of the case (the test file and the files
+//! derived from it, at the same relative locations; other files of the directory are not copied):
+//! paths given to the test function point into the copy, so the test could freely modify them.
+//! With `sandbox = chdir`, the working directory is also changed into the copy for the duration of
+//! the case, so tools resolving relative paths from the working directory could be tested. Working
+//! directory is shared by the whole process, so such cases do not run concurrently with any other
+//! tests run by `datatest` (including regular `#[test]` tests).
+//!
+//! Sandboxes are removed once the case finishes. With `keep = "on-failure"` in the configuration
+//! (or with `DATATEST_KEEP=on-failure` environment variable), sandboxes of the failed cases are
+//! kept instead, so the exact state the case left behind could be inspected; the path of the kept
+//! sandbox is printed along with the failure. `keep = "always"` keeps all sandboxes.
+//!
+//! ### Arguments without mappings
+//!
+//! By default, every argument of the test function must have a mapping. With `unmapped = default`
+//! option, arguments without mappings are constructed via `Default::default()` instead. Function
+//! returning the value could be given, too (`unmapped = make_context`). The same option is
+//! supported by `#[datatest::data]` for arguments following the test case argument.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[derive(Default)]
+//! struct Context {
+//!   verbose: bool,
+//! }
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//! }, unmapped = default)]
+//! fn sample_test(ctx: Context, input: &str) {
+//!   assert!(ctx.verbose || !input.is_empty());
+//! }
+//! ```
+//!
+//! ### Random seeds
+//!
+//! Argument of [`SeededRng`] type (or `&mut SeededRng`, spelled out as `datatest::SeededRng`, so
+//! the procedural macro could tell it apart) needs no mapping, both in files-driven and data-driven
+//! tests: it is a random number generator seeded from the name of the test case, so
+//! randomized checks are reproducible. Seed could be overridden via `DATATEST_SEED` environment
+//! variable and is printed if the test fails.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//! })]
+//! fn sample_test(input: &str, rng: &mut datatest::SeededRng) {
+//!   let mut chars = input.chars().collect::<Vec<_>>();
+//!   rng.shuffle(&mut chars);
+//!   assert_eq!(chars.len(), input.chars().count());
+//! }
+//! ```
+//!
 //! # Data-driven tests
 //!
 //! Second type of tests supported by this crate are "data-driven" tests. These tests define a
@@ -75,7 +331,76 @@
 //!
 //! Test function must take exactly one argument and the type of this argument must implement
 //! [`serde::Deserialize`]. Optionally, if this implements [`ToString`] (or [`std::fmt::Display`]),
-//! it's [`ToString::to_string`] result is used to generate test name.
+//! it's [`ToString::to_string`] result is used to generate test name. The case could be taken by
+//! value, by reference (`case: &Case`) or by mutable reference (`case: &mut Case`), so tests driving
+//! a stateful case (like a builder or a cursor) need not clone it first.
+//!
+//! Positive and negative test cases could share the same test function, with negative cases
+//! declaring the expected error (see [`assert_err_matches`]).
+//!
+//! Test cases often compare big structures, so [`assert_eq!`] and [`assert_ne!`] macros are provided
+//! as drop-in replacements of the standard ones: with `pretty_assertions` feature enabled, their
+//! failure messages show colored diff of the values. Expected outputs could also be kept in files
+//! (see [`assert_matches_file!`], which writes the files with `DATATEST_BLESS=1`), and values
+//! compared as JSON with every difference listed along with its path (see [`assert_json_eq!`]).
+//! Mismatched golden files, compile-fail diagnostics and [`assert_matches_file!`] outputs are shown
+//! as word-level diffs: changed lines are listed with the changed words underlined, aligned by the
+//! display width of the text (so combining characters and wide CJK glyphs line up).
+//!
+//! When a test case fails, its location (`tests/tests.yaml:3` or the path of the matched file for
+//! files-driven tests) is printed along with the failure, so terminals and IDEs can link to it.
+//! Helper functions shared by the tests could get the same context of the test case running on the
+//! current thread via [`current_case`]: its name, files and location, as well as the directory for
+//! its artifacts, so their diagnostics and outputs are attributed to the case.
+//!
+//! Test cases of one source with the same name or the same content (compared via [`std::fmt::Debug`],
+//! if the test case type implements it) are usually copy-pasted by mistake. They run as they are by
+//! default; with `duplicates = fail` option, such test fails instead, pointing to both cases, and
+//! with `duplicates = index` option, the index of the test case is added to the names of the
+//! duplicates.
+//!
+//! Options of individual test cases of data files are given under the reserved `datatest` field
+//! (`[case.datatest]` table in TOML files), so they do not collide with the fields of the test case
+//! type. Test cases could set environment variables via the `env` option
+//! (`datatest: {env: {FEATURE_X: "on"}}`). Environment is shared by the whole process and setting
+//! it while other threads read it is a data race, so such cases run in a separate process (the test
+//! binary running just that case) with the variables set, like the cases with the memory limit.
+//!
+//! Files packing many test cases could be split into separate test cases on a delimiter line via
+//! [`segments`] source (`#[datatest::data(datatest::segments("tests/corpus.txt", "===="))]`).
+//! Each [`Segment`] is named after the text following the delimiter and knows its line in the file.
+//!
+//! Corpora made of a directory of data files, each containing a list of test cases, are covered by
+//! [`expand_files`] source: every file matched by the pattern is parsed into test cases by the given
+//! parser, and cases are named after their file (`english.yaml::case_3`):
+//! `#[datatest::data(datatest::expand_files("tests/corpus", r"\.yaml$", datatest::try_yaml))]`.
+//!
+//! Data files are read in the format given by their extension (see [`data_file`]): TOML files
+//! (`.toml`) list the cases as `[[case]]` tables, JSON and YAML files are read by [`try_yaml`].
+//! Given to [`expand_files`] as the parser, it covers a directory mixing the formats. Similarly,
+//! files tests could take [`Deserialized<T>`](Deserialized) arguments, deserializing each matched
+//! `.json`, `.yaml` or `.toml` file into `T` by its extension.
+//!
+//! Large text corpora could avoid copying every test case into owned `String`s: [`json_lines`]
+//! source reads the file (one JSON test case per line) once and keeps it alive while the cases run,
+//! so test case types could borrow from it (`struct Line<'a> { #[serde(borrow)] text: &'a str }`).
+//! The lifetime must be spelled out in the argument type (`line: Line<'_>` or `line: &Line<'_>`).
+//!
+//! Failures persisted by `proptest` could be replayed as separate test cases via
+//! [`proptest_regressions`] source (`#[datatest::data(datatest::proptest_regressions(
+//! "proptest-regressions"))]`), so each regression gets its own entry in the test output, named
+//! after the shrunk value. With `proptest` feature enabled, [`RegressionSeed::replay`] generates
+//! the failing value from the strategy of the property test and shrinks it the same way `proptest`
+//! did, so the test runs on the shrunk value recorded in the regressions file.
+//!
+//! With `relative_to = source` option, the path of the data file is relative to the directory of
+//! the source file containing the test (like with `include_str!`), so tests in nested modules could
+//! keep their data files next to them: `#[datatest::data("cases.yaml", relative_to = source)]`.
+//!
+//! Data files generated by the build script could be used, too: either via `out_dir = true` option
+//! (`#[datatest::data("cases.yaml", out_dir = true)]` reads `$OUT_DIR/cases.yaml`) or by giving the
+//! path via `concat!` and `env!` macros (`#[datatest::data(concat!(env!("OUT_DIR"),
+//! "/cases.yaml"))]`). Either way, the existence of the file is checked at compile time.
 //!
 //! ### `#[test]` attribute
 //!
@@ -106,14 +431,889 @@
 //! # fn main() {}
 //! ```
 //!
+//! ### Enumeration errors
+//!
+//! Instead of a YAML file path, an expression returning the list of test cases could be given as a
+//! source. Such expression could also return a `Result` with any [`std::fmt::Display`] error. If
+//! test cases cannot be enumerated (file is not readable or not valid, source returns an error,
+//! root directory of the files-driven test does not exist and so on), a single failing test named
+//! `<test>::<enumerate>` is generated in place of the test cases, so other tests still run.
+//!
+//! ### Validation of data files
+//!
+//! Enumeration stops at the first case which does not match the test case type. With
+//! `validate_data = true` in the configuration (or with `DATATEST_VALIDATE_DATA=1` environment
+//! variable), a pre-pass checks every case of the data files against the test case type before any
+//! test runs, and fails the run listing all invalid cases with their locations, so they could be
+//! fixed at once. With `schema = "<path>"` option (path of the JSON Schema, relative to the crate
+//! root directory; requires the `json_schema` feature), cases are checked against the schema as
+//! well (the reserved `datatest` field is not part of the case). Tests with custom sources are not
+//! checked.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[derive(serde::Deserialize)]
+//! struct GreeterTestCase {
+//!   name: String,
+//!   expected: String,
+//! }
+//!
+//! #[datatest::data("tests/tests.yaml", schema = "tests/greeter.schema.json")]
+//! fn greeter(case: GreeterTestCase) {
+//!   assert_eq!(case.expected, format!("Hi, {}!", case.name));
+//! }
+//! ```
+//!
+//! ```text
+//! error: test 'greeter': invalid test case at tests/tests.yaml:3: missing field `expected`
+//! error: test 'greeter': test case at tests/tests.yaml:5 does not match the schema: "" is shorter than 1 character (at `/name`)
+//! ```
+//!
+//! # Parameter matrix
+//!
+//! `#[datatest::files]` accepts a `matrix(<param> = [<value>, ..], ..)` option. Each matrix
+//! parameter is passed to the test function argument with the same name and each test is run for
+//! every combination of parameter values. Values are arbitrary expressions, evaluated in the scope
+//! of the test function. Generated test names are suffixed with the values of the parameters
+//! (`sample_test::case-01.input.txt::mode=Mode::Strict`); parameters could also be used as
+//! placeholders in the name template (`name = "{fn}_{stem}_{mode}"`).
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! enum Mode {
+//!   Strict,
+//!   Lenient,
+//! }
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//! }, matrix(mode = [Mode::Strict, Mode::Lenient]))]
+//! fn sample_test(mode: Mode, input: &str) {
+//!   match mode {
+//!     Mode::Strict => assert!(!input.is_empty()),
+//!     Mode::Lenient => {}
+//!   }
+//! }
+//! ```
+//!
+//! # HTTP cassettes
+//!
+//! Tests of API clients could talk to a local recording proxy instead of the real server:
+//! [`Cassette::start`] returns a handle with the proxy URL. When `DATATEST_RECORD` environment
+//! variable is set, requests are forwarded to the upstream server and the interactions are written
+//! to `cassettes/<case name>.yaml` next to the fixture of the test case (bodies are stored as
+//! base64, so binary ones are recorded as is). Otherwise, recorded responses are served back, so
+//! tests run without network access; requests which were not recorded and recorded interactions
+//! which were not requested fail the test.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/http", { input in r"\.txt$" })]
+//! fn api_test(input: &str) {
+//!   let cassette = datatest::Cassette::start("http://api.example.com");
+//!   // Point the client at `cassette.url()` and request `input`
+//! }
+//! ```
+//!
+//! # Shared resources
+//!
+//! Expensive resources (database containers, compiled grammar tables) implement
+//! [`SharedResource`] and are injected into `#[datatest::files]` and `#[datatest::data]` tests by
+//! type, via `Shared<T>` or `&Shared<T>` argument (spelled out as `datatest::Shared<T>`) which
+//! needs no mapping. Resource is set up once,
+//! right before the first case which needs it, and dropped once all tests finish (resources which
+//! use other resources are dropped first), so `Drop` could tear it down.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! struct Keywords(Vec<&'static str>);
+//!
+//! impl datatest::SharedResource for Keywords {
+//!   fn setup() -> Self {
+//!     Keywords(vec!["fn", "let"])
+//!   }
+//! }
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//! })]
+//! fn sample_test(input: &str, keywords: &datatest::Shared<Keywords>) {
+//!   assert!(!keywords.0.contains(&input));
+//! }
+//! ```
+//!
+//! # Running individual cases from IDE
+//!
+//! IDEs only allow running functions marked with `#[test]`, so only the whole `#[datatest::files]`
+//! test could be run from the IDE. With `ide = true` option, a hidden empty `#[test]` function is
+//! additionally generated for each test file found at compile time (named after the test function
+//! and the file path, like `sample_test__case_01_input_txt`). Running such function runs the tests
+//! generated for the corresponding file. Note that files added after the test is compiled do not
+//! get their entry points until the test is recompiled.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//! }, ide = true)]
+//! fn sample_test(input: &str) {
+//!   assert!(!input.is_empty());
+//! }
+//! ```
+//!
+//! # Focusing on individual tests
+//!
+//! While working on a single test, it could be marked with `#[only]` attribute (must go after the
+//! `datatest` attribute, as `#[test]` does). If any test is marked so, only marked tests are
+//! executed. To make sure the marker is not committed by accident, such run always fails, even if
+//! all the focused tests pass.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//! })]
+//! #[only]
+//! fn sample_test(input: &str) {
+//!   assert!(!input.is_empty());
+//! }
+//! ```
+//!
+//! # Expected failures
+//!
+//! Tests which are known to fail (for example, during a migration) could be marked with `#[xfail]`
+//! (or `#[xfail = "<reason>"]`) attribute, placed the same way as `#[only]`; individual test cases
+//! of YAML files could be marked with `xfail` option (`datatest: {xfail: "<reason>"}`). Unlike
+//! `#[ignore]`, such cases still run: failing case passes and is listed as an expected failure,
+//! while the case which passes is listed as an unexpected pass (XPASS), so the marker could be
+//! removed. With `xpass = "fail"` in the configuration (or with `DATATEST_XPASS=fail` environment
+//! variable), unexpected passes fail.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//! })]
+//! #[xfail = "empty inputs are not supported yet"]
+//! fn sample_test(input: &str) {
+//!   assert!(input.is_empty());
+//! }
+//! ```
+//!
+//! # Quarantine
+//!
+//! Flaky cases could be managed centrally in a checked-in quarantine file, given by `quarantine =
+//! "<path>"` in the configuration (or by `DATATEST_QUARANTINE=<path>` environment variable).
+//! Quarantined cases still run, but their failures are only listed after the run and do not fail
+//! it. Once the `until` date of the quarantine has passed, failures of the case fail the run again.
+//!
+//! ```toml
+//! [[case]]
+//! # Full name of the test case, as printed by the test runner
+//! name = "conformance::unicode.txt"
+//! until = 2026-12-31
+//! reason = "flaky on CI, see #123"
+//! ```
+//!
+//! # Configuration
+//!
+//! Defaults shared by all tests could be defined in `datatest.toml` file placed in the crate root
+//! directory or in any of its parents up to the workspace root (directories above the crate root
+//! are only searched for the members of a workspace):
+//!
+//! ```toml
+//! [files]
+//! # Prefix for relative root directories of `#[datatest::files]` tests
+//! root = "tests/fixtures"
+//! # Default for `missing_root` option of `#[datatest::files]` tests
+//! missing_root = "fail"
+//!
+//! [data]
+//! # Prefix for relative data file paths of `#[datatest::data]` tests
+//! root = "tests/cases"
+//!
+//! [runner]
+//! # Either "fail" (default) or "allow": if run with tests marked with `#[only]` should fail
+//! focus = "allow"
+//! # Either "allow" (default) or "fail": if run should fail when some files under root directories
+//! # of `#[datatest::files]` tests are not used by any test
+//! orphans = "fail"
+//! # Path to write the fixture coverage report to (JSON)
+//! report = "target/datatest-coverage.json"
+//! # Path to write the JUnit XML report of the run to
+//! junit = "target/datatest-junit.xml"
+//! # StatsD server to send the durations and outcomes of the test cases to
+//! metrics = "statsd://127.0.0.1:8125"
+//! # Path to keep the durations of the test cases at, used to balance shards (`DATATEST_SHARD`)
+//! timings = "tests/timings.json"
+//! # Either "allow" (default) or "fail": if expected failures (`xfail`) which pass should fail
+//! xpass = "fail"
+//! # Checked-in list of flaky cases whose failures do not fail the run
+//! quarantine = "tests/quarantine.toml"
+//! # Either "never" (default), "on-failure" or "always": if sandboxes of the test cases are kept
+//! keep = "on-failure"
+//! # Default timeout of the test cases which do not have their own one
+//! timeout = "30s"
+//! # Either "allow" (default), "missing" or "never": if expected files are written with
+//! # `DATATEST_BLESS`, if missing expected files are written as well, or if `DATATEST_BLESS` is
+//! # ignored (for CI)
+//! bless = "missing"
+//! # Either "pretty", "terse" or "json": output format of the run, unless given via `--format`
+//! reporter = "terse"
+//! # Check every case of the data files against the test case type (and the schema given by the
+//! # `schema` option) before running the tests, reporting all invalid cases at once
+//! validate_data = true
+//!
+//! # Tags selecting the tests by name (substrings of the names), run via `DATATEST_TAG=<tag>`
+//! [runner.tags]
+//! slow = ["fuzz_corpus::", "integration::"]
+//!
+//! # Named configurations to run every test case under
+//! [runner.configurations.sqlite]
+//! env = { DATABASE_URL = "sqlite::memory:" }
+//! flags = ["in-memory"]
+//! ```
+//!
+//! # Configuration matrix
+//!
+//! With named configurations defined in `[runner.configurations]` section of `datatest.toml`,
+//! every case of `#[datatest::files]` and `#[datatest::data]` tests runs under each of them, with
+//! the name suffixed by `@<configuration>` (so `cargo test @sqlite` runs cases of one
+//! configuration). Cases of configurations with environment variables run in a separate process
+//! with the variables set (see the `env` option of the test cases), and the code under test could
+//! check the flags of the configuration via [`Configuration::current`]. Configuration is shared by
+//! the whole process, so cases of different configurations do not run concurrently.
+//!
+//! # Orphaned fixtures
+//!
+//! Files under root directories of `#[datatest::files]` tests which are neither matched by the
+//! pattern nor derived from a matched file by any test usually mean that tests silently stopped
+//! running after a refactoring. With `orphans = "fail"` in the configuration (or with
+//! `DATATEST_ORPHANS=fail` environment variable), the run fails listing all such files.
+//!
+//! # Coverage report
+//!
+//! With `report = "<path>"` in the configuration (or with `DATATEST_REPORT=<path>` environment
+//! variable), a JSON report is written after the run. It lists every generated test with its
+//! fixture (test file or location of the test case) and status (`passed` or `failed` as recorded in
+//! the run, `skipped` if ignored, not focused or not reached by the run, or `filtered` if it does
+//! not match the command line filters), as well as the files
+//! under root directories which are not used by any test (`unreferenced`). Cases excluded via
+//! `DATATEST_CASE` are not listed. For the failed cases, the output captured while the case ran
+//! (including the panic message) is given as `output`, so it does not need to be dug out of the
+//! interleaved output of the whole run.
+//!
+//! # JUnit report
+//!
+//! With `junit = "<path>"` in the configuration (or with `DATATEST_JUNIT=<path>` environment
+//! variable), a JUnit XML report is written after the run, for CI systems which show the results of
+//! the test cases. Each case is a `<testcase>` named after the case, with the test function as the
+//! class; cases which were not run are marked as `<skipped/>`. Failures carry the panic message,
+//! followed by the output captured while the case ran (including the captured log records, see
+//! [Log capture](#log-capture)).
+//!
+//! # Failure manifest
+//!
+//! After every run, failed cases are listed in `datatest/<test binary>/failures.json` under the
+//! target directory (`target/datatest/sample/failures.json` for `tests/sample.rs`, for example, or
+//! under `CARGO_TARGET_DIR` if it is set): the name of the test, its fixture files, the panic
+//! message, artifacts written for the case (like reduced inputs), how long it ran and the output
+//! captured while it ran. CI tools could use it to post structured comments or re-run the failed
+//! cases without scraping the output.
+//!
+//! Panic messages of the cases of `#[datatest::files]` and `#[datatest::data]` tests are preceded by
+//! the name and the fixture of the case, so panics could be attributed even when the output is not
+//! captured (`--nocapture`) and many cases run in parallel:
+//!
+//! ```text
+//! test case 'sample_test::case-02.input.txt' (tests/test-cases/case-02.input.txt) panicked:
+//! thread 'sample_test::case-02.input.txt' panicked at tests/sample.rs:8:5:
+//! ```
+//!
+//! # Log capture
+//!
+//! With `log` feature enabled, the test runner installs a logger (unless the test binary installed
+//! its own one already) writing the records emitted while a test case runs to the output of the
+//! case, so they are captured along with the output: shown with the failure of the case and
+//! attached to the reports, but not printed for the cases which pass. Records emitted
+//! outside of the test cases (or while the output is not captured, as with `--nocapture`) go to
+//! the standard error. Records up to `debug` level are captured by default, `DATATEST_LOG`
+//! environment variable sets the level (`DATATEST_LOG=trace`). Similarly, with `tracing` feature
+//! enabled, the test runner sets the global `tracing` subscriber (unless one is set already)
+//! capturing the events of the test cases.
+//!
+//! ```text
+//! [INFO  sample] parsing 3 records
+//! test case 'sample_test::case-02.input.txt' (tests/test-cases/case-02.input.txt) panicked:
+//! ```
+//!
+//! # Sharding
+//!
+//! Durations of the test cases are kept across runs in `datatest/<test binary>/timings.json` under
+//! the target directory (or in the file given by `timings = "<path>"` in the configuration or by
+//! `DATATEST_TIMINGS=<path>` environment variable, so it could be committed). With
+//! `DATATEST_SHARD=<index>/<count>` environment variable (`1/4`, for example), only one shard of
+//! the test suite is run; cases are assigned to the shards by their expected durations rather than
+//! by their count, so all shards finish at roughly the same time. Cases without the history are
+//! expected to take as long as an average case. Sharded runs do not update the durations, so all
+//! shards compute the same assignment (each case runs in exactly one shard) regardless of the order
+//! they run in; durations are updated by the runs of the whole test suite.
+//!
+//! # Scheduling
+//!
+//! When tests run in parallel, cases which took the longest in the previous runs (see
+//! [Sharding](#sharding)) are started first, so the slowest case does not start last and keep the
+//! run going while other threads are idle. Tests could also be given explicit priority via the
+//! `priority = <integer>` option (`0` by default, negative values are allowed): cases with higher
+//! priority start before all cases with lower priority, regardless of their durations.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::data("tests/strings.yaml", priority = 10)]
+//! fn slow_test(data: String) {
+//!   assert!(!data.is_empty());
+//! }
+//! ```
+//!
+//! With a single test thread (`--test-threads=1`), tests run in the [default order](#test-order).
+//!
+//! # Dependencies
+//!
+//! Test which relies on the side effects of other tests (like a setup which populates a shared
+//! directory) could declare them via `depends_on = "<test>"` option (or `depends_on = ["<test>",
+//! ..]`), naming test functions of the same module. Prerequisites are run before the dependent test
+//! regardless of the names: tests run in stages, and cases of the dependent test start only after
+//! all cases of the prerequisites finished (even when tests run in parallel). If any case of a
+//! prerequisite fails, cases of the dependent tests fail without running, naming the failed
+//! prerequisites, and are listed after the run as not run due to the failed dependency:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::data("tests/strings.yaml")]
+//! fn generate(data: String) {
+//!   assert!(!data.is_empty());
+//! }
+//!
+//! #[datatest::data("tests/strings.yaml", depends_on = "generate")]
+//! fn compare(data: String) {
+//!   assert!(!data.is_empty());
+//! }
+//! ```
+//!
+//! Prerequisites which are not going to run (filtered out or ignored) do not fail the dependent
+//! tests.
+//!
+//! # Resources
+//!
+//! Cases which use the same external resource (a database, a network port, etc) could be marked
+//! with `#[resource("<name>")]` attribute (or `#[resource("<name>", ..)]` for several resources),
+//! placed the same way as `#[only]`. Cases using the same resource run one at a time, even when
+//! they belong to different tests, while all other cases still run in parallel:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::data("tests/strings.yaml")]
+//! #[resource("database")]
+//! fn migrate(data: String) {
+//!   assert!(!data.is_empty());
+//! }
+//! ```
+//!
+//! # Metrics
+//!
+//! With `metrics = "statsd://<host>:<port>"` in the configuration (or with
+//! `DATATEST_METRICS=statsd://<host>:<port>` environment variable), duration of every case of
+//! `#[datatest::files]` and `#[datatest::data]` tests (`datatest.case.duration` timer) and its
+//! outcome (`datatest.case.passed` or `datatest.case.failed` counter) are sent to the StatsD server
+//! as the cases finish, tagged with the test function (`test`) and the fixture of the case
+//! (`fixture`). Tags use DogStatsD format; to get metrics into OpenTelemetry, point the address at
+//! the `statsd` receiver of the OpenTelemetry collector.
+//!
+//! # Grouped summary
+//!
+//! After the run, numbers of passed, failed and ignored cases of `#[datatest::files]` and
+//! `#[datatest::data]` tests (along with their total duration) are summarized per test function,
+//! per directory of the fixture (test file or data file), per data file of `#[datatest::data]`
+//! tests, per root directory of `#[datatest::files]` tests and per namespace of the cases named via
+//! `{namespace}` placeholder (see [Test names](#test-names)), so it is easy to see which area of
+//! the test corpus regressed:
+//!
+//! ```text
+//! summary by fixture directory:
+//!     ok     tests/parser: 120 passed; 0 failed; 2 ignored; 1.52s
+//!     FAILED tests/typeck: 75 passed; 3 failed; 0 ignored; 0.84s
+//!
+//! summary by data file:
+//!     ok     tests/vectors/sha256.yaml: 64 passed; 0 failed; 0 ignored; 0.12s
+//! ```
+//!
+//! # Running a single case
+//!
+//! Setting `DATATEST_CASE` environment variable restricts the run to the tests which name or test
+//! file path contains the given value (for example, `DATATEST_CASE=case-01 cargo test`). This is
+//! a shortcut for the test filters, which does not require spelling out the full test name.
+//!
+//! # Watch mode
+//!
+//! Running with `--watch` flag (`cargo test -- --watch`) keeps the test binary running after the
+//! first run: when files under root directories of `#[datatest::files]` tests or data files of
+//! `#[datatest::data]` tests change, test cases are enumerated again and the ones affected by the
+//! change (including newly added test files) are re-run. The same change detection is available to
+//! external tools via [`watch`].
+//!
+//! # Dry run
+//!
+//! Running with `--dry-run` flag (`cargo test -- --dry-run`) enumerates the test cases the same way
+//! as a regular run (walking the root directories, reading the data files), but does not run them.
+//! Instead, each selected case is printed in the order it would run, along with its location and
+//! the files it reads, which helps to debug patterns and name templates or to feed the list of
+//! cases to an external scheduler:
+//!
+//! ```text
+//! tests::sample_test::case-01.input.txt
+//!     location: tests/test-cases/case-01.input.txt
+//!     input: tests/test-cases/case-01.input.txt
+//!     input: tests/test-cases/case-01.output.txt
+//! ```
+//!
+//! # Validating fixtures
+//!
+//! Running with `--validate` flag (`cargo test -- --validate`) enumerates the test cases, but
+//! instead of running them, reports every problem with the fixtures: data files which cannot be
+//! read or deserialized into the case type, invalid cases of the data files (see
+//! [Validation of data files](#validation-of-data-files)), missing root directories, patterns
+//! matching no files, test cases with the same name and, with `orphans = "fail"` policy (see
+//! [Orphaned fixtures](#orphaned-fixtures)), files under the root directories not used by any test.
+//! The run fails if any problem is found, so it could be used as a fast pre-commit hook:
+//!
+//! ```text
+//! error: test 'tests::parse': failed to enumerate test cases: tests/parse.yaml: invalid type ..
+//! error: file 'tests/test-cases/case-03.output.txt' is not used by any test
+//! ```
+//!
+//! # Flake detection
+//!
+//! Running with `--repeat N` flag (`cargo test -- --repeat 10`, or with `DATATEST_REPEAT=10`)
+//! runs each test case `N` times in a row. Case which passes in some repetitions and fails in the
+//! others is reported as flaky (and fails the run), along with its pass and fail counts:
+//!
+//! ```text
+//! flaky test cases:
+//!     tests::parse::case-07.txt: 7 passed, 3 failed
+//! ```
+//!
+//! # Sampling
+//!
+//! Running with `--sample 5%` flag (`cargo test -- --sample 5%`, or with `DATATEST_SAMPLE=0.05`)
+//! runs a random subset of the test cases (regular tests always run), so a big test suite could
+//! be run in full nightly and sampled in pull request CI. Cases are sampled out of the ones
+//! matching the filters given on the command line. The seed selecting the cases is printed,
+//! and giving it via `DATATEST_SAMPLE_SEED` selects the same cases again (shards of the sharded run
+//! need the same seed to split the same sample):
+//!
+//! ```text
+//! sample: running 12 out of 240 test cases (seed 8117260515, rerun with DATATEST_SAMPLE_SEED=8117260515)
+//! ```
+//!
+//! # Time budget
+//!
+//! Running with `--max-duration 10m` flag (`cargo test -- --max-duration 10m`, or with
+//! `DATATEST_MAX_DURATION=10m`) bounds the duration of the whole run: test cases are selected
+//! before the run by their durations in the previous runs (see [Sharding](#sharding)), and the cases
+//! which would start after the budget is spent are ignored (with `skipped for time` reason). Cases
+//! which were not run are listed as skipped for time, and if no test failed, the run exits with
+//! status 3 (rather than passing), so a partial run is not mistaken for a full pass:
+//!
+//! ```text
+//! skipped for time (budget of 600.0s is not enough, 2 test cases not run):
+//!     conformance::unicode.txt
+//!     conformance::utf16.txt
+//! ```
+//!
+//! # Fixture manifest
+//!
+//! Hashes of the fixtures could be checked in along with them, so fixtures which must not change
+//! silently (like certified test vectors) are tamper-evident. With `manifest` option of the
+//! configuration (or `DATATEST_MANIFEST` environment variable) giving the path of the manifest,
+//! every run compares SHA-256 hashes of the fixtures (files under the root directories of
+//! `#[datatest::files]` tests and files the cases read, like data files) with the manifest and
+//! fails if some fixtures were changed, added or removed (`manifest_drift = "warn"` only prints
+//! them). Run tests with `DATATEST_UPDATE_MANIFEST=1` to regenerate the manifest (`DATATEST_BLESS`
+//! does not touch it, so updating golden files does not acknowledge changed fixtures). Manifest is
+//! shared by all test binaries of the crate: each of them checks and regenerates only the entries
+//! of its own fixtures, keeping the others. Paths are relative to the crate directory (fixtures
+//! outside of it, like data files generated into `OUT_DIR`, are not recorded). It is written in the
+//! format of `sha256sum`:
+//!
+//! ```text
+//! 16c0cc72f738be4409b79f7186b95233dcb18423bdc46013a025290b8cd8381c  tests/strings.yaml
+//! ```
+//!
+//! Running with `--only-changed` flag (`cargo test -- --only-changed`) runs only the test cases
+//! which read fixtures changed or added since the manifest was written.
+//!
+//! # Custom flags
+//!
+//! Test binaries with `harness = false` could accept their own command line flags, given to
+//! `datatest::harness!` as [`CliFlags`]. The runner takes them out of the command line before
+//! parsing the flags of the standard test runner, and tests read them via [`cli_flag`] and
+//! [`cli_option`]:
+//!
+//! ```ignore
+//! datatest::harness!(
+//!     datatest::CliFlags::new()
+//!         .flag("update-goldens", "Rewrite golden files instead of comparing with them")
+//!         .option("corpus-dir", "PATH", "Directory of the additional corpus")
+//! );
+//!
+//! #[datatest::files("tests/golden", { input in r"\.txt$" })]
+//! fn golden(input: &str) {
+//!     if datatest::cli_flag("update-goldens") {
+//!         ..
+//!     }
+//! }
+//! ```
+//!
+//! With `cargo test -- --update-goldens --corpus-dir ../corpus`, `cli_flag("update-goldens")`
+//! is `true` and `cli_option("corpus-dir")` is `Some("../corpus")`.
+//!
+//! # Registering tests at runtime
+//!
+//! Tests could also be built without the procedural macros, via [`FilesTestDesc::builder`] and
+//! [`DataTestDesc::builder`], and registered with [`register_suite`] before the test runner starts
+//! (for example, one test per plugin discovered at startup). Test functions could be closures
+//! capturing the data of the test:
+//!
+//! ```ignore
+//! fn main() {
+//!     let plugin = discover_plugin();
+//!     datatest::register_suite(vec![
+//!         datatest::FilesTestDesc::builder(
+//!             concat!(module_path!(), "::plugin_inputs"),
+//!             "tests/plugins",
+//!             r"\.input$",
+//!             move |paths, _| plugin.check_input(&paths[0]),
+//!         )
+//!         .build(),
+//!     ]);
+//!     datatest::runner(&[]);
+//! }
+//! ```
+//!
+//! # Unit tests
+//!
+//! Both `#[datatest::files]` and `#[datatest::data]` could be used for unit tests inside
+//! `#[cfg(test)]` modules of the library crate, too (paths are still relative to the crate root).
+//! On stable Rust, the test harness of the library target should be replaced the same way as for
+//! integration tests:
+//!
+//! ```toml
+//! [lib]
+//! harness = false
+//! ```
+//!
+//! and the harness should be added to the crate root (`src/lib.rs`):
+//!
+//! ```rust,ignore
+//! #[cfg(test)]
+//! datatest::harness!();
+//!
+//! #[cfg(test)]
+//! mod tests {
+//!   #[datatest::data("tests/strings.yaml")]
+//!   fn data_test(input: String) {
+//!     assert!(!input.is_empty());
+//!   }
+//!
+//!   // Regular tests must use `#[datatest::test]`, otherwise they are silently ignored
+//!   #[datatest::test]
+//!   fn regular_test() {}
+//! }
+//! ```
+//!
+//! On nightly Rust, `#![cfg_attr(test, feature(custom_test_frameworks))]` and
+//! `#![cfg_attr(test, test_runner(datatest::runner))]` could be used in `src/lib.rs` instead.
+//!
+//! # Test names
+//!
+//! By default, tests are named after the test function and the path of the matched file relative
+//! to the root directory (`#[datatest::files]`) or the name and location of the test case
+//! (`#[datatest::data]`). Both attributes accept a `name = "<template>"` option to customize
+//! the generated names (module path of the test function is always kept as a prefix). Names (and
+//! filters) are normalized to Unicode NFC, so the same file produces the same test name on macOS,
+//! which stores file names decomposed. File names which are not valid UTF-8 are escaped in test
+//! names (like `caf\xE9.txt`), but the test function still gets the real paths.
+//!
+//! Supported placeholders are:
+//!
+//! * `#[datatest::files]`: `{fn}` (test function name), `{path}` (relative path of the matched
+//!   file), `{file}` (file name), `{stem}` (file name without extension), `{namespace}` (relative
+//!   path as nested namespaces, see below) and capture groups of the pattern (`{1}`, `{name}`).
+//! * `#[datatest::data]`: `{fn}`, `{index}` (index of the test case), `{case}` (test case name or
+//!   location), `{location}` (like `tests/tests.yaml:3`) and fields of the test case (`{id}` is
+//!   replaced with `case.id`).
+//!
+//! With `{namespace}`, directories of the corpus become namespaces of the test names: with
+//! `name = "{fn}::{namespace}"`, file `parser/v2/arrays/case_001.json` gives the test named
+//! `sample_test::parser::v2::arrays::case_001`. Then, `cargo test parser::v2` selects the subtree of
+//! the corpus, while the exact filter (`cargo test -- --exact sample_test::parser::v2`) selects
+//! exactly the tests in that namespace (not the ones of `parser::v20`). Grouped summary (see
+//! [Grouped summary](#grouped-summary)) counts each case in every namespace enclosing it.
+//!
+//! Test cases of `#[datatest::data]` tests are named via [`TestName`] trait, if implemented, or via
+//! [`Display`](std::fmt::Display) otherwise (cases implementing neither are named by their
+//! location). Implementing `TestName` gives short stable names to the cases which `Display` is
+//! unsuitable for (like multiline SQL queries) without wrapping them into a newtype.
+//!
+//! Test cases which have no name of their own could be given one in the data file: with
+//! `named = true` option, cases are read as [`Named<T>`](Named) (like `{ name: empty, value: .. }`
+//! or `{ empty: .. }`, see [`Named`] for all supported shapes) and named by it, while the test
+//! function still receives `T` (or `&T`).
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*)/(?P<case>[^/]*)\.input\.txt",
+//! }, name = "{fn}_{case}")]
+//! fn sample_test(input: &str) {
+//!   assert!(!input.is_empty());
+//! }
+//! ```
+//!
+//! # Test order
+//!
+//! Tests are given to the test harness in the same order on every platform: ordered by the name
+//! of the test function, then files of `#[datatest::files]` tests are ordered by path and cases of
+//! `#[datatest::data]` tests are ordered by name (cases without names keep the order of the
+//! source). Custom order of the cases could be given via `order_by = <function>` option, where
+//! function returns the sort key (any type implementing [`Ord`]) of the file path (`&Path`) or of
+//! the test case (`&T`). Files with equal keys are ordered by path, and cases with equal keys keep
+//! the order of the source. The key is computed once for each case, so it could not borrow
+//! from the case. Cases of [`json_lines`] sources which cannot be deserialized into the test case
+//! type are ordered last, and fail once run.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! fn file_size(path: &std::path::Path) -> u64 {
+//!   std::fs::metadata(path).map_or(0, |meta| meta.len())
+//! }
+//!
+//! // Smaller inputs first
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//! }, order_by = file_size)]
+//! fn sample_test(input: &str) {
+//!   assert!(!input.is_empty());
+//! }
+//! ```
+//!
+//! # Timeouts
+//!
+//! With `timeout = "5s"` option (units are `ms`, `s`, `m` and `h`), each test case of the
+//! `#[datatest::files]` or `#[datatest::data]` test fails if it does not finish in time. Cases of
+//! YAML files could override the timeout of the test via their own `timeout` option (known to be
+//! slow cases, for instance):
+//!
+//! ```yaml
+//! - name: huge input
+//!   datatest:
+//!     timeout: 10m
+//! ```
+//!
+//! Test case which exceeds its timeout keeps running in the background until the test run ends.
+//!
+//! # Memory limits
+//!
+//! With `memory_limit = "512MB"` option (units are `KB`, `MB` and `GB`), each test case of the
+//! `#[datatest::files]` or `#[datatest::data]` test runs in a separate process (the test binary
+//! running just that case) with limited address space, so a case which tries to use more memory
+//! fails with "test case exceeded the memory limit" message instead of bringing down the whole
+//! run. Limit covers the whole process, so it should leave some room for the test binary itself.
+//! Custom command line flags (see [`CliFlags`]) and the environment of the case are passed on to
+//! the child process. Memory limits are only enforced on Unix.
+//!
+//! # Benchmarks
+//!
+//! Functions of `#[datatest::files]` and `#[datatest::data]` tests marked with `#[bench]` take
+//! `test::Bencher` as the first argument. Alternatively, they could take [`Bencher`] owned by this
+//! crate (spelled out as `&mut datatest::Bencher`, so the procedural macro could tell it apart):
+//! unlike `test::Bencher`, it could be named on the stable channel, and it offers the same `iter`
+//! method and `bytes` field. Benchmarks are measured when run with `--bench` (`cargo bench`) and
+//! run once as tests otherwise. Benchmarks of data-driven tests require the test case type to
+//! implement [`Clone`].
+//!
+//! Like tests, benchmark functions could return `Result`, failing with the error. Fallible
+//! iterations go through [`Bencher::try_iter`], which stops at the first error and returns it, so
+//! the error is propagated via `?` instead of unwrapping it in the measurement loop.
+//!
+//! ```ignore
+//! #[datatest::data("tests/tests.yaml")]
+//! #[bench]
+//! fn greeting_bench(bencher: &mut datatest::Bencher, case: &GreeterTestCase) {
+//!     bencher.iter(|| format!("Hi, {}!", case.name));
+//! }
+//!
+//! #[datatest::files("tests/numbers", { input in r"\.txt$" })]
+//! #[bench]
+//! fn parse_bench(bencher: &mut datatest::Bencher, input: &str) -> Result<(), ParseIntError> {
+//!     bencher.try_iter(|| input.trim().parse::<u64>())?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! # Conditional compilation
+//!
+//! Both `#[datatest::files]` and `#[datatest::data]` accept a `cfg = <predicate>` option (same
+//! syntax as the `#[cfg(..)]` attribute). Test function as well as all the generated items are
+//! only compiled and registered if the predicate holds:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//! }, cfg = all(unix, not(target_os = "macos")))]
+//! fn sample_test(input: &str) {
+//!   assert!(!input.is_empty());
+//! }
+//!
+//! #[datatest::data("tests/strings.yaml", cfg = unix)]
+//! fn data_test(input: String) {
+//!   assert!(!input.is_empty());
+//! }
+//! ```
+//!
+//! # Cargo features
+//!
+//! Parts of the crate pulling in sizable dependencies could be turned off along with the default
+//! features:
+//!
+//! * `yaml` (default): YAML data files ([`try_yaml`] and YAML sources of `#[datatest::data]`
+//!   tests) and HTTP cassettes. Without it, `serde_yaml` and `yaml-rust` are not built and only
+//!   JSON (read by `serde_json`) and TOML data files could be read.
+//! * `ctor` (default): registration of the tests on the stable channel. It could be turned off if
+//!   tests are only registered via `test_case_registration` (nightly).
+//!
+//! `regex` is always required, as the patterns of `#[datatest::files]` tests are regular
+//! expressions. For example, a crate using only `#[datatest::files]` tests on the stable channel
+//! could depend on `datatest = { version = "0.6", default-features = false, features = ["ctor"] }`.
+//!
+//! # Re-exporting `datatest`
+//!
+//! Generated code refers to the `datatest` crate as `::datatest`. If `datatest` is used through a
+//! re-export (for example, from a shared test utilities crate), path to it should be given via the
+//! `crate = "<path>"` option (supported by `#[datatest::test]`, too):
+//!
+//! ```rust,ignore
+//! #[test_utils::datatest::data("tests/strings.yaml", crate = "test_utils::datatest")]
+//! fn data_test(input: String) {
+//!   assert!(!input.is_empty());
+//! }
+//! ```
+//!
+//! # Module-level attributes
+//!
+//! Both attributes could also be put on an inline module. In that case, every function inside the
+//! module marked with `#[test]`, `#[bench]` or the attribute itself becomes a test with the
+//! arguments given on the module. Function can override parts of these arguments with its own
+//! attribute: the root directory and the source of the test cases could be omitted, mappings
+//! replace the module mappings with the same name (pattern replaces the module pattern) and
+//! options replace the module options.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//!   output = r"${1}.output.txt",
+//! })]
+//! mod sample_tests {
+//!   #[datatest::files]
+//!   fn lengths_match(input: &str, output: &str) {
+//!     assert!(input.len() < output.len());
+//!   }
+//!
+//!   #[datatest::files({ output = r"${1}.input.txt" }, name = "{fn}_{stem}")]
+//!   fn same_file(input: &str, output: &str) {
+//!     assert_eq!(input, output);
+//!   }
+//! }
+//! ```
+//!
 //! ## More examples
 //!
 //! For more examples, check the [tests](https://github.com/commure/datatest/blob/master/tests/datatest.rs).
 extern crate test as rustc_test;
 
+mod assertions;
+mod bench;
+mod budget;
+mod capture;
+mod case;
+#[cfg(feature = "yaml")]
+mod cassette;
+mod cli;
+mod compile_fail;
+mod config;
+mod configuration;
 mod data;
+mod dependencies;
+mod diff;
+mod directives;
+mod duration;
+pub mod filecheck;
 mod files;
+mod flaky;
+mod format;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+mod golden;
+mod isolation;
+mod listing;
+#[cfg(any(feature = "log", feature = "tracing"))]
+mod logs;
+mod manifest;
+mod metrics;
+mod quarantine;
+mod reduce;
+mod regressions;
+mod report;
+mod resources;
+mod rng;
 mod runner;
+mod sample;
+mod sandbox;
+mod schema;
+mod shared;
+mod timings;
+mod watch;
+mod xfail;
 
 #[cfg(feature = "unsafe_test_runner")]
 mod interceptor;
@@ -123,14 +1323,33 @@ mod interceptor {
     pub fn install_interceptor() {}
 }
 
+#[cfg(not(any(feature = "ctor", feature = "test_case_registration")))]
+compile_error!(
+    "either `ctor` (default) or `test_case_registration` feature of `datatest` must be enabled to \
+     register tests"
+);
+
 /// Internal re-exports for the procedural macro to use.
 #[doc(hidden)]
 pub mod __internal {
-    pub use crate::data::{DataBenchFn, DataTestDesc, DataTestFn};
-    pub use crate::files::{DeriveArg, FilesTestDesc, FilesTestFn, TakeArg};
+    pub use crate::assertions::{assert_json_eq, assert_matches_file, assert_same_output};
+    pub use crate::bench::bencher;
+    pub use crate::data::{
+        check_duplicate_cases, source_relative_path, DataBenchFn, DataTestDesc, DataTestFn,
+        DuplicateCases, IntoCases, TestContentWithDefault, TestNameWithDefault,
+    };
+    pub use crate::duration::duration;
+    pub use crate::files::{DeriveArg, FilesTestDesc, FilesTestFn, MissingRoot, TakeArg};
+    pub use crate::golden::command_arg;
+    pub use crate::reduce::Reducer;
     pub use crate::runner::assert_test_result;
     pub use crate::rustc_test::Bencher;
+    pub use crate::sandbox::Sandbox;
+    #[cfg(feature = "ctor")]
     pub use ctor::{ctor, dtor};
+    #[cfg(feature = "pretty_assertions")]
+    pub use pretty_assertions;
+    pub use serde::de::IgnoredAny;
 
     // To maintain registry on stable channel
     pub use crate::runner::{
@@ -142,7 +1361,7 @@ pub mod __internal {
     pub use datatest_derive::{data_test_case_internal, files_test_case_internal};
 }
 
-pub use crate::runner::runner;
+pub use crate::runner::{register_suite, runner, SuiteTest};
 
 #[cfg(not(feature = "test_case_registration"))]
 pub use datatest_derive::{
@@ -155,9 +1374,34 @@ pub use datatest_derive::{
     data_test_case_registration as data, files_test_case_registration as files,
 };
 
+/// Checking the error of the negative test cases against the expected one.
+pub use crate::assertions::assert_err_matches;
+pub use crate::bench::Bencher;
+pub use crate::case::{current_case, CaseInfo};
+#[cfg(feature = "yaml")]
+pub use crate::cassette::{Cassette, CassetteMode};
+pub use crate::cli::{cli_flag, cli_option, CliFlags};
+pub use crate::compile_fail::compile_fail;
+pub use crate::configuration::Configuration;
+#[cfg(feature = "yaml")]
+pub use crate::data::try_yaml;
 /// Experimental functionality.
+#[cfg(feature = "yaml")]
 #[doc(hidden)]
-pub use crate::data::{yaml, DataTestCaseDesc};
+pub use crate::data::yaml;
+pub use crate::data::{
+    data_file, expand_files, json_lines, segments, validate_data_file, BorrowedCase,
+    DataTestBuilder, DataTestCaseDesc, DataTestDesc, DataTestFn, Named, Segment, TestName,
+};
+pub use crate::directives::{Directive, Directives, Expectation, Expectations};
+pub use crate::files::{Deserialized, FilesTestBuilder, FilesTestDesc, FilesTestFn, MissingRoot};
+#[cfg(feature = "arbitrary")]
+pub use crate::fuzz::decode_arbitrary;
+pub use crate::golden::golden_command;
+pub use crate::regressions::{proptest_regressions, RegressionSeed};
+pub use crate::rng::SeededRng;
+pub use crate::shared::{Shared, SharedResource};
+pub use crate::watch::watch;
 
 use std::fs::File;
 use std::io::{BufReader, Read};
@@ -170,16 +1414,124 @@ use std::path::Path;
 /// ```
 ///
 /// Also, `harness` should be set to `false` for that test module in `Cargo.toml` (see [Configuring a target](https://doc.rust-lang.org/cargo/reference/manifest.html#configuring-a-target)).
+///
+/// Custom command line flags of the test binary could be given as [`CliFlags`]:
+/// `datatest::harness!(datatest::CliFlags::new().flag("update-goldens", ".."))`.
 #[macro_export]
 macro_rules! harness {
     () => {
         #[cfg(test)]
         fn main() {
-            ::datatest::runner(&[]);
+            $crate::runner(&[]);
+        }
+    };
+    ($flags:expr) => {
+        #[cfg(test)]
+        fn main() {
+            $crate::CliFlags::run($flags);
         }
     };
 }
 
+/// Same as [`std::assert_eq!`], but with `pretty_assertions` feature enabled, failure message shows
+/// colored line-by-line diff of the pretty-printed (`{:#?}`) values, so it is easy to spot the
+/// difference between big expected and actual structures.
+#[cfg(feature = "pretty_assertions")]
+#[macro_export]
+macro_rules! assert_eq {
+    ($($arg:tt)*) => {
+        $crate::__internal::pretty_assertions::assert_eq!($($arg)*)
+    };
+}
+
+/// Same as [`std::assert_eq!`], but with `pretty_assertions` feature enabled, failure message shows
+/// colored line-by-line diff of the pretty-printed (`{:#?}`) values, so it is easy to spot the
+/// difference between big expected and actual structures.
+///
+/// ```rust
+/// #[derive(Debug, PartialEq)]
+/// struct Token {
+///   kind: &'static str,
+///   text: String,
+/// }
+///
+/// let token = Token { kind: "ident", text: String::from("foo") };
+/// datatest::assert_eq!(token, Token { kind: "ident", text: String::from("foo") });
+/// ```
+#[cfg(not(feature = "pretty_assertions"))]
+#[macro_export]
+macro_rules! assert_eq {
+    ($($arg:tt)*) => {
+        ::std::assert_eq!($($arg)*)
+    };
+}
+
+/// Same as [`std::assert_ne!`], but with `pretty_assertions` feature enabled, failure message shows
+/// the pretty-printed (`{:#?}`) value.
+#[cfg(feature = "pretty_assertions")]
+#[macro_export]
+macro_rules! assert_ne {
+    ($($arg:tt)*) => {
+        $crate::__internal::pretty_assertions::assert_ne!($($arg)*)
+    };
+}
+
+/// Same as [`std::assert_ne!`], but with `pretty_assertions` feature enabled, failure message shows
+/// the pretty-printed (`{:#?}`) value.
+#[cfg(not(feature = "pretty_assertions"))]
+#[macro_export]
+macro_rules! assert_ne {
+    ($($arg:tt)*) => {
+        ::std::assert_ne!($($arg)*)
+    };
+}
+
+/// Assert that the text (`&str`, `String`, etc) is the same as the contents of the file at the
+/// given path (relative to the crate root directory under `cargo test`). Line endings are
+/// normalized, and the failure message shows the changed lines with the changed words underlined.
+///
+/// If `DATATEST_BLESS` environment variable is set, the file is written with the actual text
+/// instead, so expected outputs are easy to create and update.
+///
+/// ```rust
+/// # let dir = std::env::temp_dir().join("datatest-doc-matches-file");
+/// # std::fs::create_dir_all(&dir).unwrap();
+/// # std::fs::write(dir.join("greeting.txt"), "Hello, world!\n").unwrap();
+/// # let path = dir.join("greeting.txt");
+/// let greeting = format!("Hello, {}!\n", "world");
+/// datatest::assert_matches_file!(greeting, path);
+/// ```
+#[macro_export]
+macro_rules! assert_matches_file {
+    ($actual:expr, $path:expr $(,)?) => {
+        $crate::__internal::assert_matches_file(&$actual, &$path)
+    };
+}
+
+/// Assert that two values (any types implementing [`serde::Serialize`], like
+/// [`serde_json::Value`]) are the same when converted to JSON. Unlike [`assert_eq!`], the failure
+/// message lists every difference with its path, so mismatches in big structures are easy to find:
+///
+/// ```text
+/// JSON values differ in 2 places:
+///   expected[3].name: expected "bob", got "alice"
+///   expected.total: expected 4, got 5
+/// ```
+///
+/// Numbers are compared by value (`1` is the same as `1.0`), order of object fields does not
+/// matter.
+///
+/// ```rust
+/// let actual = serde_json::json!({ "name": "alice", "tags": [1, 2] });
+/// datatest::assert_json_eq!(actual, serde_json::json!({ "tags": [1.0, 2], "name": "alice" }));
+/// ```
+#[macro_export]
+macro_rules! assert_json_eq {
+    ($actual:expr, $expected:expr $(,)?) => {
+        $crate::__internal::assert_json_eq(&$actual, &$expected)
+    };
+}
+
 /// Helper function used internally.
 fn read_to_string(path: &Path) -> String {
     let mut input = String::new();
@@ -0,0 +1,124 @@
+//! File system lookups of the test discovery, cached for the duration of the discovery. Many tests
+//! could share the same root directory (and many cases the same data file), so each directory is
+//! walked once and each existing path is checked once, which matters on network file systems (NFS,
+//! SMB), where every `stat` is a round trip to the server. Outside of the discovery (while the tests
+//! run, which could create and remove files), lookups are not cached.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+#[derive(Default)]
+struct Cache {
+    /// Files found under the walked directories, by directory.
+    listings: HashMap<PathBuf, Vec<PathBuf>>,
+    /// Kinds of the existing paths, by the normalized path (see [`normalize`]): files found by the
+    /// walks (recorded all at once, when the directory is walked) and paths checked outside of the
+    /// walks. Missing paths are not cached, as the files could still be created while the tests
+    /// run (like golden files being blessed).
+    kinds: HashMap<PathBuf, Kind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    File,
+    Dir,
+    Other,
+}
+
+impl Kind {
+    fn of(path: &Path) -> Option<Kind> {
+        let file_type = std::fs::metadata(path).ok()?.file_type();
+        Some(if file_type.is_file() {
+            Kind::File
+        } else if file_type.is_dir() {
+            Kind::Dir
+        } else {
+            Kind::Other
+        })
+    }
+}
+
+static CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+fn cache() -> MutexGuard<'static, Option<Cache>> {
+    CACHE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Lexically normalized path: redundant separators and `.` components are dropped, so differently
+/// spelled paths of the same file (like `tests/./data.yaml` and `tests/data.yaml`) share the cache
+/// entry. Symlinks and `..` components are not resolved, as that would take a `stat` per path.
+fn normalize(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+/// Start the new discovery: files could have changed since the previous one (in watch mode).
+pub(crate) fn reset() {
+    *cache() = Some(Cache::default());
+}
+
+/// Finish the discovery: the tests could change the files, so lookups are not cached anymore.
+pub(crate) fn finish() {
+    *cache() = None;
+}
+
+/// Recursively list all files in the directory (following symlinks), except hidden ones. Listing is
+/// cached until the discovery finishes (see [`reset`] and [`finish`]).
+pub(crate) fn list_directory(path: &Path) -> Result<Vec<PathBuf>, walkdir::Error> {
+    if let Some(files) = cache()
+        .as_ref()
+        .and_then(|cache| cache.listings.get(path))
+        .cloned()
+    {
+        return Ok(files);
+    }
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(path).follow_links(true) {
+        let entry = entry?;
+        let hidden = entry
+            .file_name()
+            .to_str()
+            .map_or(false, |s| s.starts_with('.'));
+        if entry.file_type().is_file() && !hidden {
+            files.push(entry.path().to_path_buf());
+        }
+    }
+    if let Some(ref mut cache) = *cache() {
+        cache
+            .kinds
+            .extend(files.iter().map(|file| (normalize(file), Kind::File)));
+        cache.listings.insert(path.to_path_buf(), files.clone());
+    }
+    Ok(files)
+}
+
+/// Kind of the file at the path (following symlinks), `None` if it does not exist. Files found by
+/// the directory walks are known to exist, other existing paths are checked once per discovery.
+fn kind(path: &Path) -> Option<Kind> {
+    let mut guard = cache();
+    let cache = match *guard {
+        Some(ref mut cache) => cache,
+        None => return Kind::of(path),
+    };
+    let normalized = normalize(path);
+    if let Some(kind) = cache.kinds.get(&normalized) {
+        return Some(*kind);
+    }
+    let kind = Kind::of(path)?;
+    cache.kinds.insert(normalized, kind);
+    Some(kind)
+}
+
+/// If the path exists (like [`Path::exists`]).
+pub(crate) fn exists(path: &Path) -> bool {
+    kind(path).is_some()
+}
+
+/// If the path is an existing file (like [`Path::is_file`]).
+pub(crate) fn is_file(path: &Path) -> bool {
+    kind(path) == Some(Kind::File)
+}
+
+/// If the path is an existing directory (like [`Path::is_dir`]).
+pub(crate) fn is_dir(path: &Path) -> bool {
+    kind(path) == Some(Kind::Dir)
+}
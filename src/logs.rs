@@ -0,0 +1,124 @@
+//! Capture of the log records emitted by the test cases (`log` and `tracing` features). Records
+//! emitted while a test case runs are written to the output of the test case, so they are captured
+//! along with it: shown when the case fails and attached to the reports, but not printed for the
+//! passed cases. Records emitted outside of the test cases (for example, by background threads of
+//! shared resources) go to the standard error.
+use std::fmt::Arguments;
+
+/// Environment variable giving the most verbose level of the captured records (`debug` by
+/// default).
+const LEVEL_ENV_VAR: &str = "DATATEST_LOG";
+
+/// If the output of the current thread is captured by the test runner. Capture is set for the
+/// thread running the test case and is inherited by the threads spawned by the case.
+fn is_captured() -> bool {
+    let capture = std::io::set_output_capture(None);
+    let captured = capture.is_some();
+    std::io::set_output_capture(capture);
+    captured
+}
+
+/// Write the record to the output captured for the running test case, or to the standard error
+/// if no test case is running.
+fn write_record(record: Arguments<'_>) {
+    if is_captured() {
+        print!("{}", record);
+    } else {
+        eprint!("{}", record);
+    }
+}
+
+/// Most verbose level of the captured records, as given by `DATATEST_LOG`.
+fn level() -> String {
+    std::env::var(LEVEL_ENV_VAR).unwrap_or_else(|_| String::from("debug"))
+}
+
+/// Install the logger and the `tracing` subscriber capturing records of the test cases, unless the
+/// test binary has installed its own ones already.
+pub(crate) fn install() {
+    #[cfg(feature = "log")]
+    install_logger();
+    #[cfg(feature = "tracing")]
+    install_subscriber();
+}
+
+#[cfg(feature = "log")]
+struct CaseLogger;
+
+#[cfg(feature = "log")]
+impl log::Log for CaseLogger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        use log::Level;
+
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let level = match record.level() {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN ",
+            Level::Info => "INFO ",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        };
+        write_record(format_args!(
+            "[{} {}] {}\n",
+            level,
+            record.target(),
+            record.args()
+        ));
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(feature = "log")]
+static LOGGER: CaseLogger = CaseLogger;
+
+#[cfg(feature = "log")]
+fn install_logger() {
+    let level = level();
+    let level = level
+        .parse::<log::LevelFilter>()
+        .unwrap_or_else(|_| panic!("invalid {} value '{}'", LEVEL_ENV_VAR, level));
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Writer of the formatted `tracing` events, see [`write_record`].
+#[cfg(feature = "tracing")]
+struct CaseWriter;
+
+#[cfg(feature = "tracing")]
+impl std::io::Write for CaseWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        // Events are formatted as a whole before being written, so each write is a full event
+        write_record(format_args!("{}", String::from_utf8_lossy(buf)));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn install_subscriber() {
+    use tracing_subscriber::filter::LevelFilter;
+
+    let level = level();
+    let level = level
+        .parse::<LevelFilter>()
+        .unwrap_or_else(|_| panic!("invalid {} value '{}'", LEVEL_ENV_VAR, level));
+    // Fails if the test binary has set the global subscriber already
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(|| CaseWriter)
+        .with_ansi(false)
+        .without_time()
+        .try_init();
+}
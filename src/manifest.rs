@@ -0,0 +1,138 @@
+//! Manifest of the fixtures: SHA-256 hashes of the fixture files, checked in along with them, so
+//! changes of the fixtures (like certified test vectors) which are not acknowledged by regenerating
+//! the manifest are detected.
+//!
+//! Manifest is a text file in the format of `sha256sum` (so it could also be checked with
+//! `sha256sum -c` from the crate directory), sorted by the path relative to the crate directory:
+//!
+//! ```text
+//! 5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456  tests/vectors/sha256.yaml
+//! ```
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Hashes of the fixture files (hex-encoded SHA-256), by the path of the file.
+#[derive(Default, PartialEq)]
+pub(crate) struct Manifest(BTreeMap<String, String>);
+
+/// Differences between the manifest and the current fixtures.
+#[derive(Default)]
+pub(crate) struct Drift {
+    /// Files which content differs from the one recorded in the manifest.
+    pub(crate) changed: Vec<String>,
+    /// Files not recorded in the manifest.
+    pub(crate) added: Vec<String>,
+    /// Files recorded in the manifest which no longer exist.
+    pub(crate) removed: Vec<String>,
+}
+
+impl Manifest {
+    /// Hash the given files (pairs of the path as written to the manifest and the path to read).
+    pub(crate) fn compute(files: &[(String, PathBuf)]) -> Result<Manifest, String> {
+        let mut hashes = BTreeMap::new();
+        for (name, path) in files {
+            let content = std::fs::read(path)
+                .map_err(|err| format!("cannot read fixture '{}': {}", name, err))?;
+            hashes.insert(name.clone(), format!("{:x}", Sha256::digest(&content)));
+        }
+        Ok(Manifest(hashes))
+    }
+
+    pub(crate) fn load(path: &Path) -> Result<Manifest, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read fixture manifest '{}': {}", path.display(), err))?;
+        let mut hashes = BTreeMap::new();
+        for (idx, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            // `sha256sum` marks files hashed in binary mode with `*`
+            let parsed = line
+                .split_once(' ')
+                .map(|(hash, name)| (hash, name.trim_start_matches([' ', '*'])))
+                .filter(|(hash, name)| {
+                    hash.len() == 64
+                        && hash.bytes().all(|b| b.is_ascii_hexdigit())
+                        && !name.is_empty()
+                });
+            let (hash, name) = parsed.ok_or_else(|| {
+                format!(
+                    "invalid fixture manifest '{}': line {} is not `<sha256>  <path>`",
+                    path.display(),
+                    idx + 1
+                )
+            })?;
+            hashes.insert(name.to_string(), hash.to_ascii_lowercase());
+        }
+        Ok(Manifest(hashes))
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut content = String::new();
+        for (name, hash) in &self.0 {
+            content += &format!("{}  {}\n", hash, name);
+        }
+        std::fs::write(path, content)
+    }
+
+    /// Compare the manifest with the hashes of the current fixtures. Files missing from `current`
+    /// are only reported as removed if they are `owned` by the current test binary (manifest is
+    /// shared by all test binaries of the crate, and each of them only hashes its own fixtures).
+    pub(crate) fn drift(&self, current: &Manifest, owned: impl Fn(&str) -> bool) -> Drift {
+        let mut drift = Drift::default();
+        for (name, hash) in &current.0 {
+            match self.0.get(name) {
+                Some(expected) if expected == hash => {}
+                Some(_) => drift.changed.push(name.clone()),
+                None => drift.added.push(name.clone()),
+            }
+        }
+        drift.removed = self
+            .0
+            .keys()
+            .filter(|name| owned(name) && !current.0.contains_key(*name))
+            .cloned()
+            .collect();
+        drift
+    }
+
+    /// Manifest with the entries `owned` by the current test binary replaced by the `current` ones,
+    /// so regenerating the manifest from one test binary keeps the fixtures of the others.
+    pub(crate) fn update(&self, current: &Manifest, owned: impl Fn(&str) -> bool) -> Manifest {
+        let mut hashes = self.0.clone();
+        hashes.retain(|name, _| !owned(name));
+        hashes.extend(current.0.clone());
+        Manifest(hashes)
+    }
+}
+
+impl Drift {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /// If the file (as named in the manifest) was changed or added since the manifest was written.
+    pub(crate) fn touches(&self, name: &str) -> bool {
+        self.changed
+            .iter()
+            .chain(&self.added)
+            .any(|path| path == name)
+    }
+
+    /// One line per drifted file, like `    changed: tests/vectors/sha256.yaml`.
+    pub(crate) fn describe(&self) -> String {
+        let changed = self.changed.iter().map(|name| ("changed", name));
+        let added = self.added.iter().map(|name| ("added", name));
+        let removed = self.removed.iter().map(|name| ("removed", name));
+        changed
+            .chain(added)
+            .chain(removed)
+            .map(|(kind, name)| format!("    {}: {}", kind, name))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
@@ -0,0 +1,99 @@
+//! Emitting metrics of the test cases (durations and pass/fail counters) to StatsD.
+use std::net::UdpSocket;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Prefix of all metric names.
+const PREFIX: &str = "datatest";
+
+/// StatsD server the metrics are sent to. Metrics are sent over UDP in DogStatsD format (with
+/// tags), which is also understood by the `statsd` receiver of the OpenTelemetry collector.
+pub(crate) struct MetricsSink {
+    socket: UdpSocket,
+}
+
+impl MetricsSink {
+    /// Sink sending metrics to the given address: `statsd://<host>:<port>` (scheme is optional).
+    pub(crate) fn connect(address: &str) -> Result<MetricsSink, String> {
+        let address = match address.split_once("://") {
+            Some(("statsd", address)) => address,
+            Some((scheme, _)) => {
+                return Err(format!(
+                    "unsupported metrics sink '{}', only `statsd://` is supported (use the \
+                     `statsd` receiver to forward metrics into OpenTelemetry collector)",
+                    scheme
+                ))
+            }
+            None => address,
+        };
+        let connect = || {
+            let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+            socket.connect(address)?;
+            Ok::<_, std::io::Error>(socket)
+        };
+        let socket =
+            connect().map_err(|err| format!("cannot connect to '{}': {}", address, err))?;
+        Ok(MetricsSink { socket })
+    }
+
+    fn send(&self, metric: &str, value: &str, kind: &str, tags: &[(&str, &str)]) {
+        let tags = tags
+            .iter()
+            .map(|(key, value)| format!("{}:{}", key, sanitize(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+        let line = format!("{}.{}:{}|{}|#{}", PREFIX, metric, value, kind, tags);
+        // Metrics are best-effort, they must never fail the test
+        let _ = self.socket.send(line.as_bytes());
+    }
+}
+
+/// Tag values cannot contain separators of the DogStatsD format.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if matches!(c, ',' | '|' | '#' | '\n') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Measures the test case and emits its metrics once dropped: `datatest.case.duration` timer and
+/// either `datatest.case.passed` or `datatest.case.failed` counter, tagged with the test function
+/// and the fixture of the case.
+pub(crate) struct CaseMetrics {
+    sink: Arc<MetricsSink>,
+    function: String,
+    fixture: String,
+    started: Instant,
+}
+
+impl CaseMetrics {
+    pub(crate) fn start(sink: Arc<MetricsSink>, function: String, fixture: String) -> CaseMetrics {
+        CaseMetrics {
+            sink,
+            function,
+            fixture,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for CaseMetrics {
+    fn drop(&mut self) {
+        let millis = self.started.elapsed().as_secs_f64() * 1000.0;
+        let tags = [("test", self.function.as_str()), ("fixture", &self.fixture)];
+        self.sink
+            .send("case.duration", &format!("{:.3}", millis), "ms", &tags);
+        let counter = if std::thread::panicking() {
+            "case.failed"
+        } else {
+            "case.passed"
+        };
+        self.sink.send(counter, "1", "c", &tags);
+    }
+}
@@ -0,0 +1,128 @@
+//! Quarantine of flaky test cases: checked-in file listing the cases whose failures are reported
+//! separately and do not fail the run, until the quarantine expires.
+//!
+//! ```toml
+//! [[case]]
+//! name = "conformance::unicode.txt"
+//! # Optional: once the date has passed, failures of the case fail the run again
+//! until = 2026-12-31
+//! reason = "flaky on CI, see #123"
+//! ```
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml::value::Datetime;
+
+#[derive(Deserialize)]
+struct QuarantineFile {
+    #[serde(default)]
+    case: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    name: String,
+    #[serde(default)]
+    until: Option<Datetime>,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+/// Cases under quarantine (quarantines which already expired are not included).
+pub(crate) struct Quarantine {
+    /// Reasons of the quarantine, by the case name.
+    cases: HashMap<String, Option<String>>,
+}
+
+/// Failures of the quarantined cases run so far, by the test name.
+static FAILURES: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+impl Quarantine {
+    /// Load the quarantine file. Expired quarantines are reported and skipped, so failures of such
+    /// cases fail the run again.
+    pub(crate) fn load(path: &Path) -> Result<Quarantine, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read quarantine '{}': {}", path.display(), err))?;
+        let file: QuarantineFile = toml::from_str(&content)
+            .map_err(|err| format!("invalid quarantine '{}': {}", path.display(), err))?;
+        let today = today();
+        let mut cases = HashMap::new();
+        for entry in file.case {
+            let until = entry.until.map(|until| until.to_string());
+            match until {
+                // Dates in `YYYY-MM-DD` format are ordered the same way as strings
+                Some(ref until) if until.get(..10).unwrap_or(until) < today.as_str() => eprintln!(
+                    "warning: quarantine of '{}' expired on {}, its failures fail the run",
+                    entry.name, until
+                ),
+                _ => {
+                    cases.insert(entry.name, entry.reason);
+                }
+            }
+        }
+        Ok(Quarantine { cases })
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.cases.contains_key(name)
+    }
+
+    /// Run the quarantined test case: its failure is recorded, but does not fail the case.
+    pub(crate) fn run(name: &str, testfn: impl FnOnce()) {
+        if let Err(payload) = catch_unwind(AssertUnwindSafe(testfn)) {
+            let message = crate::runner::panic_message(payload.as_ref());
+            FAILURES
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(name.to_string(), message);
+        }
+    }
+
+    /// Print failures of the quarantined cases.
+    pub(crate) fn print_summary(&self) {
+        let failures = FAILURES.lock().unwrap_or_else(PoisonError::into_inner);
+        if failures.is_empty() {
+            return;
+        }
+        println!("quarantined failures (not failing the run):");
+        for (name, message) in failures.iter() {
+            let message = message.lines().next().unwrap_or_default();
+            match self.cases.get(name) {
+                Some(Some(reason)) => {
+                    println!("    {}: {} (quarantined: {})", name, message, reason)
+                }
+                _ => println!("    {}: {}", name, message),
+            }
+        }
+        println!();
+    }
+}
+
+/// Current date (UTC) in `YYYY-MM-DD` format.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Convert the number of days since 1970-01-01 into the civil date (year, month, day), see
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,181 @@
+//! Reduction of the failing test inputs of `#[datatest::files(..)]` tests.
+use std::cell::Cell;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+/// Environment variable overriding the directory where failure artifacts (reduced inputs) are
+/// written (`target/datatest-artifacts` by default).
+const ARTIFACTS_ENV_VAR: &str = "DATATEST_ARTIFACTS";
+
+/// Maximum number of attempts to run the test on a reduced input.
+const MAX_ATTEMPTS: usize = 1000;
+
+/// How the failing input is reduced, given by the `reduce` option.
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub enum Reducer {
+    /// Remove chunks of lines while the failure reproduces.
+    Lines,
+    /// Remove chunks of bytes while the failure reproduces.
+    Bytes,
+    /// User-supplied reducer.
+    Custom(ReduceFn),
+}
+
+/// User-supplied reducer: takes the input and the function checking if the failure reproduces on
+/// the candidate input, returns the reduced input.
+pub type ReduceFn = fn(&[u8], &mut dyn FnMut(&[u8]) -> bool) -> Vec<u8>;
+
+thread_local! {
+    /// Set while the test is run on the candidate inputs, so expected panics are not printed.
+    static SILENT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run the test function; if it fails, reduce the input file (matched by the pattern, `pattern`
+/// is its index in `paths`) while the failure reproduces and write the reduced input to the
+/// artifacts directory, named after the `artifact` (keeping the extension of the input). The
+/// original failure is reported either way.
+pub(crate) fn run_with_reduction(
+    reducer: Reducer,
+    artifact: &str,
+    pattern: usize,
+    paths: &[PathBuf],
+    testfn: impl Fn(&[PathBuf]),
+) {
+    let payload = match catch_unwind(AssertUnwindSafe(|| testfn(paths))) {
+        Ok(()) => return,
+        Err(payload) => payload,
+    };
+
+    let input_path = &paths[pattern];
+    match reduce_file(reducer, artifact, pattern, paths, &testfn) {
+        Ok(reduced) => eprintln!(
+            "reduced input of '{}' is written to '{}'",
+            input_path.display(),
+            reduced.display()
+        ),
+        Err(err) => eprintln!("cannot reduce input of '{}': {}", input_path.display(), err),
+    }
+    resume_unwind(payload)
+}
+
+/// Directory where failure artifacts are written.
+pub(crate) fn artifacts_dir() -> PathBuf {
+    std::env::var_os(ARTIFACTS_ENV_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new("target").join("datatest-artifacts"))
+}
+
+fn reduce_file(
+    reducer: Reducer,
+    artifact: &str,
+    pattern: usize,
+    paths: &[PathBuf],
+    testfn: &dyn Fn(&[PathBuf]),
+) -> std::io::Result<PathBuf> {
+    let input_path = &paths[pattern];
+    let input = std::fs::read(input_path)?;
+    let dir = artifacts_dir();
+    std::fs::create_dir_all(&dir)?;
+    // Keep the extension of the input (test names usually end with the file name already)
+    let file_name = |suffix: &str| match input_path.extension() {
+        Some(ext) => {
+            let ext = format!(".{}", ext.to_string_lossy());
+            let stem = artifact.strip_suffix(&ext).unwrap_or(artifact);
+            format!("{}{}{}", stem, suffix, ext)
+        }
+        None => format!("{}{}", artifact, suffix),
+    };
+    let candidate_path = dir.join(file_name(".candidate"));
+
+    // Failure reproduces if the test fails when the input is replaced with the candidate
+    let mut candidate_paths = paths.to_vec();
+    candidate_paths[pattern] = candidate_path.clone();
+    let mut attempts = 0;
+    let mut reproduces = |candidate: &[u8]| {
+        attempts += 1;
+        if attempts > MAX_ATTEMPTS || std::fs::write(&candidate_path, candidate).is_err() {
+            return false;
+        }
+        silence_panics();
+        SILENT.with(|silent| silent.set(true));
+        let failed = catch_unwind(AssertUnwindSafe(|| testfn(&candidate_paths))).is_err();
+        SILENT.with(|silent| silent.set(false));
+        failed
+    };
+    let reduced = match reducer {
+        Reducer::Lines => reduce_chunks(split_lines(&input), &mut reproduces),
+        Reducer::Bytes => reduce_chunks(input.chunks(1).collect(), &mut reproduces),
+        Reducer::Custom(reduce) => reduce(&input, &mut reproduces),
+    };
+    let _ = std::fs::remove_file(&candidate_path);
+
+    let reduced_path = dir.join(file_name(""));
+    std::fs::write(&reduced_path, reduced)?;
+    crate::capture::add_artifact(&reduced_path);
+    Ok(reduced_path)
+}
+
+/// Install panic hook which does not print panics of the test runs on candidate inputs (and
+/// delegates to the previous hook otherwise).
+fn silence_panics() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if !SILENT.with(Cell::get) {
+                previous(info)
+            }
+        }));
+    });
+}
+
+/// Split input into lines, keeping line terminators.
+fn split_lines(input: &[u8]) -> Vec<&[u8]> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (idx, byte) in input.iter().enumerate() {
+        if *byte == b'\n' {
+            lines.push(&input[start..=idx]);
+            start = idx + 1;
+        }
+    }
+    if start < input.len() {
+        lines.push(&input[start..]);
+    }
+    lines
+}
+
+/// Generic reducer: remove chunks of units (lines or bytes) while the failure reproduces, using
+/// smaller chunks once no chunk could be removed (simplified delta debugging).
+fn reduce_chunks(mut units: Vec<&[u8]>, reproduces: &mut dyn FnMut(&[u8]) -> bool) -> Vec<u8> {
+    let mut parts = 2;
+    while units.len() >= 2 {
+        let chunk = units.len().div_ceil(parts);
+        let mut removed = false;
+        let mut start = 0;
+        while start < units.len() {
+            let end = (start + chunk).min(units.len());
+            let candidate = units[..start]
+                .iter()
+                .chain(&units[end..])
+                .copied()
+                .collect::<Vec<_>>();
+            if reproduces(&candidate.concat()) {
+                units = candidate;
+                removed = true;
+            } else {
+                start = end;
+            }
+        }
+        if removed {
+            parts = (parts - 1).max(2);
+        } else if parts >= units.len() {
+            break;
+        } else {
+            parts = (parts * 2).min(units.len());
+        }
+    }
+    units.concat()
+}
@@ -0,0 +1,141 @@
+//! Replaying failures persisted by `proptest` as separate test cases
+use crate::data::DataTestCaseDesc;
+use std::path::{Path, PathBuf};
+
+/// Failure persisted by `proptest` in the regressions file (`cc <seed> # shrinks to <value>` line).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegressionSeed {
+    /// Seed in the persisted form, for example, `cc 4f8a..`.
+    pub seed: String,
+    /// Shrunk failing value, as recorded by `proptest` in the comment.
+    pub shrinks_to: Option<String>,
+}
+
+/// Source of `#[datatest::data(..)]` test replaying each failure persisted by `proptest`
+/// (`proptest-regressions/*.txt` files) as a separate test case, named after the shrunk value.
+/// `path` could be either a regressions file or a directory with such files.
+///
+/// ```ignore
+/// #[datatest::data(datatest::proptest_regressions("proptest-regressions/parser.txt"))]
+/// fn parser_regressions(seed: datatest::RegressionSeed) {
+///   seed.replay(".*", |input: String| check_parser(&input));
+/// }
+/// ```
+pub fn proptest_regressions(path: &str) -> Result<Vec<DataTestCaseDesc<RegressionSeed>>, String> {
+    let files = if Path::new(path).is_dir() {
+        let mut files = std::fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<std::io::Result<Vec<PathBuf>>>()
+            })
+            .map_err(|err| format!("cannot read directory '{}': {}", path, err))?;
+        files.retain(|file| file.extension().map_or(false, |ext| ext == "txt"));
+        files.sort();
+        files
+    } else {
+        vec![PathBuf::from(path)]
+    };
+
+    let mut cases = Vec::new();
+    for file in files {
+        let input = std::fs::read_to_string(&file)
+            .map_err(|err| format!("cannot read file '{}': {}", file.display(), err))?;
+        for (idx, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (seed, comment) = match line.split_once('#') {
+                Some((seed, comment)) => (seed.trim(), Some(comment.trim())),
+                None => (line, None),
+            };
+            let shrinks_to = comment
+                .map(|comment| comment.strip_prefix("shrinks to").unwrap_or(comment).trim())
+                .filter(|value| !value.is_empty())
+                .map(String::from);
+            cases.push(DataTestCaseDesc {
+                name: shrinks_to.clone(),
+                location: format!("{}:{}", file.display(), idx + 1),
+                case: RegressionSeed {
+                    seed: seed.to_string(),
+                    shrinks_to,
+                },
+                timeout: None,
+                env: Vec::new(),
+                xfail: false,
+            });
+        }
+    }
+    Ok(cases)
+}
+
+#[cfg(feature = "proptest")]
+impl RegressionSeed {
+    /// Generate the value from the `strategy` the same way `proptest` did when it found the
+    /// failure, shrink it the same way (running the `test` on the simpler values) until reaching the
+    /// shrunk value recorded in the regressions file, and run the `test` on the shrunk value. If the
+    /// `test` passes on the generated value, the regression is fixed and nothing is shrunk. Only
+    /// `cc` (ChaCha) seeds are supported.
+    pub fn replay<S: proptest::strategy::Strategy>(&self, strategy: S, test: impl Fn(S::Value)) {
+        use proptest::strategy::ValueTree;
+        use proptest::test_runner::{Config, RngAlgorithm, TestRng, TestRunner};
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let bytes = self
+            .seed
+            .strip_prefix("cc ")
+            .and_then(decode_hex)
+            .filter(|bytes| bytes.len() == 32)
+            .unwrap_or_else(|| panic!("unsupported proptest seed `{}`", self.seed));
+        let rng = TestRng::from_seed(RngAlgorithm::ChaCha, &bytes);
+        let mut runner = TestRunner::new_with_rng(Config::default(), rng);
+        let mut tree = strategy.new_tree(&mut runner).unwrap_or_else(|err| {
+            panic!("cannot generate value for seed `{}`: {}", self.seed, err)
+        });
+        let fails = |value: S::Value| catch_unwind(AssertUnwindSafe(|| test(value))).is_err();
+        if !fails(tree.current()) {
+            return;
+        }
+
+        // Same loop as the one `proptest` shrinks the failing values with
+        let mut shrunk = tree.current();
+        if !self.is_recorded(&shrunk) && tree.simplify() {
+            loop {
+                if fails(tree.current()) {
+                    shrunk = tree.current();
+                    if self.is_recorded(&shrunk) || !tree.simplify() {
+                        break;
+                    }
+                } else if !tree.complicate() {
+                    break;
+                }
+            }
+        }
+        test(shrunk)
+    }
+
+    /// If the value is the shrunk value recorded in the regressions file, either as is or as the
+    /// only argument of the `proptest!` test (`input = "a"`).
+    fn is_recorded(&self, value: &impl std::fmt::Debug) -> bool {
+        let recorded = match self.shrinks_to {
+            Some(ref recorded) => recorded,
+            None => return false,
+        };
+        let value = format!("{:?}", value);
+        *recorded == value
+            || recorded
+                .split_once(" = ")
+                .map_or(false, |(_, recorded)| recorded == value)
+    }
+}
+
+#[cfg(feature = "proptest")]
+/// Decode hexadecimal string (`None` if it has odd length or invalid digits).
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let hex = hex.trim();
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
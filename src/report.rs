@@ -0,0 +1,331 @@
+//! Fixture coverage report, written after the run if requested (`report` option of the
+//! configuration or `DATATEST_REPORT` environment variable), JUnit XML report (`junit` option or
+//! `DATATEST_JUNIT` environment variable) and the manifest of the failed cases.
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Report listing every generated test case with its fixture and whether it was executed, plus the
+/// files under root directories of `#[datatest::files]` tests which are not used by any test.
+///
+/// ```json
+/// {
+///   "cases": [
+///     {
+///       "name": "sample_test::case-01.input.txt",
+///       "fixture": "tests/test-cases/case-01.input.txt",
+///       "status": "executed"
+///     },
+///     {
+///       "name": "sample_test::case-02.input.txt",
+///       "fixture": "tests/test-cases/case-02.input.txt",
+///       "status": "failed",
+///       "output": "thread 'sample_test::case-02.input.txt' panicked at ..."
+///     }
+///   ],
+///   "unreferenced": ["tests/test-cases/case-03.output-linked.txt"]
+/// }
+/// ```
+#[derive(Serialize)]
+pub struct CoverageReport {
+    pub cases: Vec<CaseReport>,
+    pub unreferenced: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct CaseReport {
+    /// Name of the test.
+    pub name: String,
+    /// Test file (`#[datatest::files]` tests) or location of the test case (`#[datatest::data]`
+    /// tests). Not set for regular tests.
+    pub fixture: Option<String>,
+    pub status: CaseOutcome,
+    /// Output captured while the test case ran (including the panic message), only given for the
+    /// failed cases.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseStatus {
+    /// Test was run (whether it passed or not).
+    Executed,
+    /// Test was not run as it is ignored or not focused (see `#[only]`).
+    Skipped,
+    /// Test was not run as it does not match the filters given in the command line.
+    Filtered,
+}
+
+/// Outcome of the test in the coverage report.
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseOutcome {
+    /// Test was run and passed.
+    Passed,
+    /// Test was run and failed.
+    Failed,
+    /// Test was not run as it is ignored, not focused (see `#[only]`) or as the run stopped before
+    /// it.
+    Skipped,
+    /// Test was not run as it does not match the filters given in the command line.
+    Filtered,
+}
+
+impl CaseOutcome {
+    /// Outcome recorded for the test with the given name in the run (`status` tells why the test
+    /// was not run, if it was not).
+    pub fn recorded(name: &str, status: CaseStatus) -> Self {
+        match crate::capture::passed(name) {
+            Some(true) => CaseOutcome::Passed,
+            Some(false) => CaseOutcome::Failed,
+            None if status == CaseStatus::Filtered => CaseOutcome::Filtered,
+            None => CaseOutcome::Skipped,
+        }
+    }
+}
+
+impl CoverageReport {
+    /// Write report as JSON to the given path (creating parent directories as needed).
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+    }
+}
+
+/// Manifest of the test cases failed in the run, written to `datatest/<test binary>/failures.json`
+/// under the target directory after every run, so CI tools do not need to scrape the human-oriented
+/// output.
+///
+/// ```json
+/// {
+///   "failures": [
+///     {
+///       "name": "sample_test::case-02.input.txt",
+///       "fixtures": ["tests/test-cases/case-02.input.txt", "tests/test-cases/case-02.output.txt"],
+///       "message": "assertion `left == right` failed ...",
+///       "artifacts": ["target/datatest-artifacts/sample_test__case-02.input.txt"],
+///       "duration_secs": 0.012,
+///       "output": "thread 'sample_test::case-02.input.txt' panicked at ..."
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Serialize)]
+pub struct FailureManifest {
+    pub failures: Vec<FailureReport>,
+}
+
+#[derive(Serialize)]
+pub struct FailureReport {
+    /// Name of the test.
+    pub name: String,
+    /// Files the test case depends on: test file and the files derived from it (`#[datatest::files]`
+    /// tests) or the data file (`#[datatest::data]` tests).
+    pub fixtures: Vec<String>,
+    /// Panic message.
+    pub message: String,
+    /// Files written to help with the investigation of the failure (like reduced inputs).
+    pub artifacts: Vec<String>,
+    pub duration_secs: f64,
+    /// Output captured while the test case ran, including the captured log records (if the output
+    /// was captured at all).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+impl FailureManifest {
+    /// Manifest of the failed test cases among the given ones (test names with their fixtures).
+    pub fn collect(cases: &[(String, Vec<String>)]) -> Self {
+        let failures = cases
+            .iter()
+            .filter_map(|(name, fixtures)| {
+                let failure = crate::capture::failure(name)?;
+                Some(FailureReport {
+                    name: name.clone(),
+                    fixtures: fixtures.clone(),
+                    message: failure.message,
+                    artifacts: failure
+                        .artifacts
+                        .iter()
+                        .map(|path| path.to_string_lossy().into_owned())
+                        .collect(),
+                    duration_secs: failure.duration.as_secs_f64(),
+                    output: failure.output,
+                })
+            })
+            .collect();
+        FailureManifest { failures }
+    }
+
+    /// Write manifest as JSON to the given path (creating parent directories as needed).
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+    }
+}
+
+/// JUnit XML report of the run, for CI systems which show the results of the test cases. Failures
+/// carry the panic message, followed by the output captured while the case ran (including the
+/// captured log records).
+///
+/// ```xml
+/// <?xml version="1.0" encoding="UTF-8"?>
+/// <testsuites>
+///   <testsuite name="sample" tests="2" failures="1" skipped="0" time="0.015">
+///     <testcase name="case-01.input.txt" classname="sample_test" time="0.003"/>
+///     <testcase name="case-02.input.txt" classname="sample_test" time="0.012">
+///       <failure message="assertion `left == right` failed ...">...</failure>
+///     </testcase>
+///   </testsuite>
+/// </testsuites>
+/// ```
+pub struct JunitReport {
+    /// Name of the test suite (the test binary).
+    pub name: String,
+    pub cases: Vec<JunitCase>,
+}
+
+pub struct JunitCase {
+    /// Name of the test.
+    pub name: String,
+    pub duration_secs: f64,
+    pub result: JunitResult,
+}
+
+pub enum JunitResult {
+    Passed,
+    Failed {
+        message: String,
+        details: String,
+    },
+    /// Test was not run (ignored, not focused or skipped as the time budget is spent).
+    Skipped,
+}
+
+impl JunitReport {
+    /// Report of the test cases with the given names, except the ones filtered out by the command
+    /// line filters. Outcomes are taken from the cases which were run.
+    pub fn collect(name: &str, cases: &[(String, CaseStatus)]) -> Self {
+        let cases = cases
+            .iter()
+            .filter(|(_, status)| *status != CaseStatus::Filtered)
+            .map(|(name, _)| {
+                let duration_secs = crate::capture::duration(name).map_or(0.0, |d| d.as_secs_f64());
+                let result = match crate::capture::failure(name) {
+                    Some(failure) => {
+                        let mut details = failure.message.clone();
+                        if let Some(output) = failure.output.filter(|output| !output.is_empty()) {
+                            details += "\n\ncaptured output:\n";
+                            details += &output;
+                        }
+                        JunitResult::Failed {
+                            message: failure.message,
+                            details,
+                        }
+                    }
+                    None if crate::capture::passed(name).is_some() => JunitResult::Passed,
+                    None => JunitResult::Skipped,
+                };
+                JunitCase {
+                    name: name.clone(),
+                    duration_secs,
+                    result,
+                }
+            })
+            .collect();
+        JunitReport {
+            name: name.to_string(),
+            cases,
+        }
+    }
+
+    /// Render the report as XML.
+    pub fn to_xml(&self) -> String {
+        let count = |matches: fn(&JunitResult) -> bool| {
+            self.cases
+                .iter()
+                .filter(|case| matches(&case.result))
+                .count()
+        };
+        let failures = count(|result| matches!(result, JunitResult::Failed { .. }));
+        let skipped = count(|result| matches!(result, JunitResult::Skipped));
+        let time = self
+            .cases
+            .iter()
+            .map(|case| case.duration_secs)
+            .sum::<f64>();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            escape_xml(&self.name),
+            self.cases.len(),
+            failures,
+            skipped,
+            time
+        );
+        for case in &self.cases {
+            // Test function (with its module) is the class, the case is the name
+            let (classname, name) = case.name.rsplit_once("::").unwrap_or(("", &case.name));
+            let _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape_xml(name),
+                escape_xml(classname),
+                case.duration_secs
+            );
+            match case.result {
+                JunitResult::Passed => xml += "/>\n",
+                JunitResult::Failed {
+                    ref message,
+                    ref details,
+                } => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                        escape_xml(message),
+                        escape_xml(details)
+                    );
+                }
+                JunitResult::Skipped => xml += ">\n      <skipped/>\n    </testcase>\n",
+            }
+        }
+        xml += "  </testsuite>\n</testsuites>\n";
+        xml
+    }
+
+    /// Write report as XML to the given path (creating parent directories as needed).
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_xml())
+    }
+}
+
+/// Escape the text for XML attributes and character data. Control characters (other than tabs and
+/// line breaks) are not allowed in XML at all, so they are dropped.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&apos;",
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,46 @@
+//! Named resource locks (`#[resource("<name>")]`): test cases using the same resource (like a
+//! database or a network port) run one at a time, while other cases still run in parallel.
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Lock of each resource, by the name of the resource. Locks are created on first use and live
+/// until the end of the run.
+static LOCKS: Mutex<BTreeMap<&'static str, &'static Mutex<()>>> = Mutex::new(BTreeMap::new());
+
+/// Guard of the resources used by the test case.
+pub(crate) struct ResourceLock {
+    _guards: Vec<MutexGuard<'static, ()>>,
+}
+
+impl ResourceLock {
+    /// Lock the given resources (waiting until no other test case uses them). Resources are always
+    /// locked in the same order, so test cases using several resources do not deadlock.
+    pub(crate) fn acquire(names: &'static [&'static str]) -> ResourceLock {
+        if names.is_empty() {
+            return ResourceLock {
+                _guards: Vec::new(),
+            };
+        }
+        let locks = {
+            let mut locks = LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+            let mut used = names
+                .iter()
+                .map(|name| {
+                    let lock = *locks
+                        .entry(name)
+                        .or_insert_with(|| Box::leak(Box::new(Mutex::new(()))));
+                    (*name, lock)
+                })
+                .collect::<Vec<_>>();
+            used.sort_by_key(|(name, _)| *name);
+            used.dedup_by_key(|(name, _)| *name);
+            used
+        };
+        // Case which held the lock before might have failed, but that does not affect other cases
+        let guards = locks
+            .into_iter()
+            .map(|(_, lock)| lock.lock().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+        ResourceLock { _guards: guards }
+    }
+}
@@ -0,0 +1,167 @@
+//! Deterministic random number generator seeded per test case.
+use std::ops::Range;
+
+/// Environment variable overriding the seed of [`SeededRng`] (decimal or `0x`-prefixed hex).
+const SEED_ENV_VAR: &str = "DATATEST_SEED";
+
+/// Deterministic random number generator for randomized checks inside data-driven tests.
+///
+/// When used as an argument of `#[datatest::files]` or `#[datatest::data]` test function, the seed
+/// is derived from the name of the test case, so each case gets its own, but reproducible, random
+/// sequence. Seed could be overridden via `DATATEST_SEED` environment variable. If the test fails,
+/// the seed is printed along with the failure.
+///
+/// ```ignore
+/// #[datatest::data("tests/tests.yaml")]
+/// fn shuffled(case: TestCase, mut rng: datatest::SeededRng) {
+///   let mut items = case.items.clone();
+///   rng.shuffle(&mut items);
+///   assert_eq!(sort(items), case.sorted);
+/// }
+/// ```
+///
+/// Generator is `xoshiro256**`, its output for the given seed is stable across releases.
+pub struct SeededRng {
+    seed: u64,
+    state: [u64; 4],
+}
+
+impl SeededRng {
+    /// Generator with the given seed.
+    pub fn new(seed: u64) -> SeededRng {
+        // Expand seed into the state via SplitMix64, as recommended by `xoshiro` authors
+        let mut mix = seed;
+        let mut state = [0; 4];
+        for word in &mut state {
+            mix = mix.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = mix;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            *word = z ^ (z >> 31);
+        }
+        SeededRng { seed, state }
+    }
+
+    /// Generator for the test case running on the current thread: seed is given by the
+    /// `DATATEST_SEED` environment variable or derived from the name of the case. Panics if called
+    /// outside of the `datatest` test case without the seed given explicitly.
+    pub fn for_current_case() -> SeededRng {
+        if let Some(seed) = std::env::var_os(SEED_ENV_VAR) {
+            let seed = seed.to_string_lossy();
+            let seed = parse_seed(&seed)
+                .unwrap_or_else(|err| panic!("invalid {} value '{}': {}", SEED_ENV_VAR, seed, err));
+            return SeededRng::new(seed);
+        }
+        let case = crate::case::current_case().unwrap_or_else(|| {
+            panic!(
+                "`SeededRng` must be used in the `datatest` test case (or the seed must be given via {})",
+                SEED_ENV_VAR
+            )
+        });
+        SeededRng::new(name_seed(case.name()))
+    }
+
+    /// Seed of the generator.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+        result
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    /// Random value in the given range (which must not be empty).
+    pub fn gen_range(&mut self, range: Range<u64>) -> u64 {
+        assert!(range.start < range.end, "empty range {:?}", range);
+        let span = range.end - range.start;
+        // Reject values from the incomplete last span, so the result is uniform
+        let zone = u64::MAX - (u64::MAX - span + 1) % span;
+        loop {
+            let value = self.next_u64();
+            if value <= zone {
+                return range.start + value % span;
+            }
+        }
+    }
+
+    /// Random float in `[0, 1)` range.
+    pub fn gen_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// `true` with the given probability.
+    pub fn gen_bool(&mut self, probability: f64) -> bool {
+        self.gen_f64() < probability
+    }
+
+    pub fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    /// Shuffle the slice in place (Fisher-Yates).
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for idx in (1..items.len()).rev() {
+            let other = self.gen_range(0..idx as u64 + 1) as usize;
+            items.swap(idx, other);
+        }
+    }
+
+    /// Random element of the slice (`None` if it is empty).
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            items.get(self.gen_range(0..items.len() as u64) as usize)
+        }
+    }
+}
+
+impl std::fmt::Debug for SeededRng {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SeededRng")
+            .field("seed", &self.seed)
+            .finish()
+    }
+}
+
+impl Drop for SeededRng {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!(
+                "random seed: {} (run with {}={} to reproduce)",
+                self.seed, SEED_ENV_VAR, self.seed
+            );
+        }
+    }
+}
+
+/// Parse the seed given in decimal or as `0x`-prefixed hex.
+pub(crate) fn parse_seed(seed: &str) -> Result<u64, std::num::ParseIntError> {
+    match seed.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => seed.parse(),
+    }
+}
+
+/// Seed derived from the name of the test case (64-bit FNV-1a hash, so it does not change between
+/// Rust releases).
+pub(crate) fn name_seed(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
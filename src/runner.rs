@@ -1,9 +1,36 @@
+use crate::capture::Artifacts;
+use crate::case::CaseScope;
+use crate::config::{
+    ConfigurationSpec, DriftPolicy, FocusPolicy, KeepPolicy, OrphansPolicy, Reporter, RunnerConfig,
+    XpassPolicy,
+};
+use crate::configuration::ConfigurationScope;
 use crate::data::{DataTestDesc, DataTestFn};
-use crate::files::{FilesTestDesc, FilesTestFn};
-use crate::rustc_test::{Bencher, ShouldPanic, TestDesc, TestDescAndFn, TestFn, TestName};
+use crate::files::{FilesTestDesc, FilesTestFn, MissingRoot};
+use crate::manifest::Manifest;
+use crate::metrics::{CaseMetrics, MetricsSink};
+use crate::quarantine::Quarantine;
+use crate::reduce::run_with_reduction;
+use crate::report::{
+    CaseOutcome, CaseReport, CaseStatus, CoverageReport, FailureManifest, JunitReport,
+};
+use crate::resources::ResourceLock;
+use crate::rustc_test::{
+    Bencher, OutputFormat, RunIgnored, ShouldPanic, TestDesc, TestDescAndFn, TestFn, TestName,
+    TestOpts,
+};
+use crate::sample::Sample;
+use crate::sandbox::{CwdLock, Sandbox, SandboxDir};
+use crate::timings::{Shard, Timings};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::{mpsc, Arc, Once};
+use std::time::Duration;
+use unicode_normalization::UnicodeNormalization;
 
 /// Our own copy of `test::ShouldPanic` to be used on stable channel (using types from `test` crate
 /// is not allowed on stable without `#![feature(test)]`. Pretty much copy-pasted.
@@ -31,12 +58,20 @@ impl From<RegularShouldPanic> for ShouldPanic {
 pub struct RegularTestDesc {
     pub name: &'static str,
     pub ignore: bool,
+    pub ignore_message: Option<&'static str>,
+    pub only: bool,
     pub testfn: fn(),
     pub should_panic: RegularShouldPanic,
     pub source_file: &'static str,
 }
 
-fn derive_test_name(root: &Path, path: &Path, test_name: &str) -> String {
+fn derive_test_name(
+    root: &Path,
+    path: &Path,
+    desc: &FilesTestDesc,
+    re: &regex::Regex,
+    variant: &[(&str, &str)],
+) -> String {
     let relative = path.strip_prefix(root).unwrap_or_else(|_| {
         panic!(
             "failed to strip prefix '{}' from path '{}'",
@@ -44,10 +79,176 @@ fn derive_test_name(root: &Path, path: &Path, test_name: &str) -> String {
             path.display()
         )
     });
-    let mut test_name = real_name(test_name).to_string();
-    test_name += "::";
-    test_name += &relative.to_string_lossy();
-    test_name
+    let template = match desc.name_template {
+        Some(template) => template,
+        None => {
+            let mut test_name = real_name(desc.name).to_string();
+            test_name += "::";
+            test_name += &escape_path(relative);
+            if !variant.is_empty() {
+                let labels = variant
+                    .iter()
+                    .map(|(param, value)| format!("{}={}", param, value))
+                    .collect::<Vec<_>>();
+                test_name += "::";
+                test_name += &labels.join(",");
+            }
+            return test_name;
+        }
+    };
+
+    let path_str = escape_path(path);
+    let captures = re.captures(&path_str);
+    let rendered = render_name_template(template, |placeholder| match placeholder {
+        "fn" => fn_name(desc.name).to_string(),
+        "path" => escape_path(relative).into_owned(),
+        "file" => path.file_name().map_or_else(String::new, |name| {
+            escape_path(Path::new(name)).into_owned()
+        }),
+        "stem" => path.file_stem().map_or_else(String::new, |stem| {
+            escape_path(Path::new(stem)).into_owned()
+        }),
+        "namespace" => namespace(relative),
+        param if variant.iter().any(|(name, _)| *name == param) => variant
+            .iter()
+            .find(|(name, _)| *name == param)
+            .map_or_else(String::new, |(_, value)| value.to_string()),
+        group => {
+            let capture = match group.parse::<usize>() {
+                Ok(idx) => captures.as_ref().and_then(|c| c.get(idx)),
+                Err(_) => captures.as_ref().and_then(|c| c.name(group)),
+            };
+            capture.map_or_else(String::new, |m| m.as_str().to_string())
+        }
+    });
+    module_name(desc.name).to_string() + &rendered
+}
+
+/// Relative path of the test file as nested namespaces of the test name: directories and the file
+/// name without extension joined with `::` (`parser/v2/case_001.json` is `parser::v2::case_001`).
+fn namespace(relative: &Path) -> String {
+    let mut segments = relative
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .map(|component| escape_path(Path::new(component.as_os_str())).into_owned())
+        .collect::<Vec<_>>();
+    if let Some(stem) = relative.file_stem() {
+        segments.push(escape_path(Path::new(stem)).into_owned());
+    }
+    segments.join("::")
+}
+
+/// Path as a string for matching and test names. Paths which are not valid UTF-8 are escaped (bytes
+/// of invalid sequences become `\xNN` and backslashes are doubled), so they can be converted back
+/// without any loss via [`unescape_path`]. Valid paths are returned as is (borrowed).
+#[cfg(unix)]
+fn escape_path(path: &Path) -> Cow<str> {
+    use std::fmt::Write;
+    use std::os::unix::ffi::OsStrExt;
+
+    if let Some(path) = path.to_str() {
+        return Cow::Borrowed(path);
+    }
+    let mut bytes = path.as_os_str().as_bytes();
+    let mut escaped = String::with_capacity(bytes.len());
+    loop {
+        let (valid, invalid) = match std::str::from_utf8(bytes) {
+            Ok(valid) => (valid, &[][..]),
+            Err(err) => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                let (invalid, rest) = rest.split_at(err.error_len().unwrap_or(rest.len()));
+                bytes = rest;
+                (std::str::from_utf8(valid).unwrap(), invalid)
+            }
+        };
+        escaped += &valid.replace('\\', "\\\\");
+        if invalid.is_empty() {
+            break;
+        }
+        for byte in invalid {
+            write!(escaped, "\\x{:02X}", byte).unwrap();
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Non-Unicode paths are very uncommon on non-Unix platforms, so they are converted lossily.
+#[cfg(not(unix))]
+fn escape_path(path: &Path) -> Cow<str> {
+    path.to_string_lossy()
+}
+
+/// Reverse of [`escape_path`] for paths which were escaped.
+#[cfg(unix)]
+fn unescape_path(escaped: &str) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut input = escaped.as_bytes();
+    while let Some((&byte, rest)) = input.split_first() {
+        input = rest;
+        if byte == b'\\' {
+            if let Some((&b'\\', rest)) = input.split_first() {
+                bytes.push(b'\\');
+                input = rest;
+                continue;
+            }
+            let hex = input
+                .get(1..3)
+                .and_then(|hex| std::str::from_utf8(hex).ok());
+            let escaped_byte = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let (Some(b'x'), Some(escaped_byte)) = (input.first(), escaped_byte) {
+                bytes.push(escaped_byte);
+                input = &input[3..];
+                continue;
+            }
+        }
+        bytes.push(byte);
+    }
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn unescape_path(escaped: &str) -> PathBuf {
+    PathBuf::from(escaped)
+}
+
+/// Normalize test name to NFC, so names derived from file names and test case data are the same on
+/// all platforms (macOS file systems store file names decomposed, in NFD).
+fn normalize_name(name: String) -> String {
+    if unicode_normalization::is_nfc(&name) {
+        name
+    } else {
+        name.nfc().collect()
+    }
+}
+
+/// Render test name template, replacing each `{placeholder}` with the value given by `lookup` (`{{`
+/// and `}}` are escaped braces). Templates are validated by the proc macro at compile time.
+fn render_name_template(template: &str, lookup: impl Fn(&str) -> String) -> String {
+    let mut result = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '{' => {
+                let placeholder = chars.by_ref().take_while(|c| *c != '}').collect::<String>();
+                result += &lookup(&placeholder);
+            }
+            '}' => {
+                // Escaped `}}`
+                chars.next();
+                result.push('}');
+            }
+            c => result.push(c),
+        }
+    }
+    result
 }
 
 /// When compiling tests, Rust compiler collects all items marked with `#[test_case]` and passes
@@ -72,310 +273,2651 @@ impl TestDescriptor for TestDescAndFn {
     }
 }
 
-impl TestDescriptor for FilesTestDesc {
-    fn as_datatest_desc(&self) -> DatatestTestDesc {
-        DatatestTestDesc::FilesTest(self)
-    }
+impl TestDescriptor for FilesTestDesc {
+    fn as_datatest_desc(&self) -> DatatestTestDesc {
+        DatatestTestDesc::FilesTest(self)
+    }
+}
+
+impl TestDescriptor for DataTestDesc {
+    fn as_datatest_desc(&self) -> DatatestTestDesc {
+        DatatestTestDesc::DataTest(self)
+    }
+}
+
+impl TestDescriptor for RegularTestDesc {
+    fn as_datatest_desc(&self) -> DatatestTestDesc {
+        DatatestTestDesc::RegularTest(self)
+    }
+}
+
+#[doc(hidden)]
+pub enum DatatestTestDesc<'a> {
+    Test(&'a TestDescAndFn),
+    FilesTest(&'a FilesTestDesc),
+    DataTest(&'a DataTestDesc),
+    RegularTest(&'a RegularTestDesc),
+}
+
+/// Prints location of the test case (data file and line or the test file) if test fails, so it
+/// can be opened right from the terminal or IDE.
+struct FailureLocation(String);
+
+impl Drop for FailureLocation {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!("failing case: {}", self.0);
+        }
+    }
+}
+
+/// Install panic hook which prints the name and the fixture of the test case running on the current
+/// thread before the panic message (and delegates to the previous hook), so panics of the cases
+/// running in parallel could be attributed even when their output is not captured.
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if let Some(case) = crate::case::current_case() {
+                eprintln!(
+                    "test case '{}' ({}) panicked:",
+                    case.name(),
+                    case.location()
+                );
+            }
+            previous(info)
+        }));
+    });
+}
+
+/// Run the test function, failing the test if it does not finish within the `timeout`. Test
+/// function runs in a separate thread, which is abandoned (left running in the background) if
+/// the timeout expires.
+fn run_with_timeout(timeout: Option<Duration>, testfn: impl FnOnce() + Send + 'static) {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return testfn(),
+    };
+    let (sender, receiver) = mpsc::channel();
+    let artifacts = Artifacts::current();
+    let mut builder = std::thread::Builder::new();
+    if let Some(name) = std::thread::current().name() {
+        builder = builder.name(name.to_string());
+    }
+    builder
+        .spawn(move || {
+            let _artifacts = artifacts.map(Artifacts::enter);
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(testfn));
+            let _ = sender.send(result);
+        })
+        .expect("failed to spawn thread for the test case");
+    match receiver.recv_timeout(timeout) {
+        Ok(Ok(())) => {}
+        Ok(Err(payload)) => std::panic::resume_unwind(payload),
+        Err(_) => panic!("test case did not finish within {:?}", timeout),
+    }
+}
+
+/// Memory limit of the test case, if the case should run in a child process with limited memory
+/// (unless this process is such child already, see [`crate::isolation`]). Limit is only enforced
+/// on Unix.
+fn isolated_memory_limit(limit: Option<u64>) -> Option<u64> {
+    limit.filter(|_| cfg!(unix) && !crate::isolation::is_isolated())
+}
+
+/// Name of the failure artifact of the test: test name with characters which are not safe for file
+/// names replaced.
+pub(crate) fn artifact_name(test_name: &str) -> String {
+    test_name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// File part of the test case location: `<path>` for `<path>:<line>` locations.
+pub(crate) fn location_file(location: &str) -> &str {
+    match location.rsplit_once(':') {
+        Some((file, line)) if !line.is_empty() && line.bytes().all(|b| b.is_ascii_digit()) => file,
+        _ => location,
+    }
+}
+
+/// Short form of the test case location used in test names: `line <N>` for `<path>:<N>`
+/// locations, so test names do not depend on the data file path.
+fn location_label(location: &str) -> Cow<str> {
+    match location.rsplit_once(':') {
+        Some((_, line)) if !line.is_empty() && line.bytes().all(|b| b.is_ascii_digit()) => {
+            Cow::Owned(format!("line {}", line))
+        }
+        _ => Cow::Borrowed(location),
+    }
+}
+
+/// Extract message from the panic payload.
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("panic with non-string payload")
+    }
+}
+
+/// Generate a failing test in place of the tests which cases cannot be enumerated, so the error is
+/// reported for that specific test and other tests still run.
+fn render_enumeration_error(
+    name: &'static str,
+    source_file: &'static str,
+    error: String,
+    case_filter: Option<&str>,
+    rendered: &mut Vec<RenderedTest>,
+) {
+    let test_name = format!("{}::<enumerate>", real_name(name));
+    if !is_case_selected(case_filter, &test_name, None) {
+        return;
+    }
+    let message = format!("failed to enumerate test cases: {}", error);
+    let test = failing_test(test_name, source_file, message.clone());
+    let origin = format!("test '{}'", real_name(name));
+    rendered.push(RenderedTest::new(test, origin).with_error(message));
+}
+
+/// Test which always fails with the given message.
+fn failing_test(test_name: String, source_file: &'static str, message: String) -> TestDescAndFn {
+    TestDescAndFn {
+        desc: TestDesc {
+            name: TestName::DynTestName(test_name),
+            ignore: false,
+            should_panic: ShouldPanic::No,
+            allow_fail: false,
+            test_type: crate::test_type(source_file),
+        },
+        testfn: TestFn::DynTestFn(Box::new(move || panic!("{}", message))),
+    }
+}
+
+/// Environment variable overriding the `orphans` policy of the configuration (see
+/// [`OrphansPolicy`]).
+const ORPHANS_ENV_VAR: &str = "DATATEST_ORPHANS";
+
+/// Policy of the files not used by any test: `DATATEST_ORPHANS` or the `orphans` option of the
+/// configuration.
+fn orphans_policy(config: &RunnerConfig) -> OrphansPolicy {
+    match std::env::var(ORPHANS_ENV_VAR) {
+        Ok(ref value) if value == "fail" => OrphansPolicy::Fail,
+        Ok(ref value) if value == "allow" => OrphansPolicy::Allow,
+        Ok(value) => panic!(
+            "invalid value '{}' of {}, expected 'fail' or 'allow'",
+            value, ORPHANS_ENV_VAR
+        ),
+        Err(_) => config.orphans,
+    }
+}
+
+/// Find files under the root directories of `#[datatest::files]` tests which are not used by any
+/// of these tests (neither matched by the pattern nor derived from the matched file via templates).
+/// Such files are usually left behind after refactoring, when tests silently stop running.
+fn find_orphans(descriptors: &[&dyn TestDescriptor]) -> Vec<PathBuf> {
+    let mut roots: HashMap<&Path, Vec<&FilesTestDesc>> = HashMap::new();
+    for input in descriptors {
+        if let DatatestTestDesc::FilesTest(files) = input.as_datatest_desc() {
+            roots.entry(Path::new(files.root)).or_default().push(files);
+        }
+    }
+
+    let mut orphans = Vec::new();
+    for (root, descs) in roots {
+        // Errors are reported by the tests themselves
+        let mut files = match crate::listing::list_directory(root) {
+            Ok(files) => files,
+            Err(_) => continue,
+        };
+        let mut used = HashSet::new();
+        for desc in descs {
+            let pattern = desc.params[desc.pattern];
+            let re = match regex::Regex::new(pattern) {
+                Ok(re) => re,
+                Err(_) => continue,
+            };
+            for path in &files {
+                let path_str = escape_path(path);
+                if re.is_match(&path_str) && is_content_matched(desc, path) {
+                    used.extend(derive_paths(desc, &re, path, &path_str));
+                }
+            }
+        }
+        files.retain(|path| !used.contains(path));
+        orphans.extend(files);
+    }
+    orphans.sort();
+    orphans
+}
+
+/// Environment variable giving the path of the fixture manifest (overrides the `manifest` option of
+/// the configuration).
+const MANIFEST_ENV_VAR: &str = "DATATEST_MANIFEST";
+
+/// Environment variable regenerating the fixture manifest. It is deliberately separate from
+/// `DATATEST_BLESS`, so updating golden files does not silently acknowledge changed fixtures.
+const UPDATE_MANIFEST_ENV_VAR: &str = "DATATEST_UPDATE_MANIFEST";
+
+/// Command line flag running only the test cases which read fixtures changed (or added) since the
+/// fixture manifest was written.
+const ONLY_CHANGED_FLAG: &str = "--only-changed";
+
+/// Fixture files of the run, as named in the fixture manifest: files under the root directories of
+/// `#[datatest::files]` tests and files the test cases read (like data files of `#[datatest::data]`
+/// tests).
+fn fixture_files(
+    descriptors: &[&dyn TestDescriptor],
+    rendered: &[RenderedTest],
+) -> Vec<(String, PathBuf)> {
+    let mut files = Vec::new();
+    for input in descriptors {
+        if let DatatestTestDesc::FilesTest(desc) = input.as_datatest_desc() {
+            // Errors are reported by the tests themselves
+            if let Ok(listing) = crate::listing::list_directory(Path::new(desc.root)) {
+                files.extend(listing);
+            }
+        }
+    }
+    files.extend(
+        rendered
+            .iter()
+            .flat_map(|test| &test.inputs)
+            .filter(|path| crate::listing::is_file(path))
+            .cloned(),
+    );
+    files.sort();
+    files.dedup();
+    // Fixtures outside of the crate directory (like data files generated into `OUT_DIR`) depend on
+    // the machine, so they are not recorded
+    let names = ManifestNames::new();
+    files
+        .into_iter()
+        .filter_map(|path| Some((names.name(&path)?, path)))
+        .collect()
+}
+
+/// Names of the fixtures as recorded in the manifest: relative to the crate directory, so the
+/// manifest is the same on every machine. Working and crate directories are looked up once for all
+/// the fixtures.
+struct ManifestNames {
+    cwd: Option<PathBuf>,
+    crate_dir: Option<PathBuf>,
+}
+
+impl ManifestNames {
+    fn new() -> ManifestNames {
+        let cwd = std::env::current_dir().ok();
+        let crate_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .or_else(|| cwd.clone());
+        ManifestNames { cwd, crate_dir }
+    }
+
+    /// Name of the fixture at the path (`None` if the fixture is outside of the crate directory).
+    /// Components are separated by `/` on every platform, each escaped on its own (so backslashes
+    /// doubled by the escaping are kept).
+    fn name(&self, path: &Path) -> Option<String> {
+        let absolute = self.cwd.as_ref()?.join(path);
+        let relative = absolute.strip_prefix(self.crate_dir.as_ref()?).ok()?;
+        let components = relative
+            .components()
+            .map(|component| escape_path(Path::new(component.as_os_str())))
+            .collect::<Vec<_>>();
+        Some(components.join("/"))
+    }
+}
+
+/// Predicate telling if the manifest entry belongs to the current test binary: fixtures under the
+/// root directories of its `#[datatest::files]` tests, and the files its cases read. Without
+/// `complete`, only some of the cases are enumerated, so only the files which were hashed belong
+/// to it.
+fn owned_fixtures(
+    descriptors: &[&dyn TestDescriptor],
+    current: &[(String, PathBuf)],
+    complete: bool,
+) -> impl Fn(&str) -> bool {
+    let mut roots = Vec::new();
+    if complete {
+        let names = ManifestNames::new();
+        for input in descriptors {
+            if let DatatestTestDesc::FilesTest(desc) = input.as_datatest_desc() {
+                if let Some(root) = names.name(Path::new(desc.root)) {
+                    roots.push(format!("{}/", root.trim_end_matches('/')));
+                }
+            }
+        }
+    }
+    let files = current
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<HashSet<_>>();
+    move |name: &str| files.contains(name) || roots.iter().any(|root| name.starts_with(root))
+}
+
+/// Check the fixtures against the manifest at the given path (or regenerate the manifest, if run
+/// with `DATATEST_UPDATE_MANIFEST`). Drift fails the run or is only reported, depending on the policy. With
+/// `only_changed`, only the test cases which read changed fixtures are kept instead.
+fn check_manifest(
+    path: &Path,
+    descriptors: &[&dyn TestDescriptor],
+    rendered: &mut Vec<RenderedTest>,
+    case_filter: Option<&str>,
+    policy: DriftPolicy,
+    only_changed: bool,
+) {
+    let files = fixture_files(descriptors, rendered);
+    let current = Manifest::compute(&files).unwrap_or_else(|err| panic!("{}", err));
+    // Some fixtures are not hashed if only some of the cases are enumerated
+    let owned = owned_fixtures(descriptors, &files, case_filter.is_none());
+    if std::env::var_os(UPDATE_MANIFEST_ENV_VAR).is_some() {
+        // Manifest is shared by the test binaries of the crate, so the others keep their entries
+        let existing = if path.exists() {
+            Manifest::load(path).unwrap_or_else(|err| panic!("{}", err))
+        } else {
+            Manifest::default()
+        };
+        existing
+            .update(&current, &owned)
+            .write(path)
+            .unwrap_or_else(|e| {
+                panic!("cannot write fixture manifest '{}': {}", path.display(), e)
+            });
+        println!("fixture manifest is written to '{}'", path.display());
+        return;
+    }
+    let expected = Manifest::load(path).unwrap_or_else(|err| {
+        panic!(
+            "{} (run with `{}=1` to generate it)",
+            err, UPDATE_MANIFEST_ENV_VAR
+        )
+    });
+    let drift = expected.drift(&current, &owned);
+
+    if only_changed {
+        let total = rendered.len();
+        let names = ManifestNames::new();
+        rendered.retain(|test| {
+            test.inputs
+                .iter()
+                .any(|input| names.name(input).is_some_and(|name| drift.touches(&name)))
+        });
+        println!(
+            "{}: running {} out of {} tests reading fixtures changed since the manifest was \
+             written",
+            ONLY_CHANGED_FLAG,
+            rendered.len(),
+            total
+        );
+        return;
+    }
+    if drift.is_empty() {
+        return;
+    }
+    let message = format!(
+        "fixtures differ from the manifest '{}' (run with `{}=1` to regenerate it):\n{}",
+        path.display(),
+        UPDATE_MANIFEST_ENV_VAR,
+        drift.describe()
+    );
+    let manifest_test = "<fixture manifest>";
+    match policy {
+        DriftPolicy::Warn => eprintln!("warning: {}", message),
+        DriftPolicy::Fail if is_case_selected(case_filter, manifest_test, None) => {
+            let test = failing_test(manifest_test.to_string(), "", message);
+            rendered.push(RenderedTest::new(
+                test,
+                String::from("fixture manifest check"),
+            ));
+        }
+        DriftPolicy::Fail => {}
+    }
+}
+
+/// Name of the failure manifest written after every run (see [`output_dir`]).
+const FAILURES_FILE: &str = "failures.json";
+
+/// Default name of the durations of the test cases, updated after every run (see [`output_dir`]
+/// and `timings` option of the configuration).
+const TIMINGS_FILE: &str = "timings.json";
+
+/// Directory of the files written after every run: `datatest/<test binary>` under the target
+/// directory (`CARGO_TARGET_DIR` or the one the test binary is built into), so test binaries of
+/// the crate and runs from other working directories do not overwrite each other's files.
+fn output_dir() -> PathBuf {
+    let exe = std::env::current_exe().ok();
+    // Test binaries are built into `<target>/<profile>/deps/<name>-<hash>`
+    let target = std::env::var_os("CARGO_TARGET_DIR")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(exe.as_ref()?.parent()?.parent()?.parent()?.to_path_buf()))
+        .unwrap_or_else(|| PathBuf::from("target"));
+    target.join("datatest").join(binary_name())
+}
+
+/// Name of the test binary, without the hash added by Cargo.
+fn binary_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .as_ref()
+        .and_then(|exe| exe.file_stem())
+        .map(|stem| stem.to_string_lossy())
+        .map_or_else(String::new, |stem| match stem.rsplit_once('-') {
+            Some((name, hash)) if hash.chars().all(|c| c.is_ascii_hexdigit()) => name.to_string(),
+            _ => stem.into_owned(),
+        })
+}
+
+/// Environment variable giving the path of the durations of the test cases (overrides the `timings`
+/// option of the configuration).
+const TIMINGS_ENV_VAR: &str = "DATATEST_TIMINGS";
+
+/// Environment variable selecting the shard of the test suite to run (`<index>/<count>`).
+const SHARD_ENV_VAR: &str = "DATATEST_SHARD";
+
+/// Number of test cases the standard runner runs in parallel.
+fn test_threads(opts: &TestOpts) -> usize {
+    opts.test_threads
+        .or_else(|| std::env::var("RUST_TEST_THREADS").ok()?.parse().ok())
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |count| count.get()))
+}
+
+/// Order test cases for the parallel run: cases with higher priority first, then the cases which
+/// took the longest in the previous runs. Otherwise, cases keep their order.
+fn schedule(rendered: &mut Vec<RenderedTest>, timings: &Timings) {
+    let names = rendered
+        .iter()
+        .map(|test| test.test.desc.name.to_string())
+        .collect::<Vec<_>>();
+    let expected = timings.expected(&names);
+    let mut keyed = std::mem::take(rendered)
+        .into_iter()
+        .zip(expected)
+        .collect::<Vec<_>>();
+    keyed.sort_by(|(a, a_expected), (b, b_expected)| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| b_expected.total_cmp(a_expected))
+    });
+    rendered.extend(keyed.into_iter().map(|(test, _)| test));
+}
+
+/// Ignore the test cases which would start after the time budget of the run is spent, given the
+/// durations of the cases in the previous runs (cases are started in order).
+fn skip_for_time(
+    rendered: &mut [RenderedTest],
+    budget: Duration,
+    timings: &Timings,
+    opts: &TestOpts,
+) {
+    let executed = rendered
+        .iter()
+        .enumerate()
+        .filter(|(_, test)| case_status(opts, test, false) == CaseStatus::Executed)
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    let names = executed
+        .iter()
+        .map(|idx| rendered[*idx].test.desc.name.to_string())
+        .collect::<Vec<_>>();
+    let selected = crate::budget::select(budget, &timings.expected(&names), test_threads(opts));
+    let mut skipped = Vec::new();
+    for (idx, selected) in executed.into_iter().zip(selected) {
+        if !selected {
+            let test = &mut rendered[idx];
+            test.test.desc.ignore = true;
+            test.ignore_message = Some(crate::budget::SKIPPED_FOR_TIME);
+            skipped.push(test.test.desc.name.to_string());
+        }
+    }
+    crate::budget::skip(budget, skipped);
+}
+
+/// Order test cases so that all cases of the prerequisites of each test (`depends_on` option) come
+/// before its cases, assigning each case the stage it runs in (see [`run_in_stages`]). Otherwise,
+/// cases keep their order.
+fn order_by_dependencies(rendered: &mut Vec<RenderedTest>, descriptors: &[&dyn TestDescriptor]) {
+    let known = descriptors
+        .iter()
+        .map(|input| descriptor_name(*input))
+        .collect::<HashSet<_>>();
+    let mut prerequisites = BTreeMap::<&str, &[&str]>::new();
+    for test in rendered.iter() {
+        if let Some(ref function) = test.function {
+            prerequisites.insert(function, &test.depends_on);
+        }
+    }
+    for (function, tests) in &prerequisites {
+        if let Some(unknown) = tests.iter().find(|test| !known.contains(**test)) {
+            panic!("test '{}' depends on unknown test '{}'", function, unknown);
+        }
+    }
+    if prerequisites.values().all(|tests| tests.is_empty()) {
+        return;
+    }
+
+    // Depth of the test in the dependency graph: tests with no prerequisites go first, then the
+    // tests depending only on them, etc.
+    fn depth<'a>(
+        function: &'a str,
+        prerequisites: &BTreeMap<&str, &[&'a str]>,
+        depths: &mut HashMap<&'a str, usize>,
+        path: &mut Vec<&'a str>,
+    ) -> usize {
+        if let Some(depth) = depths.get(function) {
+            return *depth;
+        }
+        if let Some(pos) = path.iter().position(|test| *test == function) {
+            let mut cycle = path[pos..].to_vec();
+            cycle.push(function);
+            panic!("tests have circular dependencies: {}", cycle.join(" -> "));
+        }
+        path.push(function);
+        let depth = prerequisites
+            .get(function)
+            .into_iter()
+            .flat_map(|tests| tests.iter())
+            .map(|test| depth(test, prerequisites, depths, path) + 1)
+            .max()
+            .unwrap_or(0);
+        path.pop();
+        depths.insert(function, depth);
+        depth
+    }
+    let mut depths = HashMap::new();
+    let keys = rendered
+        .iter()
+        .map(|test| match test.function {
+            Some(ref function) => depth(function, &prerequisites, &mut depths, &mut Vec::new()),
+            None => 0,
+        })
+        .collect::<Vec<_>>();
+    let mut keyed = std::mem::take(rendered)
+        .into_iter()
+        .zip(keys)
+        .collect::<Vec<_>>();
+    keyed.sort_by_key(|(_, depth)| *depth);
+    rendered.extend(keyed.into_iter().map(|(mut test, depth)| {
+        test.stage = depth;
+        test
+    }));
+}
+
+/// Run the test cases stage by stage (see [`order_by_dependencies`]): each stage is handed to the
+/// standard runner only after all cases of the previous stages finished, so cases of the dependent
+/// tests never wait for their prerequisites on a worker thread. Cases of the tests whose
+/// prerequisites failed fail without running, naming the failed prerequisites.
+fn run_in_stages(
+    rendered: Vec<RenderedTest>,
+    opts: &TestOpts,
+    mut run: impl FnMut(Vec<RenderedTest>) -> std::io::Result<bool>,
+) -> std::io::Result<bool> {
+    if opts.list || rendered.iter().all(|test| test.stage == 0) {
+        return run(rendered);
+    }
+    let mut cases = HashMap::<String, Vec<String>>::new();
+    for test in &rendered {
+        if let Some(ref function) = test.function {
+            let name = test.test.desc.name.to_string();
+            cases.entry(function.clone()).or_default().push(name);
+        }
+    }
+    let mut stages = BTreeMap::<usize, Vec<RenderedTest>>::new();
+    for test in rendered {
+        stages.entry(test.stage).or_default().push(test);
+    }
+
+    let mut passed = true;
+    for (_, mut stage) in stages {
+        for test in &mut stage {
+            let failed = test
+                .depends_on
+                .iter()
+                .filter(|function| {
+                    cases.get(**function).is_some_and(|names| {
+                        names
+                            .iter()
+                            .any(|name| crate::capture::passed(name) == Some(false))
+                    })
+                })
+                .map(|function| function.to_string())
+                .collect::<Vec<_>>();
+            if failed.is_empty() || matches!(test.test.testfn, TestFn::DynBenchFn(_)) {
+                continue;
+            }
+            let name = test.test.desc.name.to_string();
+            test.test.testfn = TestFn::DynTestFn(Box::new(move || {
+                crate::dependencies::fail(&name, failed);
+            }));
+        }
+        passed &= run(stage)?;
+    }
+    Ok(passed)
+}
+
+/// Shard of the test suite to run, if requested.
+fn shard_spec() -> Option<Shard> {
+    let value = std::env::var(SHARD_ENV_VAR)
+        .ok()
+        .filter(|value| !value.is_empty())?;
+    let shard = Shard::parse(&value)
+        .unwrap_or_else(|err| panic!("invalid value of {}: {}", SHARD_ENV_VAR, err));
+    Some(shard)
+}
+
+/// Environment variable giving the path of the coverage report (overrides the `report` option of the
+/// configuration).
+const REPORT_ENV_VAR: &str = "DATATEST_REPORT";
+
+/// Environment variable giving the path of the JUnit report (overrides the `junit` option of the
+/// configuration).
+const JUNIT_ENV_VAR: &str = "DATATEST_JUNIT";
+
+/// Whether the test is going to be run.
+fn case_status(opts: &TestOpts, test: &RenderedTest, focus: bool) -> CaseStatus {
+    let name = test.test.desc.name.to_string();
+    let ignored = match opts.run_ignored {
+        RunIgnored::No => test.test.desc.ignore,
+        RunIgnored::Yes => false,
+        RunIgnored::Only => !test.test.desc.ignore,
+    };
+    if !is_selected(opts, &name) {
+        CaseStatus::Filtered
+    } else if ignored || (focus && !test.only) {
+        CaseStatus::Skipped
+    } else {
+        CaseStatus::Executed
+    }
+}
+
+/// Standard test descriptor generated by our runner, together with the additional information we
+/// keep about the test (which standard descriptor cannot represent).
+struct RenderedTest {
+    test: TestDescAndFn,
+    /// Human-readable description of where the test came from (used for diagnostics).
+    origin: String,
+    /// Reason for ignoring the test, given via `#[ignore = "<reason>"]`.
+    ignore_message: Option<&'static str>,
+    /// Test is focused via `#[only]`.
+    only: bool,
+    /// Full name of the entry point generated for the test to be run from IDEs.
+    entry_point: Option<String>,
+    /// Test file or location of the test case (for the coverage report).
+    fixture: Option<String>,
+    /// Files the test depends on (to find the tests affected by the changes in watch mode).
+    inputs: Vec<PathBuf>,
+    /// Name of the test function the test case is generated from (for the grouped summary).
+    function: Option<String>,
+    /// Test case is expected to fail (`#[xfail]`).
+    xfail: bool,
+    /// Scheduling priority (`priority` option): cases with higher priority are started first.
+    priority: i32,
+    /// Test functions which must pass before this test case runs (`depends_on` option).
+    depends_on: Vec<&'static str>,
+    /// Stage of the run the test case belongs to: cases run after all cases of the previous stages
+    /// finished, so the prerequisites of the test run in the earlier stages.
+    stage: usize,
+    /// Root directory of the files-driven test (for the grouped summary).
+    root: Option<String>,
+    /// Namespace of the test case named via `{namespace}` placeholder (test name without the last
+    /// segment), for the grouped summary.
+    namespace: Option<String>,
+    /// Test stands for the error of enumerating the test cases (reported by `--validate`).
+    error: Option<String>,
+}
+
+impl RenderedTest {
+    fn new(test: TestDescAndFn, origin: String) -> Self {
+        RenderedTest {
+            test,
+            origin,
+            ignore_message: None,
+            only: false,
+            entry_point: None,
+            fixture: None,
+            inputs: Vec::new(),
+            function: None,
+            xfail: false,
+            priority: 0,
+            depends_on: Vec::new(),
+            stage: 0,
+            root: None,
+            namespace: None,
+            error: None,
+        }
+    }
+
+    fn with_fixture(mut self, fixture: String) -> Self {
+        self.fixture = Some(fixture);
+        self
+    }
+
+    fn with_inputs(mut self, inputs: Vec<PathBuf>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
+    fn with_function(mut self, function: &str) -> Self {
+        self.function = Some(function.to_string());
+        self
+    }
+
+    fn with_ignore_message(mut self, ignore_message: Option<&'static str>) -> Self {
+        self.ignore_message = ignore_message;
+        self
+    }
+
+    fn with_only(mut self, only: bool) -> Self {
+        self.only = only;
+        self
+    }
+
+    fn with_xfail(mut self, xfail: bool) -> Self {
+        self.xfail = xfail;
+        self
+    }
+
+    fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    fn with_depends_on(mut self, depends_on: &'static [&'static str]) -> Self {
+        self.depends_on = depends_on.iter().map(|name| real_name(name)).collect();
+        self
+    }
+
+    fn with_root(mut self, root: &Path) -> Self {
+        self.root = Some(escape_path(root).into_owned());
+        self
+    }
+
+    fn with_namespace(mut self, namespace: Option<String>) -> Self {
+        self.namespace = namespace;
+        self
+    }
+
+    fn with_entry_point(mut self, entry_point: Option<String>) -> Self {
+        self.entry_point = entry_point;
+        self
+    }
+
+    fn with_error(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+}
+
+struct FilesBenchFn(fn(&mut Bencher, &[PathBuf], usize), Vec<PathBuf>, usize);
+
+impl rustc_test::TDynBenchFn for FilesBenchFn {
+    fn run(&self, harness: &mut Bencher) {
+        (self.0)(harness, &self.1, self.2)
+    }
+}
+
+/// Benchmark function printing the location of the case if the benchmark fails.
+struct LocatedBenchFn(Box<dyn rustc_test::TDynBenchFn>, String);
+
+impl rustc_test::TDynBenchFn for LocatedBenchFn {
+    fn run(&self, harness: &mut Bencher) {
+        let _location = FailureLocation(self.1.clone());
+        self.0.run(harness)
+    }
+}
+
+/// Values of the matrix parameters (pairs of parameter name and value label) for each combination
+/// of values. Combinations are enumerated in order, with the last parameter changing the fastest
+/// (generated test functions decode the index of the combination the same way).
+fn matrix_variants(
+    matrix: &'static [(&'static str, &'static [&'static str])],
+) -> Vec<Vec<(&'static str, &'static str)>> {
+    let count = matrix.iter().map(|(_, values)| values.len()).product();
+    (0..count)
+        .map(|variant| {
+            let mut stride = 1;
+            let mut labels = Vec::with_capacity(matrix.len());
+            for (param, values) in matrix.iter().rev() {
+                labels.push((*param, values[(variant / stride) % values.len()]));
+                stride *= values.len();
+            }
+            labels.reverse();
+            labels
+        })
+        .collect()
+}
+
+/// Generate standard test descriptors ([`test::TestDescAndFn`]) from the descriptor of
+/// `#[datatest::files(..)]`.
+///
+/// Generate list of paths to pass to the test function for the matched file. We generate a
+/// `PathBuf` for each argument of the test function and pass them to the trampoline function in a
+/// slice. See `datatest-derive` proc macro sources for more details.
+fn derive_paths(
+    desc: &FilesTestDesc,
+    re: &regex::Regex,
+    path: &Path,
+    path_str: &str,
+) -> Vec<PathBuf> {
+    let mut paths = Vec::with_capacity(desc.params.len());
+    for (idx, param) in desc.params.iter().enumerate() {
+        if idx == desc.pattern {
+            // Pattern path
+            paths.push(path.to_path_buf());
+        } else {
+            let rendered_path = re.replace_all(path_str, *param);
+            // Paths which are not valid UTF-8 are escaped, see `escape_path`
+            let rendered_path = match path.to_str() {
+                Some(_) => PathBuf::from(rendered_path.as_ref()),
+                None => unescape_path(&rendered_path),
+            };
+            paths.push(rendered_path);
+        }
+    }
+    paths
+}
+
+/// Test files seen so far (`dedup` option), by the SHA-256 hash of their contents, used to skip
+/// test files with the same contents as one of the previous files.
+#[derive(Default)]
+struct SeenContents(HashMap<Vec<u8>, Vec<PathBuf>>);
+
+impl SeenContents {
+    /// Record the test file, returning `false` if a file with the same contents was seen before.
+    /// Files which cannot be read are never skipped (and fail when the test reads them).
+    fn insert(&mut self, path: &Path) -> bool {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(_) => return true,
+        };
+        let seen = self.0.entry(Sha256::digest(&content).to_vec()).or_default();
+        // Files with the same hash are compared byte by byte, so a collision never skips a file
+        let is_duplicate = seen
+            .iter()
+            .any(|seen| std::fs::read(seen).map_or(false, |seen| seen == content));
+        if !is_duplicate {
+            seen.push(path.to_path_buf());
+        }
+        !is_duplicate
+    }
+}
+
+/// Number of bytes from the beginning of the file given to the `content_matches` predicate.
+const CONTENT_HEAD_LEN: u64 = 4096;
+
+/// If the file matched by the pattern is a test case according to the `content_matches` predicate
+/// (if any). Files which cannot be read are kept, so the test itself reports the error.
+fn is_content_matched(desc: &FilesTestDesc, path: &Path) -> bool {
+    use std::io::Read;
+
+    let predicate = match desc.content_matches {
+        Some(predicate) => predicate,
+        None => return true,
+    };
+    let mut head = Vec::new();
+    match std::fs::File::open(path)
+        .and_then(|file| file.take(CONTENT_HEAD_LEN).read_to_end(&mut head))
+    {
+        Ok(_) => predicate(&head),
+        Err(_) => true,
+    }
+}
+
+/// Fail the test with a clear message if a file derived via template does not exist (instead of
+/// failing with an I/O error when the file is read).
+fn check_derived_paths(params: &[&str], reads: &[bool], pattern: usize, paths: &[PathBuf]) {
+    for (idx, path) in paths.iter().enumerate() {
+        let read = reads.get(idx).copied().unwrap_or(false);
+        if idx != pattern && read && !crate::listing::exists(path) {
+            panic!(
+                "file '{}' derived from the test file '{}' via template '{}' does not exist",
+                escape_path(path),
+                escape_path(&paths[pattern]),
+                params[idx]
+            );
+        }
+    }
+}
+
+/// Scans all files in a given directory, finds matching ones and generates a test descriptor for
+/// each of them.
+fn render_files_test(
+    desc: &FilesTestDesc,
+    case_filter: Option<&str>,
+    keep: KeepPolicy,
+    default_timeout: Option<Duration>,
+    configuration: &CaseConfiguration,
+    rendered: &mut Vec<RenderedTest>,
+) {
+    let root = Path::new(desc.root).to_path_buf();
+    let fail = |error: String, rendered: &mut Vec<RenderedTest>| {
+        render_enumeration_error(desc.name, desc.source_file, error, case_filter, rendered)
+    };
+    if !crate::listing::is_dir(&root) {
+        match desc.missing_root {
+            MissingRoot::Fail => fail(
+                format!(
+                    "root directory '{}' does not exist (use `missing_root = skip` option if it is \
+                     optional)",
+                    desc.root
+                ),
+                rendered,
+            ),
+            MissingRoot::Skip => {}
+            MissingRoot::Warn => eprintln!(
+                "warning: root directory '{}' of test '{}' does not exist, test is skipped",
+                desc.root,
+                real_name(desc.name)
+            ),
+        }
+        return;
+    }
+
+    let pattern = desc.params[desc.pattern];
+    let re = regex::Regex::new(pattern)
+        .unwrap_or_else(|_| panic!("invalid regular expression: '{}'", pattern));
+
+    let mut files = match crate::listing::list_directory(&root) {
+        Ok(files) => files,
+        Err(err) => {
+            let path = err.path().unwrap_or(&root).display().to_string();
+            return fail(format!("cannot scan '{}': {}", path, err), rendered);
+        }
+    };
+
+    // Directory iteration order differs between file systems, so order the files by path (unless
+    // custom order is given; files with equal keys are still ordered by path)
+    files.sort();
+    if let Some(order) = desc.order {
+        order(&mut files);
+    }
+
+    let mut found = false;
+    let mut seen_contents = SeenContents::default();
+    for path in files {
+        // Paths which are not valid UTF-8 are matched and rendered in the escaped form
+        let path_str = escape_path(&path);
+        if re.is_match(&path_str) {
+            // Files rejected by their content are not test cases at all
+            if !is_content_matched(desc, &path) {
+                continue;
+            }
+            // Files with the same content as one of the previous files are skipped
+            if desc.dedup && !seen_contents.insert(&path) {
+                found = true;
+                continue;
+            }
+            let paths = derive_paths(desc, &re, &path, &path_str);
+
+            let ignore = desc.ignore
+                || desc
+                    .ignorefn
+                    .map_or(false, |ignore_func| ignore_func(&path));
+
+            let relative = path.strip_prefix(&root).unwrap_or(&path);
+            let entry_point = desc
+                .entry_points
+                .iter()
+                .find(|(_, file)| Path::new(file) == relative)
+                .map(|(name, _)| format!("{}{}", module_name(desc.name), name));
+
+            // Without matrix parameters, there is exactly one (empty) combination of values
+            for (variant, labels) in matrix_variants(desc.matrix).into_iter().enumerate() {
+                let test_name = normalize_name(derive_test_name(&root, &path, desc, &re, &labels));
+                if !is_case_selected(case_filter, &test_name, Some(&path)) {
+                    continue;
+                }
+                let paths = paths.clone();
+                let inputs = paths.clone();
+                let location = path_str.to_string();
+                let fixture = location.clone();
+                let (params, reads, pattern) = (desc.params, desc.reads, desc.pattern);
+                let timeout = desc.timeout.or(default_timeout);
+                let reduce = desc.reduce;
+                let sandbox = desc.sandbox;
+                let resources = desc.resources;
+                let memory_limit = isolated_memory_limit(desc.memory_limit);
+                let env = configuration.env.clone();
+                let isolated_name = configuration.case_name(&test_name);
+                let artifact = artifact_name(&test_name);
+                let case_name = test_name.clone();
+                let namespace = desc
+                    .name_template
+                    .filter(|template| template.contains("{namespace}"))
+                    .and_then(|_| test_name.rsplit_once("::"))
+                    .map(|(namespace, _)| namespace.to_string());
+                let testfn = match desc.testfn {
+                    FilesTestFn::TestFn(testfn) => TestFn::DynTestFn(Box::new(move || {
+                        let _location = FailureLocation(location.clone());
+                        check_derived_paths(params, reads, pattern, &paths);
+                        let _resources = ResourceLock::acquire(resources);
+                        if memory_limit.is_some() || crate::isolation::needs_env(&env) {
+                            return crate::isolation::run_isolated(
+                                &isolated_name,
+                                memory_limit,
+                                &env,
+                            );
+                        }
+                        // Lock and sandbox are owned by the code using them, so a case abandoned
+                        // on timeout keeps its working directory until it finishes
+                        run_with_timeout(timeout, move || {
+                            let _cwd = CwdLock::acquire(sandbox == Some(Sandbox::Chdir));
+                            let sandbox = sandbox.map(|mode| {
+                                SandboxDir::create(mode, &paths[pattern], &paths, &artifact, keep)
+                            });
+                            let paths = match sandbox {
+                                Some(ref sandbox) => sandbox.remap(&paths),
+                                None => paths,
+                            };
+                            let _case = CaseScope::enter(case_name, paths.clone(), location);
+                            match reduce {
+                                Some(reducer) => run_with_reduction(
+                                    reducer,
+                                    &artifact,
+                                    pattern,
+                                    &paths,
+                                    |paths| testfn(paths, variant),
+                                ),
+                                None => testfn(&paths, variant),
+                            }
+                        })
+                    })),
+                    FilesTestFn::BenchFn(benchfn) => TestFn::DynBenchFn(Box::new(LocatedBenchFn(
+                        Box::new(FilesBenchFn(benchfn, paths, variant)),
+                        location,
+                    ))),
+                };
+
+                // Generate a standard test descriptor
+                let test = TestDescAndFn {
+                    desc: TestDesc {
+                        name: TestName::DynTestName(test_name),
+                        ignore,
+                        should_panic: ShouldPanic::No,
+                        // Cannot be used on stable: https://github.com/rust-lang/rust/issues/46488
+                        allow_fail: false,
+                        test_type: crate::test_type(desc.source_file),
+                    },
+                    testfn,
+                };
+
+                let origin = format!("test '{}', file '{}'", real_name(desc.name), path_str);
+                rendered.push(
+                    RenderedTest::new(test, origin)
+                        .with_ignore_message(desc.ignore_message)
+                        .with_only(desc.only)
+                        .with_entry_point(entry_point.clone())
+                        .with_fixture(fixture)
+                        .with_inputs(inputs)
+                        .with_function(real_name(desc.name))
+                        .with_xfail(desc.xfail)
+                        .with_priority(desc.priority)
+                        .with_depends_on(desc.depends_on)
+                        .with_root(&root)
+                        .with_namespace(namespace),
+                );
+            }
+            found = true;
+        }
+    }
+
+    // We want to avoid silent fails due to typos in regexp!
+    if !found {
+        fail(
+            format!(
+                "no test cases found. Scanned directory: '{}' with pattern '{}'",
+                desc.root, pattern,
+            ),
+            rendered,
+        );
+    }
+}
+
+fn render_data_test(
+    desc: &DataTestDesc,
+    case_filter: Option<&str>,
+    default_timeout: Option<Duration>,
+    configuration: &CaseConfiguration,
+    rendered: &mut Vec<RenderedTest>,
+) {
+    let prefix_name = real_name(&desc.name);
+
+    // Sources might still panic (for example, `datatest::yaml`), so treat it as an error, too
+    let cases = std::panic::catch_unwind(std::panic::AssertUnwindSafe(desc.describefn))
+        .unwrap_or_else(|payload| Err(panic_message(&*payload)));
+    let cases = match cases {
+        Ok(cases) => cases,
+        Err(error) => {
+            return render_enumeration_error(
+                desc.name,
+                desc.source_file,
+                error,
+                case_filter,
+                rendered,
+            )
+        }
+    };
+    for case in cases {
+        // FIXME: use name provided in `case`...
+
+        let origin = format!("test '{}', case at {}", real_name(desc.name), case.location);
+        let label = location_label(&case.location);
+        let case_name = normalize_name(if desc.name_template.is_some() {
+            let name = case.name.unwrap_or_default();
+            format!("{}{}", module_name(desc.name), name)
+        } else if let Some(n) = case.name {
+            format!("{}::{} ({})", prefix_name, n, label)
+        } else {
+            format!("{}::{}", prefix_name, label)
+        });
+        if !is_case_selected(case_filter, &case_name, None) {
+            continue;
+        }
+
+        let location = case.location;
+        let fixture = location.clone();
+        let inputs = vec![PathBuf::from(location_file(&location))];
+        let fixtures = inputs.clone();
+        // Timeout of the case overrides the timeout of the test, which overrides the default one
+        let timeout = case.timeout.or(desc.timeout).or(default_timeout);
+        // Variables of the case override the ones of the configuration
+        let env = configuration
+            .env
+            .iter()
+            .cloned()
+            .chain(case.env)
+            .collect::<Vec<_>>();
+        let isolated_name = configuration.case_name(&case_name);
+        let xfail = case.xfail;
+        let resources = desc.resources;
+        let memory_limit = isolated_memory_limit(desc.memory_limit);
+        let name = case_name.clone();
+        let testfn = match case.case {
+            DataTestFn::TestFn(testfn) => TestFn::DynTestFn(Box::new(move || {
+                let _location = FailureLocation(location.clone());
+                let _resources = ResourceLock::acquire(resources);
+                if memory_limit.is_some() || crate::isolation::needs_env(&env) {
+                    return crate::isolation::run_isolated(&isolated_name, memory_limit, &env);
+                }
+                run_with_timeout(timeout, move || {
+                    let _cwd = CwdLock::acquire(false);
+                    let _case = CaseScope::enter(name, fixtures, location);
+                    testfn()
+                })
+            })),
+            DataTestFn::BenchFn(benchfn) => {
+                TestFn::DynBenchFn(Box::new(LocatedBenchFn(benchfn, location)))
+            }
+        };
+
+        // Generate a standard test descriptor
+        let test = TestDescAndFn {
+            desc: TestDesc {
+                name: TestName::DynTestName(case_name),
+                ignore: desc.ignore,
+                should_panic: ShouldPanic::No,
+                allow_fail: false,
+                test_type: crate::test_type(desc.source_file),
+            },
+            testfn,
+        };
+
+        rendered.push(
+            RenderedTest::new(test, origin)
+                .with_ignore_message(desc.ignore_message)
+                .with_only(desc.only)
+                .with_fixture(fixture)
+                .with_inputs(inputs)
+                .with_function(real_name(desc.name))
+                .with_xfail(desc.xfail || xfail)
+                .with_priority(desc.priority)
+                .with_depends_on(desc.depends_on),
+        );
+    }
+}
+
+/// We need to build our own slice of test descriptors to pass to `test::test_main`. We cannot
+/// clone `TestFn`, so we do it via matching on variants. Not sure how to handle `Dynamic*` variants,
+/// but we seem not to get them here anyway?.
+fn clone_testfn(testfn: &TestFn) -> TestFn {
+    match testfn {
+        TestFn::StaticTestFn(func) => TestFn::StaticTestFn(*func),
+        TestFn::StaticBenchFn(bench) => TestFn::StaticBenchFn(*bench),
+        _ => unimplemented!("only static functions are supported"),
+    }
+}
+
+/// Regular tests do not change the working directory, but they hold the lock of it shared, so they
+/// do not run while a sandboxed test case has changed it (see [`CwdLock`]).
+fn with_cwd_lock(testfn: TestFn) -> TestFn {
+    match testfn {
+        TestFn::StaticTestFn(testfn) => TestFn::DynTestFn(Box::new(move || {
+            let _cwd = CwdLock::acquire(false);
+            testfn()
+        })),
+        testfn => testfn,
+    }
+}
+
+/// Strip crate name. We use `module_path!` macro to generate this name, which includes crate name.
+/// However, standard test library does not include crate name into a test name.
+fn real_name(name: &str) -> &str {
+    match name.find("::") {
+        Some(pos) => &name[pos + 2..],
+        None => name,
+    }
+}
+
+/// Module part of the test name (without crate name), including trailing `::` (if not empty).
+fn module_name(name: &str) -> &str {
+    let name = real_name(name);
+    match name.rfind("::") {
+        Some(pos) => &name[..pos + 2],
+        None => "",
+    }
+}
+
+/// Name of the test function itself.
+fn fn_name(name: &str) -> &str {
+    match name.rfind("::") {
+        Some(pos) => &name[pos + 2..],
+        None => name,
+    }
+}
+
+/// When we have "--exact" option and test filter is exactly our "parent" test (which is nota a real
+/// test, but a template for children tests), we adjust options a bit to run all children tests
+/// instead.
+fn adjust_for_test_name(opts: &mut crate::rustc_test::TestOpts, name: &str) {
+    let real_test_name = real_name(name);
+    if opts.filter_exact && opts.filter.as_ref().map_or(false, |s| s == real_test_name) {
+        opts.filter_exact = false;
+        opts.filter = Some(format!("{}::", real_test_name));
+    }
+}
+
+pub struct RegistrationNode {
+    pub descriptor: &'static dyn TestDescriptor,
+    pub next: Option<&'static RegistrationNode>,
+}
+
+static REGISTRY: AtomicPtr<RegistrationNode> = AtomicPtr::new(std::ptr::null_mut());
+
+static REGISTRY_USED: AtomicBool = AtomicBool::new(false);
+
+pub fn register(new: &mut RegistrationNode) {
+    // Install interceptor that will catch invocation of `test_main_static` so we can collect all
+    // the test cases annotated with `#[test]` (built-in tests). This is needed to support regular
+    // `#[test]` tests on stable channel where we don't have a way to override test runner.
+    crate::interceptor::install_interceptor();
+
+    let mut current = REGISTRY.load(Ordering::SeqCst);
+    loop {
+        // Link the node before publishing it, so the list is never seen cut off at the new node
+        new.next = unsafe { current.as_ref() };
+        match REGISTRY.compare_exchange(current, new, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => return,
+            Err(previous) => current = previous,
+        }
+    }
+}
+
+/// Test built at runtime, see [`register_suite`].
+pub enum SuiteTest {
+    Files(FilesTestDesc),
+    Data(DataTestDesc),
+}
+
+impl From<FilesTestDesc> for SuiteTest {
+    fn from(desc: FilesTestDesc) -> Self {
+        SuiteTest::Files(desc)
+    }
+}
+
+impl From<DataTestDesc> for SuiteTest {
+    fn from(desc: DataTestDesc) -> Self {
+        SuiteTest::Data(desc)
+    }
+}
+
+/// Register tests built at runtime via [`FilesTestDesc::builder`] and [`DataTestDesc::builder`]
+/// (for example, one test per plugin discovered at startup). Tests registered after the test
+/// runner has started are not run, so this should be called from the `main` function of the test
+/// harness (with `harness = false`) before [`runner`], or from a `#[ctor]` function.
+pub fn register_suite<T: Into<SuiteTest>>(tests: impl IntoIterator<Item = T>) {
+    for test in tests {
+        let descriptor: &'static dyn TestDescriptor = match test.into() {
+            SuiteTest::Files(desc) => Box::leak(Box::new(desc)),
+            SuiteTest::Data(desc) => Box::leak(Box::new(desc)),
+        };
+        register(Box::leak(Box::new(RegistrationNode {
+            descriptor,
+            next: None,
+        })));
+    }
+}
+
+/// Custom test runner. Expands test definitions given in the format our test framework understands
+/// ([DataTestDesc]) into definitions understood by Rust test framework ([TestDescAndFn] structs).
+/// For regular tests, mapping is one-to-one, for our data driven tests, we generate as many
+/// descriptors as test cases we discovered.
+///
+/// # Notes
+/// So, how does it work? We use a nightly-only feature of [custom_test_frameworks] that allows you
+/// to annotate arbitrary function, const or static with `#[test_case]`. Attribute. Then, Rust
+/// compiler would transform the code to pass all the discovered test cases as one big slice to the
+/// test runner.
+///
+/// However, we also want to support standard `#[test]` without disrupting them as much as possible.
+/// Internally, compiler would also desugar them to the `#[test_case]` attribute, but the type of
+/// the descriptor struct would be a predefined type of `test::TestDescAndFn`. This type, however,
+/// cannot represent all the additional information we need for our tests.
+///
+/// So we do a little trick here: we rely on the fact that compiler generates code exactly like in
+/// the following snippet:
+///
+/// ```ignore
+/// test::test_main_static(&[&__test_reexports::some::test1, &__test_reexports::some::test2])
+/// ```
+///
+/// Then, we implement `TestDescriptor` trait for the standard test descriptor struct, which would
+/// generate trait objects for these structs and pass a trait object instead. We do the same for
+/// our structs and our trait object allows us to return the reference wrapped into an enum
+/// distinguishing between three different test variants (standard tests, "files" tests and "data"
+/// tests).
+///
+/// [custom_test_frameworks]: https://github.com/rust-lang/rust/blob/master/src/doc/unstable-book/src/language-features/custom-test-frameworks.md
+/// See <https://blog.jrenner.net/rust/testing/2018/07/19/test-in-2018.html>
+#[doc(hidden)]
+pub fn runner(tests: &[&dyn TestDescriptor]) {
+    let mut args = std::env::args().collect::<Vec<_>>();
+    // Watch mode is handled by us, standard runner does not know this flag
+    let watch_mode = args.iter().any(|arg| arg == WATCH_FLAG);
+    args.retain(|arg| arg != WATCH_FLAG);
+    let dry_run = args.iter().any(|arg| arg == DRY_RUN_FLAG);
+    args.retain(|arg| arg != DRY_RUN_FLAG);
+    let validate = args.iter().any(|arg| arg == VALIDATE_FLAG);
+    args.retain(|arg| arg != VALIDATE_FLAG);
+    let repeat = take_repeat(&mut args).unwrap_or_else(|msg| panic!("{}", msg));
+    let sample = take_sample(&mut args).unwrap_or_else(|msg| panic!("{}", msg));
+    let max_duration = take_max_duration(&mut args).unwrap_or_else(|msg| panic!("{}", msg));
+    let only_changed = args.iter().any(|arg| arg == ONLY_CHANGED_FLAG);
+    args.retain(|arg| arg != ONLY_CHANGED_FLAG);
+    // Same for the custom flags of the test binary, see `CliFlags`
+    crate::cli::take_custom_flags(&mut args).unwrap_or_else(|msg| panic!("{}", msg));
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        crate::cli::print_help();
+    }
+    let parsed = crate::rustc_test::test::parse_opts(&args);
+    let mut opts = match parsed {
+        Some(Ok(o)) => o,
+        Some(Err(msg)) => panic!("{:?}", msg),
+        None => return,
+    };
+    opts.filter = opts.filter.take().map(normalize_name);
+    opts.skip = opts.skip.drain(..).map(normalize_name).collect();
+
+    // Quick way to run a single case, see `is_case_selected`
+    let case_filter = std::env::var(CASE_ENV_VAR)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .map(normalize_name);
+    let case_filter = case_filter.as_deref();
+
+    let mut descriptors = tests.to_vec();
+
+    // Indicate that we used our registry
+    REGISTRY_USED.store(true, Ordering::SeqCst);
+
+    // Gather tests registered via our registry (stable channel)
+    let mut current = unsafe { REGISTRY.load(Ordering::SeqCst).as_ref() };
+    while let Some(node) = current {
+        descriptors.push(node.descriptor);
+        current = node.next;
+    }
+
+    // Registration order depends on the linker, so order tests by name to get the same order on
+    // every platform (cases of each test are ordered when they are enumerated)
+    descriptors.sort_by_cached_key(|input| descriptor_name(*input));
+
+    let config = crate::config::load();
+    apply_reporter(&config, &args, &mut opts);
+
+    let mut rendered = render_all(&descriptors, case_filter, &config, &mut opts);
+
+    if validate {
+        let orphans = orphans_policy(&config) == OrphansPolicy::Fail;
+        validate_fixtures(&descriptors, &rendered, orphans);
+        return;
+    }
+    // Child process running a single case was validated by the parent already
+    let validate_data = config.validate_data || std::env::var_os(VALIDATE_DATA_ENV_VAR).is_some();
+    if validate_data && !opts.list && !crate::isolation::is_isolated() {
+        let problems = validate_data_files(&descriptors);
+        if !problems.is_empty() {
+            for problem in &problems {
+                println!("error: {}", problem);
+            }
+            println!();
+            panic!(
+                "data validation failed: found {} invalid test cases, no tests were run",
+                problems.len()
+            );
+        }
+    }
+    if repeat > 1 {
+        // Dynamic test functions could only be called once, so each repetition is rendered anew
+        let repetitions = (1..repeat)
+            .map(|_| render_all(&descriptors, case_filter, &config, &mut opts))
+            .collect();
+        repeat_cases(&mut rendered, repetitions, repeat);
+    }
+    check_duplicate_names(&rendered);
+    select_tagged(&config, &mut rendered, &opts);
+    select_entry_point(&mut opts, &mut rendered);
+    select_namespace(&mut opts, &mut rendered);
+
+    let manifest_path = std::env::var_os(MANIFEST_ENV_VAR)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| config.manifest.as_ref().map(PathBuf::from));
+    match manifest_path {
+        Some(path) => check_manifest(
+            &path,
+            &descriptors,
+            &mut rendered,
+            case_filter,
+            config.manifest_drift,
+            only_changed,
+        ),
+        None if only_changed => panic!(
+            "`{}` requires the fixture manifest (`manifest` option of the configuration or {})",
+            ONLY_CHANGED_FLAG, MANIFEST_ENV_VAR
+        ),
+        None => {}
+    }
+    if let Some(sample) = sample {
+        sample_cases(sample, &mut rendered, &opts);
+    }
+
+    let timings_path = std::env::var_os(TIMINGS_ENV_VAR)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| config.timings.as_ref().map(PathBuf::from))
+        .unwrap_or_else(|| output_dir().join(TIMINGS_FILE));
+    let mut timings = Timings::load(&timings_path).unwrap_or_else(|err| panic!("{}", err));
+    let shard = shard_spec();
+    if let Some(ref shard) = shard {
+        let names = rendered
+            .iter()
+            .map(|test| test.test.desc.name.to_string())
+            .collect::<Vec<_>>();
+        let (selected, expected) = shard.select(&names, &timings);
+        let total = rendered.len();
+        let mut selected = selected.into_iter();
+        rendered.retain(|_| selected.next().unwrap_or(false));
+        if !opts.list {
+            println!(
+                "shard {}/{}: running {} out of {} tests (expected duration {:.1}s)",
+                shard.index,
+                shard.count,
+                rendered.len(),
+                total,
+                expected
+            );
+        }
+    }
+
+    if let Some(case_filter) = case_filter {
+        if !opts.list {
+            println!(
+                "{}={}: running {} matching tests",
+                CASE_ENV_VAR,
+                case_filter,
+                rendered.len()
+            );
+        }
+    }
+
+    // Strict mode: fail the run if some fixtures are not used by any test
+    let orphans = orphans_policy(&config);
+    let orphans_test = "<orphaned fixtures>";
+    if orphans == OrphansPolicy::Fail && is_case_selected(case_filter, orphans_test, None) {
+        let orphans = find_orphans(&descriptors);
+        if !orphans.is_empty() {
+            let list = orphans
+                .iter()
+                .map(|path| format!("    {}", escape_path(path)))
+                .collect::<Vec<_>>();
+            let message = format!(
+                "found {} files not used by any test (orphaned fixtures):\n{}",
+                orphans.len(),
+                list.join("\n")
+            );
+            let test = failing_test(orphans_test.to_string(), "", message);
+            rendered.push(RenderedTest::new(
+                test,
+                String::from("orphaned fixtures check"),
+            ));
+        }
+    }
+
+    // Focus mode: if any test is marked with `#[only]`, run only these tests
+    let focused = rendered.iter().filter(|test| test.only).count();
+
+    let report_path = std::env::var_os(REPORT_ENV_VAR)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| config.report.as_ref().map(PathBuf::from));
+    // Outcomes of the cases are only known after the run
+    let report = report_path.map(|path| {
+        let cases = rendered
+            .iter()
+            .map(|test| {
+                let name = test.test.desc.name.to_string();
+                let status = case_status(&opts, test, focused > 0);
+                (name, test.fixture.clone(), status)
+            })
+            .collect::<Vec<_>>();
+        let unreferenced = find_orphans(&descriptors)
+            .iter()
+            .map(|path| escape_path(path).into_owned())
+            .collect::<Vec<_>>();
+        (path, cases, unreferenced)
+    });
+    let junit_path = std::env::var_os(JUNIT_ENV_VAR)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| config.junit.as_ref().map(PathBuf::from));
+    let junit = junit_path.map(|path| {
+        let cases = rendered
+            .iter()
+            .map(|test| {
+                let name = test.test.desc.name.to_string();
+                (name, case_status(&opts, test, focused > 0))
+            })
+            .collect::<Vec<_>>();
+        (path, cases)
+    });
+    if focused > 0 {
+        if !opts.list {
+            println!(
+                "focus mode: running only tests marked with `#[only]` ({} out of {})",
+                focused,
+                rendered.len()
+            );
+        }
+        rendered.retain(|test| test.only);
+    }
+
+    // Standard runner has no way to show why test is ignored, so we print reasons ourselves
+    let ignore_messages = if opts.list || opts.run_ignored != RunIgnored::No {
+        Vec::new()
+    } else {
+        rendered
+            .iter()
+            .filter(|test| test.test.desc.ignore)
+            .filter_map(|test| {
+                let name = test.test.desc.name.to_string();
+                test.ignore_message.map(|message| (name, message))
+            })
+            .filter(|(name, _)| is_selected(&opts, name))
+            .collect::<Vec<_>>()
+    };
+
+    // Long-running cases should not be started last, extending the run while other threads idle
+    if !opts.list && test_threads(&opts) > 1 {
+        schedule(&mut rendered, &timings);
+    }
+    order_by_dependencies(&mut rendered, &descriptors);
+    // Child process running a single case was selected by the parent already
+    let budget = max_duration.filter(|_| !opts.list && !crate::isolation::is_isolated());
+    if let Some(budget) = budget {
+        skip_for_time(&mut rendered, budget, &timings, &opts);
+    }
+    if dry_run {
+        print_dry_run(&rendered, &opts);
+        return;
+    }
+
+    let rendered_fixtures = if watch_mode {
+        watched_roots(&descriptors, &rendered)
+    } else {
+        Vec::new()
+    };
+
+    let groups = rendered
+        .iter()
+        .filter_map(|test| {
+            let function = test.function.clone()?;
+            let fixture = test.fixture.as_deref()?;
+            let status = case_status(&opts, test, false);
+            if status == CaseStatus::Filtered {
+                return None;
+            }
+            let dir = Path::new(location_file(fixture))
+                .parent()
+                .map(|dir| escape_path(dir).into_owned())
+                .filter(|dir| !dir.is_empty())
+                .unwrap_or_else(|| String::from("."));
+            // Cases given by the custom sources might not come from any file
+            let source = match test.root {
+                Some(ref root) => Some(CaseSource::Root(root.clone())),
+                None => test
+                    .inputs
+                    .first()
+                    .filter(|path| crate::listing::is_file(path))
+                    .map(|path| CaseSource::DataFile(escape_path(path).into_owned())),
+            };
+            Some(GroupedCase {
+                name: test.test.desc.name.to_string(),
+                function,
+                dir,
+                source,
+                namespace: test.namespace.clone(),
+                ignored: status == CaseStatus::Skipped,
+            })
+        })
+        .collect::<Vec<_>>();
+    let fixtures = rendered
+        .iter()
+        .filter(|test| test.function.is_some())
+        .map(|test| {
+            let inputs = test.inputs.iter();
+            let inputs = inputs.map(|path| escape_path(path).into_owned()).collect();
+            (test.test.desc.name.to_string(), inputs)
+        })
+        .collect::<Vec<_>>();
+
+    // Child process running a single case: outcome is handled by the parent
+    let isolated = crate::isolation::is_isolated();
+    if isolated {
+        rendered.iter_mut().for_each(|test| test.xfail = false);
+    }
+
+    // Run tests via standard runner!
+    install_panic_hook();
+    #[cfg(any(feature = "log", feature = "tracing"))]
+    crate::logs::install();
+    let metrics = metrics_sink(&config).filter(|_| !isolated);
+    let xpass = xpass_policy(&config);
+    let quarantine = load_quarantine(&config).filter(|_| !isolated);
+    let result = run_in_stages(rendered, &opts, |stage| {
+        let stage = record_outcomes(
+            stage.into_iter(),
+            metrics.as_ref(),
+            xpass,
+            quarantine.as_ref(),
+        );
+        crate::rustc_test::run_tests_console(&opts, stage)
+    });
+    crate::shared::teardown();
+    if isolated {
+        crate::isolation::report_failure(fixtures.iter().map(|(name, _)| name.clone()));
+    }
+
+    if let Some((path, cases, unreferenced)) = report {
+        if !opts.list && !isolated {
+            let cases = cases
+                .into_iter()
+                .map(|(name, fixture, status)| CaseReport {
+                    status: CaseOutcome::recorded(&name, status),
+                    output: crate::capture::failure(&name).and_then(|f| f.output),
+                    name,
+                    fixture,
+                })
+                .collect();
+            let report = CoverageReport {
+                cases,
+                unreferenced,
+            };
+            report.write(&path).unwrap_or_else(|e| {
+                panic!("cannot write coverage report '{}': {}", path.display(), e)
+            });
+            println!("coverage report is written to '{}'", path.display());
+        }
+    }
+
+    if let Some((path, cases)) = junit {
+        if !opts.list && !isolated {
+            let junit = JunitReport::collect(&binary_name(), &cases);
+            junit.write(&path).unwrap_or_else(|e| {
+                panic!("cannot write JUnit report '{}': {}", path.display(), e)
+            });
+            println!("JUnit report is written to '{}'", path.display());
+        }
+    }
+
+    let mut skipped_for_time = 0;
+    if !opts.list && !isolated {
+        crate::xfail::print_summary();
+        crate::flaky::print_summary();
+        skipped_for_time = crate::budget::print_summary();
+        crate::dependencies::print_summary();
+        if let Some(ref quarantine) = quarantine {
+            quarantine.print_summary();
+        }
+        print_grouped_summary(&groups);
+        let manifest = FailureManifest::collect(&fixtures);
+        let path = output_dir().join(FAILURES_FILE);
+        manifest.write(&path).unwrap_or_else(|e| {
+            panic!("cannot write failure manifest '{}': {}", path.display(), e)
+        });
+        if !manifest.failures.is_empty() {
+            println!("failure manifest is written to '{}'", path.display());
+        }
+        // Shards of the run must see the same timings to compute the same assignment, so only the
+        // runs of the whole test suite update them
+        if shard.is_none() {
+            timings.update(crate::capture::durations());
+            timings.write(&timings_path).unwrap_or_else(|e| {
+                panic!("cannot write timings '{}': {}", timings_path.display(), e)
+            });
+        }
+    }
+
+    if !ignore_messages.is_empty() {
+        println!("ignored tests:");
+        for (name, message) in ignore_messages {
+            println!("    {}: {}", name, message);
+        }
+        println!();
+    }
+
+    if watch_mode && !opts.list {
+        watch_and_rerun(
+            &descriptors,
+            case_filter,
+            &config,
+            &mut opts,
+            &rendered_fixtures,
+            metrics.as_ref(),
+            quarantine.as_ref(),
+        );
+    }
+
+    match result {
+        // Focus markers should never be committed, so focused run always fails
+        Ok(true) if focused > 0 && config.focus == FocusPolicy::Fail => panic!(
+            "focused tests passed, but the run is failed as some tests are marked with `#[only]`; \
+             remove `#[only]` to run all tests"
+        ),
+        // Passed, but not every test case was run, which should not be mistaken for a full pass
+        Ok(true) if skipped_for_time > 0 => {
+            eprintln!(
+                "time budget is spent: {} test cases were skipped for time",
+                skipped_for_time
+            );
+            std::process::exit(crate::budget::SKIPPED_FOR_TIME_EXIT_CODE);
+        }
+        Ok(true) => {}
+        Ok(false) => panic!("Some tests failed"),
+        Err(e) => panic!("io error when running tests: {:?}", e),
+    }
+}
+
+/// Render test cases of all test descriptors.
+fn render_all(
+    descriptors: &[&dyn TestDescriptor],
+    case_filter: Option<&str>,
+    config: &RunnerConfig,
+    opts: &mut TestOpts,
+) -> Vec<RenderedTest> {
+    crate::listing::reset();
+    let mut rendered = Vec::new();
+    let keep = keep_policy(config);
+    let timeout = default_timeout(config);
+    for &input in descriptors {
+        render_test_descriptor(
+            input,
+            case_filter,
+            &config.configurations,
+            keep,
+            timeout,
+            opts,
+            &mut rendered,
+        );
+    }
+    crate::listing::finish();
+    rendered
+}
+
+/// Record outcomes and capture the output of the test cases run via dynamic test functions (which
+/// are the ones generated by our runner), named as they are going to be reported. Metrics of the
+/// cases are emitted to the `metrics` sink, if given. Failures of the cases under `quarantine` do not
+/// fail them.
+fn record_outcomes(
+    tests: impl Iterator<Item = RenderedTest>,
+    metrics: Option<&Arc<MetricsSink>>,
+    xpass: XpassPolicy,
+    quarantine: Option<&Quarantine>,
+) -> Vec<TestDescAndFn> {
+    tests
+        .map(|rendered| {
+            let mut test = rendered.test;
+            let metrics = match (metrics, rendered.function) {
+                (Some(sink), Some(function)) => Some((sink.clone(), function, rendered.fixture)),
+                _ => None,
+            };
+            test.testfn = match test.testfn {
+                TestFn::DynTestFn(mut testfn) => {
+                    let name = test.desc.name.to_string();
+                    if rendered.xfail {
+                        let name = name.clone();
+                        testfn = Box::new(move || {
+                            crate::xfail::run_expecting_failure(&name, xpass, testfn)
+                        });
+                    }
+                    if quarantine.map_or(false, |quarantine| quarantine.contains(&name)) {
+                        let name = name.clone();
+                        testfn = Box::new(move || Quarantine::run(&name, testfn));
+                    }
+                    TestFn::DynTestFn(Box::new(move || {
+                        let _metrics = metrics.map(|(sink, function, fixture)| {
+                            CaseMetrics::start(sink, function, fixture.unwrap_or_default())
+                        });
+                        crate::capture::record_case(&name, testfn)
+                    }))
+                }
+                testfn => testfn,
+            };
+            test
+        })
+        .collect()
+}
+
+/// Test case of `#[datatest::files]` or `#[datatest::data]` test, for the grouped summary.
+struct GroupedCase {
+    name: String,
+    function: String,
+    /// Directory of the fixture (test file or data file).
+    dir: String,
+    source: Option<CaseSource>,
+    /// Namespace of the test case (`{namespace}` placeholder), counted in every enclosing namespace.
+    namespace: Option<String>,
+    /// Test case is not run, as it is ignored.
+    ignored: bool,
+}
+
+/// Where the test case comes from.
+enum CaseSource {
+    /// Data file of the data-driven test.
+    DataFile(String),
+    /// Root directory of the files-driven test.
+    Root(String),
+}
+
+/// Numbers of test cases in the group of the grouped summary and their total duration.
+#[derive(Default)]
+struct GroupCounts {
+    passed: usize,
+    failed: usize,
+    ignored: usize,
+    duration: Duration,
+}
+
+/// Print the numbers of passed, failed and ignored test cases (and their total duration) grouped by
+/// the test function, by the directory of the fixture, by the source of the test cases (data file
+/// or root directory) and by the namespace of the test names, so it is easy to see which area of
+/// the test corpus regressed.
+fn print_grouped_summary(groups: &[GroupedCase]) {
+    let mut by_function = BTreeMap::<&str, GroupCounts>::new();
+    let mut by_dir = BTreeMap::<&str, GroupCounts>::new();
+    let mut by_data_file = BTreeMap::<&str, GroupCounts>::new();
+    let mut by_root = BTreeMap::<&str, GroupCounts>::new();
+    let mut by_namespace = BTreeMap::<&str, GroupCounts>::new();
+    for case in groups {
+        // Ignored cases are counted, cases which did not run (or were skipped) are not
+        let outcome = if case.ignored {
+            None
+        } else {
+            match crate::capture::passed(&case.name) {
+                Some(passed) => Some((
+                    passed,
+                    crate::capture::duration(&case.name).unwrap_or_default(),
+                )),
+                None => continue,
+            }
+        };
+        let add = |counts: &mut GroupCounts| match outcome {
+            None => counts.ignored += 1,
+            Some((passed, duration)) => {
+                if passed {
+                    counts.passed += 1;
+                } else {
+                    counts.failed += 1;
+                }
+                counts.duration += duration;
+            }
+        };
+        add(by_function.entry(&case.function).or_default());
+        add(by_dir.entry(&case.dir).or_default());
+        match case.source {
+            Some(CaseSource::DataFile(ref path)) => add(by_data_file.entry(path).or_default()),
+            Some(CaseSource::Root(ref root)) => add(by_root.entry(root).or_default()),
+            None => {}
+        }
+        // Case is counted in its namespace and in every namespace enclosing it
+        if let Some(ref namespace) = case.namespace {
+            let ends = namespace.match_indices("::").map(|(pos, _)| pos);
+            for end in ends.chain(Some(namespace.len())) {
+                add(by_namespace.entry(&namespace[..end]).or_default());
+            }
+        }
+    }
+    if by_function.is_empty() {
+        return;
+    }
+    for (title, groups) in [
+        ("test function", by_function),
+        ("fixture directory", by_dir),
+        ("data file", by_data_file),
+        ("root directory", by_root),
+        ("namespace", by_namespace),
+    ] {
+        if groups.is_empty() {
+            continue;
+        }
+        println!("summary by {}:", title);
+        for (group, counts) in groups {
+            let marker = if counts.failed > 0 { "FAILED" } else { "ok" };
+            println!(
+                "    {:<6} {}: {} passed; {} failed; {} ignored; {:.2}s",
+                marker,
+                group,
+                counts.passed,
+                counts.failed,
+                counts.ignored,
+                counts.duration.as_secs_f64()
+            );
+        }
+        println!();
+    }
+}
+
+/// Environment variable giving the address of the metrics sink (overrides the `metrics` option of
+/// the configuration).
+const METRICS_ENV_VAR: &str = "DATATEST_METRICS";
+
+/// Sink for the metrics of the test cases, if configured.
+fn metrics_sink(config: &RunnerConfig) -> Option<Arc<MetricsSink>> {
+    let address = std::env::var(METRICS_ENV_VAR)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .or_else(|| config.metrics.clone())?;
+    let sink = MetricsSink::connect(&address)
+        .unwrap_or_else(|err| panic!("cannot set up metrics sink: {}", err));
+    Some(Arc::new(sink))
 }
 
-impl TestDescriptor for DataTestDesc {
-    fn as_datatest_desc(&self) -> DatatestTestDesc {
-        DatatestTestDesc::DataTest(self)
+/// Environment variable overriding the `xpass` policy of the configuration (see [`XpassPolicy`]).
+const XPASS_ENV_VAR: &str = "DATATEST_XPASS";
+
+/// What to do with expected failures which pass.
+fn xpass_policy(config: &RunnerConfig) -> XpassPolicy {
+    match std::env::var(XPASS_ENV_VAR) {
+        Ok(ref value) if value == "fail" => XpassPolicy::Fail,
+        Ok(ref value) if value == "allow" => XpassPolicy::Allow,
+        Ok(value) => panic!(
+            "invalid value '{}' of {}, expected 'fail' or 'allow'",
+            value, XPASS_ENV_VAR
+        ),
+        Err(_) => config.xpass,
     }
 }
 
-impl TestDescriptor for RegularTestDesc {
-    fn as_datatest_desc(&self) -> DatatestTestDesc {
-        DatatestTestDesc::RegularTest(self)
+/// Environment variable overriding the `keep` policy of the configuration (see [`KeepPolicy`]).
+const KEEP_ENV_VAR: &str = "DATATEST_KEEP";
+
+/// What to do with the sandboxes of the test cases once they finish.
+fn keep_policy(config: &RunnerConfig) -> KeepPolicy {
+    match std::env::var(KEEP_ENV_VAR) {
+        Ok(ref value) if value == "never" => KeepPolicy::Never,
+        Ok(ref value) if value == "on-failure" => KeepPolicy::OnFailure,
+        Ok(ref value) if value == "always" => KeepPolicy::Always,
+        Ok(value) => panic!(
+            "invalid value '{}' of {}, expected 'never', 'on-failure' or 'always'",
+            value, KEEP_ENV_VAR
+        ),
+        Err(_) => config.keep,
     }
 }
 
-#[doc(hidden)]
-pub enum DatatestTestDesc<'a> {
-    Test(&'a TestDescAndFn),
-    FilesTest(&'a FilesTestDesc),
-    DataTest(&'a DataTestDesc),
-    RegularTest(&'a RegularTestDesc),
+/// Timeout of the test cases which do not have their own one (`timeout` option of the
+/// configuration).
+fn default_timeout(config: &RunnerConfig) -> Option<Duration> {
+    config.timeout.as_ref().map(|timeout| {
+        crate::duration::parse(timeout)
+            .unwrap_or_else(|err| panic!("invalid `timeout` option of the configuration: {}", err))
+    })
 }
 
-/// Helper function to iterate through all the files in the given directory, skipping hidden files,
-/// and return an iterator of their paths.
-fn iterate_directory(path: &Path) -> impl Iterator<Item = PathBuf> {
-    walkdir::WalkDir::new(path)
-        .follow_links(true)
-        .into_iter()
-        .map(Result::unwrap)
-        .filter(|entry| {
-            entry.file_type().is_file()
-                && entry
-                    .file_name()
-                    .to_str()
-                    .map_or(false, |s| !s.starts_with('.')) // Skip hidden files
-        })
-        .map(|entry| entry.path().to_path_buf())
+/// Output format given by the `reporter` option of the configuration, unless the format is given
+/// in the command line (`--format`, `-q` or `--quiet`).
+fn apply_reporter(config: &RunnerConfig, args: &[String], opts: &mut TestOpts) {
+    let given = args.iter().any(|arg| {
+        arg == "-q" || arg == "--quiet" || arg == "--format" || arg.starts_with("--format=")
+    });
+    if given {
+        return;
+    }
+    if let Some(reporter) = config.reporter {
+        opts.format = match reporter {
+            Reporter::Pretty => OutputFormat::Pretty,
+            Reporter::Terse => OutputFormat::Terse,
+            Reporter::Json => OutputFormat::Json,
+        };
+    }
 }
 
-struct FilesBenchFn(fn(&mut Bencher, &[PathBuf]), Vec<PathBuf>);
+/// Environment variable selecting the tests by the tag defined in the `tags` section of the
+/// configuration.
+const TAG_ENV_VAR: &str = "DATATEST_TAG";
 
-impl rustc_test::TDynBenchFn for FilesBenchFn {
-    fn run(&self, harness: &mut Bencher) {
-        (self.0)(harness, &self.1)
+/// Keep only the tests tagged with the tag given by `DATATEST_TAG`: tests which names contain any
+/// of the patterns of the tag.
+fn select_tagged(config: &RunnerConfig, rendered: &mut Vec<RenderedTest>, opts: &TestOpts) {
+    let tag = match std::env::var(TAG_ENV_VAR) {
+        Ok(tag) if !tag.is_empty() => tag,
+        _ => return,
+    };
+    let patterns = config.tags.get(&tag).unwrap_or_else(|| {
+        let known = config.tags.keys().cloned().collect::<Vec<_>>();
+        panic!(
+            "unknown tag '{}' given by {} (tags of the configuration: {})",
+            tag,
+            TAG_ENV_VAR,
+            known.join(", ")
+        )
+    });
+    rendered.retain(|test| {
+        let name = test.test.desc.name.to_string();
+        patterns
+            .iter()
+            .any(|pattern| name.contains(pattern.as_str()))
+    });
+    if !opts.list {
+        println!(
+            "{}={}: running {} tagged tests",
+            TAG_ENV_VAR,
+            tag,
+            rendered.len()
+        );
     }
 }
 
-/// Generate standard test descriptors ([`test::TestDescAndFn`]) from the descriptor of
-/// `#[datatest::files(..)]`.
-///
-/// Scans all files in a given directory, finds matching ones and generates a test descriptor for
-/// each of them.
-fn render_files_test(desc: &FilesTestDesc, rendered: &mut Vec<TestDescAndFn>) {
-    let root = Path::new(desc.root).to_path_buf();
+/// Environment variable giving the path of the quarantine file (overrides the `quarantine` option
+/// of the configuration).
+const QUARANTINE_ENV_VAR: &str = "DATATEST_QUARANTINE";
 
-    let pattern = desc.params[desc.pattern];
-    let re = regex::Regex::new(pattern)
-        .unwrap_or_else(|_| panic!("invalid regular expression: '{}'", pattern));
+/// Cases under quarantine, if the quarantine file is configured.
+fn load_quarantine(config: &RunnerConfig) -> Option<Quarantine> {
+    let path = std::env::var_os(QUARANTINE_ENV_VAR)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| config.quarantine.as_ref().map(PathBuf::from))?;
+    let quarantine = Quarantine::load(&path).unwrap_or_else(|err| panic!("{}", err));
+    Some(quarantine)
+}
 
-    let mut found = false;
-    for path in iterate_directory(&root) {
-        let input_path = path.to_string_lossy();
-        if re.is_match(&input_path) {
-            // Generate list of paths to pass to the test function. We generate a `PathBuf` for each
-            // argument of the test function and pass them to the trampoline function in a slice.
-            // See `datatest-derive` proc macro sources for more details.
-            let mut paths = Vec::with_capacity(desc.params.len());
-
-            let path_str = path.to_string_lossy();
-            for (idx, param) in desc.params.iter().enumerate() {
-                if idx == desc.pattern {
-                    // Pattern path
-                    paths.push(path.to_path_buf());
-                } else {
-                    let rendered_path = re.replace_all(&path_str, *param);
-                    let rendered_path = Path::new(rendered_path.as_ref()).to_path_buf();
-                    paths.push(rendered_path);
-                }
-            }
+/// Command line flag enabling watch mode.
+const WATCH_FLAG: &str = "--watch";
 
-            let test_name = derive_test_name(&root, &path, desc.name);
-            let ignore = desc.ignore
-                || desc
-                    .ignorefn
-                    .map_or(false, |ignore_func| ignore_func(&path));
+/// Command line flag enumerating the test cases without running them.
+const DRY_RUN_FLAG: &str = "--dry-run";
 
-            let testfn = match desc.testfn {
-                FilesTestFn::TestFn(testfn) => TestFn::DynTestFn(Box::new(move || testfn(&paths))),
-                FilesTestFn::BenchFn(benchfn) => {
-                    TestFn::DynBenchFn(Box::new(FilesBenchFn(benchfn, paths)))
-                }
-            };
+/// Command line flag running each test case the given number of times (`--repeat N`).
+const REPEAT_FLAG: &str = "--repeat";
 
-            // Generate a standard test descriptor
-            let desc = TestDescAndFn {
-                desc: TestDesc {
-                    name: TestName::DynTestName(test_name),
-                    ignore,
-                    should_panic: ShouldPanic::No,
-                    // Cannot be used on stable: https://github.com/rust-lang/rust/issues/46488
-                    allow_fail: false,
-                    test_type: crate::test_type(desc.source_file),
-                },
-                testfn,
-            };
+/// Environment variable giving the number of repetitions of each test case (same as `--repeat`).
+const REPEAT_ENV_VAR: &str = "DATATEST_REPEAT";
 
-            rendered.push(desc);
-            found = true;
+/// Remove `<flag> <value>` (or `<flag>=<value>`) from the arguments and return the value (falling
+/// back to the environment variable if the flag is not given).
+fn take_flag_value(
+    args: &mut Vec<String>,
+    flag: &str,
+    env_var: &str,
+) -> Result<Option<String>, String> {
+    let mut value = std::env::var(env_var)
+        .ok()
+        .filter(|value| !value.is_empty());
+    let mut remaining = Vec::with_capacity(args.len());
+    let mut iter = std::mem::take(args).into_iter();
+    while let Some(arg) = iter.next() {
+        if arg == flag {
+            value = Some(
+                iter.next()
+                    .ok_or_else(|| format!("flag `{}` requires a value", flag))?,
+            );
+        } else if let Some(rest) = arg
+            .strip_prefix(flag)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            value = Some(rest.to_string());
+        } else {
+            remaining.push(arg);
         }
     }
+    *args = remaining;
+    Ok(value)
+}
 
-    // We want to avoid silent fails due to typos in regexp!
-    if !found {
-        panic!(
-            "no test cases found for test '{}'. Scanned directory: '{}' with pattern '{}'",
-            desc.name, desc.root, pattern,
-        );
+/// Remove `--repeat N` (or `--repeat=N`) from the arguments and return the number of repetitions
+/// of each test case (falling back to `DATATEST_REPEAT`, one if neither is given).
+fn take_repeat(args: &mut Vec<String>) -> Result<usize, String> {
+    match take_flag_value(args, REPEAT_FLAG, REPEAT_ENV_VAR)? {
+        Some(value) => match value.parse::<usize>() {
+            Ok(count) if count > 0 => Ok(count),
+            _ => Err(format!(
+                "invalid number of repetitions '{}', expected a positive integer",
+                value
+            )),
+        },
+        None => Ok(1),
     }
 }
 
-fn render_data_test(desc: &DataTestDesc, rendered: &mut Vec<TestDescAndFn>) {
-    let prefix_name = real_name(&desc.name);
+/// Command line flag running a random sample of the test cases (`--sample 5%`).
+const SAMPLE_FLAG: &str = "--sample";
 
-    let cases = (desc.describefn)();
-    for case in cases {
-        // FIXME: use name provided in `case`...
+/// Environment variable giving the sample of the test cases to run (same as `--sample`).
+const SAMPLE_ENV_VAR: &str = "DATATEST_SAMPLE";
 
-        let case_name = if let Some(n) = case.name {
-            format!("{}::{} ({})", prefix_name, n, case.location)
-        } else {
-            format!("{}::{}", prefix_name, case.location)
-        };
+/// Environment variable giving the seed selecting the sampled test cases (random if not given).
+const SAMPLE_SEED_ENV_VAR: &str = "DATATEST_SAMPLE_SEED";
 
-        let testfn = match case.case {
-            DataTestFn::TestFn(testfn) => TestFn::DynTestFn(testfn),
-            DataTestFn::BenchFn(benchfn) => TestFn::DynBenchFn(benchfn),
-        };
+/// Remove `--sample 5%` (or `--sample=5%`) from the arguments and return the sample to run
+/// (falling back to `DATATEST_SAMPLE`, `None` if neither is given).
+fn take_sample(args: &mut Vec<String>) -> Result<Option<Sample>, String> {
+    let fraction = match take_flag_value(args, SAMPLE_FLAG, SAMPLE_ENV_VAR)? {
+        Some(value) => Sample::parse_fraction(&value)?,
+        None => return Ok(None),
+    };
+    let seed = match std::env::var(SAMPLE_SEED_ENV_VAR) {
+        Ok(ref value) if !value.is_empty() => crate::rng::parse_seed(value)
+            .map_err(|err| format!("invalid {} value '{}': {}", SAMPLE_SEED_ENV_VAR, value, err))?,
+        _ => crate::sample::random_seed(),
+    };
+    Ok(Some(Sample { fraction, seed }))
+}
 
-        // Generate a standard test descriptor
-        let desc = TestDescAndFn {
-            desc: TestDesc {
-                name: TestName::DynTestName(case_name),
-                ignore: desc.ignore,
-                should_panic: ShouldPanic::No,
-                allow_fail: false,
-                test_type: crate::test_type(desc.source_file),
-            },
-            testfn,
-        };
+/// Command line flag giving the time budget of the whole run (`--max-duration 10m`).
+const MAX_DURATION_FLAG: &str = "--max-duration";
 
-        rendered.push(desc);
+/// Environment variable giving the time budget of the whole run (same as `--max-duration`).
+const MAX_DURATION_ENV_VAR: &str = "DATATEST_MAX_DURATION";
+
+/// Remove `--max-duration 10m` (or `--max-duration=10m`) from the arguments and return the time
+/// budget of the run (falling back to `DATATEST_MAX_DURATION`, `None` if neither is given).
+fn take_max_duration(args: &mut Vec<String>) -> Result<Option<Duration>, String> {
+    match take_flag_value(args, MAX_DURATION_FLAG, MAX_DURATION_ENV_VAR)? {
+        Some(value) => crate::duration::parse(&value).map(Some),
+        None => Ok(None),
     }
 }
 
-/// We need to build our own slice of test descriptors to pass to `test::test_main`. We cannot
-/// clone `TestFn`, so we do it via matching on variants. Not sure how to handle `Dynamic*` variants,
-/// but we seem not to get them here anyway?.
-fn clone_testfn(testfn: &TestFn) -> TestFn {
-    match testfn {
-        TestFn::StaticTestFn(func) => TestFn::StaticTestFn(*func),
-        TestFn::StaticBenchFn(bench) => TestFn::StaticBenchFn(*bench),
-        _ => unimplemented!("only static functions are supported"),
+/// Keep only the sampled test cases. Regular tests and tests reporting errors (like failed
+/// enumeration of the test cases) are always kept.
+fn sample_cases(sample: Sample, rendered: &mut Vec<RenderedTest>, opts: &TestOpts) {
+    // Cases excluded by the command line filters are not run anyway, so they are not sampled
+    let is_case = |test: &RenderedTest| {
+        test.function.is_some()
+            && test.error.is_none()
+            && is_selected(opts, &test.test.desc.name.to_string())
+    };
+    let names = rendered
+        .iter()
+        .filter(|test| is_case(test))
+        .map(|test| test.test.desc.name.to_string())
+        .collect::<Vec<_>>();
+    let total = names.len();
+    let mut selected = sample.select(&names).into_iter();
+    rendered.retain(|test| !is_case(test) || selected.next().unwrap_or(false));
+    if !opts.list {
+        let sampled = rendered.iter().filter(|test| is_case(test)).count();
+        println!(
+            "sample: running {} out of {} test cases (seed {}, rerun with {}={})",
+            sampled, total, sample.seed, SAMPLE_SEED_ENV_VAR, sample.seed
+        );
     }
 }
 
-/// Strip crate name. We use `module_path!` macro to generate this name, which includes crate name.
-/// However, standard test library does not include crate name into a test name.
-fn real_name(name: &str) -> &str {
-    match name.find("::") {
-        Some(pos) => &name[pos + 2..],
-        None => name,
+/// Flake detection: make each test case run its own function along with the functions of the same
+/// case rendered for the other repetitions (see [`crate::flaky::run_repeated`]). Tests expected to
+/// panic are run once, as the outcomes of their repetitions could not be told apart.
+fn repeat_cases(rendered: &mut [RenderedTest], repetitions: Vec<Vec<RenderedTest>>, repeat: usize) {
+    let mut others: HashMap<String, Vec<Box<dyn FnOnce() + Send>>> = HashMap::new();
+    for test in repetitions.into_iter().flatten() {
+        if let TestFn::DynTestFn(testfn) = test.test.testfn {
+            let name = test.test.desc.name.to_string();
+            others.entry(name).or_default().push(testfn);
+        }
+    }
+    for test in rendered {
+        if test.error.is_some() || test.test.desc.should_panic != ShouldPanic::No {
+            continue;
+        }
+        let name = test.test.desc.name.to_string();
+        let repetitions: Vec<Box<dyn FnOnce() + Send>> = match test.test.testfn {
+            TestFn::StaticTestFn(testfn) => (0..repeat)
+                .map(|_| Box::new(testfn) as Box<dyn FnOnce() + Send>)
+                .collect(),
+            TestFn::DynTestFn(_) => match others.remove(&name) {
+                Some(others) => others,
+                None => continue,
+            },
+            _ => continue,
+        };
+        let testfn = std::mem::replace(&mut test.test.testfn, TestFn::DynTestFn(Box::new(|| {})));
+        let repetitions = match testfn {
+            TestFn::DynTestFn(testfn) => std::iter::once(testfn).chain(repetitions).collect(),
+            _ => repetitions,
+        };
+        test.test.testfn = TestFn::DynTestFn(Box::new(move || {
+            crate::flaky::run_repeated(&name, repetitions)
+        }));
     }
 }
 
-/// When we have "--exact" option and test filter is exactly our "parent" test (which is nota a real
-/// test, but a template for children tests), we adjust options a bit to run all children tests
-/// instead.
-fn adjust_for_test_name(opts: &mut crate::rustc_test::TestOpts, name: &str) {
-    let real_test_name = real_name(name);
-    if opts.filter_exact && opts.filter.as_ref().map_or(false, |s| s == real_test_name) {
-        opts.filter_exact = false;
-        opts.filter = Some(format!("{}::", real_test_name));
+/// Command line flag checking the fixtures without running the tests.
+const VALIDATE_FLAG: &str = "--validate";
+
+/// Validation mode: report every problem with the fixtures found while enumerating the test cases
+/// (data files which cannot be read or parsed into the case type, missing root directories,
+/// patterns matching nothing), every invalid case of the data files (see [`validate_data_files`])
+/// and test cases with the same name. Files not used by any test are
+/// only reported with the `fail` policy of the orphaned fixtures, as root directories could be
+/// shared with the tests of other test binaries. Fails if any problem is found. Nothing is run, so
+/// it is fast enough for a pre-commit hook.
+fn validate_fixtures(
+    descriptors: &[&dyn TestDescriptor],
+    rendered: &[RenderedTest],
+    orphans: bool,
+) {
+    let mut problems = rendered
+        .iter()
+        .filter_map(|test| {
+            let error = test.error.as_ref()?;
+            Some(format!("{}: {}", test.origin, error))
+        })
+        .collect::<Vec<_>>();
+    if orphans {
+        problems.extend(
+            find_orphans(descriptors)
+                .iter()
+                .map(|path| format!("file '{}' is not used by any test", escape_path(path))),
+        );
+    }
+    problems.extend(validate_data_files(descriptors));
+    problems.extend(
+        find_duplicate_names(rendered)
+            .into_iter()
+            .map(|duplicate| format!("test cases with duplicate names:\n{}", duplicate)),
+    );
+
+    let cases = rendered.iter().filter(|test| test.error.is_none()).count();
+    if !problems.is_empty() {
+        for problem in &problems {
+            println!("error: {}", problem);
+        }
+        println!();
+        panic!(
+            "fixture validation failed: found {} problems ({} test cases checked)",
+            problems.len(),
+            cases
+        );
     }
+    println!(
+        "fixture validation: {} test cases checked, no problems found",
+        cases
+    );
 }
 
-pub struct RegistrationNode {
-    pub descriptor: &'static dyn TestDescriptor,
-    pub next: Option<&'static RegistrationNode>,
+/// Environment variable enabling the validation pre-pass of the data files (same as the
+/// `validate_data` option of the configuration).
+const VALIDATE_DATA_ENV_VAR: &str = "DATATEST_VALIDATE_DATA";
+
+/// Validation pre-pass: check every case of the data files of `#[datatest::data]` tests against
+/// the test case type and the JSON Schema given by the `schema` option, so all invalid cases are
+/// reported at once, before any test runs. Tests with custom sources are not checked.
+fn validate_data_files(descriptors: &[&dyn TestDescriptor]) -> Vec<String> {
+    descriptors
+        .iter()
+        .filter_map(|input| match input.as_datatest_desc() {
+            DatatestTestDesc::DataTest(desc) => Some((desc.name, desc.validatefn?)),
+            _ => None,
+        })
+        .flat_map(|(name, validatefn)| {
+            let name = real_name(name);
+            validatefn()
+                .into_iter()
+                .map(move |problem| format!("test '{}': {}", name, problem))
+        })
+        .collect()
 }
 
-static REGISTRY: AtomicPtr<RegistrationNode> = AtomicPtr::new(std::ptr::null_mut());
+/// Dry run: print the selected test cases in the order they would run, along with the files each
+/// case reads (test files of the files-driven tests and data files of the data-driven tests).
+fn print_dry_run(rendered: &[RenderedTest], opts: &TestOpts) {
+    let mut count = 0;
+    for test in rendered {
+        // Focus mode is already applied to the list of tests
+        let status = case_status(opts, test, false);
+        if status == CaseStatus::Filtered {
+            continue;
+        }
+        count += 1;
+        let name = test.test.desc.name.to_string();
+        let mut markers = Vec::new();
+        if status == CaseStatus::Skipped {
+            markers.push("ignored");
+        }
+        if test.xfail {
+            markers.push("xfail");
+        }
+        match markers.is_empty() {
+            true => println!("{}", name),
+            false => println!("{} [{}]", name, markers.join(", ")),
+        }
+        if let Some(ref fixture) = test.fixture {
+            println!("    location: {}", fixture);
+        }
+        for input in &test.inputs {
+            println!("    input: {}", escape_path(input));
+        }
+    }
+    println!();
+    println!("dry run: {} test cases found, none were run", count);
+}
 
-static REGISTRY_USED: AtomicBool = AtomicBool::new(false);
+/// Files and directories watched in watch mode: root directories of files-driven tests and files
+/// the data-driven test cases come from.
+fn watched_roots(descriptors: &[&dyn TestDescriptor], rendered: &[RenderedTest]) -> Vec<PathBuf> {
+    let mut roots = descriptors
+        .iter()
+        .filter_map(|input| match input.as_datatest_desc() {
+            DatatestTestDesc::FilesTest(files) => Some(PathBuf::from(files.root)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    roots.extend(
+        rendered
+            .iter()
+            .flat_map(|test| &test.inputs)
+            .filter(|path| {
+                crate::listing::is_file(path) && !roots.iter().any(|root| path.starts_with(root))
+            })
+            .cloned()
+            .collect::<Vec<_>>(),
+    );
+    roots.sort();
+    roots.dedup();
+    roots
+}
 
-pub fn register(new: &mut RegistrationNode) {
-    // Install interceptor that will catch invocation of `test_main_static` so we can collect all
-    // the test cases annotated with `#[test]` (built-in tests). This is needed to support regular
-    // `#[test]` tests on stable channel where we don't have a way to override test runner.
-    crate::interceptor::install_interceptor();
+/// Watch mode: once fixtures change, re-enumerate the test cases and re-run the ones which depend
+/// on the changed files. Runs until the process is interrupted.
+fn watch_and_rerun(
+    descriptors: &[&dyn TestDescriptor],
+    case_filter: Option<&str>,
+    config: &RunnerConfig,
+    opts: &mut TestOpts,
+    roots: &[PathBuf],
+    metrics: Option<&Arc<MetricsSink>>,
+    quarantine: Option<&Quarantine>,
+) {
+    println!("watch mode: watching {} paths for changes", roots.len());
+    crate::watch(roots, |changed| {
+        let affected = render_all(descriptors, case_filter, config, opts)
+            .into_iter()
+            .filter(|test| test.inputs.iter().any(|input| changed.contains(input)));
+        let affected = record_outcomes(affected, metrics, xpass_policy(config), quarantine);
+        println!(
+            "watch mode: {} files changed, re-running {} affected tests",
+            changed.len(),
+            affected.len()
+        );
+        if !affected.is_empty() {
+            if let Err(err) = crate::rustc_test::run_tests_console(opts, affected) {
+                eprintln!("io error when running tests: {:?}", err);
+            }
+            crate::shared::teardown();
+        }
+        true
+    });
+}
 
-    let reg = &REGISTRY;
-    let mut current = reg.load(Ordering::SeqCst);
-    loop {
-        let previous = reg.compare_and_swap(current, new, Ordering::SeqCst);
-        if previous == current {
-            new.next = unsafe { previous.as_ref() };
-            return;
+/// Make sure every generated test has a unique name. Otherwise, tests with the same name would be
+/// indistinguishable in the output and filters, which makes it easy to miss some of them.
+fn check_duplicate_names(rendered: &[RenderedTest]) {
+    let duplicates = find_duplicate_names(rendered);
+    if !duplicates.is_empty() {
+        panic!(
+            "found test cases with duplicate names:\n{}",
+            duplicates.join("\n")
+        );
+    }
+}
+
+/// Test names shared by multiple test cases, along with the origins of the first two cases.
+fn find_duplicate_names(rendered: &[RenderedTest]) -> Vec<String> {
+    let mut seen: HashMap<String, &RenderedTest> = HashMap::with_capacity(rendered.len());
+    let mut duplicates = Vec::new();
+    for test in rendered {
+        let name = test.test.desc.name.to_string();
+        if let Some(first) = seen.get(&name) {
+            duplicates.push(format!(
+                "    '{}':\n        {}\n        {}",
+                name, first.origin, test.origin
+            ));
         } else {
-            current = previous;
+            seen.insert(name, test);
         }
     }
+    duplicates
 }
 
-/// Custom test runner. Expands test definitions given in the format our test framework understands
-/// ([DataTestDesc]) into definitions understood by Rust test framework ([TestDescAndFn] structs).
-/// For regular tests, mapping is one-to-one, for our data driven tests, we generate as many
-/// descriptors as test cases we discovered.
-///
-/// # Notes
-/// So, how does it work? We use a nightly-only feature of [custom_test_frameworks] that allows you
-/// to annotate arbitrary function, const or static with `#[test_case]`. Attribute. Then, Rust
-/// compiler would transform the code to pass all the discovered test cases as one big slice to the
-/// test runner.
-///
-/// However, we also want to support standard `#[test]` without disrupting them as much as possible.
-/// Internally, compiler would also desugar them to the `#[test_case]` attribute, but the type of
-/// the descriptor struct would be a predefined type of `test::TestDescAndFn`. This type, however,
-/// cannot represent all the additional information we need for our tests.
-///
-/// So we do a little trick here: we rely on the fact that compiler generates code exactly like in
-/// the following snippet:
-///
-/// ```ignore
-/// test::test_main_static(&[&__test_reexports::some::test1, &__test_reexports::some::test2])
-/// ```
-///
-/// Then, we implement `TestDescriptor` trait for the standard test descriptor struct, which would
-/// generate trait objects for these structs and pass a trait object instead. We do the same for
-/// our structs and our trait object allows us to return the reference wrapped into an enum
-/// distinguishing between three different test variants (standard tests, "files" tests and "data"
-/// tests).
-///
-/// [custom_test_frameworks]: https://github.com/rust-lang/rust/blob/master/src/doc/unstable-book/src/language-features/custom-test-frameworks.md
-/// See <https://blog.jrenner.net/rust/testing/2018/07/19/test-in-2018.html>
-#[doc(hidden)]
-pub fn runner(tests: &[&dyn TestDescriptor]) {
-    let args = std::env::args().collect::<Vec<_>>();
-    let parsed = crate::rustc_test::test::parse_opts(&args);
-    let mut opts = match parsed {
-        Some(Ok(o)) => o,
-        Some(Err(msg)) => panic!("{:?}", msg),
+/// Handle entry points generated for IDEs (`ide = true` option of `#[datatest::files]`). These are
+/// empty `#[test]` functions, one per test file. When IDE runs one of them (using `--exact` filter),
+/// we run all the tests generated for the corresponding file instead.
+fn select_entry_point(opts: &mut TestOpts, rendered: &mut Vec<RenderedTest>) {
+    let entry_points = rendered
+        .iter()
+        .filter_map(|test| test.entry_point.clone())
+        .collect::<HashSet<_>>();
+    if entry_points.is_empty() {
+        return;
+    }
+
+    // Entry points themselves are not real tests (these are only collected when `#[test]` is
+    // handled by the test runner).
+    rendered.retain(|test| {
+        test.entry_point.is_some() || !entry_points.contains(&test.test.desc.name.to_string())
+    });
+
+    if opts.filter_exact {
+        if let Some(filter) = opts.filter.clone() {
+            if entry_points.contains(&filter) {
+                rendered.retain(|test| test.entry_point.as_ref() == Some(&filter));
+                opts.filter = None;
+                opts.filter_exact = false;
+            }
+        }
+    }
+}
+
+/// Test names are namespaced by `::`, so exact filter (`--exact`) naming a namespace rather than a
+/// test (like `parser::v2`, with the cases named via `{namespace}` placeholder) selects all the
+/// tests under it, but not the tests of the sibling namespaces (like `parser::v20`).
+fn select_namespace(opts: &mut TestOpts, rendered: &mut Vec<RenderedTest>) {
+    if !opts.filter_exact {
+        return;
+    }
+    let filter = match opts.filter {
+        Some(ref filter) => filter.clone(),
         None => return,
     };
+    let prefix = format!("{}::", filter);
+    let name = |test: &RenderedTest| test.test.desc.name.to_string();
+    if rendered.iter().any(|test| name(test) == filter)
+        || !rendered.iter().any(|test| name(test).starts_with(&prefix))
+    {
+        return;
+    }
+    rendered.retain(|test| name(test).starts_with(&prefix));
+    opts.filter = None;
+    opts.filter_exact = false;
+}
+
+/// Check if test with a given name is selected by the filters given on the command line (mirrors
+/// the way standard test runner filters tests).
+fn is_selected(opts: &TestOpts, name: &str) -> bool {
+    let matches = |filter: &String| {
+        if opts.filter_exact {
+            name == filter
+        } else {
+            name.contains(filter.as_str())
+        }
+    };
+    opts.filter.as_ref().map_or(true, matches) && !opts.skip.iter().any(matches)
+}
+
+/// Environment variable restricting the run to the matching test cases.
+const CASE_ENV_VAR: &str = "DATATEST_CASE";
 
-    let mut rendered: Vec<TestDescAndFn> = Vec::new();
-    for input in tests.iter() {
-        render_test_descriptor(*input, &mut opts, &mut rendered);
+/// Check if test case is selected by the `DATATEST_CASE` environment variable: either the test name
+/// or the path of the test file (for `#[datatest::files]` tests) should contain its value. Unlike
+/// standard filters, it is applied while test cases are enumerated, so non-matching cases are not
+/// even generated.
+fn is_case_selected(case_filter: Option<&str>, name: &str, path: Option<&Path>) -> bool {
+    match case_filter {
+        None => true,
+        Some(filter) => {
+            name.contains(filter)
+                || path.map_or(false, |path| {
+                    normalize_name(escape_path(path).into_owned()).contains(filter)
+                })
+        }
     }
+}
 
-    // Indicate that we used our registry
-    REGISTRY_USED.store(true, Ordering::SeqCst);
+/// Name of the test defined by the descriptor (for `#[datatest::files]` and `#[datatest::data]`
+/// tests, name of the test function).
+fn descriptor_name(input: &dyn TestDescriptor) -> String {
+    match input.as_datatest_desc() {
+        DatatestTestDesc::Test(test) => test.desc.name.to_string(),
+        DatatestTestDesc::FilesTest(files) => real_name(files.name).to_string(),
+        DatatestTestDesc::DataTest(data) => real_name(data.name).to_string(),
+        DatatestTestDesc::RegularTest(desc) => real_name(desc.name).to_string(),
+    }
+}
 
-    // Gather tests registered via our registry (stable channel)
-    let mut current = unsafe { REGISTRY.load(Ordering::SeqCst).as_ref() };
-    while let Some(node) = current {
-        render_test_descriptor(node.descriptor, &mut opts, &mut rendered);
-        current = node.next;
+/// Named configuration the test cases are rendered for (see [`with_configurations`]).
+#[derive(Default)]
+struct CaseConfiguration {
+    /// Suffix of the case names (`@<configuration>`), added once the cases are rendered.
+    suffix: String,
+    /// Environment variables of the configuration, set for the cases in a child process.
+    env: Vec<(String, String)>,
+}
+
+impl CaseConfiguration {
+    /// Name the case with the given name gets under this configuration, which is how the child
+    /// process running just that case selects it.
+    fn case_name(&self, name: &str) -> String {
+        format!("{}{}", name, self.suffix)
     }
+}
 
-    // Run tests via standard runner!
-    match crate::rustc_test::run_tests_console(&opts, rendered) {
-        Ok(true) => {}
-        Ok(false) => panic!("Some tests failed"),
-        Err(e) => panic!("io error when running tests: {:?}", e),
+/// Render test cases once for each configuration of the test suite (or just once, if there are no
+/// configurations). Names of the cases are suffixed by `@<configuration>` and cases run with the
+/// configuration applied.
+fn with_configurations(
+    configurations: &BTreeMap<String, ConfigurationSpec>,
+    rendered: &mut Vec<RenderedTest>,
+    render: impl Fn(&CaseConfiguration, &mut Vec<RenderedTest>),
+) {
+    if configurations.is_empty() {
+        return render(&CaseConfiguration::default(), rendered);
+    }
+    for (name, spec) in configurations {
+        let configuration = CaseConfiguration {
+            suffix: format!("@{}", name),
+            env: spec.env.clone().into_iter().collect(),
+        };
+        let mut cases = Vec::new();
+        render(&configuration, &mut cases);
+        for mut case in cases {
+            let desc = &mut case.test.desc;
+            desc.name = TestName::DynTestName(configuration.case_name(&desc.name.to_string()));
+            case.test.testfn = match case.test.testfn {
+                TestFn::DynTestFn(testfn) => {
+                    let (name, spec) = (name.clone(), spec.clone());
+                    TestFn::DynTestFn(Box::new(move || {
+                        let _config = ConfigurationScope::enter(&name, &spec);
+                        testfn()
+                    }))
+                }
+                // Benchmarks are not affected by the configuration, only named after it
+                testfn => testfn,
+            };
+            rendered.push(case);
+        }
     }
 }
 
 fn render_test_descriptor(
     input: &dyn TestDescriptor,
+    case_filter: Option<&str>,
+    configurations: &BTreeMap<String, ConfigurationSpec>,
+    keep: KeepPolicy,
+    timeout: Option<Duration>,
     opts: &mut crate::rustc_test::TestOpts,
-    rendered: &mut Vec<TestDescAndFn>,
+    rendered: &mut Vec<RenderedTest>,
 ) {
     match input.as_datatest_desc() {
         DatatestTestDesc::Test(test) => {
+            if !is_case_selected(case_filter, &test.desc.name.to_string(), None) {
+                return;
+            }
             // Make a copy as we cannot take ownership
-            rendered.push(TestDescAndFn {
-                desc: test.desc.clone(),
-                testfn: clone_testfn(&test.testfn),
-            })
+            let origin = format!("test '{}'", test.desc.name);
+            rendered.push(RenderedTest::new(
+                TestDescAndFn {
+                    desc: test.desc.clone(),
+                    testfn: with_cwd_lock(clone_testfn(&test.testfn)),
+                },
+                origin,
+            ))
         }
         DatatestTestDesc::FilesTest(files) => {
-            render_files_test(files, rendered);
+            with_configurations(configurations, rendered, |configuration, rendered| {
+                render_files_test(files, case_filter, keep, timeout, configuration, rendered)
+            });
             adjust_for_test_name(opts, &files.name);
         }
         DatatestTestDesc::DataTest(data) => {
-            render_data_test(data, rendered);
+            with_configurations(configurations, rendered, |configuration, rendered| {
+                render_data_test(data, case_filter, timeout, configuration, rendered)
+            });
             adjust_for_test_name(opts, &data.name);
         }
         DatatestTestDesc::RegularTest(desc) => {
-            rendered.push(TestDescAndFn {
+            if !is_case_selected(case_filter, real_name(desc.name), None) {
+                return;
+            }
+            let test = TestDescAndFn {
                 desc: TestDesc {
                     name: TestName::StaticTestName(real_name(desc.name)),
                     ignore: desc.ignore,
@@ -384,8 +2926,14 @@ fn render_test_descriptor(
                     allow_fail: false,
                     test_type: crate::test_type(desc.source_file),
                 },
-                testfn: TestFn::StaticTestFn(desc.testfn),
-            })
+                testfn: with_cwd_lock(TestFn::StaticTestFn(desc.testfn)),
+            };
+            let origin = format!("test '{}' in '{}'", real_name(desc.name), desc.source_file);
+            rendered.push(
+                RenderedTest::new(test, origin)
+                    .with_ignore_message(desc.ignore_message)
+                    .with_only(desc.only),
+            )
         }
     }
 }
@@ -396,7 +2944,12 @@ fn render_test_descriptor(
 /// So, for every test we have registered, we make sure this test actually gets
 pub fn check_test_runner() {
     if !REGISTRY_USED.load(Ordering::SeqCst) {
-        panic!("test runner was not configured!");
+        panic!(
+            "test runner was not configured! Either use `#![test_runner(datatest::runner)]` \
+             (nightly only) or set `harness = false` for the test target in `Cargo.toml` \
+             (`[lib]` section for unit tests in `src/`) and add `datatest::harness!()` to its root \
+             module."
+        );
     }
 }
 
@@ -0,0 +1,59 @@
+//! Random sampling of the test cases: running a reproducible subset of a big test suite (like in
+//! pull request CI, while the full suite runs nightly).
+use crate::rng::{name_seed, SeededRng};
+
+/// Fraction of the test cases to run and the seed selecting them.
+#[derive(Clone, Copy)]
+pub(crate) struct Sample {
+    pub(crate) fraction: f64,
+    pub(crate) seed: u64,
+}
+
+impl Sample {
+    /// Parse the fraction given either in percent (`5%`) or as a number (`0.05`).
+    pub(crate) fn parse_fraction(value: &str) -> Result<f64, String> {
+        let value = value.trim();
+        let parsed = match value.strip_suffix('%') {
+            Some(percent) => percent.trim().parse::<f64>().map(|percent| percent / 100.0),
+            None => value.parse::<f64>(),
+        };
+        match parsed {
+            Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(fraction),
+            _ => Err(format!(
+                "invalid sample '{}', expected a percentage (like `5%`) or a fraction (like `0.05`)",
+                value
+            )),
+        }
+    }
+
+    /// Select the sampled test cases: the given fraction of the cases (rounded up) with the lowest
+    /// ranks, where the rank is derived from the seed and the name of the case. Selection only
+    /// depends on the seed and the names, so it is the same for every run with the same seed (and
+    /// adding a case does not reshuffle the others). Returns if each of the cases is selected.
+    pub(crate) fn select(&self, names: &[String]) -> Vec<bool> {
+        let count = (names.len() as f64 * self.fraction).ceil() as usize;
+        let ranks = names
+            .iter()
+            .map(|name| SeededRng::new(self.seed ^ name_seed(name)).next_u64())
+            .collect::<Vec<_>>();
+        let mut order = (0..names.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| {
+            ranks[a]
+                .cmp(&ranks[b])
+                .then_with(|| names[a].cmp(&names[b]))
+        });
+        let mut selected = vec![false; names.len()];
+        for idx in order.into_iter().take(count) {
+            selected[idx] = true;
+        }
+        selected
+    }
+}
+
+/// Seed for the run without the seed given explicitly.
+pub(crate) fn random_seed() -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    SeededRng::new(nanos ^ u64::from(std::process::id())).next_u64()
+}
@@ -0,0 +1,197 @@
+//! Isolated working directories for the test cases of `#[datatest::files(..)]` tests.
+use crate::config::KeepPolicy;
+use std::path::{Path, PathBuf};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// How the test case is isolated, given by the `sandbox` option.
+#[doc(hidden)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Sandbox {
+    /// Run the case in a temporary copy of the directory of the test file.
+    Copy,
+    /// Same as `Copy`, but also change the working directory into the copy for the duration of the
+    /// case.
+    Chdir,
+}
+
+/// Working directory is shared by the whole process, so test cases which change it hold this lock
+/// exclusively while other test cases hold it shared.
+static CWD_LOCK: RwLock<()> = RwLock::new(());
+
+/// Guard of the working directory, see [`CWD_LOCK`].
+pub(crate) enum CwdLock {
+    Shared {
+        _guard: RwLockReadGuard<'static, ()>,
+    },
+    Exclusive {
+        _guard: RwLockWriteGuard<'static, ()>,
+    },
+}
+
+impl CwdLock {
+    /// Lock the working directory (exclusively, if the test case changes it).
+    pub(crate) fn acquire(exclusive: bool) -> CwdLock {
+        // Previous case holding the lock might have failed, but it still restored the directory
+        if exclusive {
+            CwdLock::Exclusive {
+                _guard: CWD_LOCK.write().unwrap_or_else(PoisonError::into_inner),
+            }
+        } else {
+            CwdLock::Shared {
+                _guard: CWD_LOCK.read().unwrap_or_else(PoisonError::into_inner),
+            }
+        }
+    }
+}
+
+/// Temporary directory mirroring the directory of the test file, with copies of the files of the
+/// test case; removed (unless kept according to the `keep` policy) and previous working directory
+/// restored on drop.
+pub(crate) struct SandboxDir {
+    /// Directory of the test file.
+    fixture_dir: PathBuf,
+    dir: PathBuf,
+    previous_cwd: Option<PathBuf>,
+    keep: KeepPolicy,
+}
+
+impl SandboxDir {
+    /// Copy the files of the test case (`paths`, matched by the pattern or derived from the test
+    /// file at `fixture`) which are inside the directory of the test file into a fresh temporary
+    /// directory named after the test case (`artifact` is the name of the test safe for file
+    /// names), keeping their relative locations. Other files of the directory are not copied.
+    pub(crate) fn create(
+        mode: Sandbox,
+        fixture: &Path,
+        paths: &[PathBuf],
+        artifact: &str,
+        keep: KeepPolicy,
+    ) -> SandboxDir {
+        let fixture_dir = fixture
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let fixture_dir = absolute(fixture_dir);
+        let dir = std::env::temp_dir().join("datatest-sandbox").join(format!(
+            "{}-{}",
+            std::process::id(),
+            artifact
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)
+            .unwrap_or_else(|err| panic!("cannot create sandbox '{}': {}", dir.display(), err));
+        for path in paths {
+            let path = absolute(path);
+            let relative = match path.strip_prefix(&fixture_dir) {
+                Ok(relative) => relative,
+                Err(_) => continue,
+            };
+            copy_path(&path, &dir.join(relative)).unwrap_or_else(|err| {
+                panic!(
+                    "cannot copy '{}' into sandbox '{}': {}",
+                    path.display(),
+                    dir.display(),
+                    err
+                )
+            });
+        }
+
+        let mut sandbox = SandboxDir {
+            fixture_dir,
+            dir,
+            previous_cwd: None,
+            keep,
+        };
+        if mode == Sandbox::Chdir {
+            let previous = std::env::current_dir().expect("cannot get current directory");
+            std::env::set_current_dir(&sandbox.dir).unwrap_or_else(|err| {
+                panic!(
+                    "cannot change directory to '{}': {}",
+                    sandbox.dir.display(),
+                    err
+                )
+            });
+            sandbox.previous_cwd = Some(previous);
+        }
+        sandbox
+    }
+
+    /// Paths given to the test function: paths inside the directory of the test file point into
+    /// the sandbox, other paths are made absolute (so they stay valid if the working directory is
+    /// changed).
+    pub(crate) fn remap(&self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        paths
+            .iter()
+            .map(|path| {
+                let path = match self.previous_cwd {
+                    Some(ref cwd) => cwd.join(path),
+                    None => absolute(path),
+                };
+                match path.strip_prefix(&self.fixture_dir) {
+                    Ok(relative) => self.dir.join(relative),
+                    Err(_) => path,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Drop for SandboxDir {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous_cwd.take() {
+            let _ = std::env::set_current_dir(previous);
+        }
+        // Sandbox is dropped while unwinding if the case fails
+        let failed = std::thread::panicking();
+        let keep = match self.keep {
+            KeepPolicy::Never => false,
+            KeepPolicy::OnFailure => failed,
+            KeepPolicy::Always => true,
+        };
+        if keep {
+            // Printed into the captured output, so it is shown along with the failure
+            println!(
+                "sandbox of the test case is kept at '{}'",
+                self.dir.display()
+            );
+        } else {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    match std::env::current_dir() {
+        Ok(cwd) => cwd.join(path),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Copy the file or the directory at `from` to `to`, creating the parent directories. Derived files
+/// which do not exist (like outputs written by the test) are skipped, but their parent directories
+/// are still created, so the test could write them.
+fn copy_path(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    match std::fs::metadata(from) {
+        Ok(metadata) if metadata.is_dir() => copy_dir(from, to),
+        Ok(_) => std::fs::copy(from, to).map(|_| ()),
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
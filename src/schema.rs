@@ -0,0 +1,54 @@
+//! Validation of the test cases against the JSON Schema given by the `schema` option of
+//! `#[datatest::data(..)]` tests (`json_schema` feature), see [`validate_data_file`].
+//!
+//! [`validate_data_file`]: crate::validate_data_file
+
+/// Compiled JSON Schema the test cases are validated against (never loaded without the
+/// `json_schema` feature).
+#[cfg_attr(not(feature = "json_schema"), allow(dead_code))]
+pub(crate) struct Schema {
+    #[cfg(feature = "json_schema")]
+    schema: jsonschema::JSONSchema,
+}
+
+impl Schema {
+    /// Read and compile the schema from the JSON file at `path`.
+    #[cfg(feature = "json_schema")]
+    pub(crate) fn load(path: &str) -> Result<Schema, String> {
+        let input = std::fs::read_to_string(path)
+            .map_err(|err| format!("cannot read schema '{}': {}", path, err))?;
+        let value: serde_json::Value = serde_json::from_str(&input)
+            .map_err(|err| format!("cannot parse schema '{}': {}", path, err))?;
+        let schema = jsonschema::JSONSchema::compile(&value)
+            .map_err(|err| format!("invalid schema '{}': {}", path, err))?;
+        Ok(Schema { schema })
+    }
+
+    #[cfg(not(feature = "json_schema"))]
+    pub(crate) fn load(path: &str) -> Result<Schema, String> {
+        Err(format!(
+            "cannot validate test cases against the schema '{}': requires the `json_schema` \
+             feature of `datatest`",
+            path
+        ))
+    }
+
+    /// Every violation of the schema by the value, along with the path of the invalid part.
+    #[cfg(feature = "json_schema")]
+    pub(crate) fn validate(&self, value: &serde_json::Value) -> Vec<String> {
+        match self.schema.validate(value) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|err| match err.instance_path.to_string() {
+                    path if path.is_empty() => err.to_string(),
+                    path => format!("{} (at `{}`)", err, path),
+                })
+                .collect(),
+        }
+    }
+
+    #[cfg(not(feature = "json_schema"))]
+    pub(crate) fn validate(&self, _value: &serde_json::Value) -> Vec<String> {
+        Vec::new()
+    }
+}
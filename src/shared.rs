@@ -0,0 +1,112 @@
+//! Expensive resources shared by the test cases of the whole test suite.
+use std::any::{Any, TypeId};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+/// Resource shared by all test cases which need it (database container, compiled grammar tables,
+/// etc). It is set up once, right before the first case which uses it, and torn down (dropped)
+/// once all the tests finish.
+///
+/// ```ignore
+/// struct Database { url: String }
+///
+/// impl datatest::SharedResource for Database {
+///   fn setup() -> Self {
+///     Database { url: start_container() }
+///   }
+/// }
+///
+/// impl Drop for Database {
+///   fn drop(&mut self) {
+///     stop_container(&self.url);
+///   }
+/// }
+///
+/// #[datatest::files("tests/queries", { input in r"\.sql$" })]
+/// fn query(input: &str, db: &datatest::Shared<Database>) {
+///   ..
+/// }
+/// ```
+pub trait SharedResource: Send + Sync + 'static {
+    /// Construct the resource. If it panics, every test case using the resource fails (and the
+    /// set up is attempted again by the next case).
+    fn setup() -> Self;
+}
+
+/// Handle to the [`SharedResource`] of type `T`, dereferences to the resource itself.
+///
+/// When used as an argument of `#[datatest::files]` or `#[datatest::data]` test function (either
+/// `Shared<T>` or `&Shared<T>`, spelled out as `datatest::Shared<T>`), it does not need a mapping
+/// and is injected by its type.
+pub struct Shared<T: SharedResource>(Arc<T>);
+
+/// Slot of the resource of the given type (shared between the threads setting it up).
+type Slot<T> = OnceLock<Arc<T>>;
+
+/// Slots of the resources requested so far.
+static SLOTS: Mutex<Vec<(TypeId, Arc<dyn Any + Send + Sync>)>> = Mutex::new(Vec::new());
+
+/// Resources set up so far, in the order their set up finished.
+static RESOURCES: Mutex<Vec<Arc<dyn Any + Send + Sync>>> = Mutex::new(Vec::new());
+
+impl<T: SharedResource> Shared<T> {
+    /// Resource of type `T`, set up on the first call. Resources are only torn down by the
+    /// `datatest` test runner; when used outside of it, resource lives until the process exits.
+    pub fn get() -> Shared<T> {
+        let slot = {
+            let mut slots = SLOTS.lock().unwrap_or_else(PoisonError::into_inner);
+            let existing = slots
+                .iter()
+                .find(|(type_id, _)| *type_id == TypeId::of::<T>())
+                .map(|(_, slot)| slot.clone());
+            existing.unwrap_or_else(|| {
+                let slot: Arc<dyn Any + Send + Sync> = Arc::new(Slot::<T>::new());
+                slots.push((TypeId::of::<T>(), slot.clone()));
+                slot
+            })
+        };
+        // Lock is not held while setting up, so resources could use other resources
+        let slot = slot
+            .downcast_ref::<Slot<T>>()
+            .expect("resource slot of a different type");
+        let resource = slot.get_or_init(|| {
+            let resource = Arc::new(T::setup());
+            RESOURCES
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(resource.clone());
+            resource
+        });
+        Shared(resource.clone())
+    }
+}
+
+impl<T: SharedResource> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: SharedResource> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Shared(self.0.clone())
+    }
+}
+
+impl<T: SharedResource + std::fmt::Debug> std::fmt::Debug for Shared<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Shared").field(&self.0).finish()
+    }
+}
+
+/// Tear down all resources set up so far, in the reverse order (so resources which use other
+/// resources are torn down first). Called by the runner once the tests finish.
+pub(crate) fn teardown() {
+    SLOTS.lock().unwrap_or_else(PoisonError::into_inner).clear();
+    let resources = std::mem::take(&mut *RESOURCES.lock().unwrap_or_else(PoisonError::into_inner));
+    for resource in resources.into_iter().rev() {
+        drop(resource);
+    }
+}
@@ -0,0 +1,118 @@
+//! History of the durations of the test cases, used to split the test suite into shards which take
+//! roughly the same time to run and to start the slowest cases first.
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// Durations of the test cases (in seconds) measured by the previous runs, by the test name. Stored
+/// as a JSON object, sorted by the test name, so it could be committed:
+///
+/// ```json
+/// {
+///   "sample_test::case-01.input.txt": 0.012,
+///   "sample_test::case-02.input.txt": 1.5
+/// }
+/// ```
+#[derive(Default)]
+pub(crate) struct Timings(BTreeMap<String, f64>);
+
+impl Timings {
+    /// Load timings from the given path. Missing file means no history yet.
+    pub(crate) fn load(path: &Path) -> Result<Timings, String> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Timings::default()),
+            Err(err) => return Err(format!("cannot read timings '{}': {}", path.display(), err)),
+        };
+        let timings = serde_json::from_str(&content)
+            .map_err(|err| format!("invalid timings '{}': {}", path.display(), err))?;
+        Ok(Timings(timings))
+    }
+
+    /// Remember the latest durations of the given test cases (other cases keep their durations,
+    /// so the history is kept when only a part of the test suite is run).
+    pub(crate) fn update(&mut self, durations: impl IntoIterator<Item = (String, Duration)>) {
+        for (name, duration) in durations {
+            // Round to milliseconds to keep the diffs of the committed file readable
+            let secs = (duration.as_secs_f64() * 1000.0).round() / 1000.0;
+            self.0.insert(name, secs);
+        }
+    }
+
+    /// Write timings as JSON to the given path (creating parent directories as needed).
+    pub(crate) fn write(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(&self.0)?;
+        std::fs::write(path, json + "\n")
+    }
+
+    /// Expected duration of each of the test cases. Cases without history are expected to take as
+    /// long as an average case.
+    pub(crate) fn expected(&self, names: &[String]) -> Vec<f64> {
+        let known = names
+            .iter()
+            .filter_map(|name| self.0.get(name))
+            .collect::<Vec<_>>();
+        let average = if known.is_empty() {
+            1.0
+        } else {
+            known.iter().copied().sum::<f64>() / known.len() as f64
+        };
+        names
+            .iter()
+            .map(|name| self.0.get(name).copied().unwrap_or(average))
+            .collect()
+    }
+}
+
+/// Shard of the test suite to run, given as `<index>/<count>` (index starts from 1).
+#[derive(Clone, Copy)]
+pub(crate) struct Shard {
+    pub(crate) index: usize,
+    pub(crate) count: usize,
+}
+
+impl Shard {
+    pub(crate) fn parse(value: &str) -> Result<Shard, String> {
+        let invalid = || {
+            format!(
+                "invalid shard '{}', expected `<index>/<count>` (like `1/4`)",
+                value
+            )
+        };
+        let (index, count) = value.split_once('/').ok_or_else(invalid)?;
+        let index = index.trim().parse::<usize>().map_err(|_| invalid())?;
+        let count = count.trim().parse::<usize>().map_err(|_| invalid())?;
+        if index == 0 || index > count {
+            return Err(invalid());
+        }
+        Ok(Shard { index, count })
+    }
+
+    /// Select test cases of this shard: cases are assigned to the shards by their expected duration
+    /// (longest first, each to the shard with the least total duration so far), so all shards take
+    /// roughly the same time. Every shard computes the same assignment, as long as it is given the
+    /// same test cases and timings. Returns if each of the cases belongs to this shard and the total
+    /// expected duration of the shard.
+    pub(crate) fn select(&self, names: &[String], timings: &Timings) -> (Vec<bool>, f64) {
+        let expected = timings.expected(names);
+        let mut order = (0..names.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| {
+            expected[b]
+                .total_cmp(&expected[a])
+                .then_with(|| names[a].cmp(&names[b]))
+        });
+        let mut totals = vec![0.0f64; self.count];
+        let mut selected = vec![false; names.len()];
+        for idx in order {
+            let shard = (0..self.count)
+                .min_by(|&a, &b| totals[a].total_cmp(&totals[b]))
+                .unwrap_or(0);
+            totals[shard] += expected[idx];
+            selected[idx] = shard + 1 == self.index;
+        }
+        (selected, totals[self.index - 1])
+    }
+}
@@ -0,0 +1,77 @@
+//! Watching fixture files for changes.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How often the files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+
+/// State of the file used to detect changes: modification time and size.
+type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+/// Watch the files under the `roots` (directories or individual files) and call the `callback`
+/// with the list of files which were added, modified or removed since the previous call. Return
+/// `false` from the callback to stop watching. Changes are detected by polling, so no extra
+/// dependencies are needed; quick successive changes are reported together.
+///
+/// Test runner uses it in watch mode (`cargo test -- --watch`), re-enumerating and re-running the
+/// affected cases on every change. Could also be used by `cargo watch`-style wrappers:
+///
+/// ```no_run
+/// datatest::watch(&["tests/test-cases"], |changed| {
+///   println!("changed: {:?}", changed);
+///   true
+/// });
+/// ```
+pub fn watch<P: AsRef<Path>>(roots: &[P], mut callback: impl FnMut(&[PathBuf]) -> bool) {
+    let mut previous = snapshot(roots);
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let mut current = snapshot(roots);
+        if current == previous {
+            continue;
+        }
+        // Wait until the files are settled (editors often write files in several steps)
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let settled = snapshot(roots);
+            if settled == current {
+                break;
+            }
+            current = settled;
+        }
+        let mut changed = current
+            .iter()
+            .filter(|(path, state)| previous.get(*path) != Some(state))
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        changed.extend(
+            previous
+                .keys()
+                .filter(|path| !current.contains_key(*path))
+                .cloned(),
+        );
+        changed.sort();
+        previous = current;
+        if !callback(&changed) {
+            return;
+        }
+    }
+}
+
+fn snapshot<P: AsRef<Path>>(roots: &[P]) -> Snapshot {
+    let mut snapshot = Snapshot::new();
+    for root in roots {
+        let entries = walkdir::WalkDir::new(root.as_ref())
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| !entry.file_type().is_dir());
+        for entry in entries {
+            if let Ok(metadata) = entry.metadata() {
+                let state = (metadata.modified().ok(), metadata.len());
+                snapshot.insert(entry.into_path(), state);
+            }
+        }
+    }
+    snapshot
+}
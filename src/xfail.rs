@@ -0,0 +1,54 @@
+//! Expected failures: test cases marked with `#[xfail]` (or with `xfail` field of the YAML test
+//! case), which are known to fail. Such cases pass as long as they fail, and unexpected passes
+//! (XPASS) are reported, so the marker is removed once the case is fixed.
+use crate::config::XpassPolicy;
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Mutex, PoisonError};
+
+/// Outcomes of the expected failures run so far, by the test name: panic message if the case
+/// failed as expected, `None` if it unexpectedly passed.
+static OUTCOMES: Mutex<BTreeMap<String, Option<String>>> = Mutex::new(BTreeMap::new());
+
+/// Run the test case which is expected to fail. Failure of the case is swallowed; if the case
+/// passes, it is recorded as XPASS (and fails, if the policy says so).
+pub(crate) fn run_expecting_failure(name: &str, policy: XpassPolicy, testfn: impl FnOnce()) {
+    let failure = catch_unwind(AssertUnwindSafe(testfn))
+        .err()
+        .map(|payload| crate::runner::panic_message(payload.as_ref()));
+    let passed = failure.is_none();
+    // Case might have been run before (in watch mode), the latest outcome wins
+    OUTCOMES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(name.to_string(), failure);
+    if passed && policy == XpassPolicy::Fail {
+        panic!(
+            "test case is marked as expected failure (`xfail`), but it passed (XPASS); remove the \
+             marker if the case is fixed"
+        );
+    }
+}
+
+/// Print expected failures and unexpected passes of the run.
+pub(crate) fn print_summary() {
+    let outcomes = OUTCOMES.lock().unwrap_or_else(PoisonError::into_inner);
+    let (failed, passed): (Vec<_>, Vec<_>) =
+        outcomes.iter().partition(|(_, failure)| failure.is_some());
+    if !failed.is_empty() {
+        println!("expected failures (xfail):");
+        for (name, failure) in failed {
+            let message = failure.as_deref().unwrap_or_default();
+            let message = message.lines().next().unwrap_or_default();
+            println!("    {}: {}", name, message);
+        }
+        println!();
+    }
+    if !passed.is_empty() {
+        println!("unexpected passes (XPASS):");
+        for (name, _) in passed {
+            println!("    {}", name);
+        }
+        println!();
+    }
+}
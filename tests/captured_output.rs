@@ -0,0 +1,112 @@
+//! Output of the failed test cases is attached to the report, failures are listed in the failure
+//! manifest and in the JUnit report. With `log` and `tracing` features enabled, log records and
+//! events of the cases are captured as well.
+mod common;
+
+use serde_json::Value;
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(data: String) {
+    println!("checking {}", data);
+    log::info!("logged {}", data);
+    log::trace!("traced {}", data);
+    tracing::info!("event of {}", data);
+    assert_ne!(data, "secondsecond", "second case fails");
+}
+
+fn main() {
+    let path = common::temp_path("output.json");
+    let junit = common::temp_path("junit.xml");
+    std::env::set_var("DATATEST_REPORT", &path);
+    std::env::set_var("DATATEST_JUNIT", &junit);
+    assert!(!common::run_tests(), "run must fail");
+
+    let report: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let case = |name: &str| {
+        report["cases"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|case| case["name"] == name)
+            .unwrap_or_else(|| panic!("case '{}' is not in the report", name))
+            .clone()
+    };
+    assert_eq!(case("data_test::firstfirst (line 1)")["status"], "passed");
+    let failed = case("data_test::secondsecond (line 2)");
+    assert_eq!(failed["status"], "failed");
+    let output = failed["output"]
+        .as_str()
+        .expect("output of the failed case");
+    assert!(output.contains("checking secondsecond\n"), "{}", output);
+    assert!(output.contains("second case fails"), "{}", output);
+    // Panic message is preceded by the case it came from
+    let context = "test case 'data_test::secondsecond (line 2)' (tests/strings.yaml:2) panicked:\n";
+    let context = output.find(context).expect("case context is printed");
+    assert!(
+        context < output.find("second case fails").unwrap(),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("failing case: tests/strings.yaml:2"),
+        "{}",
+        output
+    );
+    if cfg!(feature = "log") {
+        let record = "[INFO  captured_output] logged secondsecond\n";
+        assert!(output.contains(record), "{}", output);
+        // Records more verbose than `debug` are not captured by default
+        assert!(!output.contains("traced"), "{}", output);
+    }
+    if cfg!(feature = "tracing") {
+        let event = " INFO captured_output: event of secondsecond\n";
+        assert!(output.contains(event), "{}", output);
+    }
+    assert!(!output.contains("checking firstfirst"), "{}", output);
+    assert!(case("data_test::firstfirst (line 1)")
+        .get("output")
+        .is_none());
+
+    let failures = common::failures();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0]["name"], "data_test::secondsecond (line 2)");
+    assert_eq!(
+        failures[0]["fixtures"],
+        serde_json::json!(["tests/strings.yaml"])
+    );
+    let message = failures[0]["message"].as_str().unwrap();
+    assert!(message.contains("second case fails"), "{}", message);
+    assert!(failures[0]["duration_secs"].is_f64());
+    let output = failures[0]["output"].as_str().unwrap();
+    assert!(output.contains("checking secondsecond\n"), "{}", output);
+
+    let junit_xml = std::fs::read_to_string(&junit).unwrap();
+    std::fs::remove_file(&junit).unwrap();
+    assert!(
+        junit_xml
+            .contains(r#"<testsuite name="captured_output" tests="3" failures="1" skipped="0""#),
+        "{}",
+        junit_xml
+    );
+    assert!(
+        junit_xml.contains(r#"<testcase name="firstfirst (line 1)" classname="data_test" time=""#),
+        "{}",
+        junit_xml
+    );
+    let failure = junit_xml
+        .split("<failure message=\"")
+        .nth(1)
+        .expect("failure of the second case");
+    let (message, details) = failure.split_once("\">").unwrap();
+    assert!(message.contains("second case fails"), "{}", junit_xml);
+    let details = &details[..details.find("</failure>").unwrap()];
+    assert!(details.starts_with(message), "{}", junit_xml);
+    assert!(details.contains("\n\ncaptured output:\n"), "{}", junit_xml);
+    assert!(details.contains("checking secondsecond\n"), "{}", junit_xml);
+    if cfg!(feature = "log") {
+        let record = "[INFO  captured_output] logged secondsecond\n";
+        assert!(details.contains(record), "{}", junit_xml);
+    }
+}
@@ -0,0 +1,37 @@
+//! `DATATEST_CASE` environment variable restricts the run to the test cases with matching names or
+//! test file paths.
+use datatest::test;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static SELECTED_RAN: AtomicUsize = AtomicUsize::new(0);
+static OTHERS_RAN: AtomicUsize = AtomicUsize::new(0);
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+fn files_test(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+    if input == "Kylie" {
+        SELECTED_RAN.fetch_add(1, Ordering::SeqCst);
+    } else {
+        OTHERS_RAN.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(_data: String) {
+    OTHERS_RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+fn regular_test() {
+    OTHERS_RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+fn main() {
+    std::env::set_var("DATATEST_CASE", "test-cases/case-01");
+    datatest::runner(&[]);
+    assert_eq!(SELECTED_RAN.load(Ordering::SeqCst), 1);
+    assert_eq!(OTHERS_RAN.load(Ordering::SeqCst), 0);
+}
@@ -0,0 +1,2 @@
+echo "$0: cannot open $1" >&2
+exit 2
@@ -0,0 +1,70 @@
+//! Custom flags registered via `CliFlags` are taken out of the command line by the runner and
+//! exposed to the tests.
+mod common;
+
+use datatest::test;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static RAN: AtomicUsize = AtomicUsize::new(0);
+static FLAG_SEEN: AtomicUsize = AtomicUsize::new(0);
+static OPTION_SEEN: AtomicUsize = AtomicUsize::new(0);
+
+fn record_flags() {
+    assert!(!datatest::cli_flag("verbose"));
+    RAN.fetch_add(1, Ordering::SeqCst);
+    if datatest::cli_flag("update-goldens") {
+        FLAG_SEEN.fetch_add(1, Ordering::SeqCst);
+    }
+    if datatest::cli_option("--corpus-dir") == Some("tests/test-cases") {
+        OPTION_SEEN.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+})]
+fn files_test(input: &str) {
+    assert!(!input.is_empty());
+    record_flags();
+}
+
+#[test]
+fn regular_test() {
+    record_flags();
+}
+
+fn flags() -> datatest::CliFlags {
+    datatest::CliFlags::new()
+        .flag("update-goldens", "Rewrite golden files")
+        .flag("verbose", "Print more details")
+        .option("corpus-dir", "PATH", "Directory of the additional corpus")
+}
+
+fn main() {
+    flags().run();
+    let ran = RAN.load(Ordering::SeqCst);
+    assert_eq!(ran, 3);
+    if common::is_child() {
+        assert_eq!(FLAG_SEEN.load(Ordering::SeqCst), ran);
+        assert_eq!(OPTION_SEEN.load(Ordering::SeqCst), ran);
+        return;
+    }
+    assert_eq!(FLAG_SEEN.load(Ordering::SeqCst), 0);
+    assert_eq!(OPTION_SEEN.load(Ordering::SeqCst), 0);
+
+    // Run the binary again, now with the custom flags
+    let status = common::rerun(&["--update-goldens", "--corpus-dir", "tests/test-cases"])
+        .status()
+        .unwrap();
+    assert!(status.success(), "tests must see the custom flags");
+
+    let output = common::rerun(&["--help"]).output().unwrap();
+    let help = common::stdout(&output);
+    assert!(help.contains("--corpus-dir PATH"), "{}", help);
+    assert!(help.contains("Rewrite golden files"), "{}", help);
+
+    let output = common::rerun(&["--corpus-dir"]).output().unwrap();
+    assert!(!output.status.success(), "missing value must fail the run");
+    let stderr = common::stderr(&output);
+    assert!(stderr.contains("requires a value"), "{}", stderr);
+}
@@ -0,0 +1,86 @@
+//! Helpers shared by the test binaries checking the behaviour of the whole run. These binaries
+//! replace the test harness (`harness = false`) and run their tests from `main`, either directly or
+//! by re-running the binary with other arguments and environment.
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::Mutex;
+
+/// Set for the test binary re-run by [`rerun`].
+const CHILD_ENV_VAR: &str = "DATATEST_TEST_CHILD";
+
+/// Run the tests of the binary, returning `false` if the run failed.
+pub fn run_tests() -> bool {
+    std::panic::catch_unwind(|| datatest::runner(&[])).is_ok()
+}
+
+/// Same as [`run_tests`], but also returns the messages of the panics raised while the tests ran
+/// (failures of the test cases included).
+pub fn run_tests_with_panics() -> (bool, Vec<String>) {
+    static MESSAGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    std::panic::set_hook(Box::new(|info| {
+        let message = info
+            .payload()
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_default();
+        MESSAGES.lock().unwrap().push(message);
+    }));
+    let passed = run_tests();
+    let _ = std::panic::take_hook();
+    (passed, std::mem::take(&mut *MESSAGES.lock().unwrap()))
+}
+
+/// If the test binary is re-run by [`rerun`].
+pub fn is_child() -> bool {
+    std::env::var_os(CHILD_ENV_VAR).is_some()
+}
+
+/// Command re-running the test binary with the given arguments (see [`is_child`]).
+pub fn rerun(args: &[&str]) -> Command {
+    let mut command = Command::new(std::env::current_exe().unwrap());
+    command.args(args).env(CHILD_ENV_VAR, "1");
+    command
+}
+
+/// Standard output of the re-run test binary.
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Standard error of the re-run test binary.
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// Lines of the output reporting the problems found (`error: ...`).
+pub fn errors(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .filter(|line| line.starts_with("error: "))
+        .collect()
+}
+
+/// Path of the temporary file, unique for the run of the test binary.
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("datatest-{}-{}", name, std::process::id()))
+}
+
+/// Failures listed in the failure manifest written by the last run of the test binary.
+pub fn failures() -> Vec<Value> {
+    let path = format!("target/datatest/{}/failures.json", env!("CARGO_CRATE_NAME"));
+    let manifest = std::fs::read_to_string(path).unwrap();
+    let manifest: Value = serde_json::from_str(&manifest).unwrap();
+    manifest["failures"].as_array().unwrap().clone()
+}
+
+/// Names of the cases listed in the failure manifest.
+pub fn failed_cases() -> Vec<String> {
+    failures()
+        .iter()
+        .map(|failure| failure["name"].as_str().unwrap().to_string())
+        .collect()
+}
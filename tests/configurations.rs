@@ -0,0 +1,48 @@
+//! Every case runs under each configuration of the test suite (`[runner.configurations]` section
+//! of `datatest.toml`), with the environment of the configuration applied. Cases run in child
+//! processes with the environment set, so they record their runs into a file. Only the cases with
+//! the tag given by `DATATEST_TAG` (`[runner.tags]` section) are run.
+mod common;
+
+use std::io::Write;
+
+/// Environment variable giving the file the cases record their runs into.
+const RAN_ENV_VAR: &str = "CONFIGURATIONS_RAN";
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(data: String) {
+    let config = datatest::Configuration::current().expect("configuration must be applied");
+    assert_eq!(std::env::var("DATATEST_DATABASE").unwrap(), config.name());
+    assert_eq!(config.is_enabled("in-memory"), config.name() == "sqlite");
+    let mut ran = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(std::env::var_os(RAN_ENV_VAR).unwrap())
+        .unwrap();
+    writeln!(ran, "{}@{}", data, config.name()).unwrap();
+}
+
+fn main() {
+    let path = common::temp_path("configurations");
+    // Configuration file is looked up starting from the crate directory
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/configurations");
+    std::env::set_var("CARGO_MANIFEST_DIR", dir);
+    std::env::set_var(RAN_ENV_VAR, &path);
+    std::env::set_var("DATATEST_TAG", "first");
+    datatest::runner(&[]);
+
+    let ran = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    let mut ran = ran.lines().collect::<Vec<_>>();
+    ran.sort();
+    assert_eq!(
+        ran,
+        [
+            "firstfirst@postgres",
+            "firstfirst@sqlite",
+            "thirdthird@sqlite",
+        ]
+    );
+    assert!(std::env::var_os("DATATEST_DATABASE").is_none());
+    assert!(datatest::Configuration::current().is_none());
+}
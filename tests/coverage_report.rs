@@ -0,0 +1,50 @@
+//! With `DATATEST_REPORT=<path>`, report listing every test case with its fixture and status is
+//! written after the run.
+mod common;
+
+use serde_json::Value;
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+fn files_test(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+#[datatest::data("tests/strings.yaml")]
+#[ignore]
+fn data_test(_data: String) {}
+
+fn main() {
+    let path = common::temp_path("report.json");
+    std::env::set_var("DATATEST_REPORT", &path);
+    datatest::runner(&[]);
+
+    let report: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let case = |name: &str| {
+        report["cases"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|case| case["name"] == name)
+            .unwrap_or_else(|| panic!("case '{}' is not in the report", name))
+            .clone()
+    };
+    let files_case = case("files_test::case-01.input.txt");
+    assert_eq!(files_case["fixture"], "tests/test-cases/case-01.input.txt");
+    assert_eq!(files_case["status"], "passed");
+    let data_case = case("data_test::firstfirst (line 1)");
+    assert_eq!(data_case["fixture"], "tests/strings.yaml:1");
+    assert_eq!(data_case["status"], "skipped");
+    assert_eq!(report["cases"].as_array().unwrap().len(), 5);
+    assert_eq!(
+        report["unreferenced"],
+        serde_json::json!([
+            "tests/test-cases/case-03.input-linked.txt",
+            "tests/test-cases/case-03.output-linked.txt"
+        ])
+    );
+}
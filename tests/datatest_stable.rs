@@ -17,3 +17,8 @@ fn regular_test_result() -> Result<(), Box<dyn std::error::Error>> {
     println!("regular tests also work!");
     Ok(())
 }
+
+#[crate::test_utils::test(crate = "crate::test_utils")]
+fn regular_test_reexported() {
+    println!("regular tests also work through re-exports!");
+}
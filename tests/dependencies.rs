@@ -0,0 +1,74 @@
+//! Dependent tests run after all cases of their prerequisites, and fail without running if any of
+//! them failed.
+mod common;
+
+use std::sync::Mutex;
+
+static RAN: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn ran(case: String) {
+    RAN.lock().unwrap().push(case);
+}
+
+// Runs after `setup`, even though it comes first by name
+#[datatest::data("tests/strings.yaml", depends_on = "setup")]
+fn after_setup(data: String) {
+    let setup = RAN
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|case| case.starts_with("setup"))
+        .count();
+    assert_eq!(setup, 3);
+    ran(format!("after_setup {}", data));
+}
+
+#[datatest::data("tests/strings.yaml")]
+fn setup(data: String) {
+    ran(format!("setup {}", data));
+}
+
+#[datatest::data("tests/strings.yaml")]
+fn broken(data: String) {
+    assert_ne!(data, "secondsecond");
+}
+
+#[datatest::data("tests/strings.yaml", depends_on = ["setup", "broken"])]
+fn after_broken(data: String) {
+    ran(format!("after_broken {}", data));
+}
+
+fn main() {
+    std::env::set_var("RUST_TEST_THREADS", "4");
+    assert!(!common::run_tests(), "run must fail");
+
+    let mut ran = RAN.lock().unwrap().clone();
+    ran.sort();
+    assert_eq!(
+        ran,
+        [
+            "after_setup firstfirst",
+            "after_setup secondsecond",
+            "after_setup thirdthird",
+            "setup firstfirst",
+            "setup secondsecond",
+            "setup thirdthird",
+        ]
+    );
+
+    let failures = common::failures();
+    let not_run = failures
+        .iter()
+        .filter(|failure| {
+            failure["name"]
+                .as_str()
+                .unwrap()
+                .starts_with("after_broken")
+        })
+        .map(|failure| failure["message"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        not_run,
+        ["not run, as prerequisite tests failed: broken"; 3]
+    );
+}
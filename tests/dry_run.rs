@@ -0,0 +1,58 @@
+//! With `--dry-run`, test cases are enumerated and printed along with their inputs, but not run.
+mod common;
+
+use datatest::test;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static RAN: AtomicUsize = AtomicUsize::new(0);
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+fn files_test(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(_data: String) {
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+#[test]
+#[ignore]
+fn regular_test() {
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+fn main() {
+    datatest::runner(&[]);
+    if common::is_child() {
+        assert_eq!(RAN.load(Ordering::SeqCst), 0, "dry run must not run tests");
+        return;
+    }
+    assert_eq!(RAN.load(Ordering::SeqCst), 5);
+
+    let output = common::rerun(&["--dry-run", "_test"]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = common::stdout(&output);
+    let expected = "\
+files_test::case-01.input.txt
+    location: tests/test-cases/case-01.input.txt
+    input: tests/test-cases/case-01.input.txt
+    input: tests/test-cases/case-01.output.txt
+";
+    assert!(stdout.contains(expected), "{}", stdout);
+    assert!(
+        stdout.contains("data_test::firstfirst (line 1)\n    location: tests/strings.yaml:1\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("regular_test [ignored]\n"), "{}", stdout);
+    assert!(
+        stdout.contains("dry run: 6 test cases found, none were run"),
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,42 @@
+//! Errors while enumerating test cases fail only the affected tests instead of aborting the whole
+//! run.
+mod common;
+
+use datatest::DataTestCaseDesc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static HEALTHY_RAN: AtomicBool = AtomicBool::new(false);
+static DUPLICATES_RAN: AtomicBool = AtomicBool::new(false);
+
+fn broken_source() -> Result<Vec<DataTestCaseDesc<String>>, String> {
+    Err(String::from("cannot connect to the database"))
+}
+
+#[datatest::data(broken_source())]
+fn broken_data(_data: String) {}
+
+/// Duplicate test cases fail the whole test, if asked to
+#[datatest::data("tests/duplicates.yaml", duplicates = fail)]
+fn duplicate_data(_data: String) {
+    DUPLICATES_RAN.store(true, Ordering::SeqCst);
+}
+
+#[datatest::data("tests/strings.yaml")]
+fn healthy_data(_data: String) {
+    HEALTHY_RAN.store(true, Ordering::SeqCst);
+}
+
+fn main() {
+    assert!(
+        !common::run_tests(),
+        "run with enumeration errors must fail"
+    );
+    assert!(
+        HEALTHY_RAN.load(Ordering::SeqCst),
+        "tests without enumeration errors must still run"
+    );
+    assert!(
+        !DUPLICATES_RAN.load(Ordering::SeqCst),
+        "test with duplicate cases must not run"
+    );
+}
@@ -0,0 +1,67 @@
+//! With the fixture manifest, fixtures which differ from the hashes recorded in the manifest fail
+//! the run; `DATATEST_UPDATE_MANIFEST` regenerates the manifest.
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static RAN: AtomicUsize = AtomicUsize::new(0);
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+fn files_test(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(_data: String) {
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+fn main() {
+    let dir = common::temp_path("fixture-manifest");
+    let path = dir.join("fixtures.sha256");
+    std::fs::create_dir_all(&dir).unwrap();
+    // Entry of the fixture of another test binary is kept when the manifest is regenerated
+    let other = format!("{}  tests/other/fixture.txt\n", "1".repeat(64));
+    std::fs::write(&path, &other).unwrap();
+    std::env::set_var("DATATEST_MANIFEST", &path);
+
+    std::env::set_var("DATATEST_UPDATE_MANIFEST", "1");
+    datatest::runner(&[]);
+    std::env::remove_var("DATATEST_UPDATE_MANIFEST");
+    assert_eq!(RAN.load(Ordering::SeqCst), 5);
+    let manifest = std::fs::read_to_string(&path).unwrap();
+    assert!(
+        manifest.contains(
+            "16c0cc72f738be4409b79f7186b95233dcb18423bdc46013a025290b8cd8381c  tests/strings.yaml\n"
+        ),
+        "{}",
+        manifest
+    );
+    assert!(manifest.contains(&other), "{}", manifest);
+    // Files not used by any test are in the manifest, too
+    assert!(
+        manifest.contains("  tests/test-cases/case-03.output-linked.txt\n"),
+        "{}",
+        manifest
+    );
+
+    // Manifest matches the fixtures
+    datatest::runner(&[]);
+    assert_eq!(RAN.load(Ordering::SeqCst), 10);
+
+    let tampered = manifest
+        .lines()
+        .map(|line| match line.split_once("  ") {
+            Some((_, "tests/strings.yaml")) => format!("{}  tests/strings.yaml", "0".repeat(64)),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    std::fs::write(&path, tampered).unwrap();
+    assert!(!common::run_tests(), "run with changed fixtures must fail");
+    assert_eq!(RAN.load(Ordering::SeqCst), 15, "tests must still run");
+}
@@ -0,0 +1,38 @@
+//! Tests marked with `#[only]` are the only tests executed, but the whole run is still failed (so
+//! focus markers are not committed by accident).
+mod common;
+
+use datatest::test;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static FOCUSED_RAN: AtomicBool = AtomicBool::new(false);
+static UNFOCUSED_RAN: AtomicBool = AtomicBool::new(false);
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+#[only]
+fn focused(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+    FOCUSED_RAN.store(true, Ordering::SeqCst);
+}
+
+#[datatest::data("tests/strings.yaml")]
+fn unfocused_data(_data: String) {
+    UNFOCUSED_RAN.store(true, Ordering::SeqCst);
+}
+
+#[test]
+fn unfocused_regular() {
+    UNFOCUSED_RAN.store(true, Ordering::SeqCst);
+}
+
+fn main() {
+    assert!(!common::run_tests(), "run with focused tests must fail");
+    assert!(FOCUSED_RAN.load(Ordering::SeqCst), "focused test must run");
+    assert!(
+        !UNFOCUSED_RAN.load(Ordering::SeqCst),
+        "tests without `#[only]` must not run"
+    );
+}
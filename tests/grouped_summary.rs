@@ -0,0 +1,50 @@
+//! Grouped summary printed after the run breaks the outcomes down by the data file of the
+//! data-driven tests and by the root directory of the files-driven tests.
+mod common;
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+fn files_test(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(data: String) {
+    let half = data.len() / 2;
+    assert_eq!(data[0..half], data[half..]);
+}
+
+#[datatest::data("tests/strings.yaml")]
+#[ignore]
+fn ignored_data_test(_data: String) {}
+
+fn main() {
+    datatest::runner(&[]);
+    if common::is_child() {
+        return;
+    }
+
+    let output = common::rerun(&[]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = common::stdout(&output);
+    let data_files = stdout
+        .split("summary by data file:\n")
+        .nth(1)
+        .expect("summary by data file is printed");
+    assert!(
+        data_files.starts_with("    ok     tests/strings.yaml: 3 passed; 0 failed; 3 ignored; "),
+        "{}",
+        stdout
+    );
+    let roots = stdout
+        .split("summary by root directory:\n")
+        .nth(1)
+        .expect("summary by root directory is printed");
+    assert!(
+        roots.starts_with("    ok     tests/test-cases: 2 passed; 0 failed; 0 ignored; "),
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,36 @@
+//! With `DATATEST_KEEP=on-failure`, sandboxes of the failed test cases are kept for inspection,
+//! while sandboxes of the passed cases are removed.
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Sandbox directory of each case and if the case failed.
+static SANDBOXES: Mutex<Vec<(PathBuf, bool)>> = Mutex::new(Vec::new());
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+}, sandbox = copy)]
+fn modifies_fixture(input: &Path) {
+    std::fs::write(input, "modified").unwrap();
+    let fail = input.ends_with("case-02.input.txt");
+    let dir = input.parent().unwrap().to_path_buf();
+    SANDBOXES.lock().unwrap().push((dir, fail));
+    assert!(!fail, "second case fails");
+}
+
+fn main() {
+    std::env::set_var("DATATEST_KEEP", "on-failure");
+    assert!(!common::run_tests(), "run with the failed case must fail");
+
+    let sandboxes = SANDBOXES.lock().unwrap();
+    assert_eq!(sandboxes.len(), 2);
+    for (dir, failed) in sandboxes.iter() {
+        assert_eq!(dir.exists(), *failed, "sandbox '{}'", dir.display());
+        if *failed {
+            let modified = std::fs::read_to_string(dir.join("case-02.input.txt")).unwrap();
+            assert_eq!(modified, "modified");
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Tests built at runtime via the builders and registered with `register_suite` run along with the
+//! ones generated by the procedural macros. Test functions could capture the data discovered at
+//! startup.
+use datatest::{DataTestCaseDesc, DataTestDesc, DataTestFn, FilesTestDesc};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static FILES_RAN: AtomicUsize = AtomicUsize::new(0);
+static DATA_RAN: AtomicUsize = AtomicUsize::new(0);
+
+fn files_test(greeting: &str, paths: &[PathBuf]) {
+    let input = std::fs::read_to_string(&paths[0]).unwrap();
+    let output = std::fs::read_to_string(&paths[1]).unwrap();
+    assert_eq!(format!("{}, {}!", greeting, input), output);
+    FILES_RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Cases of the plugins discovered at startup
+fn describe_plugins(plugins: &[String]) -> Result<Vec<DataTestCaseDesc<DataTestFn>>, String> {
+    let cases = plugins.iter().map(|plugin| {
+        let name = plugin.clone();
+        let case = DataTestFn::TestFn(Box::new(move || {
+            assert!(name.is_ascii());
+            DATA_RAN.fetch_add(1, Ordering::SeqCst);
+        }));
+        DataTestCaseDesc::new(case, Some(plugin.clone()), format!("plugin {}", plugin))
+    });
+    Ok(cases.collect())
+}
+
+fn main() {
+    let greeting = String::from("Hello");
+    let files = FilesTestDesc::builder(
+        concat!(module_path!(), "::files_test"),
+        "tests/test-cases",
+        r"^(.*)\.input\.txt",
+        move |paths, _| files_test(&greeting, paths),
+    )
+    .with_param(r"${1}.output.txt")
+    .build();
+    // Stands for the plugins discovered at startup
+    let plugins = vec![
+        String::from("alpha"),
+        String::from("beta"),
+        String::from("gamma"),
+    ];
+    let data = DataTestDesc::builder(concat!(module_path!(), "::plugins"), move || {
+        describe_plugins(&plugins)
+    })
+    .build();
+    let ignored = DataTestDesc::builder(
+        concat!(module_path!(), "::ignored"),
+        Box::new(|| {
+            let case = DataTestFn::TestFn(Box::new(|| panic!("not ready yet")));
+            Ok(vec![DataTestCaseDesc::new(case, None, "pending")])
+        }),
+    )
+    .with_ignore_message("not ready yet")
+    .build();
+    datatest::register_suite(vec![files]);
+    datatest::register_suite(vec![data, ignored]);
+
+    datatest::runner(&[]);
+    assert_eq!(FILES_RAN.load(Ordering::SeqCst), 2);
+    assert_eq!(DATA_RAN.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,49 @@
+//! With `--max-duration`, test cases which would start after the time budget is spent (given their
+//! durations in the previous runs) are ignored as skipped for time, and the run exits with a
+//! distinct status.
+mod common;
+
+#[datatest::data("tests/strings.yaml")]
+fn slow_test(_data: String) {}
+
+fn main() {
+    if common::is_child() {
+        datatest::runner(&[]);
+        return;
+    }
+
+    // Each case took 0.6s before, so only two of them start within a second
+    let timings = common::temp_path("max-duration.json");
+    std::fs::write(
+        &timings,
+        r#"{
+  "slow_test::firstfirst (line 1)": 0.6,
+  "slow_test::secondsecond (line 2)": 0.6,
+  "slow_test::thirdthird (line 3)": 0.6
+}"#,
+    )
+    .unwrap();
+    let output = common::rerun(&["--max-duration", "1s", "--test-threads", "1"])
+        .env("DATATEST_TIMINGS", &timings)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&timings).unwrap();
+    let stdout = common::stdout(&output);
+    assert_eq!(output.status.code(), Some(3), "{}", stdout);
+    assert!(
+        stdout.contains("test slow_test::thirdthird (line 3) ... ignored"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout
+            .contains("budget of 1.0s is not enough, 1 test cases not run):\n    slow_test::third"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("test slow_test::firstfirst (line 1) ... ok"),
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,55 @@
+//! Test cases with `memory_limit` run in a separate process, so a case which runs out of memory
+//! fails without bringing down the whole run.
+mod common;
+
+#[datatest::data("tests/strings.yaml", memory_limit = "256MB")]
+fn data_test(data: String) {
+    match data.as_str() {
+        "firstfirst" => assert!(datatest::cli_flag("forwarded"), "flag is not forwarded"),
+        // Address space is limited, so the allocation fails without touching the memory
+        "secondsecond" => {
+            let huge = vec![1u8; 512 << 20];
+            assert_eq!(huge.len(), 512 << 20);
+        }
+        "thirdthird" => panic!("regular failure"),
+        _ => {}
+    }
+}
+
+fn main() {
+    // Custom flag of the re-run binary is passed on to the child processes running the cases
+    if common::is_child() {
+        datatest::CliFlags::new()
+            .flag("forwarded", "Checked by the test cases")
+            .run();
+        return;
+    }
+
+    let output = common::rerun(&["--forwarded"]).output().unwrap();
+    let stdout = common::stdout(&output);
+    assert!(!output.status.success(), "run must fail: {}", stdout);
+
+    let failures = common::failures()
+        .iter()
+        .map(|failure| {
+            let name = failure["name"].as_str().unwrap().to_string();
+            let message = failure["message"].as_str().unwrap().to_string();
+            (name, message)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        failures,
+        [
+            (
+                "data_test::secondsecond (line 2)".to_string(),
+                "test case exceeded the memory limit of 256MB".to_string()
+            ),
+            (
+                "data_test::thirdthird (line 3)".to_string(),
+                "test case failed in the separate process:\nregular failure".to_string()
+            ),
+        ],
+        "{}",
+        stdout
+    );
+}
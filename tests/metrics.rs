@@ -0,0 +1,37 @@
+//! Durations and outcomes of the test cases are sent to the StatsD server.
+use std::net::UdpSocket;
+use std::time::Duration;
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(_data: String) {}
+
+fn main() {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    server
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let address = format!("statsd://{}", server.local_addr().unwrap());
+    std::env::set_var("DATATEST_METRICS", address);
+    datatest::runner(&[]);
+
+    let mut lines = Vec::new();
+    let mut buffer = [0; 1024];
+    for _ in 0..6 {
+        let len = server.recv(&mut buffer).unwrap();
+        lines.push(String::from_utf8(buffer[..len].to_vec()).unwrap());
+    }
+    let tags = "#test:data_test,fixture:tests/strings.yaml:2";
+    assert!(
+        lines.contains(&format!("datatest.case.passed:1|c|{}", tags)),
+        "{:?}",
+        lines
+    );
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.starts_with("datatest.case.duration:")
+                && line.ends_with(&format!("|ms|{}", tags))),
+        "{:?}",
+        lines
+    );
+}
@@ -0,0 +1,24 @@
+//! If a file derived via template does not exist, only the affected case fails, with a message
+//! naming the test file, the template and the expected path.
+mod common;
+
+#[datatest::files("tests/test-cases", {
+    _input in r"^(.*)\.input\.txt",
+    _expected = r"${1}.expected.txt",
+})]
+fn missing_file(_input: &str, _expected: &str) {
+    panic!("must not be called");
+}
+
+fn main() {
+    let (passed, messages) = common::run_tests_with_panics();
+    assert!(!passed, "run with missing files must fail");
+    assert!(
+        messages.iter().any(|message| message
+            == "file 'tests/test-cases/case-01.expected.txt' derived from the test file \
+                'tests/test-cases/case-01.input.txt' via template '${1}.expected.txt' does not \
+                exist"),
+        "unexpected messages: {:?}",
+        messages
+    );
+}
@@ -0,0 +1,68 @@
+//! Directories of the corpus become nested namespaces of the test names via `{namespace}`
+//! placeholder, so exact filters select whole subtrees of the corpus.
+mod common;
+
+use std::sync::Mutex;
+
+static RAN: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[datatest::files("tests/namespaces", {
+    input in r"\.txt$",
+}, name = "{namespace}")]
+fn files_test(input: &str) {
+    assert!(!input.is_empty());
+    let name = datatest::current_case().unwrap().name().to_string();
+    RAN.lock().unwrap().push(name);
+}
+
+fn main() {
+    datatest::runner(&[]);
+    let mut ran = RAN.lock().unwrap().clone();
+    ran.sort();
+    if common::is_child() {
+        assert_eq!(
+            ran,
+            [
+                "parser::v2::arrays::case_001",
+                "parser::v2::arrays::case_002",
+                "parser::v2::objects::case_003",
+            ]
+        );
+        return;
+    }
+    assert_eq!(
+        ran,
+        [
+            "lexer::case_005",
+            "parser::v20::case_004",
+            "parser::v2::arrays::case_001",
+            "parser::v2::arrays::case_002",
+            "parser::v2::objects::case_003",
+        ]
+    );
+
+    // Exact filter naming the namespace selects the tests under it, but not under `parser::v20`
+    let output = common::rerun(&["--exact", "parser::v2"]).output().unwrap();
+    let stdout = common::stdout(&output);
+    assert!(output.status.success(), "{}", stdout);
+    let namespaces = stdout
+        .split("summary by namespace:\n")
+        .nth(1)
+        .expect("summary by namespace is printed");
+    let lines = namespaces
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .map(|line| line.rsplit_once("; ").unwrap().0)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            "    ok     parser: 3 passed; 0 failed; 0 ignored",
+            "    ok     parser::v2: 3 passed; 0 failed; 0 ignored",
+            "    ok     parser::v2::arrays: 2 passed; 0 failed; 0 ignored",
+            "    ok     parser::v2::objects: 1 passed; 0 failed; 0 ignored",
+        ],
+        "{}",
+        stdout
+    );
+}
@@ -0,0 +1,45 @@
+//! Files which names are not valid UTF-8 are still enumerated: test names are escaped, but tests
+//! get the real paths.
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static RAN: AtomicUsize = AtomicUsize::new(0);
+
+    #[datatest::files("target/non-utf8-cases", {
+        input in r"^(.*)\.input\.txt",
+        output = r"${1}.output.txt",
+    })]
+    fn non_utf8(input: &Path, output: &str) {
+        assert_eq!(
+            input.file_name(),
+            Some(OsStr::from_bytes(b"caf\xE9.input.txt"))
+        );
+        assert_eq!(output, "Hello!");
+        RAN.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn main() {
+        let root = Path::new("target/non-utf8-cases");
+        std::fs::create_dir_all(root).unwrap();
+        std::fs::write(root.join(OsStr::from_bytes(b"caf\xE9.input.txt")), "").unwrap();
+        std::fs::write(
+            root.join(OsStr::from_bytes(b"caf\xE9.output.txt")),
+            "Hello!",
+        )
+        .unwrap();
+
+        // Escaped name is used to select the test
+        std::env::set_var("DATATEST_CASE", r"non_utf8::caf\xE9.input.txt");
+        datatest::runner(&[]);
+        assert_eq!(RAN.load(Ordering::SeqCst), 1);
+    }
+}
+
+fn main() {
+    #[cfg(target_os = "linux")]
+    linux::main();
+}
@@ -0,0 +1,116 @@
+//! Test cases are enumerated in a deterministic order: files are ordered by path, data cases are
+//! ordered by name, unless custom order is given via `order_by` option.
+mod common;
+
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+static ORDER: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn reversed_path(path: &Path) -> Reverse<PathBuf> {
+    Reverse(path.to_path_buf())
+}
+
+// Key function takes a reference to the test case type
+#[allow(clippy::ptr_arg)]
+fn reversed_case(case: &String) -> Reverse<String> {
+    Reverse(case.clone())
+}
+
+/// Test case type borrowing from the source file
+#[derive(Deserialize)]
+struct CorpusLine<'a> {
+    #[serde(borrow)]
+    text: &'a str,
+    words: usize,
+}
+
+// Longer lines first, lines of the same length keep the order of the source
+fn word_count(line: &CorpusLine<'_>) -> Reverse<usize> {
+    Reverse(line.words)
+}
+
+#[datatest::data(::datatest::json_lines("tests/corpus.jsonl"), order_by = word_count)]
+fn data_borrowed_ordered(line: CorpusLine<'_>) {
+    ORDER
+        .lock()
+        .unwrap()
+        .push(format!("data_borrowed_ordered {}", line.text));
+}
+
+/// Key is computed once for each case; cases which cannot be deserialized go last and fail on their
+/// own
+#[datatest::data(::datatest::json_lines("tests/corpus-invalid.jsonl"), order_by = word_count)]
+fn data_borrowed_invalid(line: CorpusLine<'_>) {
+    ORDER
+        .lock()
+        .unwrap()
+        .push(format!("data_borrowed_invalid {}", line.text));
+}
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+})]
+fn files_default(input: &str) {
+    ORDER
+        .lock()
+        .unwrap()
+        .push(format!("files_default {}", input));
+}
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+}, order_by = reversed_path)]
+fn files_ordered(input: &str) {
+    ORDER
+        .lock()
+        .unwrap()
+        .push(format!("files_ordered {}", input));
+}
+
+#[datatest::data("tests/duplicates.yaml")]
+fn data_default(data: String) {
+    ORDER.lock().unwrap().push(format!("data_default {}", data));
+}
+
+#[datatest::data("tests/strings.yaml", order_by = reversed_case)]
+fn data_ordered(data: String) {
+    ORDER.lock().unwrap().push(format!("data_ordered {}", data));
+}
+
+fn main() {
+    // Run tests one by one, in the order they are given to the test harness
+    std::env::set_var("RUST_TEST_THREADS", "1");
+    assert!(!common::run_tests(), "invalid case must fail");
+    assert_eq!(
+        *ORDER.lock().unwrap(),
+        [
+            "data_borrowed_invalid one two three",
+            "data_borrowed_invalid one",
+            "data_borrowed_ordered the quick brown fox",
+            "data_borrowed_ordered hello world",
+            "data_borrowed_ordered zero copy",
+            "data_default firstfirst",
+            "data_default firstfirst",
+            "data_default secondsecond",
+            "data_ordered thirdthird",
+            "data_ordered secondsecond",
+            "data_ordered firstfirst",
+            "files_default Kylie",
+            "files_default Rahid",
+            "files_ordered Rahid",
+            "files_ordered Kylie",
+        ]
+    );
+
+    let failures = common::failures();
+    assert_eq!(failures.len(), 1, "{:?}", failures);
+    let message = failures[0]["message"].as_str().unwrap();
+    assert!(
+        message.starts_with("cannot parse test case at tests/corpus-invalid.jsonl:2: "),
+        "{}",
+        message
+    );
+}
@@ -0,0 +1,31 @@
+//! With `DATATEST_ORPHANS=fail`, the run fails if some files under root directories are not used
+//! by any test.
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static RAN: AtomicUsize = AtomicUsize::new(0);
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+fn files_test(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Uses only the input file, so `case-03.output-linked.txt` is not used by any test
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input-linked\.txt",
+})]
+fn files_test_linked(input: &str) {
+    assert!(!input.is_empty());
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+fn main() {
+    std::env::set_var("DATATEST_ORPHANS", "fail");
+    assert!(!common::run_tests(), "run with orphaned fixtures must fail");
+    assert_eq!(RAN.load(Ordering::SeqCst), 3, "tests must still run");
+}
@@ -0,0 +1,15 @@
+//! Failures of the quarantined cases do not fail the run, unless the quarantine expired.
+mod common;
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(data: String) {
+    assert_eq!(data, "thirdthird", "{} is flaky", data);
+}
+
+fn main() {
+    std::env::set_var("DATATEST_QUARANTINE", "tests/quarantine.toml");
+    assert!(!common::run_tests(), "run must fail");
+
+    // Quarantine of the second case has expired, so it fails the run
+    assert_eq!(common::failed_cases(), ["data_test::secondsecond (line 2)"]);
+}
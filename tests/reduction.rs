@@ -0,0 +1,25 @@
+//! Failing input is reduced while the failure reproduces and written to the artifacts directory.
+mod common;
+
+use std::path::Path;
+
+#[datatest::files("tests/reduce", { input in r"\.txt$" }, reduce = lines)]
+fn reduced(input: &str) {
+    assert!(!input.lines().any(|line| line == "bad"), "found bad line");
+}
+
+fn main() {
+    let artifacts = Path::new("target").join("reduction-artifacts");
+    let _ = std::fs::remove_dir_all(&artifacts);
+    std::env::set_var("DATATEST_ARTIFACTS", &artifacts);
+
+    assert!(!common::run_tests(), "run with failing case must fail");
+    let reduced = std::fs::read_to_string(artifacts.join("reduced__input.txt")).unwrap();
+    assert_eq!(reduced, "bad\n");
+
+    // Reduced input is listed in the failure manifest
+    assert_eq!(
+        common::failures()[0]["artifacts"],
+        serde_json::json!([artifacts.join("reduced__input.txt")])
+    );
+}
@@ -0,0 +1,53 @@
+//! With `DATATEST_REPEAT=N`, each test case runs `N` times, and cases with different outcomes
+//! across the repetitions fail as flaky.
+mod common;
+
+use datatest::test;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+static STABLE_RAN: AtomicUsize = AtomicUsize::new(0);
+static REGULAR_RAN: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of runs of each case of the flaky test, by the case data.
+static FLAKY_RUNS: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+fn stable_test(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+    STABLE_RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Every case fails on every other run
+#[datatest::data("tests/strings.yaml")]
+fn flaky_test(data: String) {
+    let run = {
+        let mut runs = FLAKY_RUNS.lock().unwrap();
+        let run = runs
+            .get_or_insert_with(HashMap::new)
+            .entry(data)
+            .or_insert(0);
+        *run += 1;
+        *run
+    };
+    assert_eq!(run % 2, 1, "even run");
+}
+
+#[test]
+fn regular_test() {
+    REGULAR_RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+fn main() {
+    std::env::set_var("DATATEST_REPEAT", "3");
+    assert!(!common::run_tests(), "run with flaky tests must fail");
+    assert_eq!(STABLE_RAN.load(Ordering::SeqCst), 2 * 3);
+    assert_eq!(REGULAR_RAN.load(Ordering::SeqCst), 3);
+    let runs = FLAKY_RUNS.lock().unwrap().take().unwrap_or_default();
+    assert_eq!(runs.len(), 3);
+    assert!(runs.values().all(|runs| *runs == 3), "{:?}", runs);
+}
@@ -0,0 +1,100 @@
+//! With `DATATEST_SAMPLE`, only a part of the test cases runs, selected the same way for the same
+//! `DATATEST_SAMPLE_SEED`.
+mod common;
+
+use datatest::test;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+static RAN: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static REGULAR_RAN: AtomicUsize = AtomicUsize::new(0);
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+})]
+fn files_test(input: &str) {
+    RAN.lock().unwrap().push(input.to_string());
+}
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(data: String) {
+    RAN.lock().unwrap().push(data);
+}
+
+#[test]
+fn regular_test() {
+    REGULAR_RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Run the test suite, returning the sorted inputs of the cases which ran.
+fn sampled() -> Vec<String> {
+    datatest::runner(&[]);
+    let mut ran = std::mem::take(&mut *RAN.lock().unwrap());
+    ran.sort();
+    ran
+}
+
+/// Run the test binary with half of the cases sampled, returning the cases which ran and the output.
+fn sampled_in_child(args: &[&str], seed: Option<&str>) -> (String, String) {
+    let mut command = common::rerun(args);
+    command.env("DATATEST_SAMPLE", "50%");
+    if let Some(seed) = seed {
+        command.env("DATATEST_SAMPLE_SEED", seed);
+    }
+    let output = command.output().unwrap();
+    let stdout = common::stdout(&output);
+    assert!(output.status.success(), "{}", stdout);
+    let ran = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("ran: "))
+        .unwrap_or_else(|| panic!("{}", stdout))
+        .to_string();
+    (ran, stdout)
+}
+
+fn main() {
+    if common::is_child() {
+        println!("ran: {}", sampled().join(","));
+        return;
+    }
+
+    std::env::set_var("DATATEST_SAMPLE", "50%");
+    std::env::set_var("DATATEST_SAMPLE_SEED", "42");
+    let first = sampled();
+    // Half of the five cases, rounded up
+    assert_eq!(first.len(), 3, "{:?}", first);
+    assert_eq!(REGULAR_RAN.load(Ordering::SeqCst), 1);
+    assert_eq!(first, sampled(), "same seed must select the same cases");
+
+    let mut subsets = (1..=5)
+        .map(|seed| {
+            std::env::set_var("DATATEST_SAMPLE_SEED", seed.to_string());
+            sampled()
+        })
+        .collect::<Vec<_>>();
+    subsets.dedup();
+    assert!(
+        subsets.len() > 1,
+        "different seeds must select different cases"
+    );
+    std::env::remove_var("DATATEST_SAMPLE");
+    std::env::remove_var("DATATEST_SAMPLE_SEED");
+
+    // Printed seed selects the same cases again
+    let (ran, stdout) = sampled_in_child(&[], None);
+    let seed = stdout
+        .split("DATATEST_SAMPLE_SEED=")
+        .nth(1)
+        .and_then(|rest| rest.split(')').next())
+        .unwrap_or_else(|| panic!("{}", stdout));
+    assert_eq!(sampled_in_child(&[], Some(seed)).0, ran, "seed {}", seed);
+
+    // Cases are sampled out of the ones matching the filter
+    let (ran, stdout) = sampled_in_child(&["data_test"], Some("42"));
+    assert_eq!(ran.split(',').count(), 2, "{}", stdout);
+    assert!(
+        stdout.contains("sample: running 2 out of 3 test cases"),
+        "{}",
+        stdout
+    );
+}
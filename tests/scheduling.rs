@@ -0,0 +1,56 @@
+//! When tests run in parallel, cases with higher priority and the slowest cases are started first.
+//! Order the cases are submitted in is checked via `--dry-run`, which lists them in that order.
+mod common;
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(_data: String) {}
+
+#[datatest::data("tests/strings.yaml", priority = 1)]
+fn urgent_test(_data: String) {}
+
+fn main() {
+    if common::is_child() {
+        datatest::runner(&[]);
+        return;
+    }
+
+    let timings = common::temp_path("schedule.json");
+    std::fs::write(
+        &timings,
+        r#"{
+  "data_test::firstfirst (line 1)": 0.1,
+  "data_test::secondsecond (line 2)": 2.0,
+  "data_test::thirdthird (line 3)": 3.0
+}"#,
+    )
+    .unwrap();
+    let output = common::rerun(&["--dry-run"])
+        .env("DATATEST_TIMINGS", &timings)
+        .env("RUST_TEST_THREADS", "2")
+        .output()
+        .unwrap();
+    std::fs::remove_file(&timings).unwrap();
+    let stdout = common::stdout(&output);
+    assert!(output.status.success(), "{}", stdout);
+
+    // Cases without history are expected to take as long as an average case, so urgent cases keep
+    // their order
+    let submitted = stdout
+        .lines()
+        .take_while(|line| !line.is_empty())
+        .filter(|line| !line.starts_with(' '))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        submitted,
+        [
+            "urgent_test::firstfirst (line 1)",
+            "urgent_test::secondsecond (line 2)",
+            "urgent_test::thirdthird (line 3)",
+            "data_test::thirdthird (line 3)",
+            "data_test::secondsecond (line 2)",
+            "data_test::firstfirst (line 1)",
+        ],
+        "{}",
+        stdout
+    );
+}
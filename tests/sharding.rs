@@ -0,0 +1,63 @@
+//! Test cases are split into shards by their durations measured by the previous runs. Sharded runs
+//! do not update the durations, so the shards run one after another cover every case exactly once.
+mod common;
+
+use std::sync::Mutex;
+
+static RAN: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(data: String) {
+    RAN.lock().unwrap().push(data);
+}
+
+/// Run the tests, returning the sorted data of the cases which ran.
+fn run(shard: Option<&str>) -> Vec<String> {
+    match shard {
+        Some(shard) => std::env::set_var("DATATEST_SHARD", shard),
+        None => std::env::remove_var("DATATEST_SHARD"),
+    }
+    RAN.lock().unwrap().clear();
+    datatest::runner(&[]);
+    let mut ran = RAN.lock().unwrap().clone();
+    ran.sort();
+    ran
+}
+
+fn main() {
+    let timings = common::temp_path("timings.json");
+    std::env::set_var("DATATEST_TIMINGS", &timings);
+    // First case took as long as the other two together
+    let history = r#"{
+  "data_test::firstfirst (line 1)": 2.0,
+  "data_test::secondsecond (line 2)": 1.0,
+  "data_test::thirdthird (line 3)": 1.0,
+  "other_test::case (line 1)": 5.0
+}"#;
+    std::fs::write(&timings, history).unwrap();
+
+    let first = run(Some("1/2"));
+    assert_eq!(std::fs::read_to_string(&timings).unwrap(), history);
+    let second = run(Some("2/2"));
+    assert_eq!(first, ["firstfirst"]);
+    assert_eq!(second, ["secondsecond", "thirdthird"]);
+    let mut all = first.into_iter().chain(second).collect::<Vec<_>>();
+    all.sort();
+    assert_eq!(all, ["firstfirst", "secondsecond", "thirdthird"]);
+
+    // Durations are updated by the run of the whole suite, durations of the cases which did not
+    // run are kept
+    assert_eq!(run(None).len(), 3);
+    let updated: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&timings).unwrap()).unwrap();
+    std::fs::remove_file(&timings).unwrap();
+    assert_eq!(updated["other_test::case (line 1)"], 5.0);
+    assert!(updated["data_test::firstfirst (line 1)"].as_f64().unwrap() < 1.0);
+    assert!(
+        updated["data_test::secondsecond (line 2)"]
+            .as_f64()
+            .unwrap()
+            < 1.0
+    );
+    assert!(updated["data_test::thirdthird (line 3)"].as_f64().unwrap() < 1.0);
+}
@@ -0,0 +1,66 @@
+//! Shared resources are set up once, before the first case using them, and torn down once all
+//! the tests finish (dependent resources first).
+use datatest::{Shared, SharedResource};
+use std::sync::Mutex;
+
+static EVENTS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+fn event(event: &'static str) {
+    EVENTS.lock().unwrap().push(event);
+}
+
+struct Server;
+
+impl SharedResource for Server {
+    fn setup() -> Self {
+        event("server up");
+        Server
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        event("server down");
+    }
+}
+
+/// Resource using another resource
+struct Client {
+    _server: Shared<Server>,
+}
+
+impl SharedResource for Client {
+    fn setup() -> Self {
+        let server = Shared::get();
+        event("client up");
+        Client { _server: server }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        event("client down");
+    }
+}
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(_data: String, _client: &datatest::Shared<Client>) {
+    event("case");
+}
+
+fn main() {
+    datatest::runner(&[]);
+
+    assert_eq!(
+        *EVENTS.lock().unwrap(),
+        [
+            "server up",
+            "client up",
+            "case",
+            "case",
+            "case",
+            "client down",
+            "server down"
+        ]
+    );
+}
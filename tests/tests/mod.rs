@@ -79,6 +79,16 @@ fn symbolic_files_test_strings(input: &str, output: &str) {
     assert_eq!(format!("Hello, {}!", input), output);
 }
 
+/// Same as above, but uses `cfg` option to only compile the test (and generated items) on unix
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input-linked\.txt",
+    output = r"${1}.output-linked.txt",
+}, cfg = unix)]
+#[test]
+fn symbolic_files_test_cfg(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
 /// Can declare with `&std::path::Path` to get path instead of the content
 #[datatest::files("tests/test-cases", {
     input in r"^(.*)\.input\.txt",
@@ -105,6 +115,29 @@ fn files_test_slices(input: &[u8], output: &[u8]) {
     assert_eq!(actual, output);
 }
 
+/// Derived paths spelled differently from the paths found by the directory walk (here, with an
+/// extra `.` component) still resolve to the walked files
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)/(.*)\.input\.txt",
+    output = r"${1}/./${2}.output.txt",
+})]
+#[test]
+fn files_test_derived_path_spelling(input: &str, output: &Path) {
+    assert!(output.display().to_string().contains("/./"));
+    let output = std::fs::read_to_string(output).unwrap();
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// Paths are given as is, so the file derived via template is not required to exist
+#[datatest::files("tests/test-cases", {
+    _input in r"^(.*)\.input\.txt",
+    expected = r"${1}.expected.txt",
+})]
+#[test]
+fn files_test_missing_path(_input: &str, expected: &Path) {
+    assert!(!expected.exists());
+}
+
 fn is_ignore(path: &Path) -> bool {
     path.display().to_string().ends_with("case-02.input.txt")
 }
@@ -119,6 +152,148 @@ fn files_test_ignore(input: &str) {
     assert_eq!(input, "Kylie");
 }
 
+/// Ignore function could also be given as an inline closure
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt" if !|path: &Path| path.ends_with("case-02.input.txt"),
+})]
+#[test]
+fn files_test_ignore_closure(input: &str) {
+    assert_eq!(input, "Kylie");
+}
+
+/// Test names could be customized with a template
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)/(?P<case>[^/]*)\.input\.txt",
+    output = r"${1}/${case}.output.txt",
+}, name = "{fn}_{case}")]
+#[test]
+fn files_test_name_template(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// Context shared by test helpers, not provided by the test itself
+#[derive(Default)]
+struct TestContext {
+    greeting: String,
+}
+
+fn hello_context() -> TestContext {
+    TestContext {
+        greeting: "Hello".to_string(),
+    }
+}
+
+/// Arguments without mappings could be constructed via `Default` (or via the given function)
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+}, unmapped = default)]
+#[test]
+fn files_test_unmapped_default(ctx: TestContext, input: &str, output: &str) {
+    assert!(ctx.greeting.is_empty());
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+}, unmapped = hello_context)]
+#[test]
+fn files_test_unmapped_fn(input: &str, ctx: TestContext, output: &str) {
+    assert_eq!(format!("{}, {}!", ctx.greeting, input), output);
+}
+
+/// Each test is run for every combination of the matrix parameter values
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+}, matrix(greeting = ["Hello", "Hi"], excited = [true, false]))]
+#[test]
+fn files_test_matrix(greeting: &str, excited: bool, input: &str, output: &str) {
+    let expected = format!("{}, {}{}", greeting, input, if excited { "!" } else { "" });
+    assert_eq!(expected == output, greeting == "Hello" && excited);
+}
+
+/// Matrix parameters could be used in the name template
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)/(?P<case>[^/]*)\.input\.txt",
+    output = r"${1}/${case}.output.txt",
+}, matrix(punctuation = ['!', '?']), name = "{fn}_{case}_{punctuation}")]
+#[test]
+fn files_test_matrix_name(punctuation: char, input: &str, output: &str) {
+    let greeting = format!("Hello, {}{}", input, punctuation);
+    assert_eq!(greeting == output, punctuation == '!');
+}
+
+/// Generate a hidden `#[test]` function for every test file, so IDEs could run individual cases
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+}, ide = true)]
+#[test]
+fn files_test_ide(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// Root directory could be optional (for example, populated from an optional git submodule)
+#[datatest::files("tests/missing-test-cases", {
+    _input in r"^(.*)\.input\.txt",
+}, missing_root = skip)]
+#[test]
+fn files_test_missing_root_skip(_input: &str) {
+    panic!("there are no test files!");
+}
+
+#[datatest::files("tests/missing-test-cases", {
+    _input in r"^(.*)\.input\.txt",
+}, missing_root = warn)]
+#[test]
+fn files_test_missing_root_warn(_input: &str) {
+    panic!("there are no test files!");
+}
+
+/// If `datatest` is re-exported by some other crate (or module), path to it should be given via
+/// `crate` option.
+mod test_utils {
+    pub use datatest::*;
+}
+
+#[test_utils::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+}, crate = "crate::test_utils")]
+#[test]
+fn files_test_reexported(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+#[test_utils::data("tests/tests.yaml", crate = "crate::test_utils")]
+#[test]
+fn data_test_reexported(data: &GreeterTestCase) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Attribute could be put on the module, so every test function inside shares the same arguments.
+/// Functions can override mappings and options with their own attribute.
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+mod files_test_module {
+    #[test]
+    fn inherited(input: &str, output: &str) {
+        assert_eq!(format!("Hello, {}!", input), output);
+    }
+
+    #[datatest::files({
+        input in r"^(.*)\.input-linked\.txt",
+        output = r"${1}.output-linked.txt",
+    }, cfg = unix)]
+    fn overridden(input: &str, output: &str) {
+        assert_eq!(format!("Hello, {}!", input), output);
+    }
+}
+
 /// Regular tests are also allowed!
 #[test]
 fn simple_test() {
@@ -135,6 +310,13 @@ fn simple_test_ignored() {
     panic!("ignored test!")
 }
 
+/// Reason for ignoring the test could be given, too
+#[test]
+#[ignore = "reason is printed by the test runner"]
+fn simple_test_ignored_reason() {
+    panic!("ignored test!")
+}
+
 /// Regular tests are also allowed! Also, could use `#[should_panic]`
 #[test]
 #[should_panic]
@@ -182,6 +364,15 @@ fn data_test_take_owned(mut data: GreeterTestCase) {
     assert_eq!(data.expected, format!("Hi, {}!", data.name));
 }
 
+/// Or borrow mutably, as the test owns the case anyway
+#[datatest::data("tests/tests.yaml")]
+#[test]
+fn data_test_take_mut(data: &mut GreeterTestCase) {
+    data.expected += "boo!";
+    data.name += "!boo";
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
 #[ignore]
 #[datatest::data("tests/tests.yaml")]
 #[test]
@@ -189,6 +380,14 @@ fn data_test_line_only_hoplessly_broken(_data: &GreeterTestCase) {
     panic!("this test always fails, but this is okay because we marked it as ignored!")
 }
 
+/// Reason for ignoring the data test is printed for each of its cases
+#[ignore = "reason is printed by the test runner"]
+#[datatest::data("tests/tests.yaml")]
+#[test]
+fn data_test_ignored_reason(_data: &GreeterTestCase) {
+    panic!("ignored test!")
+}
+
 /// This test case item implements [`std::fmt::Display`], which is used to generate test name
 #[derive(Deserialize)]
 struct GreeterTestCaseNamed {
@@ -208,6 +407,84 @@ fn data_test_name_and_line(data: &GreeterTestCaseNamed) {
     assert_eq!(data.expected, format!("Hi, {}!", data.name));
 }
 
+/// This test case item implements [`datatest::TestName`], which takes precedence over its
+/// multiline [`std::fmt::Display`] implementation
+#[derive(Deserialize)]
+struct GreeterTestCaseTestName {
+    name: String,
+    expected: String,
+}
+
+impl fmt::Display for GreeterTestCaseTestName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "name: {}\nexpected: {}", self.name, self.expected)
+    }
+}
+
+impl datatest::TestName for GreeterTestCaseTestName {
+    fn test_name(&self) -> String {
+        self.name.to_lowercase()
+    }
+}
+
+#[datatest::data("tests/tests.yaml")]
+#[test]
+fn data_test_test_name(data: &GreeterTestCaseTestName) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+    let case = datatest::current_case().unwrap();
+    let expected = format!("data_test_test_name::{} (line ", data.name.to_lowercase());
+    assert!(case.name().contains(&expected), "{}", case.name());
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Greeting {
+    input: String,
+    expected: String,
+}
+
+/// With `named = true`, cases are read as `datatest::Named<T>` and named after the given name, but
+/// the test function still receives `T`
+#[datatest::data("tests/named.yaml", named = true)]
+#[test]
+fn data_test_named(data: &Greeting) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.input));
+    let case = datatest::current_case().unwrap();
+    let named = ["value", "fields", "keyed", "pair"]
+        .iter()
+        .any(|name| case.name().contains(&format!("::{} (line ", name)));
+    assert!(named, "{}", case.name());
+}
+
+/// Test names could be customized with a template, which can also refer to test case fields
+#[datatest::data("tests/tests.yaml", name = "{fn}::{name}_{index}")]
+#[test]
+fn data_test_name_template(data: &GreeterTestCase) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+#[datatest::data("tests/tests.yaml", unmapped = default)]
+#[test]
+fn data_test_unmapped_default(data: &GreeterTestCase, ctx: TestContext) {
+    assert!(ctx.greeting.is_empty());
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Same as for `#[files(..)]`, attribute could be put on the module
+#[datatest::data("tests/tests.yaml")]
+mod data_test_module {
+    use super::GreeterTestCase;
+
+    #[test]
+    fn inherited(data: &GreeterTestCase) {
+        assert_eq!(data.expected, format!("Hi, {}!", data.name));
+    }
+
+    #[datatest::data(name = "{fn}_{index}")]
+    fn overridden(data: &GreeterTestCase) {
+        assert_eq!(data.expected, format!("Hi, {}!", data.name));
+    }
+}
+
 /// Can also take string inputs
 #[datatest::data("tests/strings.yaml")]
 #[test]
@@ -216,6 +493,13 @@ fn data_test_string(data: String) {
     assert_eq!(data[0..half], data[half..]);
 }
 
+/// Test (and generated items) are not compiled at all if `cfg` predicate does not hold
+#[datatest::data("tests/strings.yaml", cfg = any())]
+#[test]
+fn data_test_cfg_never(_data: String) {
+    panic!("should not be compiled!")
+}
+
 /// Can also use `::datatest::yaml` explicitly
 #[datatest::data(::datatest::yaml("tests/strings.yaml"))]
 #[test]
@@ -237,13 +521,13 @@ fn load_test_cases(path: &str) -> Vec<::datatest::DataTestCaseDesc<StringTestCas
     lines
         .chunks(2)
         .enumerate()
-        .map(|(idx, line)| ::datatest::DataTestCaseDesc {
-            case: StringTestCase {
+        .map(|(idx, line)| {
+            let case = StringTestCase {
                 input: line[0].to_string(),
                 output: line[1].to_string(),
-            },
-            name: Some(line[0].to_string()),
-            location: format!("line {}", idx * 2),
+            };
+            let name = Some(line[0].to_string());
+            ::datatest::DataTestCaseDesc::new(case, name, format!("line {}", idx * 2))
         })
         .collect()
 }
@@ -254,3 +538,826 @@ fn load_test_cases(path: &str) -> Vec<::datatest::DataTestCaseDesc<StringTestCas
 fn data_test_custom(data: StringTestCase) {
     assert_eq!(data.output, format!("Hello, {}!", data.input));
 }
+
+fn decomposed_test_cases() -> Vec<::datatest::DataTestCaseDesc<String>> {
+    // "Café" with combining acute accent
+    let case = String::from("Cafe\u{301}");
+    vec![::datatest::DataTestCaseDesc::new(
+        case.clone(),
+        Some(case),
+        "line 1",
+    )]
+}
+
+/// Test names are normalized to NFC, so names derived from decomposed (NFD) file names or test case
+/// data match the same filters on all platforms
+#[datatest::data(decomposed_test_cases())]
+#[test]
+fn data_test_nfc_name(data: String) {
+    assert_eq!(data, "Cafe\u{301}");
+    let case = datatest::current_case().unwrap();
+    assert!(case.name().contains("::Caf\u{e9} (line 1)"), "{}", case.name());
+}
+
+/// Test cases with the same name or content run as they are by default
+#[datatest::data("tests/duplicates.yaml")]
+#[test]
+fn data_test_duplicates_keep(data: String) {
+    let half = data.len() / 2;
+    assert_eq!(data[0..half], data[half..]);
+}
+
+/// With `duplicates = index`, index of the test case is added to the name of the duplicate
+#[datatest::data("tests/duplicates.yaml", duplicates = index)]
+#[test]
+fn data_test_duplicates_index(data: String) {
+    let half = data.len() / 2;
+    assert_eq!(data[0..half], data[half..]);
+}
+
+/// Source could return `Result`, so enumeration errors fail this test only
+#[datatest::data(::datatest::try_yaml("tests/tests.yaml"))]
+#[test]
+fn data_test_try_yaml(data: &GreeterTestCase) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Parse errors point to the data file, location and the case being parsed
+#[test]
+fn data_parse_error_location() {
+    match ::datatest::try_yaml::<GreeterTestCase>("tests/invalid.yaml") {
+        Ok(_) => panic!("invalid test cases must not be parsed"),
+        Err(err) => assert!(
+            err.starts_with("cannot parse test cases from 'tests/invalid.yaml' (line 4, column 13, case #1)"),
+            "unexpected error: {}",
+            err
+        ),
+    }
+}
+
+/// Validation pre-pass reports all invalid cases of the data file at once
+#[test]
+fn data_validate_invalid_cases() {
+    let problems = ::datatest::validate_data_file::<GreeterTestCase>("tests/invalid.yaml", None);
+    assert_eq!(
+        problems,
+        [
+            "invalid test case at tests/invalid.yaml:3: invalid type: sequence, expected a string",
+            "invalid test case at tests/invalid.yaml:5: missing field `expected`",
+        ]
+    );
+}
+
+/// Cases are validated against the JSON Schema as well
+#[cfg(feature = "json_schema")]
+#[test]
+fn data_validate_schema() {
+    let problems = ::datatest::validate_data_file::<serde_json::Value>(
+        "tests/invalid.yaml",
+        Some("tests/greeter.schema.json"),
+    );
+    assert_eq!(
+        problems,
+        [
+            "test case at tests/invalid.yaml:3 does not match the schema: [\"Hi, Re-L!\"] is not of type \"string\" (at `/expected`)",
+            "test case at tests/invalid.yaml:5 does not match the schema: \"expected\" is a required property",
+        ]
+    );
+}
+
+/// Positive and negative cases could share the same test function: negative cases declare the
+/// expected error
+#[derive(Deserialize)]
+struct ParseTestCase {
+    input: String,
+    #[serde(default)]
+    expected: Option<i32>,
+    #[serde(default, alias = "expect_err")]
+    error: Option<String>,
+}
+
+#[datatest::data("tests/parse.yaml")]
+#[test]
+fn data_test_expected_error(case: &ParseTestCase) {
+    if let Some(value) = ::datatest::assert_err_matches(case.input.parse::<i32>(), &case.error) {
+        assert_eq!(Some(value), case.expected);
+    }
+}
+
+#[test]
+#[should_panic(expected = "expected error containing `invalid digit`, got Ok(42)")]
+fn expected_error_got_ok() {
+    ::datatest::assert_err_matches("42".parse::<i32>(), &Some("invalid digit"));
+}
+
+#[test]
+#[should_panic(expected = "expected Ok, got error `invalid digit found in string`")]
+fn unexpected_error() {
+    ::datatest::assert_err_matches("forty two".parse::<i32>(), &None::<String>);
+}
+
+#[derive(Deserialize)]
+struct EnvTestCase {
+    variable: String,
+    expected: String,
+}
+
+/// Environment variables given by the `env` field are set for the duration of the test case
+#[datatest::data("tests/env.yaml")]
+#[test]
+fn data_test_env(case: EnvTestCase) {
+    assert_eq!(std::env::var(&case.variable).unwrap(), case.expected);
+}
+
+/// Options of the test cases in TOML files are given under the reserved `datatest` table
+#[datatest::data("tests/env.toml")]
+#[test]
+fn data_test_env_toml(case: EnvTestCase) {
+    assert_eq!(std::env::var(&case.variable).unwrap(), case.expected);
+}
+
+/// Test files could declare how they are tested via directives
+#[datatest::files("tests/lit", {
+    input in r"\.txt$" if !::datatest::Directives::is_skipped,
+})]
+#[test]
+fn files_test_directives(input: ::datatest::Directives) {
+    input.run();
+}
+
+#[test]
+fn parse_directives() {
+    let source = "// RUN: mycc %s\n// not a directive: x\nint x;\n;; CHECK: one\n# CHECK: two\n";
+    let directives = ::datatest::Directives::parse("input.c", source.to_string());
+    assert_eq!(directives.get("RUN"), Some("mycc %s"));
+    assert_eq!(
+        directives.get_all("CHECK").collect::<Vec<_>>(),
+        vec!["one", "two"]
+    );
+    assert_eq!(
+        directives.iter().map(|d| d.line).collect::<Vec<_>>(),
+        vec![1, 4, 5]
+    );
+    assert_eq!(directives.skip_reason(), None);
+}
+
+#[test]
+fn filecheck_matches() {
+    let checks = "// CHECK: fn main\n// CHECK-NEXT: ret {{[0-9]+}}\n// CHECK-NOT: panic\n";
+    ::datatest::filecheck::verify("define\nfn main\nret 0\nend\n", checks);
+}
+
+#[test]
+#[should_panic(expected = "CHECK-NEXT: ret {{[0-9]+}} (check on line 2) did not match")]
+fn filecheck_next_mismatch() {
+    let checks = "// CHECK: fn main\n// CHECK-NEXT: ret {{[0-9]+}}\n";
+    ::datatest::filecheck::verify("fn main\nnop\nret 0\n", checks);
+}
+
+#[test]
+#[should_panic(expected = "CHECK-NOT: panic (check on line 2) matched")]
+fn filecheck_not_matched() {
+    let checks = "// CHECK: fn main\n// CHECK-NOT: panic\n// CHECK: ret\n";
+    ::datatest::filecheck::verify("fn main\npanic!\nret 0\n", checks);
+}
+
+/// Expected output could be given in the comments of the test file
+#[datatest::files("tests/annotated", {
+    input in r"\.txt$",
+    expected = comments("#~ expect:"),
+})]
+#[test]
+fn files_test_comments(input: &str, expected: &::datatest::Expectations) {
+    let sums = input
+        .lines()
+        .filter_map(|line| line.split("#~").next())
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.split_whitespace()
+                .map(|n| n.parse::<i32>().unwrap())
+                .sum::<i32>()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    assert!(!expected.is_empty());
+    assert_eq!(sums, expected.texts());
+}
+
+/// Single file could be split into multiple cases on the delimiter
+#[datatest::data(::datatest::segments("tests/segments.txt", "===="))]
+#[test]
+fn data_test_segments(segment: ::datatest::Segment) {
+    let file = std::fs::read_to_string("tests/segments.txt").unwrap();
+    let mut lines = segment.text.lines();
+    let expr = lines.next().unwrap();
+    // Segment knows where its contents start in the file
+    assert_eq!(file.lines().nth(segment.line - 1), Some(expr));
+    let parts = expr.split_whitespace().collect::<Vec<_>>();
+    let (a, b) = (parts[0].parse::<i32>().unwrap(), parts[2].parse::<i32>().unwrap());
+    let value = match parts[1] {
+        "+" => a + b,
+        "*" => a * b,
+        op => panic!("unknown operator {}", op),
+    };
+    assert_eq!(value.to_string(), lines.next().unwrap());
+}
+
+/// Each failure persisted by `proptest` is a separate test case
+#[datatest::data(::datatest::proptest_regressions("tests/proptest-regressions"))]
+#[test]
+fn data_test_proptest_regressions(seed: ::datatest::RegressionSeed) {
+    assert!(seed.seed.starts_with("cc "));
+    assert_eq!(seed.seed.len(), 3 + 64);
+    assert!(seed.shrinks_to.unwrap().starts_with("input = "));
+}
+
+/// Replaying a persisted failure shrinks the generated value to the recorded one
+#[cfg(feature = "proptest")]
+#[test]
+#[should_panic(expected = "replayed \"a\"")]
+fn proptest_regression_replays_shrunk_value() {
+    let seed = ::datatest::RegressionSeed {
+        seed: String::from("cc 4f1ab3c9d2e87a6510bc3d94e8f2a71c06d5b8e3f4a9c2d1e0b7f6a5c4d3e2f1"),
+        shrinks_to: Some(String::from("input = \"a\"")),
+    };
+    seed.replay("[a-z]{1,8}", |input: String| {
+        assert!(input.is_empty(), "replayed {:?}", input)
+    });
+}
+
+/// Test files could be compiled and checked against the expected diagnostics
+#[datatest::files("tests/ui", {
+    input in r"\.rs$",
+}, compile_fail = true)]
+#[test]
+fn files_test_compile_fail(input: &Path) {}
+
+fn split_words_reference(input: &str) -> Vec<String> {
+    input.split(' ').map(String::from).collect()
+}
+
+fn split_words_candidate(input: &str) -> Vec<String> {
+    input.split_whitespace().map(String::from).collect()
+}
+
+/// Two implementations could be compared on every test file
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*).input\.txt",
+}, differential(reference = split_words_reference, candidate = split_words_candidate))]
+#[test]
+fn files_test_differential(input: &str) {}
+
+#[test]
+#[should_panic(expected = "reference and candidate diverged on 'input.txt'\n\
+                           first difference on line 3 of the output:\n  \
+                           reference: \"\",\n  candidate: \"b\",")]
+fn differential_diverged() {
+    ::datatest::__internal::assert_same_output(
+        Path::new("input.txt"),
+        &split_words_reference("a  b"),
+        &split_words_candidate("a  b"),
+    );
+}
+
+/// Files with the same content (for example, crash artifacts saved in the corpus) are run once
+#[datatest::files("tests/fuzz", { input in r"/(corpus|artifacts)/" }, dedup = true)]
+#[test]
+fn files_test_dedup(input: &[u8]) {
+    assert!(input.starts_with(b"GET /"));
+}
+
+/// Structured inputs are decoded from the raw bytes the same way fuzz targets do
+#[cfg(feature = "arbitrary")]
+#[test]
+fn decode_arbitrary_input() {
+    let (level, text): (u16, String) = ::datatest::decode_arbitrary(b"\x01\x00hi");
+    assert_eq!(level, 1);
+    assert_eq!(text, "hi");
+}
+
+/// Send `GET` request to the server at `url` (`http://host:port`), return status and body.
+fn http_get(url: &str, path: &str) -> (u16, String) {
+    use std::io::{Read, Write};
+    let mut stream = std::net::TcpStream::connect(url.trim_start_matches("http://")).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n", path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
+
+/// Interactions of the case are served from the cassette next to the test file
+#[datatest::files("tests/http", { input in r"\.txt$" })]
+#[test]
+fn files_test_cassette(input: &str) {
+    let cassette = ::datatest::Cassette::start("http://api.example.com");
+    assert_eq!(cassette.mode(), ::datatest::CassetteMode::Replay);
+    let (status, body) = http_get(&cassette.url(), input.trim());
+    assert_eq!(status, 200);
+    assert_eq!(body, r#"{"id": 1, "name": "Alice"}"#);
+}
+
+#[test]
+fn cassette_record_replay() {
+    use std::io::{BufRead, Write};
+    // Upstream server answering with the chunked response
+    let upstream = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream_url = format!("http://{}", upstream.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (stream, _) = upstream.accept().unwrap();
+        let mut reader = std::io::BufReader::new(stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        let mut stream = reader.into_inner();
+        let response = "HTTP/1.1 201 Created\r\nX-Id: 7\r\nTransfer-Encoding: chunked\r\n\r\n\
+                        3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+        stream.write_all(response.as_bytes()).unwrap();
+    });
+
+    let path = std::env::temp_dir().join(format!("datatest-cassette-{}.yaml", std::process::id()));
+    {
+        let cassette = ::datatest::Cassette::record(&path, &upstream_url);
+        assert_eq!(http_get(&cassette.url(), "/items"), (201, String::from("abcde")));
+    }
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    assert!(recorded.contains("X-Id"), "{}", recorded);
+    // Bodies are stored as base64
+    assert!(recorded.contains("body: YWJjZGU="), "{}", recorded);
+
+    // Upstream server is gone, interaction is served from the cassette
+    let cassette = ::datatest::Cassette::replay(&path);
+    assert_eq!(http_get(&cassette.url(), "/items"), (201, String::from("abcde")));
+    drop(cassette);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "GET /missing: no matching interaction recorded")]
+fn cassette_unmatched_request() {
+    let cassette = ::datatest::Cassette::replay("tests/http/cassettes/files_test_cassette__user.txt.yaml");
+    assert_eq!(http_get(&cassette.url(), "/missing").0, 500);
+}
+
+#[test]
+#[should_panic(expected = "GET /users/1: recorded interaction was not requested")]
+fn cassette_unused_interaction() {
+    let cassette =
+        ::datatest::Cassette::replay("tests/http/cassettes/files_test_cassette__user.txt.yaml");
+    drop(cassette);
+}
+
+/// Random number generator is seeded from the name of the test case
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*).input\.txt",
+})]
+#[test]
+fn files_test_seeded_rng(input: &str, mut rng: ::datatest::SeededRng) {
+    let mut same = ::datatest::SeededRng::for_current_case();
+    assert_eq!(rng.seed(), same.seed());
+    assert_eq!(rng.next_u64(), same.next_u64());
+    assert!(rng.gen_range(3..5) >= 3);
+    let mut chars = input.chars().collect::<Vec<_>>();
+    rng.shuffle(&mut chars);
+    assert_eq!(chars.len(), input.chars().count());
+}
+
+#[datatest::data("tests/tests.yaml")]
+#[test]
+fn data_test_seeded_rng(case: GreeterTestCase, rng: &mut ::datatest::SeededRng) {
+    assert!(rng.choose(case.name.as_bytes()).is_some());
+}
+
+#[test]
+fn seeded_rng_is_stable() {
+    let mut rng = ::datatest::SeededRng::new(42);
+    let first = rng.next_u64();
+    assert_eq!(::datatest::SeededRng::new(42).next_u64(), first);
+    assert_ne!(::datatest::SeededRng::new(43).next_u64(), first);
+}
+
+/// Each case could run in a copy of the files of its test case, with relative paths resolved
+/// in the directory of the copy
+#[datatest::files("tests/sandbox", {
+    input in r"^(.*)input\.txt$",
+    data = r"${1}data.txt",
+}, sandbox = chdir)]
+#[test]
+fn files_test_sandbox_chdir(input: &str, data: &str) {
+    assert_eq!(std::fs::read_to_string("data.txt").unwrap(), data);
+    assert_eq!(input.trim().len(), data.trim().parse::<usize>().unwrap());
+    std::fs::write("output.txt", input).unwrap();
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sandbox");
+    assert!(!fixtures.join("one/output.txt").exists());
+    assert!(!fixtures.join("two/output.txt").exists());
+}
+
+/// Only the files of the test case are copied into the sandbox
+#[datatest::files("tests/sandbox", {
+    input in r"^(.*)input\.txt$",
+    data = r"${1}data.txt",
+}, sandbox = copy)]
+#[test]
+fn files_test_sandbox_copy(input: &Path, data: &Path) {
+    assert!(!input.starts_with(env!("CARGO_MANIFEST_DIR")));
+    assert_eq!(data, input.with_file_name("data.txt"));
+    assert!(data.exists());
+    assert!(!input.with_file_name("unrelated.txt").exists());
+    std::fs::write(input.with_file_name("output.txt"), "generated").unwrap();
+}
+
+/// Command could be run for every test file, with its output compared to the golden files
+#[datatest::files("tests/cli", {
+    input in r"\.sh$",
+}, command = ["sh", "{input}", "missing.txt"], cfg = unix)]
+#[test]
+fn files_test_command(input: &Path) {}
+
+#[test]
+fn watch_reports_changed_files() {
+    let dir = std::env::temp_dir().join(format!("datatest-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("input.txt");
+    std::fs::write(&file, "first").unwrap();
+
+    // File is changed until the change is reported, as the watcher might not have looked at the
+    // files yet when the first change is made. Pauses between the changes grow, so the watcher
+    // eventually sees the files settled.
+    let (reported_tx, reported_rx) = std::sync::mpsc::channel();
+    let modified = file.clone();
+    let writer = std::thread::spawn(move || {
+        let mut pause = std::time::Duration::from_millis(50);
+        for version in 1.. {
+            std::fs::write(&modified, format!("version {}", version)).unwrap();
+            if reported_rx.recv_timeout(pause).is_ok() {
+                break;
+            }
+            pause *= 2;
+        }
+    });
+    let mut reported = Vec::new();
+    ::datatest::watch(&[&dir], |changed| {
+        reported.extend_from_slice(changed);
+        false
+    });
+    reported_tx.send(()).unwrap();
+    writer.join().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(reported, vec![file]);
+}
+
+/// Expensive resource shared by all test cases using it
+#[derive(Debug)]
+struct Keywords(Vec<&'static str>);
+
+static KEYWORDS_SETUPS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+impl ::datatest::SharedResource for Keywords {
+    fn setup() -> Self {
+        KEYWORDS_SETUPS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Keywords(vec!["fn", "let", "match"])
+    }
+}
+
+/// Shared resources are injected by their type and set up only once
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*).input\.txt",
+})]
+#[test]
+fn files_test_shared_resource(input: &str, keywords: &::datatest::Shared<Keywords>) {
+    assert!(!keywords.0.contains(&input));
+    assert_eq!(KEYWORDS_SETUPS.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[datatest::data("tests/tests.yaml")]
+#[test]
+fn data_test_shared_resource(case: GreeterTestCase, keywords: ::datatest::Shared<Keywords>) {
+    assert!(!keywords.0.contains(&case.name.as_str()));
+    assert_eq!(KEYWORDS_SETUPS.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+/// Path of the data file could be given via `concat!` and `env!` macros (for example, to refer to
+/// the files generated by the build script into `OUT_DIR`)
+#[datatest::data(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/strings.yaml"))]
+#[test]
+fn data_test_path_macro(data: String) {
+    assert!(data.len() >= 10);
+}
+
+/// Path of the data file could be relative to this source file
+#[datatest::data("relative.yaml", relative_to = source)]
+#[test]
+fn data_test_source_relative(data: String) {
+    assert!(data == "nested" || data == "module");
+}
+
+/// Test case type borrowing from the source file (read via `json_lines`)
+#[derive(Deserialize, Debug)]
+struct CorpusLine<'a> {
+    #[serde(borrow)]
+    text: &'a str,
+    words: usize,
+}
+
+impl fmt::Display for CorpusLine<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text.replace(' ', "_"))
+    }
+}
+
+#[datatest::data(::datatest::json_lines("tests/corpus.jsonl"))]
+#[test]
+fn data_test_borrowed(line: CorpusLine<'_>) {
+    assert_eq!(line.text.split_whitespace().count(), line.words);
+}
+
+#[datatest::data(::datatest::json_lines("tests/corpus.jsonl"), name = "{fn}::{text}")]
+#[test]
+fn data_test_borrowed_ref(line: &CorpusLine<'_>, _rng: &mut ::datatest::SeededRng) {
+    assert_eq!(line.text.split_whitespace().count(), line.words);
+}
+
+/// Several `#[files]` attributes could be stacked on one function (the same test applied to
+/// different fixture layouts), test cases of all of them are generated
+#[datatest::files("tests/annotated", { input in r"\.txt$" })]
+#[datatest::files("tests/lit", { input in r"\.txt$" })]
+#[test]
+fn files_test_stacked(input: &Path) {
+    assert!(input.starts_with("tests/annotated") || input.starts_with("tests/lit"));
+    assert!(input.is_file());
+}
+
+/// Each data file matched by the pattern is expanded into multiple test cases (YAML parser also
+/// reads JSON files)
+#[datatest::data(::datatest::expand_files(
+    "tests/expanded",
+    r"\.(yaml|json)$",
+    ::datatest::try_yaml
+))]
+#[test]
+fn data_test_expand_files(data: &GreeterTestCase) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Data files are parsed in the format given by their extension, so a directory could mix JSON,
+/// YAML and TOML files (TOML files list the cases as `[[case]]` tables)
+#[datatest::data(::datatest::expand_files(
+    "tests/expanded",
+    r"\.(yaml|json|toml)$",
+    ::datatest::data_file
+))]
+#[test]
+fn data_test_mixed_formats(data: &GreeterTestCase) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// String literal source is parsed by the extension of the file as well
+#[datatest::data("tests/expanded/toml.toml")]
+#[test]
+fn data_test_toml(data: GreeterTestCase) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Each matched file is deserialized into the argument type in the format given by its extension
+#[datatest::files("tests/mixed", { data in r"\.(json|yaml|toml)$" })]
+#[test]
+fn files_test_deserialized(data: ::datatest::Deserialized<GreeterTestCase>) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Matched files could be further selected by their content
+#[datatest::files("tests/mixed", {
+    input in r"\.(json|yaml|toml)$",
+}, content_matches = |head: &[u8]| head.starts_with(b"{"))]
+#[test]
+fn files_test_content_matches(input: &Path) {
+    assert_eq!(input.extension().unwrap(), "json");
+}
+
+/// Cases using the same resource never run at the same time, even across tests
+static DATABASE_IN_USE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+fn use_database() {
+    use std::sync::atomic::Ordering;
+    assert!(!DATABASE_IN_USE.swap(true, Ordering::SeqCst), "database is in use");
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    DATABASE_IN_USE.store(false, Ordering::SeqCst);
+}
+
+#[datatest::data("tests/strings.yaml")]
+#[resource("database")]
+#[test]
+fn data_test_resource(_data: String) {
+    use_database();
+}
+
+#[datatest::files("tests/test-cases", { input in r"^(.*)\.input\.txt" })]
+#[resource("database", "network")]
+#[test]
+fn files_test_resource(input: &str) {
+    assert!(!input.is_empty());
+    use_database();
+}
+
+/// `datatest::assert_eq!` shows the diff of the values with `pretty_assertions` feature enabled
+#[datatest::data("tests/tests.yaml")]
+#[test]
+fn data_test_pretty_assert(data: &GreeterTestCase) {
+    datatest::assert_eq!(data.expected, format!("Hi, {}!", data.name));
+    datatest::assert_ne!(data.expected, data.name, "greeting is not just the name");
+}
+
+/// Output of each case is compared with the file next to the input
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+#[test]
+fn files_test_matches_file(input: &str, output: &Path) {
+    datatest::assert_matches_file!(format!("Hello, {}!", input), output);
+}
+
+/// Expected file of the mismatch test, written to a temporary directory, so the mismatching output
+/// does not overwrite the fixtures when the tests are run with `DATATEST_BLESS`
+fn expected_file(name: &str, content: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("datatest-{}-{}.txt", name, std::process::id()));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+#[should_panic(expected = "@@ line 1 @@\n- Hello, Kylie!\n  ^^^^^  ^^^^^\n+ Hi, World!\n  ^^  ^^^^^\n")]
+fn matches_file_mismatch() {
+    let expected = expected_file("mismatch", "Hello, Kylie!");
+    datatest::assert_matches_file!("Hi, World!", expected);
+}
+
+#[test]
+fn json_eq() {
+    let actual = serde_json::json!({ "items": [{ "name": "a" }, { "name": "b" }], "total": 2 });
+    datatest::assert_json_eq!(
+        actual,
+        serde_json::json!({ "total": 2.0, "items": [{ "name": "a" }, { "name": "b" }] })
+    );
+}
+
+#[test]
+#[should_panic(expected = "JSON values differ in 4 places:\n  \
+    expected.items[1].name: expected \"b\", got \"c\"\n  \
+    expected.items: expected 3 elements, got 2\n  \
+    expected[\"the total\"]: missing (expected 3)\n  \
+    expected.extra: unexpected (got true)")]
+fn json_eq_mismatch() {
+    let actual = serde_json::json!({ "items": [{ "name": "a" }, { "name": "c" }], "extra": true });
+    datatest::assert_json_eq!(
+        actual,
+        serde_json::json!({ "items": [{ "name": "a" }, { "name": "b" }, {}], "the total": 3 })
+    );
+}
+
+/// Changed words are underlined according to their display width: combining characters take no
+/// space and CJK characters take two columns
+#[test]
+#[should_panic(expected = "@@ line 1 @@\n\
+    - Cafe\u{301} \u{6771}\u{4eac} \u{30bf}\u{30ef}\u{30fc}\n       ^^^^\n\
+    + Cafe\u{301} \u{5927}\u{962a} \u{30bf}\u{30ef}\u{30fc}\n       ^^^^\n")]
+fn matches_file_multilingual_diff() {
+    let expected = expected_file(
+        "multilingual",
+        "Cafe\u{301} \u{6771}\u{4eac} \u{30bf}\u{30ef}\u{30fc}\n",
+    );
+    datatest::assert_matches_file!(
+        "Cafe\u{301} \u{5927}\u{962a} \u{30bf}\u{30ef}\u{30fc}\n",
+        expected
+    );
+}
+
+/// Context of the running case is available to the helpers called by the test
+fn check_current_case(prefix: &str) -> datatest::CaseInfo {
+    let case = datatest::current_case().expect("not in a test case");
+    assert!(case.name().contains(prefix), "unexpected name {}", case.name());
+    assert!(case.artifact_dir().starts_with("target/datatest-artifacts"));
+    case
+}
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+#[test]
+fn files_test_current_case(input: &Path, output: &Path) {
+    let case = check_current_case("files_test_current_case::case-0");
+    assert_eq!(case.fixtures(), [input, output]);
+    assert_eq!(case.location(), input.to_string_lossy());
+}
+
+#[datatest::data("tests/tests.yaml")]
+#[test]
+fn data_test_current_case(_data: &GreeterTestCase) {
+    let case = check_current_case("data_test_current_case::");
+    assert_eq!(case.fixtures(), [Path::new("tests/tests.yaml")]);
+    assert!(case.location().starts_with("tests/tests.yaml:"));
+    // Threads spawned by the case do not run it
+    assert!(std::thread::spawn(datatest::current_case).join().unwrap().is_none());
+}
+
+/// Benchmarks take `datatest::Bencher`, so they compile on stable; unless run with `--bench`, the
+/// iteration runs once, as a test (with `--bench`, it is measured)
+#[datatest::data("tests/strings.yaml")]
+#[bench]
+fn data_bench(bencher: &mut datatest::Bencher, data: String) {
+    let mut iterations = 0;
+    bencher.iter(|| {
+        iterations += 1;
+        data.to_uppercase()
+    });
+    assert!(iterations >= 1);
+}
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+#[bench]
+fn files_bench(bencher: &mut datatest::Bencher, input: &str, output: &str) {
+    bencher.bytes = input.len() as u64;
+    bencher.iter(|| assert_eq!(format!("Hello, {}!", input), output));
+}
+
+/// Benchmarks could return `Result`: failure of the setup or of an iteration (`try_iter` stops at
+/// the first error) fails the benchmark with the error
+#[datatest::data("tests/strings.yaml")]
+#[bench]
+fn data_bench_result(bencher: &mut datatest::Bencher, data: String) -> Result<(), String> {
+    let expected = data.len().to_string();
+    bencher.try_iter(|| {
+        let len = data.len().to_string();
+        if len == expected {
+            Ok(len)
+        } else {
+            Err(format!("unexpected length {}", len))
+        }
+    })?;
+    Ok(())
+}
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+})]
+#[bench]
+fn files_bench_result(bencher: &mut datatest::Bencher, input: &str) -> Result<(), std::num::ParseIntError> {
+    let len = input.trim().len().to_string();
+    bencher.try_iter(|| len.parse::<usize>())
+}
+
+/// With `camino` feature, paths could be taken as UTF-8 paths
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+}, cfg = feature = "camino")]
+#[test]
+fn files_test_utf8_paths(input: &camino::Utf8Path, output: camino::Utf8PathBuf) {
+    assert_eq!(output, input.as_str().replace(".input.", ".output."));
+}
+
+/// Raw identifiers could name the test function as well as its arguments
+#[datatest::files("tests/test-cases", {
+    r#in in r"^(.*)\.input\.txt",
+    r#type = r"${1}.output.txt",
+})]
+#[test]
+fn r#files_test_raw_identifiers(r#in: &str, r#type: &str) {
+    assert_eq!(format!("Hello, {}!", r#in), r#type);
+}
+
+/// Lifetime parameters and where-clauses of the test function are kept as is
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+#[test]
+fn files_test_where_clause<'a, 'b>(input: &'a str, output: &'b str)
+where
+    'a: 'b,
+{
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// Test case type with a const generic parameter
+#[derive(Deserialize)]
+#[serde(from = "String")]
+struct Prefix<const N: usize>(String);
+
+impl<const N: usize> From<String> for Prefix<N> {
+    fn from(value: String) -> Self {
+        Prefix(value.chars().take(N).collect())
+    }
+}
+
+#[datatest::data("tests/strings.yaml")]
+#[test]
+fn data_test_const_generic(data: Prefix<2>) {
+    assert!(data.0.chars().count() <= 2);
+}
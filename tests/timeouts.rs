@@ -0,0 +1,34 @@
+//! Test cases failing to finish within the timeout fail; the `timeout` field of the case
+//! overrides the timeout of the test.
+mod common;
+
+use serde::Deserialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Deserialize)]
+struct Case {
+    sleep: u64,
+}
+
+#[datatest::data("tests/timeouts.yaml", timeout = "500ms")]
+fn sleeping(case: Case) {
+    std::thread::sleep(Duration::from_millis(case.sleep));
+    FINISHED.fetch_add(1, Ordering::SeqCst);
+}
+
+fn main() {
+    let (passed, messages) = common::run_tests_with_panics();
+    assert!(!passed, "run with timed out case must fail");
+    // Second case is slower than the timeout of the test, but within its own timeout
+    assert_eq!(FINISHED.load(Ordering::SeqCst), 2);
+    assert!(
+        messages
+            .iter()
+            .any(|message| message == "test case did not finish within 50ms"),
+        "unexpected messages: {:?}",
+        messages
+    );
+}
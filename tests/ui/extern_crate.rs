@@ -0,0 +1,5 @@
+use datatest::Segment;
+
+pub fn segment() -> Segment {
+    "forty two"
+}
@@ -0,0 +1,6 @@
+// `#[datatest::files]` only expands under `cfg(test)`, so the attribute it expands to is used
+#[datatest::__internal::files_ctor_internal("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+fn sample_test(input: &str, ouput: &str) {}
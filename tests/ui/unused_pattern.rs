@@ -0,0 +1,5 @@
+// `#[datatest::files]` only expands under `cfg(test)`, so the attribute it expands to is used
+#[datatest::__internal::files_ctor_internal("tests/test-cases", {
+    inptu in r"^(.*)\.input\.txt",
+}, unmapped = default)]
+fn sample_test(input: &str) {}
@@ -0,0 +1,135 @@
+//! With `--validate`, fixtures are checked for problems without running the tests. With
+//! `DATATEST_VALIDATE_DATA=1`, invalid cases of the data files fail the run before any test runs.
+mod common;
+
+use datatest::DataTestCaseDesc;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static RAN: AtomicUsize = AtomicUsize::new(0);
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+fn files_test(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Uses only the input file, so `case-03.output-linked.txt` is not used by any test
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input-linked\.txt",
+})]
+fn files_test_linked(input: &str) {
+    assert!(!input.is_empty());
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+fn broken_source() -> Result<Vec<DataTestCaseDesc<String>>, String> {
+    Err(String::from("cannot parse the data file"))
+}
+
+#[datatest::data(broken_source())]
+fn broken_data(_data: String) {
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+#[datatest::data("tests/strings.yaml")]
+fn data_test(_data: String) {
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+#[derive(Deserialize)]
+struct GreeterTestCase {
+    name: String,
+    expected: String,
+}
+
+#[datatest::data("tests/invalid.yaml")]
+fn invalid_data(case: GreeterTestCase) {
+    assert_eq!(case.expected, format!("Hi, {}!", case.name));
+    RAN.fetch_add(1, Ordering::SeqCst);
+}
+
+fn main() {
+    if common::is_child() {
+        let passed = common::run_tests();
+        assert_eq!(
+            RAN.load(Ordering::SeqCst),
+            0,
+            "validation must not run tests"
+        );
+        if !passed {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let output = common::rerun(&["--validate"])
+        .env("DATATEST_ORPHANS", "fail")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = common::stdout(&output);
+    let problems = common::errors(&stdout);
+    // Enumeration stops at the first invalid case, while validation lists all of them
+    let enumeration =
+        "error: test 'invalid_data': failed to enumerate test cases: cannot parse test \
+                       cases from 'tests/invalid.yaml' (line 4, column 13, case #1)";
+    assert_eq!(problems.len(), 5, "{}", stdout);
+    assert!(problems[1].starts_with(enumeration), "{}", stdout);
+    assert_eq!(
+        [problems[0], problems[2], problems[3], problems[4]],
+        [
+            "error: test 'broken_data': failed to enumerate test cases: cannot parse the data file",
+            "error: file 'tests/test-cases/case-03.output-linked.txt' is not used by any test",
+            "error: test 'invalid_data': invalid test case at tests/invalid.yaml:3: invalid type: \
+             sequence, expected a string",
+            "error: test 'invalid_data': invalid test case at tests/invalid.yaml:5: missing field \
+             `expected`",
+        ],
+        "{}",
+        stdout
+    );
+    let stderr = common::stderr(&output);
+    assert!(
+        stderr.contains("fixture validation failed: found 5 problems (6 test cases checked)"),
+        "{}",
+        stderr
+    );
+
+    // Root directories could be shared with other test binaries, so unused files are only
+    // reported if orphaned fixtures fail the run
+    let output = common::rerun(&["--validate"])
+        .env("DATATEST_ORPHANS", "allow")
+        .output()
+        .unwrap();
+    let stdout = common::stdout(&output);
+    assert!(!stdout.contains("is not used by any test"), "{}", stdout);
+
+    // Validation pre-pass of the data files fails the regular run before any test runs
+    let output = common::rerun(&[])
+        .env("DATATEST_VALIDATE_DATA", "1")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = common::stdout(&output);
+    assert_eq!(
+        common::errors(&stdout),
+        [
+            "error: test 'invalid_data': invalid test case at tests/invalid.yaml:3: invalid type: \
+             sequence, expected a string",
+            "error: test 'invalid_data': invalid test case at tests/invalid.yaml:5: missing field \
+             `expected`",
+        ],
+        "{}",
+        stdout
+    );
+    let stderr = common::stderr(&output);
+    assert!(
+        stderr.contains("data validation failed: found 2 invalid test cases, no tests were run"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,31 @@
+//! Failures of the test cases marked as expected failures (`xfail`) do not fail the run, while
+//! unexpected passes (XPASS) are reported and fail the run with `DATATEST_XPASS=fail`.
+mod common;
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Case {
+    value: u32,
+}
+
+#[datatest::data("tests/xfail.yaml")]
+fn data_test(case: Case) {
+    assert_eq!(case.value % 2, 1, "odd values only");
+}
+
+#[datatest::files("tests/annotated", { input in r"\.txt$" })]
+#[xfail = "parser is not there yet"]
+fn files_test(input: &str) {
+    assert!(input.is_empty(), "not parsed");
+}
+
+fn main() {
+    datatest::runner(&[]);
+    let failed = common::failed_cases();
+    assert!(failed.is_empty(), "{:?}", failed);
+
+    std::env::set_var("DATATEST_XPASS", "fail");
+    assert!(!common::run_tests(), "run must fail");
+    assert_eq!(common::failed_cases(), ["data_test::line 4"]);
+}